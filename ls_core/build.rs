@@ -1,6 +1,13 @@
 use std::path::PathBuf;
 
 fn main() {
+    // The test grammar is only needed when parsing support is compiled in,
+    // and under wasm grammars come from web-tree-sitter instead.
+    if std::env::var_os("CARGO_FEATURE_PARSING").is_none()
+        || std::env::var("CARGO_CFG_TARGET_ARCH").as_deref() == Ok("wasm32") {
+        return;
+    }
+
     let dir: PathBuf = ["..", "grammars", "test", "src"].iter().collect();
 
     cc::Build::new()
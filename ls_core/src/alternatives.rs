@@ -0,0 +1,151 @@
+//! Choosing among a recognizer's N-best transcriptions.
+//!
+//! Speech recognizers often return several transcriptions of an utterance,
+//! each with a confidence. The most confident one is not always what was
+//! meant: "select parse tree" beats "select pass tree" in a document full of
+//! `parse_tree`, and "next bookmark" beats "necks bookmark" because it is a
+//! command. Each alternative is scored by its confidence, plus
+//! [`GRAMMAR_WEIGHT`] if it parses as a [`Command`], plus
+//! [`VOCABULARY_WEIGHT`] times the fraction of its words found in the
+//! document's identifiers. The best is used and the rest are kept, best
+//! first, so the user can correct a wrong choice.
+
+use crate::commands::{Command, Outcome};
+use crate::document::Document;
+use crate::segmentation::Segmentation;
+use crate::util::Oops;
+
+/// How much parsing as a command adds to an alternative's score.
+pub const GRAMMAR_WEIGHT: f32 = 0.5;
+
+/// How much an alternative made entirely of document vocabulary adds to
+/// its score.
+pub const VOCABULARY_WEIGHT: f32 = 0.25;
+
+/// One transcription from a recognizer.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Alternative {
+    pub text: String,
+
+    /// The recognizer's confidence, usually from 0 to 1.
+    pub confidence: f32
+}
+
+impl Alternative {
+    pub fn from(text: &str, confidence: f32) -> Alternative {
+        Alternative { text: String::from(text), confidence }
+    }
+}
+
+/// What an alternative would do.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Interpretation {
+    pub text: String,
+
+    /// The command it parses as, or `None` for dictation.
+    pub command: Option<Command>,
+
+    pub score: f32
+}
+
+/// The interpretation chosen for an utterance, and the others, best first.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Recognition {
+    pub chosen: Interpretation,
+    pub runners_up: Vec<Interpretation>
+}
+
+impl Document {
+    /// Scores `alternatives` against the command grammar and this document's
+    /// vocabulary (see the [module documentation](self)), and returns the
+    /// best with the runners-up, or `None` if there are no alternatives.
+    /// Equal scores keep the recognizer's order.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::alternatives::*;
+    /// let document = Document::from("let parse_tree = 1;");
+    /// let recognition = document.recognize(&[
+    ///     Alternative::from("pass tree", 0.6),
+    ///     Alternative::from("parse tree", 0.5),
+    ///     Alternative::from("necks bookmark", 0.4),
+    ///     Alternative::from("next bookmark", 0.3)
+    /// ]).unwrap();
+    ///
+    /// assert_eq!(recognition.chosen.text, "next bookmark");
+    /// assert!(recognition.chosen.command.is_some());
+    /// assert_eq!(recognition.runners_up[0].text, "parse tree");
+    /// assert_eq!(recognition.runners_up.len(), 3);
+    /// ```
+    pub fn recognize(&self, alternatives: &[Alternative]) -> Option<Recognition> {
+        let vocabulary = self.identifier_index().vocabulary(&Segmentation::default());
+        let known = |word: &str| vocabulary.iter().any(|v| v.eq_ignore_ascii_case(word));
+
+        let mut interpretations: Vec<Interpretation> = alternatives.iter()
+            .map(|alternative| {
+                let command = Command::parse(&alternative.text);
+                let words: Vec<&str> = alternative.text.split_whitespace().collect();
+                let familiar = match words.len() {
+                    0 => 0.0,
+                    count => words.iter().filter(|w| known(w)).count() as f32 / count as f32
+                };
+                let grammar = if command.is_some() { GRAMMAR_WEIGHT } else { 0.0 };
+
+                Interpretation {
+                    text: alternative.text.clone(),
+                    score: alternative.confidence + grammar + VOCABULARY_WEIGHT * familiar,
+                    command
+                }
+            })
+            .collect();
+
+        interpretations.sort_by(|a, b| b.score.total_cmp(&a.score));
+        let mut interpretations = interpretations.into_iter();
+        let chosen = interpretations.next()?;
+        Some(Recognition { chosen, runners_up: interpretations.collect() })
+    }
+
+    /// Acts on the best of `alternatives` (see [`Document::recognize`]):
+    /// executes it if it is a command, and otherwise dictates it. Returns
+    /// what happened along with the recognition, so a wrong choice can be
+    /// undone and a runner-up tried instead. Returns `Err` if there are no
+    /// alternatives or acting on the chosen one fails.
+    pub fn execute_alternatives(&mut self, alternatives: &[Alternative]) -> Result<(Outcome, Recognition), Oops> {
+        let recognition = self.recognize(alternatives).ok_or(Oops::EmptyString("no alternatives"))?;
+
+        let chosen = &recognition.chosen;
+        let outcome = self.act_on_utterance(&chosen.text, chosen.command.as_ref())?;
+        Ok((outcome, recognition))
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Position, Range};
+
+    #[test]
+    fn vocabulary_and_grammar_outweigh_confidence() {
+        let mut document = Document::from("total_width = 0\n");
+        document.set_cursor_and_mark(&Position::from(1, 0)).unwrap();
+        assert!(document.execute_alternatives(&[]).is_err());
+
+        let (outcome, recognition) = document.execute_alternatives(&[
+            Alternative::from("total with", 0.7),
+            Alternative::from("total width", 0.6)
+        ]).unwrap();
+        assert_eq!(outcome, Outcome::Dictated(Range::from(1, 0, 1, 11)));
+        assert_eq!(recognition.chosen.command, None);
+        assert_eq!(recognition.runners_up[0].text, "total with");
+        assert_eq!(document.lines()[1].content, "total_width");
+
+        let (outcome, _) = document.execute_alternatives(&[
+            Alternative::from("four line", 0.9),
+            Alternative::from("first line", 0.45)
+        ]).unwrap();
+        assert_eq!(outcome, Outcome::Moved(Position::from(0, 0)));
+    }
+}
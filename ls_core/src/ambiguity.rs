@@ -0,0 +1,195 @@
+//! Asking which one: when a spoken command matches several places, the
+//! interpreter lists them as numbered candidates and the user says "choose
+//! two" instead of repeating the command more precisely.
+//!
+//! A resolver which finds several equally good places returns an
+//! [`Ambiguity`] and leaves it pending on the document, remembering what it
+//! was going to do with the place. [`Document::choose`] finishes the
+//! command with the chosen candidate. Candidates are numbered from one,
+//! best guess first, which for text matches means nearest to the cursor.
+//! Any edit invalidates the pending choice, since the candidates' ranges may
+//! no longer be right, and a new ambiguity replaces an old one.
+
+use crate::document::{Document, Range};
+use crate::search::SearchOptions;
+use crate::util::Oops;
+
+/// How many characters of a candidate's line its preview shows.
+pub const PREVIEW_LENGTH: usize = 40;
+
+/// What to do with the chosen candidate.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ChoiceAction {
+    /// Select its range.
+    Select,
+
+    /// Move the cursor and mark to its beginning.
+    GoTo
+}
+
+/// One of several places a command could mean.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Candidate {
+    /// The number to say to choose this candidate, from one.
+    pub number: usize,
+
+    pub range: Range,
+
+    /// The candidate's line, trimmed, at most [`PREVIEW_LENGTH`] characters.
+    pub preview: String
+}
+
+/// Several places a command could mean, waiting for the user to choose.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Ambiguity {
+    pub action: ChoiceAction,
+    pub candidates: Vec<Candidate>
+}
+
+/// The outcome of resolving a spoken command to a place.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Resolution {
+    /// There was one place, and the command acted on it.
+    Done(Range),
+
+    /// There were several; see [`Document::choose`].
+    Ambiguous(Ambiguity)
+}
+
+/// An ambiguity pending on a document, and the revision it belongs to.
+pub(crate) struct PendingChoice {
+    ambiguity: Ambiguity,
+    revision: u64
+}
+
+impl Document {
+    /// Sorts `ranges` nearest to the cursor first.
+    pub(crate) fn sort_by_distance(&self, ranges: &mut [Range]) {
+        let cursor = self.cursor().position;
+        ranges.sort_by_key(|r| {
+            let rows = (r.beginning.row as isize - cursor.row as isize).unsigned_abs();
+            let columns = (r.beginning.column as isize - cursor.column as isize).unsigned_abs();
+            (rows, columns)
+        });
+    }
+
+    /// Numbers `ranges` in order and leaves them pending for
+    /// [`Document::choose`] to finish `action` with.
+    pub(crate) fn make_ambiguous(&mut self, action: ChoiceAction, ranges: Vec<Range>) -> Ambiguity {
+        let candidates = ranges.iter()
+            .enumerate()
+            .map(|(i, range)| Candidate {
+                number: i + 1,
+                range: *range,
+                preview: self.lines()[range.beginning.row].content.trim().chars().take(PREVIEW_LENGTH).collect()
+            })
+            .collect();
+
+        let ambiguity = Ambiguity { action, candidates };
+        self.pending_choice = Some(PendingChoice { ambiguity: ambiguity.clone(), revision: self.revision() });
+        ambiguity
+    }
+
+    /// Returns the ambiguity waiting for [`Document::choose`], if any.
+    pub fn pending_ambiguity(&self) -> Option<&Ambiguity> {
+        self.pending_choice.as_ref()
+            .filter(|pending| pending.revision == self.revision())
+            .map(|pending| &pending.ambiguity)
+    }
+
+    /// Forgets the pending ambiguity.
+    pub fn cancel_choice(&mut self) {
+        self.pending_choice = None;
+    }
+
+    /// Finishes the pending ambiguous command with candidate `number`, and
+    /// returns the candidate's range. Returns `Err` if nothing is pending,
+    /// the document has changed since, or there is no such candidate.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::ambiguity::*;
+    /// let mut document = Document::from("let width = 1;\nlet height = width;");
+    /// document.set_cursor_and_mark(&Position::from(1, 0)).unwrap();
+    ///
+    /// let ambiguity = match document.select_text("width").unwrap() {
+    ///     Resolution::Ambiguous(ambiguity) => ambiguity,
+    ///     other => panic!("{:?}", other)
+    /// };
+    /// assert_eq!(ambiguity.candidates[0].range, Range::from(1, 13, 1, 18));
+    /// assert_eq!(ambiguity.candidates[1].preview, "let width = 1;");
+    ///
+    /// assert_eq!(document.choose(2), Ok(Range::from(0, 4, 0, 9)));
+    /// assert_eq!(document.selection(), Range::from(0, 4, 0, 9));
+    /// assert!(document.choose(1).is_err());
+    /// ```
+    pub fn choose(&mut self, number: usize) -> Result<Range, Oops> {
+        let ambiguity = self.pending_ambiguity().ok_or(Oops::Ouch("nothing to choose from"))?;
+        let action = ambiguity.action;
+        let range = ambiguity.candidates.iter()
+            .find(|c| c.number == number)
+            .map(|c| c.range)
+            .ok_or(Oops::Ouch("no such choice"))?;
+
+        match action {
+            ChoiceAction::Select => self.set_selection(&range)?,
+            ChoiceAction::GoTo => self.set_cursor_and_mark(&range.beginning)?
+        }
+        self.pending_choice = None;
+        Ok(range)
+    }
+
+    /// Selects the text matching `spoken`, ignoring case and diacritics.
+    /// Returns `Err` if there is no match, and leaves the matches pending
+    /// (see [`Document::choose`]) if there are several.
+    pub fn select_text(&mut self, spoken: &str) -> Result<Resolution, Oops> {
+        let mut matches = self.find_all(spoken, &SearchOptions::spoken());
+        self.sort_by_distance(&mut matches);
+        match matches.len() {
+            0 => Err(Oops::Ouch("no such text")),
+            1 => {
+                self.set_selection(&matches[0])?;
+                Ok(Resolution::Done(matches[0]))
+            },
+            _ => Ok(Resolution::Ambiguous(self.make_ambiguous(ChoiceAction::Select, matches)))
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::Outcome;
+    use crate::document::{Position, InsertOptions};
+
+    #[test]
+    fn choices_expire_with_edits() {
+        let mut document = Document::from("Total = total + TOTAL;\ntally");
+        assert!(document.select_text("nothing").is_err());
+        assert_eq!(document.select_text("tally"), Ok(Resolution::Done(Range::from(1, 0, 1, 5))));
+
+        document.set_cursor_and_mark(&Position::from(0, 22)).unwrap();
+        match document.execute_phrase("select total") {
+            Ok(Outcome::Ambiguous(ambiguity)) => {
+                let numbers: Vec<usize> = ambiguity.candidates.iter().map(|c| c.number).collect();
+                assert_eq!(numbers, vec![1, 2, 3]);
+                assert_eq!(ambiguity.candidates[0].range, Range::from(0, 16, 0, 21));
+            },
+            other => panic!("{:?}", other)
+        }
+        assert!(document.choose(4).is_err());
+        assert_eq!(document.execute_phrase("choose two"), Ok(Outcome::Selected(Range::from(0, 8, 0, 13))));
+
+        document.select_text("total").unwrap();
+        document.insert("!", &InsertOptions::exact_at(&Range::from(1, 0, 1, 0))).unwrap();
+        assert_eq!(document.pending_ambiguity(), None);
+        assert!(document.choose(1).is_err());
+
+        document.select_text("total").unwrap();
+        document.cancel_choice();
+        assert!(document.choose(1).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+//! Suspending anchor adjustment during bulk rewrites.
+//!
+//! Every edit normally moves each tracked anchor after it and records the
+//! move on the undo stack. A reformat or reflow made of hundreds of small
+//! edits drags the anchors inside it through hundreds of intermediate
+//! positions, which is slow and, since each step only sees one edit,
+//! rarely lands them where they belong. [`Document::with_anchors_suspended`]
+//! instead leaves the tracked anchors in a range where they are while the
+//! rewrite runs, then snaps each to a target the rewrite computed, with one
+//! recorded move per anchor. Anchors outside the range, and floating
+//! anchors, follow the edits as usual.
+
+use std::collections::HashMap;
+
+use crate::document::{Document, Anchor, AnchorHandle, Position, Range};
+use crate::util::Oops;
+
+/// The anchors suspended by [`Document::with_anchors_suspended`], and where
+/// they should snap to.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct AnchorTargets {
+    original: HashMap<AnchorHandle, Position>,
+    targets: HashMap<AnchorHandle, Position>
+}
+
+impl AnchorTargets {
+    /// Returns the suspended anchors and their positions before the rewrite,
+    /// in no particular order.
+    pub fn suspended(&self) -> impl Iterator<Item = (AnchorHandle, Position)> + '_ {
+        self.original.iter().map(|(handle, position)| (*handle, *position))
+    }
+
+    /// Snaps the suspended anchor `handle` to `position` once the rewrite is
+    /// done. Returns `Err` if `handle` was not suspended.
+    pub fn snap(&mut self, handle: AnchorHandle, position: &Position) -> Result<(), Oops> {
+        if !self.original.contains_key(&handle) {
+            return Err(Oops::NonexistentAnchor(handle));
+        }
+        self.targets.insert(handle, *position);
+        Ok(())
+    }
+
+    /// Snaps every suspended anchor to `f(handle, original position)`.
+    pub fn snap_all<F>(&mut self, mut f: F) where F: FnMut(AnchorHandle, &Position) -> Position {
+        for (handle, position) in self.original.iter() {
+            self.targets.insert(*handle, f(*handle, position));
+        }
+    }
+}
+
+/// Returns `position` moved onto the nearest position in `document`.
+fn clamp(document: &Document, position: &Position) -> Position {
+    let row = position.row.min(document.rows() - 1);
+    Position::from(row, position.column.min(document.lines()[row].length))
+}
+
+impl Document {
+    /// Runs `f` as a single undoable packet labeled `label`, with the tracked
+    /// anchors in `range` (ends included) suspended: edits leave them where
+    /// they are. Afterwards each is set to the target `f` gave it through the
+    /// [`AnchorTargets`], or kept where it was if it has none, moved onto the
+    /// nearest valid position if the text has shrunk past it.
+    ///
+    /// While `f` runs, suspended anchors (possibly including the cursor and
+    /// mark) hold stale positions, so `f` should edit explicit ranges rather
+    /// than the selection. Returns `Err` if `range` is invalid or anchors are
+    /// already suspended, and whatever `f` returns otherwise; anchors are
+    /// snapped even if `f` fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("a b c d");
+    /// document.set_cursor_and_mark(&Position::from(0, 6)).unwrap();
+    ///
+    /// document.with_anchors_suspended(&Range::from(0, 0, 0, 7), "unspace", |document, targets| {
+    ///     for column in (1..6).step_by(2).rev() {
+    ///         document.remove(&RemoveOptions::exact_at(&Range::from(0, column, 0, column + 1)))?;
+    ///     }
+    ///     targets.snap_all(|_, position| Position::from(0, position.column / 2));
+    ///     Ok(())
+    /// }).unwrap();
+    ///
+    /// assert_eq!(document.text(), "abcd");
+    /// assert_eq!(document.cursor().position, Position::from(0, 3));
+    /// ```
+    pub fn with_anchors_suspended<T, F>(&mut self, range: &Range, label: &str, f: F) -> Result<T, Oops>
+        where F: FnOnce(&mut Document, &mut AnchorTargets) -> Result<T, Oops>
+    {
+        if !self.range_valid(range) {
+            return Err(Oops::InvalidRange(*range, "with_anchors_suspended"));
+        }
+
+        let original: HashMap<AnchorHandle, Position> = self.anchors()
+            .filter(|(handle, anchor)| {
+                anchor.position >= range.beginning && anchor.position <= range.ending
+                    && !self.anchor_is_floating(**handle)
+            })
+            .map(|(handle, anchor)| (*handle, anchor.position))
+            .collect();
+
+        if self.anchors_suspended() {
+            return Err(Oops::Ouch("anchors are already suspended"));
+        }
+
+        self.with_packet(label, |document| {
+            let mut targets = AnchorTargets { original, targets: HashMap::new() };
+            document.suspend_anchors(targets.original.keys().copied());
+
+            let result = f(document, &mut targets);
+            let suspended = document.resume_anchors();
+
+            for handle in suspended {
+                let target = targets.targets.get(&handle).unwrap_or(&targets.original[&handle]);
+                let position = clamp(document, target);
+                let moved = document.anchor(handle).is_some_and(|anchor| anchor.position != position);
+                if moved {
+                    document.set_anchor(handle, &Anchor { position })?;
+                }
+            }
+            result
+        })
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{InsertOptions, RemoveOptions};
+
+    #[test]
+    fn suspended_anchors_snap_once_and_undo() {
+        let mut document = Document::from("one two\nthree");
+        document.set_cursor_and_mark(&Position::from(0, 7)).unwrap();
+        let outside = document.create_anchor(&Anchor { position: Position::from(1, 5) }).unwrap();
+        let depth = document.undo_redo().depth();
+
+        document.with_anchors_suspended(&Range::from(0, 0, 0, 7), "upper", |document, targets| {
+            assert_eq!(targets.suspended().count(), 2);
+            assert!(targets.snap(outside, &Position::from(0, 0)).is_err());
+            document.insert("ONE", &InsertOptions::exact_at(&Range::from(0, 0, 0, 3)))?;
+            document.insert("\nTWO", &InsertOptions::exact_at(&Range::from(0, 3, 0, 7)))?;
+            targets.snap(crate::document::Anchors::CURSOR, &Position::from(1, 3))?;
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(document.text(), "ONE\nTWO\nthree");
+        assert_eq!(document.cursor().position, Position::from(1, 3));
+        assert_eq!(document.mark().position, Position::from(0, 3));
+        assert_eq!(document.anchor(outside).unwrap().position, Position::from(2, 5));
+        assert_eq!(document.undo_redo().depth().0, depth.0 + 1);
+
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), "one two\nthree");
+        assert_eq!(document.cursor().position, Position::from(0, 7));
+        assert_eq!(document.mark().position, Position::from(0, 7));
+
+        let failed = document.with_anchors_suspended(&Range::from(0, 0, 0, 7), "fail", |document, _| {
+            document.remove(&RemoveOptions::exact_at(&Range::from(0, 3, 0, 7)))?;
+            Err::<(), Oops>(Oops::Ouch("failed"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(document.cursor().position, Position::from(0, 3));
+        assert!(document.insert("!", &InsertOptions::exact()).is_ok());
+    }
+}
@@ -0,0 +1,175 @@
+//! Anchor watches: notifications about specific anchors, for clients which
+//! track a few positions (a breakpoint, a tooltip, a group of placeholders)
+//! and want to hear when they move or disappear without diffing every
+//! anchor after each edit.
+//!
+//! A watch covers a group of anchors and compares only those against their
+//! last known positions, once each edit, anchor change, undo, or redo has
+//! finished. Callbacks receive just the event, not the document, so they
+//! cannot re-enter it mid-edit.
+
+use std::collections::BTreeMap;
+
+use crate::document::{Document, Position, AnchorHandle};
+use crate::util::Oops;
+
+/// Something which happened to a watched anchor.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum AnchorEvent {
+    /// The anchor moved from `from` to `to`.
+    Moved { handle: AnchorHandle, from: Position, to: Position },
+
+    /// The anchor was removed while at `last`. It is no longer watched.
+    Removed { handle: AnchorHandle, last: Position }
+}
+
+/// Hears about the anchors in one watch.
+pub type AnchorCallback = Box<dyn FnMut(&AnchorEvent)>;
+
+/// Identifies a watch so that it can be removed.
+pub type WatchHandle = u32;
+
+/// One group of watched anchors.
+struct Watch {
+    handle: WatchHandle,
+
+    /// Each watched anchor's position when last reported.
+    positions: BTreeMap<AnchorHandle, Position>,
+
+    callback: AnchorCallback
+}
+
+/// A document's anchor watches.
+#[derive(Default)]
+pub(crate) struct AnchorWatches {
+    watches: Vec<Watch>,
+    next_handle: WatchHandle
+}
+
+impl Document {
+    /// Watches the anchors `handles` as a group, calling `callback` whenever
+    /// one of them moves or is removed. Returns the watch's handle, or `Err`
+    /// if any anchor does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::rc::Rc;
+    /// use std::cell::RefCell;
+    /// use ls_core::document::*;
+    /// use ls_core::anchor_watches::*;
+    /// let mut document = Document::from("let x = 1;");
+    /// let breakpoint = document.create_floating_anchor(&Position::from(0, 4)).unwrap();
+    /// let heard = Rc::new(RefCell::new(vec![]));
+    /// let log = heard.clone();
+    /// document.watch_anchors(&[breakpoint], Box::new(move |event| log.borrow_mut().push(*event))).unwrap();
+    ///
+    /// document.insert("mut ", &InsertOptions::exact_at(&Range::from(0, 4, 0, 4))).unwrap();
+    /// document.insert("!", &InsertOptions::exact_at(&Range::from(0, 14, 0, 14))).unwrap();
+    /// document.remove_floating_anchor(breakpoint).unwrap();
+    /// assert_eq!(*heard.borrow(), vec![
+    ///     AnchorEvent::Moved { handle: breakpoint, from: Position::from(0, 4), to: Position::from(0, 8) },
+    ///     AnchorEvent::Removed { handle: breakpoint, last: Position::from(0, 8) }
+    /// ]);
+    /// ```
+    pub fn watch_anchors(&mut self, handles: &[AnchorHandle], callback: AnchorCallback) -> Result<WatchHandle, Oops> {
+        let mut positions = BTreeMap::new();
+        for handle in handles {
+            let anchor = self.anchor(*handle).ok_or(Oops::NonexistentAnchor(*handle))?;
+            positions.insert(*handle, anchor.position);
+        }
+
+        self.anchor_watches.next_handle += 1;
+        let handle = self.anchor_watches.next_handle;
+        self.anchor_watches.watches.push(Watch { handle, positions, callback });
+        Ok(handle)
+    }
+
+    /// Removes the watch `handle`. Returns false if there was none.
+    pub fn unwatch_anchors(&mut self, handle: WatchHandle) -> bool {
+        let before = self.anchor_watches.watches.len();
+        self.anchor_watches.watches.retain(|w| w.handle != handle);
+        self.anchor_watches.watches.len() != before
+    }
+
+    /// Reports every watched anchor which has moved or been removed since it
+    /// was last reported.
+    pub(crate) fn notify_anchor_watches(&mut self) {
+        if self.anchor_watches.watches.is_empty() {
+            return;
+        }
+
+        let mut watches = std::mem::take(&mut self.anchor_watches.watches);
+
+        for watch in watches.iter_mut() {
+            let mut events = vec![];
+
+            watch.positions.retain(|handle, last| match self.anchor(*handle) {
+                None => {
+                    events.push(AnchorEvent::Removed { handle: *handle, last: *last });
+                    false
+                },
+                Some(anchor) => {
+                    if anchor.position != *last {
+                        events.push(AnchorEvent::Moved { handle: *handle, from: *last, to: anchor.position });
+                        *last = anchor.position;
+                    }
+                    true
+                }
+            });
+
+            for event in events.iter() {
+                (watch.callback)(event);
+            }
+        }
+
+        self.anchor_watches.watches = watches;
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::document::*;
+
+    #[test]
+    fn groups_hear_about_their_anchors_only() {
+        let mut document = Document::from("abc\ndef");
+        let first = document.create_anchor(&Anchor::from(0, 1)).unwrap();
+        let second = document.create_anchor(&Anchor::from(1, 1)).unwrap();
+        let other = document.create_anchor(&Anchor::from(1, 2)).unwrap();
+        assert!(document.watch_anchors(&[AnchorHandle::from(99, 0)], Box::new(|_| ())).is_err());
+
+        let heard = Rc::new(RefCell::new(vec![]));
+        let log = heard.clone();
+        let watch = document.watch_anchors(&[first, second], Box::new(move |e| log.borrow_mut().push(*e))).unwrap();
+
+        document.checkpoint();
+        document.remove(&RemoveOptions::exact_at(&Range::from(0, 0, 1, 0))).unwrap();
+        assert_eq!(*heard.borrow(), vec![
+            AnchorEvent::Moved { handle: first, from: Position::from(0, 1), to: Position::from(0, 0) },
+            AnchorEvent::Moved { handle: second, from: Position::from(1, 1), to: Position::from(0, 1) }
+        ]);
+
+        heard.borrow_mut().clear();
+        document.set_anchor(other, &Anchor::from(0, 0)).unwrap();
+        assert!(heard.borrow().is_empty());
+
+        document.undo_once().unwrap();
+        assert_eq!(heard.borrow().len(), 2);
+        assert_eq!(document.anchor(first).unwrap().position, Position::from(0, 1));
+
+        heard.borrow_mut().clear();
+        document.remove_anchor(second).unwrap();
+        assert_eq!(*heard.borrow(), vec![AnchorEvent::Removed { handle: second, last: Position::from(1, 1) }]);
+
+        assert!(document.unwatch_anchors(watch));
+        assert!(!document.unwatch_anchors(watch));
+        heard.borrow_mut().clear();
+        document.set_anchor(first, &Anchor::from(0, 0)).unwrap();
+        assert!(heard.borrow().is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+//! Line annotations: externally computed per-line information, such as git
+//! blame or profiling counts, shown in a gutter beside the text.
+//!
+//! Annotations are grouped into named channels, one per source. Each channel
+//! holds at most one annotation per line and is remapped as the document is
+//! edited, undone, or redone, so an annotation stays with the line it was
+//! computed for until the source recomputes it:
+//!
+//! * Lines inserted by an edit have no annotation.
+//! * A line split at its start keeps its annotation on the part holding its
+//!   old text; split anywhere else, the first part keeps it.
+//! * When lines are joined, the first line's annotation is kept, unless all
+//!   of the first line's text was removed, in which case the last line's is.
+//!
+//! Like virtual text, annotations are not part of the text and are not
+//! recorded on the undo stack. See [`RenderLine::gutter`](crate::render::RenderLine::gutter).
+
+use std::collections::BTreeMap;
+
+use crate::document::{Document, Position, Range};
+use crate::util::Oops;
+
+/// An annotation on one line. `style` is an opaque class name handed to the
+/// renderer, as for [virtual text](crate::virtual_text::VirtualText).
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct LineAnnotation {
+    pub text: String,
+    pub style: String
+}
+
+impl LineAnnotation {
+    /// Returns the annotation `text` with style class `style`.
+    pub fn from(text: &str, style: &str) -> LineAnnotation {
+        LineAnnotation {
+            text: String::from(text),
+            style: String::from(style)
+        }
+    }
+}
+
+/// A document's annotation channels, by name. Each holds one slot per line.
+#[derive(Default)]
+pub(crate) struct Annotations {
+    channels: BTreeMap<String, Vec<Option<LineAnnotation>>>
+}
+
+impl Annotations {
+    /// Remaps every channel after `lines` lines of text were inserted at
+    /// `position`.
+    pub(crate) fn after_insert(&mut self, position: &Position, lines: usize) {
+        if lines < 2 {
+            return;
+        }
+
+        let at = if position.column == 0 { position.row } else { position.row + 1 };
+        for slots in self.channels.values_mut() {
+            let at = at.min(slots.len());
+            slots.splice(at..at, std::iter::repeat_n(None, lines - 1));
+        }
+    }
+
+    /// Remaps every channel after `range` was removed.
+    pub(crate) fn after_remove(&mut self, range: &Range) {
+        if range.beginning.row == range.ending.row {
+            return;
+        }
+
+        let (first, last) = if range.beginning.column == 0 {
+            (range.beginning.row, range.ending.row - 1)
+        } else {
+            (range.beginning.row + 1, range.ending.row)
+        };
+        for slots in self.channels.values_mut() {
+            let end = (last + 1).min(slots.len());
+            slots.drain(first.min(end)..end);
+        }
+    }
+}
+
+impl Document {
+    /// Replaces the channel `channel` with `annotations`, given as rows and
+    /// their annotations. Rows not listed have no annotation. Returns `Err`
+    /// if any row does not exist.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::annotations::*;
+    /// let mut document = Document::from("a\nb\nc");
+    /// document.set_annotations("blame", &[
+    ///     (0, LineAnnotation::from("alice", "author")),
+    ///     (2, LineAnnotation::from("bob", "author"))
+    /// ]).unwrap();
+    ///
+    /// document.insert("new\n", &InsertOptions::exact_at(&Range::from(1, 0, 1, 0))).unwrap();
+    /// assert_eq!(document.line_annotation("blame", 1), None);
+    /// assert_eq!(document.line_annotation("blame", 3).unwrap().text, "bob");
+    ///
+    /// document.undo_once().unwrap();
+    /// assert_eq!(document.line_annotation("blame", 2).unwrap().text, "bob");
+    /// ```
+    pub fn set_annotations(&mut self, channel: &str, annotations: &[(usize, LineAnnotation)]) -> Result<(), Oops> {
+        let mut slots = vec![None; self.rows()];
+        for (row, annotation) in annotations {
+            *slots.get_mut(*row).ok_or(Oops::InvalidIndex(*row, "set_annotations"))? = Some(annotation.clone());
+        }
+
+        self.annotations.channels.insert(String::from(channel), slots);
+        self.damage.record_from(0);
+        Ok(())
+    }
+
+    /// Sets or, if `annotation` is `None`, removes the annotation on `row` in
+    /// `channel`, creating the channel if needed. Returns `Err` if `row` does
+    /// not exist.
+    pub fn set_line_annotation(&mut self, channel: &str, row: usize, annotation: Option<LineAnnotation>) -> Result<(), Oops> {
+        if row >= self.rows() {
+            return Err(Oops::InvalidIndex(row, "set_line_annotation"));
+        }
+
+        let rows = self.rows();
+        let slots = self.annotations.channels.entry(String::from(channel)).or_insert_with(|| vec![None; rows]);
+        slots[row] = annotation;
+        self.damage.record(row, row);
+        Ok(())
+    }
+
+    /// Returns the annotation on `row` in `channel`, if any.
+    pub fn line_annotation(&self, channel: &str, row: usize) -> Option<&LineAnnotation> {
+        self.annotations.channels.get(channel)?.get(row)?.as_ref()
+    }
+
+    /// Removes the channel `channel`. Returns false if there was none.
+    pub fn clear_annotations(&mut self, channel: &str) -> bool {
+        let removed = self.annotations.channels.remove(channel).is_some();
+        if removed {
+            self.damage.record_from(0);
+        }
+        removed
+    }
+
+    /// Returns the names of every channel, in alphabetical order.
+    pub fn annotation_channels(&self) -> Vec<&str> {
+        self.annotations.channels.keys().map(String::as_str).collect()
+    }
+
+    /// Returns the annotations on `row` with their channels, in alphabetical
+    /// order of channel.
+    pub fn annotations_on_row(&self, row: usize) -> Vec<(&str, &LineAnnotation)> {
+        self.annotations.channels
+            .iter()
+            .filter_map(|(channel, slots)| slots.get(row)?.as_ref().map(|a| (channel.as_str(), a)))
+            .collect()
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::*;
+
+    #[test]
+    fn annotations_follow_lines() {
+        let mut document = Document::from("zero\none\ntwo\nthree\nfour");
+        let counts: Vec<(usize, LineAnnotation)> = (0..5)
+            .map(|row| (row, LineAnnotation::from(&row.to_string(), "count")))
+            .collect();
+        document.set_annotations("profile", &counts).unwrap();
+        assert!(document.set_line_annotation("profile", 9, None).is_err());
+        let texts = |document: &Document| -> Vec<Option<String>> {
+            (0..document.rows()).map(|row| document.line_annotation("profile", row).map(|a| a.text.clone())).collect()
+        };
+        let some = |s: &str| Some(String::from(s));
+
+        document.insert("\n", &InsertOptions::exact_at(&Range::from(1, 3, 1, 3))).unwrap();
+        assert_eq!(texts(&document), vec![some("0"), some("1"), None, some("2"), some("3"), some("4")]);
+
+        document.remove(&RemoveOptions::exact_at(&Range::from(3, 0, 5, 0))).unwrap();
+        assert_eq!(document.text(), "zero\none\n\nfour");
+        assert_eq!(texts(&document), vec![some("0"), some("1"), None, some("4")]);
+
+        document.remove(&RemoveOptions::exact_at(&Range::from(0, 2, 1, 1))).unwrap();
+        assert_eq!(texts(&document), vec![some("0"), None, some("4")]);
+
+        document.set_line_annotation("blame", 2, Some(LineAnnotation::from("carol", "author"))).unwrap();
+        assert_eq!(document.annotation_channels(), vec!["blame", "profile"]);
+        assert_eq!(document.annotations_on_row(2).len(), 2);
+        assert!(document.clear_annotations("profile"));
+        assert!(!document.clear_annotations("profile"));
+        assert_eq!(document.viewport(2, 1).lines[0].gutter, vec![
+            (String::from("blame"), LineAnnotation::from("carol", "author"))
+        ]);
+    }
+}
@@ -0,0 +1,367 @@
+//! Auto-closing pairs: typing an opening bracket or quote also inserts its
+//! closing counterpart.
+//!
+//! Which pairs apply is data-driven. Each language has a [`PairTable`] of
+//! [`Pair`]s, and each pair has [`Condition`]s which are checked against
+//! the text and the parse tree before it is inserted, so that, for instance,
+//! quotes are not paired inside strings and `<` is only paired after a type
+//! name. Tables can be replaced per language with
+//! [`Document::set_pair_table`].
+
+use std::borrow::Cow;
+
+use crate::document::{Document, Anchor, AnchorHandle, Position, Range, InsertOptions, RemoveOptions};
+use crate::util::{self, Oops};
+
+/// A requirement which must hold at the cursor for a [`Pair`] to be
+/// inserted.
+///
+/// Without a parse tree, `NotInString` and `NotInComment` always hold.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum Condition {
+    /// The cursor is not inside a string literal.
+    NotInString,
+    /// The cursor is not inside a comment.
+    NotInComment,
+    /// The opening text directly follows a word character (see
+    /// [`Document::is_word_char`]), as in `Vec<`.
+    AfterWord,
+    /// The opening text does not directly follow a word character, as in
+    /// `don't`.
+    NotAfterWord,
+}
+
+/// Opening text which, when typed, is completed by `close`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Pair {
+    pub open: String,
+    pub close: String,
+    pub conditions: Vec<Condition>
+}
+
+/// The pairs which apply to a language.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct PairTable {
+    pub pairs: Vec<Pair>
+}
+
+impl Pair {
+    /// Returns the pair `open`/`close`, inserted when all of `conditions` hold.
+    pub fn from(open: &str, close: &str, conditions: &[Condition]) -> Pair {
+        Pair {
+            open: String::from(open),
+            close: String::from(close),
+            conditions: conditions.to_vec()
+        }
+    }
+}
+
+impl PairTable {
+    /// Returns a table with no pairs.
+    pub fn new() -> PairTable {
+        PairTable { pairs: vec![] }
+    }
+
+    /// Returns the built-in table for `language`. Unknown languages get
+    /// brackets and double quotes.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::autopair::*;
+    /// let rust = PairTable::for_language("rs");
+    /// assert!(rust.pairs.iter().any(|p| p.open == "<"));
+    /// assert!(!rust.pairs.iter().any(|p| p.open == "'"));
+    ///
+    /// let python = PairTable::for_language("py");
+    /// assert!(python.pairs.iter().any(|p| p.open == "\"\"\""));
+    /// ```
+    pub fn for_language(language: &str) -> PairTable {
+        use Condition::*;
+        let code = [NotInString, NotInComment];
+        let quote = [NotInString, NotInComment, NotAfterWord];
+        let generic = [NotInString, NotInComment, AfterWord];
+
+        let mut table = PairTable::new();
+
+        if language == "md" {
+            table.add(Pair::from("(", ")", &[]));
+            table.add(Pair::from("[", "]", &[]));
+            table.add(Pair::from("`", "`", &[NotAfterWord]));
+            table.add(Pair::from("```", "```", &[]));
+            return table;
+        }
+
+        table.add(Pair::from("(", ")", &code));
+        table.add(Pair::from("[", "]", &code));
+        table.add(Pair::from("{", "}", &code));
+        table.add(Pair::from("\"", "\"", &quote));
+
+        match language {
+            "rs" | "cpp" | "java" | "ts" | "tsx" => {
+                table.add(Pair::from("<", ">", &generic));
+            },
+            _ => ()
+        }
+
+        match language {
+            "rs" => (),
+            "py" => {
+                table.add(Pair::from("'", "'", &quote));
+                table.add(Pair::from("\"\"\"", "\"\"\"", &[NotInComment]));
+                table.add(Pair::from("'''", "'''", &[NotInComment]));
+            },
+            "js" | "ts" | "tsx" => {
+                table.add(Pair::from("'", "'", &quote));
+                table.add(Pair::from("`", "`", &quote));
+            },
+            _ => {
+                table.add(Pair::from("'", "'", &quote));
+            }
+        }
+
+        table
+    }
+
+    /// Adds `pair` to this table.
+    pub fn add(&mut self, pair: Pair) {
+        self.pairs.push(pair);
+    }
+}
+
+impl Document {
+    /// Replaces the pair table used for documents with language `language`,
+    /// or restores the built-in table if `table` is `None`.
+    pub fn set_pair_table(&mut self, language: &str, table: Option<PairTable>) {
+        match table {
+            Some(table) => { self.pair_tables.insert(String::from(language), table); },
+            None => { self.pair_tables.remove(language); }
+        }
+    }
+
+    /// Returns the pair table for this document's language.
+    pub fn pair_table(&self) -> Cow<'_, PairTable> {
+        match self.pair_tables.get(self.language()) {
+            Some(table) => Cow::Borrowed(table),
+            None => Cow::Owned(PairTable::for_language(self.language()))
+        }
+    }
+
+    /// Types `c` at the cursor, replacing the selection, with auto-pairing:
+    ///
+    /// * if `c` is the next character and closes a pair, the cursor steps
+    ///   over it instead;
+    /// * if `c` completes the opening text of a pair whose conditions hold,
+    ///   the closing text is inserted after the cursor.
+    ///
+    /// If auto-pairs are switched off in the [configuration](crate::config),
+    /// `c` is simply inserted.
+    ///
+    /// Like [`Document::insert`], this does not checkpoint.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language("", "rs");
+    /// for c in "f(x".chars() {
+    ///     document.type_char(c).unwrap();
+    /// }
+    /// assert_eq!(document.text(), "f(x)");
+    /// document.type_char(')').unwrap();
+    /// assert_eq!(document.text(), "f(x)");
+    /// assert_eq!(document.cursor().position, Position::from(0, 4));
+    /// ```
+    pub fn type_char(&mut self, c: char) -> Result<(), Oops> {
+        let text = c.to_string();
+
+        if !self.selection().empty() || !self.config().auto_pairs {
+            return self.insert(&text, &InsertOptions::exact());
+        }
+
+        let cursor = self.cursor().position;
+        let line = &self.lines()[cursor.row].content;
+        let before = String::from(util::slice(line, ..cursor.column)) + &text;
+        let after = String::from(util::slice(line, cursor.column..));
+
+        let table = self.pair_table().into_owned();
+
+        if after.starts_with(c) && table.pairs.iter().any(|p| p.close.starts_with(c)) {
+            return self.set_cursor_and_mark(&Position::from(cursor.row, cursor.column + 1));
+        }
+
+        let mut candidates: Vec<&Pair> = table.pairs.iter()
+            .filter(|p| before.ends_with(p.open.as_str()))
+            .collect();
+        candidates.sort_by_key(|p| std::cmp::Reverse(p.open.chars().count()));
+
+        for pair in candidates {
+            let open_start = cursor.column + 1 - pair.open.chars().count();
+            if self.pair_conditions_hold(pair, &Position::from(cursor.row, open_start)) {
+                self.insert(&(text + &pair.close), &InsertOptions::exact())?;
+                return self.set_cursor_and_mark(&Position::from(cursor.row, cursor.column + 1));
+            }
+        }
+
+        self.insert(&text, &InsertOptions::exact())
+    }
+
+    /// If the cursor sits between the opening and closing text of a pair,
+    /// removes both and returns `true`. Otherwise does nothing and returns
+    /// `false`.
+    pub fn remove_empty_pair(&mut self) -> Result<bool, Oops> {
+        if !self.selection().empty() {
+            return Ok(false);
+        }
+
+        let cursor = self.cursor().position;
+        let line = &self.lines()[cursor.row].content;
+        let before = util::slice(line, ..cursor.column);
+        let after = util::slice(line, cursor.column..);
+
+        let found = self.pair_table().pairs.iter()
+            .filter(|p| before.ends_with(p.open.as_str()) && after.starts_with(p.close.as_str()))
+            .map(|p| (p.open.chars().count(), p.close.chars().count()))
+            .max();
+
+        match found {
+            None => Ok(false),
+            Some((open, close)) => {
+                self.remove(&RemoveOptions::exact_at(&Range::from(
+                    cursor.row, cursor.column - open, cursor.row, cursor.column + close
+                )))?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Wraps `range`, or the selection if `range` is `None`, in `left` and
+    /// `right` as one undoable [`ChangePacket`](crate::document::ChangePacket).
+    /// Anchors inside the range, ends included, stay with the text they are
+    /// on, so the selection still covers the wrapped text and an empty range
+    /// leaves the cursor between the delimiters. Returns `Err` if `range` is
+    /// invalid or both delimiters are empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("call x + 1 now");
+    /// document.set_selection(&Range::from(0, 5, 0, 10)).unwrap();
+    /// document.surround(None, "(", ")").unwrap();
+    /// assert_eq!(document.text(), "call (x + 1) now");
+    /// assert_eq!(document.selection(), Range::from(0, 6, 0, 11));
+    ///
+    /// document.undo_once().unwrap();
+    /// assert_eq!(document.text(), "call x + 1 now");
+    /// ```
+    pub fn surround(&mut self, range: Option<&Range>, left: &str, right: &str) -> Result<(), Oops> {
+        let range = match range {
+            None => self.selection(),
+            Some(r) if self.range_valid(r) => *r,
+            Some(r) => return Err(Oops::InvalidRange(*r, "surround"))
+        };
+        if left.is_empty() && right.is_empty() {
+            return Err(Oops::EmptyString("surround with nothing"));
+        }
+
+        let at_end: Vec<AnchorHandle> = self.anchors()
+            .filter(|(_, anchor)| anchor.position == range.ending)
+            .map(|(handle, _)| *handle)
+            .collect();
+
+        self.with_packet("surround", |document| {
+            if !right.is_empty() {
+                document.insert(right, &InsertOptions::exact_at(&Range { beginning: range.ending, ending: range.ending }))?;
+                for handle in at_end {
+                    document.set_anchor(handle, &Anchor { position: range.ending })?;
+                }
+            }
+            if !left.is_empty() {
+                document.insert(left, &InsertOptions::exact_at(&Range { beginning: range.beginning, ending: range.beginning }))?;
+            }
+            Ok(())
+        })
+    }
+
+    fn pair_conditions_hold(&self, pair: &Pair, open_start: &Position) -> bool {
+        let previous = if open_start.column == 0 {
+            None
+        } else {
+            self.lines()[open_start.row].content.chars().nth(open_start.column - 1)
+        };
+        let after_word = previous.is_some_and(|c| self.is_word_char(c));
+
+        pair.conditions.iter().all(|condition| match condition {
+            Condition::NotInString => !self.is_in_string(open_start),
+            Condition::NotInComment => !self.is_in_comment(open_start),
+            Condition::AfterWord => after_word,
+            Condition::NotAfterWord => !after_word
+        })
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_str(document: &mut Document, text: &str) {
+        for c in text.chars() {
+            document.type_char(c).unwrap();
+        }
+    }
+
+    #[test]
+    fn pairs_follow_table_and_context() {
+        let mut document = Document::from("");
+        type_str(&mut document, "don't (");
+        assert_eq!(document.text(), "don't ()");
+        assert!(document.remove_empty_pair().unwrap());
+        assert_eq!(document.text(), "don't ");
+        assert!(!document.remove_empty_pair().unwrap());
+
+        let mut table = PairTable::new();
+        table.add(Pair::from("<<", ">>", &[]));
+        document.set_pair_table("", Some(table));
+        type_str(&mut document, "(<<");
+        assert_eq!(document.text(), "don't (<<>>");
+
+        document.set_pair_table("", None);
+        assert_eq!(document.pair_table().into_owned(), PairTable::for_language(""));
+    }
+
+    #[test]
+    fn surround_keeps_anchors_outside_and_moves_the_cursor_inside() {
+        let mut document = Document::from("a\nb");
+        let after = document.create_anchor(&Anchor { position: Position::from(1, 1) }).unwrap();
+        document.surround(Some(&Range::from(0, 1, 0, 1)), "/* ", "\n */").unwrap();
+        assert_eq!(document.text(), "a/* \n */\nb");
+        assert_eq!(document.cursor().position, Position::from(0, 0));
+        assert_eq!(document.anchor(after).unwrap().position, Position::from(2, 1));
+        document.set_cursor_and_mark(&Position::from(2, 1)).unwrap();
+        document.surround(None, "\"", "\"").unwrap();
+        assert_eq!(document.cursor().position, Position::from(2, 2));
+        assert!(document.surround(None, "", "").is_err());
+        assert!(document.surround(Some(&Range::from(5, 0, 5, 0)), "(", ")").is_err());
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn context_predicates() {
+        let mut document = Document::from_with_language("", "rs");
+        type_str(&mut document, "let v: Vec<u8");
+        assert_eq!(document.text(), "let v: Vec<u8>");
+
+        let mut document = Document::from_with_language("", "rs");
+        type_str(&mut document, "a < \"x(");
+        assert_eq!(document.text(), "a < \"x(\"");
+
+        let mut document = Document::from_with_language("", "py");
+        type_str(&mut document, "\"\"\"");
+        assert_eq!(document.text(), "\"\"\"\"\"\"");
+        assert_eq!(document.cursor().position, Position::from(0, 3));
+
+        let mut document = Document::from_with_language("", "py");
+        type_str(&mut document, "# it's [");
+        assert_eq!(document.text(), "# it's [");
+    }
+}
@@ -0,0 +1,250 @@
+//! Block (rectangular) selections, for editing aligned tables and
+//! repetitive argument lists a column at a time.
+//!
+//! A [`Block`] spans a run of rows between two display columns, where tabs
+//! advance to the next multiple of the document's
+//! [`Indentation::spaces_per_tab`](crate::document::Indentation). A
+//! character is in the block if it starts inside it, so a tab straddling
+//! the left edge is left out and one straddling the right edge is taken in.
+//!
+//! [`Document::block_insert`], [`Document::block_remove`], and
+//! [`Document::block_replace`] edit every row of a block as one undoable
+//! packet. Rows too short to reach the block are padded with spaces when
+//! text is inserted into them, and left alone otherwise. In block mode, the
+//! mark and cursor are the corners of the [selected block](Document::block_selection),
+//! and each block edit selects the block it leaves behind.
+
+use crate::document::{Document, Position, Range};
+use crate::util::Oops;
+
+/// A rectangle of text: rows `first_row` through `last_row`, and display
+/// columns from `left` up to but not including `right`.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub struct Block {
+    pub first_row: usize,
+    pub last_row: usize,
+    pub left: usize,
+    pub right: usize
+}
+
+impl Block {
+    /// Returns the number of rows in the block.
+    pub fn rows(&self) -> usize {
+        self.last_row - self.first_row + 1
+    }
+
+    /// Returns the number of display columns in the block.
+    pub fn width(&self) -> usize {
+        self.right - self.left
+    }
+}
+
+/// Returns the display column after `c` is drawn at `column`.
+fn advance(column: usize, c: char, tab: usize) -> usize {
+    if c == '\t' { (column / tab + 1) * tab } else { column + 1 }
+}
+
+impl Document {
+    /// Returns true if the document is in block mode.
+    pub fn block_mode(&self) -> bool {
+        self.block_mode
+    }
+
+    /// Turns block mode on or off. The mark and cursor stay where they are.
+    pub fn set_block_mode(&mut self, on: bool) {
+        self.block_mode = on;
+    }
+
+    /// Returns the display column of `position`, or `None` if the position
+    /// isn't in the document.
+    pub fn display_column(&self, position: &Position) -> Option<usize> {
+        let tab = self.indentation().spaces_per_tab;
+        let line = self.lines().get(position.row)?;
+        if position.column > line.length {
+            return None;
+        }
+        Some(line.content.chars().take(position.column).fold(0, |column, c| advance(column, c, tab)))
+    }
+
+    /// Returns the column of the first character on `row` starting at or
+    /// after display column `display`, and how many display columns short of
+    /// `display` the row ends if it doesn't reach it.
+    fn column_at_display(&self, row: usize, display: usize) -> (usize, usize) {
+        let tab = self.indentation().spaces_per_tab;
+        let mut width = 0;
+        for (column, c) in self.lines()[row].content.chars().enumerate() {
+            if width >= display {
+                return (column, 0);
+            }
+            width = advance(width, c, tab);
+        }
+        (self.lines()[row].length, display.saturating_sub(width))
+    }
+
+    /// Returns the block with corners at `a` and `b`, or `Err` if either
+    /// isn't in the document.
+    pub fn block_between(&self, a: &Position, b: &Position) -> Result<Block, Oops> {
+        let left = self.display_column(a).ok_or(Oops::InvalidPosition(*a, "block_between"))?;
+        let right = self.display_column(b).ok_or(Oops::InvalidPosition(*b, "block_between"))?;
+        Ok(Block {
+            first_row: a.row.min(b.row),
+            last_row: a.row.max(b.row),
+            left: left.min(right),
+            right: left.max(right)
+        })
+    }
+
+    /// Returns the block between the mark and the cursor, if the document is
+    /// in block mode.
+    pub fn block_selection(&self) -> Option<Block> {
+        if !self.block_mode {
+            return None;
+        }
+        self.block_between(&self.mark().position, &self.cursor().position).ok()
+    }
+
+    /// Returns the text range the block covers on each of its rows, skipping
+    /// rows which end before its left edge.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let document = Document::from("a | b\nlonger | c\nx\nd | e");
+    /// let block = document.block_between(&Position::from(0, 2), &Position::from(3, 4)).unwrap();
+    /// assert_eq!((block.rows(), block.width()), (4, 2));
+    /// assert_eq!(document.block_ranges(&block), vec![
+    ///     Range::from(0, 2, 0, 4), Range::from(1, 2, 1, 4), Range::from(3, 2, 3, 4)
+    /// ]);
+    /// ```
+    pub fn block_ranges(&self, block: &Block) -> Vec<Range> {
+        (block.first_row..=block.last_row.min(self.rows().saturating_sub(1)))
+            .filter_map(|row| {
+                let (beginning, short) = self.column_at_display(row, block.left);
+                let (ending, _) = self.column_at_display(row, block.right);
+                if short == 0 {
+                    Some(Range::from(row, beginning, row, ending))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Inserts `text` at the left edge of every row of `block` as one
+    /// undoable packet, padding rows which end before it. If `text` has as
+    /// many lines as the block has rows, each row gets its own line;
+    /// otherwise it must be one line, which every row gets. Returns the block
+    /// spanning the inserted text, or `Err` without changing anything if the
+    /// block runs past the end of the document or the lines don't fit.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("f(a, b);\nf(c);\nf(d, e);");
+    /// let column = document.block_between(&Position::from(0, 2), &Position::from(2, 2)).unwrap();
+    ///
+    /// let inserted = document.block_insert(&column, "ctx, ").unwrap();
+    /// assert_eq!(document.text(), "f(ctx, a, b);\nf(ctx, c);\nf(ctx, d, e);");
+    /// assert_eq!((inserted.left, inserted.right), (2, 7));
+    ///
+    /// document.block_replace(&inserted, "one, \ntwo, \nthree, ").unwrap();
+    /// assert_eq!(document.text(), "f(one, a, b);\nf(two, c);\nf(three, d, e);");
+    ///
+    /// document.undo_once().unwrap();
+    /// document.undo_once().unwrap();
+    /// assert_eq!(document.text(), "f(a, b);\nf(c);\nf(d, e);");
+    /// ```
+    pub fn block_insert(&mut self, block: &Block, text: &str) -> Result<Block, Oops> {
+        let at = Block { right: block.left, ..*block };
+        self.block_edit(&at, text, "block insert")
+    }
+
+    /// Removes the text in `block` from each of its rows as one undoable
+    /// packet, and returns the empty block left at its left edge.
+    pub fn block_remove(&mut self, block: &Block) -> Result<Block, Oops> {
+        self.block_edit(block, "", "block remove")
+    }
+
+    /// Replaces the text in `block` on each of its rows with `text` (split
+    /// across rows as for [`Document::block_insert`]) as one undoable packet,
+    /// and returns the block spanning the new text.
+    pub fn block_replace(&mut self, block: &Block, text: &str) -> Result<Block, Oops> {
+        self.block_edit(block, text, "block replace")
+    }
+
+    /// Replaces the text in `block` with `text` row by row in a packet
+    /// labeled `label`.
+    fn block_edit(&mut self, block: &Block, text: &str, label: &'static str) -> Result<Block, Oops> {
+        if block.last_row >= self.rows() {
+            return Err(Oops::InvalidIndex(block.last_row, label));
+        }
+        let lines: Vec<&str> = text.split('\n').collect();
+        if lines.len() != 1 && lines.len() != block.rows() {
+            return Err(Oops::Ouch("block text must be one line, or one line per row"));
+        }
+
+        let tab = self.indentation().spaces_per_tab;
+        let mut edits = vec![];
+        let mut right = block.left;
+        for (i, row) in (block.first_row..=block.last_row).enumerate() {
+            let line = lines[if lines.len() == 1 { 0 } else { i }];
+            right = right.max(line.chars().fold(block.left, |column, c| advance(column, c, tab)));
+
+            let (beginning, short) = self.column_at_display(row, block.left);
+            if short > 0 && line.is_empty() {
+                continue;
+            }
+            let (ending, _) = self.column_at_display(row, block.right);
+            let replacement = format!("{}{}", " ".repeat(short), line);
+            let range = Range::from(row, beginning, row, ending);
+            if !range.empty() || !replacement.is_empty() {
+                edits.push((range, replacement));
+            }
+        }
+
+        let result = Block { right, ..*block };
+        self.with_packet(label, |document| {
+            document.apply_edits(edits)?;
+            if document.block_mode {
+                let (left, _) = document.column_at_display(result.first_row, result.left);
+                let (right, _) = document.column_at_display(result.last_row, result.right);
+                document.set_mark(&Position::from(result.first_row, left))?;
+                document.set_cursor(&Position::from(result.last_row, right))?;
+            }
+            Ok(())
+        })?;
+        Ok(result)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_edits_pad_short_rows_and_follow_the_selection() {
+        let mut document = Document::from("a\tb\nxy\n\nabcdefgh");
+        document.set_block_mode(true);
+        document.set_mark(&Position::from(0, 2)).unwrap();
+        document.set_cursor(&Position::from(3, 6)).unwrap();
+
+        let block = document.block_selection().unwrap();
+        assert_eq!(block, Block { first_row: 0, last_row: 3, left: 4, right: 6 });
+        assert_eq!(document.block_ranges(&block), vec![Range::from(0, 2, 0, 3), Range::from(3, 4, 3, 6)]);
+
+        let depth = document.undo_redo().depth().0;
+        let removed = document.block_remove(&block).unwrap();
+        assert_eq!(document.text(), "a\t\nxy\n\nabcdgh");
+        assert_eq!(document.undo_redo().depth().0, depth + 1);
+
+        document.block_insert(&removed, "|").unwrap();
+        assert_eq!(document.text(), "a\t|\nxy  |\n    |\nabcd|gh");
+        assert_eq!(document.selection(), Range::from(0, 2, 3, 5));
+        assert!(document.block_insert(&removed, "1\n2").is_err());
+
+        document.set_block_mode(false);
+        assert_eq!(document.block_selection(), None);
+    }
+}
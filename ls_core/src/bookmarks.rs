@@ -0,0 +1,212 @@
+//! Bookmarks: labeled places in a document, with optional notes, which can
+//! be listed ("list bookmarks"), stepped through ("next bookmark"), and saved
+//! with a session.
+//!
+//! Like [named anchors](crate::named_anchors), each bookmark is held by a
+//! floating anchor, so it follows the text as it is edited and changes to
+//! bookmarks are not undoable. Unlike them, bookmarks carry a free-form
+//! label and note and are meant to outlive the editing session: see
+//! [`Document::restore_bookmarks`].
+
+use std::collections::BTreeMap;
+
+use crate::document::{Document, Position, AnchorHandle};
+use crate::util::Oops;
+
+/// A bookmark as listed to clients and saved with a session.
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bookmark {
+    pub label: String,
+    pub position: Position,
+    #[cfg_attr(feature = "serialization", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub note: Option<String>
+}
+
+/// A [`Bookmark`] as stored in a document: its position is tracked by a
+/// floating anchor.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub(crate) struct AnchoredBookmark {
+    anchor: AnchorHandle,
+    note: Option<String>
+}
+
+/// A document's bookmarks, by label.
+pub(crate) type Bookmarks = BTreeMap<String, AnchoredBookmark>;
+
+impl Document {
+    /// Bookmarks `position` with `label` and an optional `note`, replacing
+    /// any bookmark with the same label. Returns `Err` if `position` is
+    /// invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("fn main() {}");
+    /// document.add_bookmark("entry", &Position::from(0, 3), Some("start here")).unwrap();
+    /// document.insert("pub ", &InsertOptions::exact()).unwrap();
+    /// let bookmark = document.bookmark("entry").unwrap();
+    /// assert_eq!(bookmark.position, Position::from(0, 7));
+    /// assert_eq!(bookmark.note.as_deref(), Some("start here"));
+    /// ```
+    pub fn add_bookmark(&mut self, label: &str, position: &Position, note: Option<&str>) -> Result<(), Oops> {
+        let anchor = self.create_floating_anchor(position)?;
+        let bookmark = AnchoredBookmark { anchor, note: note.map(String::from) };
+        if let Some(old) = self.bookmarks.insert(String::from(label), bookmark) {
+            self.remove_floating_anchor(old.anchor)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the bookmark labeled `label`, if any.
+    pub fn bookmark(&self, label: &str) -> Option<Bookmark> {
+        self.bookmarks.get_key_value(label).map(|(label, b)| self.listed_bookmark(label, b))
+    }
+
+    /// Removes the bookmark labeled `label`. Returns false if there was none.
+    pub fn remove_bookmark(&mut self, label: &str) -> bool {
+        match self.bookmarks.remove(label) {
+            None => false,
+            Some(bookmark) => {
+                let _ = self.remove_floating_anchor(bookmark.anchor);
+                true
+            }
+        }
+    }
+
+    /// Removes every bookmark, returning how many there were.
+    pub fn clear_bookmarks(&mut self) -> usize {
+        let bookmarks = std::mem::take(&mut self.bookmarks);
+        for bookmark in bookmarks.values() {
+            let _ = self.remove_floating_anchor(bookmark.anchor);
+        }
+        bookmarks.len()
+    }
+
+    /// Returns every bookmark in document order, ties broken by label.
+    pub fn bookmarks(&self) -> Vec<Bookmark> {
+        let mut result: Vec<Bookmark> = self.bookmarks
+            .iter()
+            .map(|(label, b)| self.listed_bookmark(label, b))
+            .collect();
+        result.sort_by(|a, b| (a.position, &a.label).cmp(&(b.position, &b.label)));
+        result
+    }
+
+    fn listed_bookmark(&self, label: &str, bookmark: &AnchoredBookmark) -> Bookmark {
+        Bookmark {
+            label: String::from(label),
+            position: self.anchor(bookmark.anchor).unwrap().position,
+            note: bookmark.note.clone()
+        }
+    }
+
+    /// Moves the cursor and mark to the first bookmark after the cursor when
+    /// going `forward`, or the last one before it otherwise, wrapping around
+    /// the document. Returns that bookmark, or `Err` if there are no
+    /// bookmarks.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("a\nb\nc");
+    /// document.add_bookmark("one", &Position::from(0, 1), None).unwrap();
+    /// document.add_bookmark("two", &Position::from(2, 0), None).unwrap();
+    /// document.set_cursor_and_mark(&Position::from(1, 0)).unwrap();
+    /// assert_eq!(document.go_to_next_bookmark(true).unwrap().label, "two");
+    /// assert_eq!(document.go_to_next_bookmark(true).unwrap().label, "one");
+    /// assert_eq!(document.go_to_next_bookmark(false).unwrap().label, "two");
+    /// ```
+    pub fn go_to_next_bookmark(&mut self, forward: bool) -> Result<Bookmark, Oops> {
+        let cursor = self.cursor().position;
+        let bookmarks = self.bookmarks();
+
+        let found = if forward {
+            bookmarks.iter().find(|b| b.position > cursor).or_else(|| bookmarks.first())
+        } else {
+            bookmarks.iter().rev().find(|b| b.position < cursor).or_else(|| bookmarks.last())
+        };
+
+        let bookmark = found.cloned().ok_or(Oops::Ouch("no bookmarks"))?;
+        self.set_cursor_and_mark(&bookmark.position)?;
+        Ok(bookmark)
+    }
+
+    /// Replaces every bookmark with `bookmarks`, as saved from an earlier
+    /// session by [`Document::bookmarks`]. The document may have changed
+    /// since, so positions past the end of a line or of the document are
+    /// clamped to it.
+    pub fn restore_bookmarks(&mut self, bookmarks: &[Bookmark]) -> Result<(), Oops> {
+        self.clear_bookmarks();
+
+        for bookmark in bookmarks {
+            let row = bookmark.position.row.min(self.rows() - 1);
+            let column = bookmark.position.column.min(self.lines()[row].length);
+            self.add_bookmark(&bookmark.label, &Position::from(row, column), bookmark.note.as_deref())?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the bookmarks as JSON, for saving with a session.
+    #[cfg(feature = "serialization")]
+    pub fn bookmarks_to_json(&self) -> String {
+        serde_json::to_string(&self.bookmarks()).unwrap()
+    }
+
+    /// Restores bookmarks saved by [`Document::bookmarks_to_json`]. See
+    /// [`Document::restore_bookmarks`]. Returns `Err` if `json` is not a list
+    /// of bookmarks.
+    #[cfg(feature = "serialization")]
+    pub fn restore_bookmarks_json(&mut self, json: &str) -> Result<(), Oops> {
+        let bookmarks: Vec<Bookmark> = serde_json::from_str(json)
+            .map_err(|_| Oops::InvalidEncoding("restore_bookmarks_json"))?;
+        self.restore_bookmarks(&bookmarks)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::*;
+
+    #[test]
+    fn bookmarks_survive_edits_and_sessions() {
+        let mut document = Document::from("one\ntwo\nthree");
+        document.add_bookmark("b", &Position::from(2, 5), None).unwrap();
+        document.add_bookmark("a", &Position::from(1, 1), Some("second")).unwrap();
+        assert!(document.add_bookmark("c", &Position::from(9, 0), None).is_err());
+
+        document.remove(&RemoveOptions::exact_at(&Range::from(0, 0, 1, 0))).unwrap();
+        let saved = document.bookmarks();
+        assert_eq!(saved.iter().map(|b| b.position).collect::<Vec<_>>(), vec![
+            Position::from(0, 1), Position::from(1, 5)
+        ]);
+
+        let mut restored = Document::from("tw");
+        restored.restore_bookmarks(&saved).unwrap();
+        assert_eq!(restored.bookmark("b").unwrap().position, Position::from(0, 2));
+        assert_eq!(restored.bookmark("a").unwrap().note.as_deref(), Some("second"));
+
+        assert!(restored.remove_bookmark("a"));
+        assert!(!restored.remove_bookmark("a"));
+        assert_eq!(restored.clear_bookmarks(), 1);
+        assert_eq!(restored.anchors().len(), 2);
+        assert!(restored.go_to_next_bookmark(true).is_err());
+    }
+
+    #[cfg(feature = "serialization")]
+    #[test]
+    fn bookmarks_round_trip_through_json() {
+        let mut document = Document::from("abc");
+        document.add_bookmark("here", &Position::from(0, 2), None).unwrap();
+        let json = document.bookmarks_to_json();
+
+        let mut restored = Document::from("abc");
+        restored.restore_bookmarks_json(&json).unwrap();
+        assert_eq!(restored.bookmarks(), document.bookmarks());
+        assert!(restored.restore_bookmarks_json("{").is_err());
+    }
+}
@@ -0,0 +1,199 @@
+//! Capitalizing inserted prose to fit the text around it.
+//!
+//! [`InsertOptions::capitalization`] picks a [`Capitalization`]. Sentence
+//! and title capitalization look backwards from the insertion point to
+//! decide whether the inserted text begins a sentence: it does at the start
+//! of the document or of a paragraph, after `.`, `!`, or `?` (and any
+//! closing quotes or brackets), and right after a line comment marker such
+//! as `//` or `#`. Otherwise the first word is left as dictated, so "and
+//! then" inserted mid-sentence stays lowercase.
+//!
+//! Capitalization only ever raises letters to upper case; it never lowers
+//! them, so acronyms and names dictated in capitals survive.
+
+use crate::document::{Document, Position};
+
+/// How inserted text is capitalized.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default)]
+pub enum Capitalization {
+    /// Leave the text as it is.
+    #[default]
+    None,
+
+    /// Capitalize the first letter of each sentence, including the first
+    /// word if the insertion point begins a sentence.
+    Sentence,
+
+    /// Capitalize every word but short articles, conjunctions and
+    /// prepositions, which are capitalized only when they begin a sentence.
+    Title,
+
+    /// Capitalize every letter.
+    Upper
+}
+
+/// Characters which end a sentence.
+const TERMINATORS: &[char] = &['.', '!', '?'];
+
+/// Quotes and brackets, which are looked through to find a terminator.
+const QUOTES_AND_BRACKETS: &[char] = &['"', '\'', '\u{201c}', '\u{201d}', '\u{2018}', '\u{2019}', '(', ')', '[', ']', '{', '}'];
+
+/// Line comment markers, after which a sentence begins.
+const COMMENT_MARKERS: &[&str] = &["//", "///", "//!", "/*", "/**", "*", "#", "--", ";", ";;", "%"];
+
+/// Words a title leaves in lower case unless they begin it.
+const SMALL_WORDS: &[&str] = &[
+    "a", "an", "the", "and", "but", "or", "nor", "for", "so", "yet",
+    "as", "at", "by", "in", "of", "on", "to", "up", "via"
+];
+
+/// Returns `word` with its first letter in upper case.
+fn capitalize_word(word: &str) -> String {
+    match word.char_indices().find(|(_, c)| c.is_alphabetic()) {
+        Some((i, c)) => format!("{}{}{}", &word[..i], c.to_uppercase(), &word[i + c.len_utf8()..]),
+        None => String::from(word)
+    }
+}
+
+/// Returns `text` with each sentence's first letter in upper case; the
+/// first sentence only if `starts` is true.
+fn sentence_case(text: &str, starts: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut capitalize = starts;
+    let mut ended = false;
+
+    for c in text.chars() {
+        if capitalize && c.is_alphabetic() {
+            result.extend(c.to_uppercase());
+            capitalize = false;
+            continue;
+        }
+        if c.is_alphanumeric() {
+            capitalize = false;
+        }
+        result.push(c);
+
+        if TERMINATORS.contains(&c) {
+            ended = true;
+        } else if c.is_whitespace() {
+            capitalize |= ended;
+            ended = false;
+        } else if !QUOTES_AND_BRACKETS.contains(&c) {
+            ended = false;
+        }
+    }
+    result
+}
+
+/// Returns `text` in title case, its first word capitalized even if small
+/// when `starts` is true.
+fn title_case(text: &str, starts: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut first = true;
+
+    while !rest.is_empty() {
+        let blank = rest.len() - rest.trim_start().len();
+        result.push_str(&rest[..blank]);
+        rest = &rest[blank..];
+
+        let length = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let word = &rest[..length];
+        let bare = word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        if (first && starts) || !SMALL_WORDS.contains(&bare.as_str()) {
+            result.push_str(&capitalize_word(word));
+        } else {
+            result.push_str(word);
+        }
+
+        first = first && word.is_empty();
+        rest = &rest[length..];
+    }
+    result
+}
+
+impl Document {
+    /// Returns true if text inserted at `position` would begin a sentence
+    /// (see the [module documentation](self)).
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let document = Document::from("Done. (Next\n\nnew\n// ");
+    /// assert!(document.begins_sentence(&Position::from(0, 0)));
+    /// assert!(document.begins_sentence(&Position::from(0, 7)));
+    /// assert!(!document.begins_sentence(&Position::from(0, 11)));
+    /// assert!(document.begins_sentence(&Position::from(2, 0)));
+    /// assert!(!document.begins_sentence(&Position::from(2, 3)));
+    /// assert!(document.begins_sentence(&Position::from(3, 3)));
+    /// ```
+    pub fn begins_sentence(&self, position: &Position) -> bool {
+        let mut row = position.row;
+        let mut before: String = self.lines()[row].content.chars().take(position.column).collect();
+
+        loop {
+            let trimmed = before.trim();
+            if COMMENT_MARKERS.contains(&trimmed) {
+                return true;
+            }
+            if !trimmed.is_empty() {
+                return trimmed.trim_end_matches(|c: char| c.is_whitespace() || QUOTES_AND_BRACKETS.contains(&c))
+                    .chars()
+                    .last()
+                    .is_none_or(|c| TERMINATORS.contains(&c));
+            }
+            if row == 0 {
+                return true;
+            }
+
+            row -= 1;
+            before = self.lines()[row].content.clone();
+            if before.trim().is_empty() {
+                return true;
+            }
+        }
+    }
+
+    /// Capitalizes `lines`, about to be inserted at `position`, as
+    /// `capitalization` says.
+    pub(crate) fn capitalize_lines(&self, lines: &mut Vec<String>, position: &Position, capitalization: Capitalization) {
+        let text = lines.join("\n");
+        let capitalized = match capitalization {
+            Capitalization::None => return,
+            Capitalization::Upper => text.to_uppercase(),
+            Capitalization::Sentence => sentence_case(&text, self.begins_sentence(position)),
+            Capitalization::Title => title_case(&text, self.begins_sentence(position))
+        };
+        *lines = capitalized.split('\n').map(String::from).collect();
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Range, InsertOptions};
+
+    #[test]
+    fn capitalization_follows_context() {
+        let mut document = Document::from("It works. \n\nit");
+        let at = |row, column| InsertOptions {
+            capitalization: Capitalization::Sentence,
+            ..InsertOptions::exact_at(&Range::from(row, column, row, column))
+        };
+        document.insert("then it didn't! \"really?\" yes. NASA", &at(0, 10)).unwrap();
+        assert_eq!(document.lines()[0].content, "It works. Then it didn't! \"Really?\" Yes. NASA");
+        document.insert(" and so on", &at(2, 2)).unwrap();
+        assert_eq!(document.lines()[2].content, "it and so on");
+
+        let title = InsertOptions { capitalization: Capitalization::Title, ..InsertOptions::exact_at(&Range::from(2, 0, 2, 12)) };
+        document.insert("the lord of the rings", &title).unwrap();
+        assert_eq!(document.lines()[2].content, "The Lord of the Rings");
+
+        let upper = InsertOptions { capitalization: Capitalization::Upper, ..InsertOptions::exact_at(&Range::from(2, 0, 2, 3)) };
+        document.insert("straße", &upper).unwrap();
+        assert_eq!(document.lines()[2].content, "STRASSE Lord of the Rings");
+        assert_eq!(title_case("  of mice and men", false), "  of Mice and Men");
+    }
+}
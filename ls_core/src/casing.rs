@@ -0,0 +1,207 @@
+//! Identifier casing: joining spoken words into an identifier under a
+//! naming convention, and converting identifiers between conventions.
+//!
+//! "foo bar baz" becomes `foo_bar_baz` in Rust or Python and `fooBarBaz` in
+//! JavaScript or Java. [`Document::casing`] gives the convention for a
+//! document's language, and [`InsertOptions::casing`] applies one to
+//! inserted text. Words are split as by
+//! [`split_identifier`](crate::segmentation::split_identifier), so an
+//! identifier dictated in one casing converts cleanly to another.
+
+use crate::document::Document;
+use crate::segmentation::split_identifier;
+
+/// How the words of an identifier are joined.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum Casing {
+    /// `parse_the_file`
+    Snake,
+    /// `parseTheFile`
+    Camel,
+    /// `ParseTheFile`
+    Pascal,
+    /// `PARSE_THE_FILE`
+    ScreamingSnake,
+    /// `parse-the-file`
+    Kebab
+}
+
+impl Casing {
+    /// Returns the usual casing for identifiers in `language`.
+    pub fn for_language(language: &str) -> Casing {
+        match language {
+            "js" | "ts" | "tsx" | "java" => Casing::Camel,
+            "css" | "scss" | "html" | "lisp" | "clj" | "el" => Casing::Kebab,
+            _ => Casing::Snake
+        }
+    }
+
+    /// Returns the casing named `name`, as spoken or written, if any.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::casing::*;
+    /// assert_eq!(Casing::from_name("camel case"), Some(Casing::Camel));
+    /// assert_eq!(Casing::from_name("SCREAMING_SNAKE"), Some(Casing::ScreamingSnake));
+    /// assert_eq!(Casing::from_name("upper camel"), Some(Casing::Pascal));
+    /// assert_eq!(Casing::from_name("title"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Casing> {
+        let lowered = name.to_lowercase().replace(['_', '-'], " ");
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+        let words = match words.as_slice() {
+            [rest @ .., "case"] => rest,
+            all => all
+        };
+
+        match words {
+            ["snake"] => Some(Casing::Snake),
+            ["camel"] | ["lower", "camel"] => Some(Casing::Camel),
+            ["pascal"] | ["upper", "camel"] => Some(Casing::Pascal),
+            ["screaming", "snake"] | ["constant"] | ["upper", "snake"] => Some(Casing::ScreamingSnake),
+            ["kebab"] | ["dash"] | ["lisp"] => Some(Casing::Kebab),
+            _ => None
+        }
+    }
+
+    /// Joins `words` into an identifier. Words which are themselves
+    /// identifiers are split into their own words first, as by
+    /// [`split_identifier`].
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::casing::*;
+    /// let words = ["Parse", "the", "FILE"];
+    /// assert_eq!(Casing::Snake.apply(&words), "parse_the_file");
+    /// assert_eq!(Casing::Camel.apply(&words), "parseTheFile");
+    /// assert_eq!(Casing::Pascal.apply(&words), "ParseTheFile");
+    /// assert_eq!(Casing::ScreamingSnake.apply(&words), "PARSE_THE_FILE");
+    /// assert_eq!(Casing::Kebab.apply(&words), "parse-the-file");
+    /// assert_eq!(Casing::Snake.apply(&["openURL", "now"]), "open_url_now");
+    /// ```
+    pub fn apply(&self, words: &[&str]) -> String {
+        let lower: Vec<String> = words.iter()
+            .flat_map(|w| split_identifier(w))
+            .map(|w| w.to_lowercase())
+            .collect();
+
+        match self {
+            Casing::Snake => lower.join("_"),
+            Casing::Kebab => lower.join("-"),
+            Casing::ScreamingSnake => lower.join("_").to_uppercase(),
+            Casing::Camel => lower.iter()
+                .enumerate()
+                .map(|(i, w)| if i == 0 { w.clone() } else { capitalize(w) })
+                .collect(),
+            Casing::Pascal => lower.iter().map(|w| capitalize(w)).collect()
+        }
+    }
+
+    /// Joins the whitespace-separated words of `phrase` into an identifier.
+    pub fn apply_phrase(&self, phrase: &str) -> String {
+        self.apply(&phrase.split_whitespace().collect::<Vec<&str>>())
+    }
+
+    /// Returns the name of this casing, as spoken.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Casing::Snake => "snake case",
+            Casing::Camel => "camel case",
+            Casing::Pascal => "pascal case",
+            Casing::ScreamingSnake => "screaming snake case",
+            Casing::Kebab => "kebab case"
+        }
+    }
+
+    /// Returns true if `identifier` is written in this casing. Leading and
+    /// trailing underscores, as in `_unused` or `__init__`, are ignored, and
+    /// so are digits. Acronyms may be capitalized within camel and pascal
+    /// case.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::casing::*;
+    /// assert!(Casing::Snake.matches("_parse_v2"));
+    /// assert!(!Casing::Snake.matches("parseFile"));
+    /// assert!(Casing::Camel.matches("parseHTTPResponse"));
+    /// assert!(Casing::Pascal.matches("X"));
+    /// assert!(Casing::ScreamingSnake.matches("X"));
+    /// assert!(!Casing::Kebab.matches("max_length"));
+    /// ```
+    pub fn matches(&self, identifier: &str) -> bool {
+        let core = identifier.trim_matches('_');
+        let first = match core.chars().next() {
+            Some(first) => first,
+            None => return true
+        };
+        let has = |f: fn(&char) -> bool| core.chars().any(|c| f(&c));
+
+        match self {
+            Casing::Snake => !has(|c| c.is_uppercase() || *c == '-'),
+            Casing::ScreamingSnake => !has(|c| c.is_lowercase() || *c == '-'),
+            Casing::Kebab => !has(|c| c.is_uppercase() || *c == '_'),
+            Casing::Camel => !first.is_uppercase() && !has(|c| *c == '_' || *c == '-'),
+            Casing::Pascal => first.is_uppercase() && !has(|c| *c == '_' || *c == '-')
+        }
+    }
+
+    /// Converts `identifier`, in any casing, to this one.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::casing::*;
+    /// assert_eq!(Casing::Snake.convert("parseHTTPResponse"), "parse_http_response");
+    /// assert_eq!(Casing::Camel.convert("MAX_LINE_LENGTH"), "maxLineLength");
+    /// assert_eq!(Casing::Kebab.convert("IOError"), "io-error");
+    /// ```
+    pub fn convert(&self, identifier: &str) -> String {
+        self.apply(&[identifier])
+    }
+}
+
+/// Returns `word` with its first character in upper case.
+pub(crate) fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(first) => first.to_uppercase().chain(chars).collect()
+    }
+}
+
+impl Document {
+    /// Returns the usual casing for identifiers in this document's language.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language("let ", "js");
+    /// document.set_cursor_and_mark(&Position::from(0, 4)).unwrap();
+    /// document.insert("max line length", &InsertOptions::identifier(document.casing())).unwrap();
+    /// assert_eq!(document.text(), "let maxLineLength");
+    /// ```
+    pub fn casing(&self) -> Casing {
+        Casing::for_language(self.language())
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Range, InsertOptions};
+
+    #[test]
+    fn casing_follows_language_and_names() {
+        for casing in [Casing::Snake, Casing::Camel, Casing::Pascal, Casing::ScreamingSnake, Casing::Kebab] {
+            let name = format!("{:?}", casing);
+            assert_eq!(Casing::from_name(&Casing::Snake.convert(&name)), Some(casing));
+        }
+
+        let mut document = Document::from_with_language("x = ", "py");
+        let at_end = Range::from(0, 4, 0, 4);
+        document.insert("  total Width ", &InsertOptions { range: Some(at_end), ..InsertOptions::identifier(document.casing()) }).unwrap();
+        assert_eq!(document.text(), "x = total_width");
+        assert!(document.insert("   ", &InsertOptions::identifier(Casing::Camel)).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! The changelist: where the most recent edits were made, like Vim's, so
+//! "go to last change" can jump back to them.
+//!
+//! Each insertion and removal records where it left the text. Entries are
+//! anchors which follow later edits, undos, and redos like floating anchors
+//! do, but they are kept apart from the document's anchor handles so they
+//! never show up in [`Document::anchors`]. An edit on the same
+//! line as the newest entry moves that entry instead of adding another, so
+//! typing out a line records it once. Only the newest [`CHANGELIST_LIMIT`]
+//! entries are kept.
+//!
+//! The changelist is independent of the undo history. Undoing an edit does
+//! not forget where it was made, and going to a change only moves the
+//! cursor.
+
+use std::collections::VecDeque;
+
+use crate::document::{Document, Position, Range, Anchor};
+use crate::util::Oops;
+
+/// How many changes are remembered.
+pub const CHANGELIST_LIMIT: usize = 100;
+
+/// A document's recent changes.
+#[derive(Default)]
+pub(crate) struct Changelist {
+    /// The changes, oldest first.
+    entries: VecDeque<Anchor>,
+
+    /// The entry last gone to, while stepping through the list. A new
+    /// change starts over from the newest.
+    current: Option<usize>
+}
+
+impl Changelist {
+    /// Moves every entry after `text` was inserted at `position`.
+    pub(crate) fn after_insert(&mut self, position: &Position, text: &[String]) {
+        for entry in self.entries.iter_mut() {
+            entry.position = entry.position.after_insert(position, text);
+        }
+    }
+
+    /// Moves every entry after `range` was removed.
+    pub(crate) fn after_remove(&mut self, range: &Range) {
+        for entry in self.entries.iter_mut() {
+            entry.position = entry.position.after_remove(range);
+        }
+    }
+}
+
+impl Document {
+    /// Records an edit which left the text at `position`.
+    pub(crate) fn record_change(&mut self, position: &Position) {
+        let changelist = &mut self.changelist;
+        changelist.current = None;
+
+        match changelist.entries.back_mut() {
+            Some(newest) if newest.position.row == position.row => newest.position = *position,
+            _ => changelist.entries.push_back(Anchor { position: *position })
+        }
+        if changelist.entries.len() > CHANGELIST_LIMIT {
+            changelist.entries.pop_front();
+        }
+    }
+
+    /// Returns where the remembered changes are now, oldest first.
+    pub fn changes(&self) -> Vec<Position> {
+        self.changelist.entries.iter().map(|a| a.position).collect()
+    }
+
+    /// Moves the cursor and mark to the change at `index` in the changelist.
+    fn go_to_change_at(&mut self, index: usize) -> Result<Position, Oops> {
+        let position = self.changelist.entries[index].position;
+        self.set_cursor_and_mark(&position)?;
+        self.changelist.current = Some(index);
+        Ok(position)
+    }
+
+    /// Moves the cursor and mark to the most recent change, and returns
+    /// where it is. Returns `Err` if nothing has changed.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("one\ntwo\nthree");
+    /// document.insert("!", &InsertOptions::exact_at(&Range::from(0, 3, 0, 3))).unwrap();
+    /// document.insert("?", &InsertOptions::exact_at(&Range::from(2, 0, 2, 0))).unwrap();
+    /// document.set_cursor_and_mark(&Position::from(1, 0)).unwrap();
+    ///
+    /// assert_eq!(document.go_to_last_change().unwrap(), Position::from(2, 1));
+    /// assert_eq!(document.go_to_next_change(false).unwrap(), Position::from(0, 4));
+    /// assert!(document.go_to_next_change(false).is_err());
+    /// assert_eq!(document.go_to_next_change(true).unwrap(), Position::from(2, 1));
+    /// ```
+    pub fn go_to_last_change(&mut self) -> Result<Position, Oops> {
+        match self.changelist.entries.len() {
+            0 => Err(Oops::Ouch("no changes")),
+            length => self.go_to_change_at(length - 1)
+        }
+    }
+
+    /// Moves the cursor and mark one step through the changelist from the
+    /// change last gone to: to the next newer change when going `forward`,
+    /// or the next older one otherwise. Going back after an edit starts
+    /// from the most recent change. Returns where the change is, or `Err`
+    /// if there is no change that way.
+    pub fn go_to_next_change(&mut self, forward: bool) -> Result<Position, Oops> {
+        let length = self.changelist.entries.len();
+        let index = match (self.changelist.current, forward) {
+            _ if length == 0 => return Err(Oops::Ouch("no changes")),
+            (None, false) => length - 1,
+            (Some(0), false) => return Err(Oops::Ouch("at the oldest change")),
+            (Some(current), false) => current - 1,
+            (Some(current), true) if current + 1 < length => current + 1,
+            (_, true) => return Err(Oops::Ouch("at the newest change"))
+        };
+        self.go_to_change_at(index)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::document::*;
+    use super::CHANGELIST_LIMIT;
+
+    #[test]
+    fn changes_follow_edits_but_not_undo() {
+        let mut document = Document::from("a\nb\nc\nd");
+        for (i, row) in [3, 3, 1].iter().enumerate() {
+            document.insert(&i.to_string(), &InsertOptions::exact_at(&Range::from(*row, 0, *row, 0))).unwrap();
+        }
+        assert_eq!(document.changes(), vec![Position::from(3, 1), Position::from(1, 1)]);
+
+        document.checkpoint();
+        document.remove(&RemoveOptions::exact_at(&Range::from(0, 0, 1, 0))).unwrap();
+        assert_eq!(document.changes(), vec![Position::from(2, 1), Position::from(0, 0)]);
+
+        document.undo_once().unwrap();
+        assert_eq!(document.changes().len(), 2);
+        assert_eq!(document.go_to_next_change(false).unwrap(), Position::from(1, 0));
+        assert_eq!(document.go_to_next_change(false).unwrap(), Position::from(3, 1));
+        assert!(document.go_to_next_change(false).is_err());
+
+        for _ in 0..=CHANGELIST_LIMIT {
+            document.insert("x\n", &InsertOptions::exact_at(&Range::from(0, 0, 0, 0))).unwrap();
+        }
+        assert_eq!(document.changes().len(), CHANGELIST_LIMIT);
+        assert!(document.go_to_next_change(true).is_err());
+    }
+}
@@ -0,0 +1,416 @@
+//! Change sets: text edits between two revisions, in one shape for every
+//! subsystem which hands deltas around.
+//!
+//! A [`ChangeSet`] is an ordered list of [`TextEdit`]s. Edits apply in
+//! order, so each edit's range refers to the text as left by the edits
+//! before it. Each edit keeps the text it replaced as well as the text it
+//! inserted, so sets can be inverted without the document at hand, and
+//! [composed](ChangeSet::compose) end to end.
+//!
+//! [`Document::changes_since`] reads a set back from the undo history, for
+//! clients (language servers, collaborators, render caches) which need to
+//! catch up from a revision they saw, and [`Document::apply_change_set`]
+//! applies one as a single undoable packet; [`Document::apply_edits`] does
+//! the same for unordered edits against the current text, as formatters
+//! and language servers send them. Formatting diffs are applied as change
+//! sets, and [JSON patches](crate::jsonpatch) can be made from them.
+
+use crate::document::{Document, Position, Range, Change, InsertOptions, RemoveOptions};
+use crate::util::{self, Oops};
+
+/// Replaces `removed`, the text in `range`, with `inserted`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextEdit {
+    pub range: Range,
+    pub removed: String,
+    pub inserted: String
+}
+
+impl TextEdit {
+    /// Returns the range `inserted` covers once this edit is made.
+    pub fn inserted_range(&self) -> Range {
+        let lines: Vec<String> = self.inserted.split('\n').map(String::from).collect();
+        let beginning = self.range.beginning;
+        Range { beginning, ending: beginning.after_insert(&beginning, &lines) }
+    }
+
+    /// Returns the edit which undoes this one.
+    pub fn inverse(&self) -> TextEdit {
+        TextEdit {
+            range: self.inserted_range(),
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone()
+        }
+    }
+}
+
+/// Text edits taking a document from revision `before` to revision `after`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serialization", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChangeSet {
+    pub before: u64,
+    pub after: u64,
+    pub edits: Vec<TextEdit>
+}
+
+impl ChangeSet {
+    /// Returns a set without edits at `revision`.
+    pub fn empty(revision: u64) -> ChangeSet {
+        ChangeSet { before: revision, after: revision, edits: vec![] }
+    }
+
+    /// Returns true if this set edits nothing.
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Returns this set followed by `next`, which must start at the revision
+    /// this set ends at. Returns `Err` if it does not.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("one");
+    /// document.insert("two ", &InsertOptions::exact_at(&Range::from(0, 0, 0, 0))).unwrap();
+    /// let middle = document.revision();
+    /// let first = document.changes_since(0).unwrap();
+    /// document.remove(&RemoveOptions::exact_at(&Range::from(0, 4, 0, 7))).unwrap();
+    /// let second = document.changes_since(middle).unwrap();
+    ///
+    /// assert_eq!(first.compose(&second), document.changes_since(0));
+    /// assert!(second.compose(&first).is_err());
+    /// ```
+    pub fn compose(&self, next: &ChangeSet) -> Result<ChangeSet, Oops> {
+        if self.after != next.before {
+            return Err(Oops::Ouch("change sets do not meet"));
+        }
+        Ok(ChangeSet {
+            before: self.before,
+            after: next.after,
+            edits: self.edits.iter().chain(next.edits.iter()).cloned().collect()
+        })
+    }
+
+    /// Returns the set which undoes this one: applied to the text at
+    /// `after`, it restores the text at `before`, so its revisions are
+    /// swapped.
+    pub fn invert(&self) -> ChangeSet {
+        ChangeSet {
+            before: self.after,
+            after: self.before,
+            edits: self.edits.iter().rev().map(TextEdit::inverse).collect()
+        }
+    }
+}
+
+/// Replaces `range` in `lines` with `text`, returning the text replaced, or
+/// `None` if `range` is not in `lines`.
+fn splice(lines: &mut Vec<String>, range: &Range, text: &str) -> Option<String> {
+    let (b, e) = (range.beginning, range.ending);
+    if b > e || e.row >= lines.len() {
+        return None;
+    }
+    let start = util::cp_index_to_byte(&lines[b.row], b.column)?;
+    let end = util::cp_index_to_byte(&lines[e.row], e.column)?;
+
+    let removed = if b.row == e.row {
+        String::from(&lines[b.row][start..end])
+    } else {
+        let mut parts = vec![&lines[b.row][start..]];
+        parts.extend(lines[b.row + 1..e.row].iter().map(String::as_str));
+        parts.push(&lines[e.row][..end]);
+        parts.join("\n")
+    };
+
+    let joined = format!("{}{}{}", &lines[b.row][..start], text, &lines[e.row][end..]);
+    lines.splice(b.row..=e.row, joined.split('\n').map(String::from));
+    Some(removed)
+}
+
+impl Document {
+    /// Returns the text edits made since `revision`, read back from the undo
+    /// history. Returns `Err` if `revision` is in the future, or the way
+    /// back to it is not all on the undo stack, as when packets have since
+    /// been undone, or if it falls inside a packet which was undone and
+    /// redone, and so can only be read back whole.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("hello");
+    /// let seen = document.revision();
+    /// document.insert("J", &InsertOptions::exact_at(&Range::from(0, 0, 0, 1))).unwrap();
+    ///
+    /// let changes = document.changes_since(seen).unwrap();
+    /// assert_eq!(changes.edits[0].removed, "h");
+    /// assert_eq!(changes.after, document.revision());
+    ///
+    /// document.apply_change_set(&changes.invert(), "revert").unwrap();
+    /// assert_eq!(document.text(), "hello");
+    /// ```
+    pub fn changes_since(&self, revision: u64) -> Result<ChangeSet, Oops> {
+        let mut current = self.revision();
+        if revision > current {
+            return Err(Oops::Ouch("changes_since a future revision"));
+        }
+
+        let mut lines: Vec<String> = self.lines().iter().map(|line| line.content.clone()).collect();
+        let mut edits = vec![];
+        let mut packets = self.undo_redo().undo_packets();
+
+        while current > revision {
+            let packet = match packets.next() {
+                Some(packet) if packet.revision() == current => packet,
+                _ => return Err(Oops::Ouch("changes_since a revision no longer on the undo stack"))
+            };
+
+            // A packet recorded change by change can be read back part way;
+            // one undone, redone, or compacted can only be read back whole.
+            let whole = revision <= packet.base_revision();
+            let stepwise = current - packet.base_revision() == packet.changes().len() as u64;
+            if !whole && !stepwise {
+                return Err(Oops::Ouch("changes_since a revision inside a packet"));
+            }
+
+            for inverse in packet.changes().iter().rev() {
+                if stepwise {
+                    if current == revision {
+                        break;
+                    }
+                    current -= 1;
+                }
+
+                match inverse {
+                    Change::Insert { text, position } => {
+                        let removed = text.join("\n");
+                        splice(&mut lines, &Range { beginning: *position, ending: *position }, &removed)
+                            .ok_or(Oops::InvalidPosition(*position, "changes_since"))?;
+                        edits.push(TextEdit {
+                            range: Range { beginning: *position, ending: position.after_insert(position, text) },
+                            removed,
+                            inserted: String::new()
+                        });
+                    },
+                    Change::Remove { range } => {
+                        let inserted = splice(&mut lines, range, "")
+                            .ok_or(Oops::InvalidRange(*range, "changes_since"))?;
+                        edits.push(TextEdit {
+                            range: Range { beginning: range.beginning, ending: range.beginning },
+                            removed: String::new(),
+                            inserted
+                        });
+                    },
+                    _ => ()
+                }
+            }
+            if !stepwise {
+                current = packet.base_revision();
+            }
+        }
+
+        edits.reverse();
+        Ok(ChangeSet { before: revision, after: self.revision(), edits })
+    }
+
+    /// Applies `changes` as a single undoable packet labeled `label`.
+    /// Returns `Err` without changing anything if `changes` is based on a
+    /// revision other than the current one, or an edit's range is invalid
+    /// or does not hold the text it says it removes. `changes.after` is not
+    /// checked, since it cannot be known before the edits are made.
+    pub fn apply_change_set(&mut self, changes: &ChangeSet, label: &str) -> Result<(), Oops> {
+        if changes.before != self.revision() {
+            return Err(Oops::Ouch("change set is based on a stale revision"));
+        }
+
+        let mut lines: Vec<String> = self.lines().iter().map(|line| line.content.clone()).collect();
+        for edit in changes.edits.iter() {
+            match splice(&mut lines, &edit.range, &edit.inserted) {
+                Some(removed) if removed == edit.removed => (),
+                _ => return Err(Oops::InvalidRange(edit.range, "apply_change_set"))
+            }
+        }
+
+        self.with_packet(label, |document| {
+            for edit in changes.edits.iter() {
+                if !edit.range.empty() {
+                    document.remove(&RemoveOptions::exact_at(&edit.range))?;
+                }
+                if !edit.inserted.is_empty() {
+                    let at = Range { beginning: edit.range.beginning, ending: edit.range.beginning };
+                    document.insert(&edit.inserted, &InsertOptions::exact_at(&at))?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Applies `edits`, each replacing a range of the current text with a
+    /// string, as a single undoable packet labeled "apply edits", and returns
+    /// the change set applied. The edits may come in any order; they are made
+    /// from first to last in the document, each range shifted for the edits
+    /// before it. Edits inserting at the same position keep their order.
+    /// Returns `Err` without changing anything if a range is invalid or two
+    /// overlap.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("let a = b;\nf(a, b);");
+    /// let edits = vec![
+    ///     (Range::from(1, 5, 1, 6), String::from("c")),
+    ///     (Range::from(0, 4, 0, 5), String::from("one\ntwo")),
+    ///     (Range::from(1, 2, 1, 3), String::from("two")),
+    ///     (Range::from(0, 8, 0, 9), String::from("c"))
+    /// ];
+    ///
+    /// let changes = document.apply_edits(edits).unwrap();
+    /// assert_eq!(document.text(), "let one\ntwo = c;\nf(two, c);");
+    /// assert_eq!(changes.edits[1].range, Range::from(1, 6, 1, 7));
+    ///
+    /// document.undo_once().unwrap();
+    /// assert_eq!(document.text(), "let a = b;\nf(a, b);");
+    /// assert!(document.apply_edits(vec![
+    ///     (Range::from(0, 0, 0, 5), String::new()),
+    ///     (Range::from(0, 4, 0, 6), String::new())
+    /// ]).is_err());
+    /// ```
+    pub fn apply_edits(&mut self, mut edits: Vec<(Range, String)>) -> Result<ChangeSet, Oops> {
+        edits.sort_by_key(|(range, _)| (range.beginning, range.ending));
+        if let Some(pair) = edits.windows(2).find(|pair| pair[1].0.beginning < pair[0].0.ending) {
+            return Err(Oops::InvalidRange(pair[1].0, "apply_edits - overlapping"));
+        }
+
+        // Where the last edit ended, before and after the edits so far.
+        // Later positions on its row move with its end; rows below move by
+        // the lines added or removed.
+        let (mut old_end, mut new_end) = (Position::from(0, 0), Position::from(0, 0));
+        let mut changes = ChangeSet::empty(self.revision());
+
+        for (range, text) in edits {
+            let removed = self.text_range(&range).ok_or(Oops::InvalidRange(range, "apply_edits"))?;
+            let shift = |position: Position| if position.row == old_end.row {
+                Position::from(new_end.row, new_end.column + position.column - old_end.column)
+            } else {
+                Position::from(position.row - old_end.row + new_end.row, position.column)
+            };
+
+            let edit = TextEdit {
+                range: Range { beginning: shift(range.beginning), ending: shift(range.ending) },
+                removed,
+                inserted: text
+            };
+            old_end = range.ending;
+            new_end = edit.inserted_range().ending;
+            changes.edits.push(edit);
+        }
+
+        self.apply_change_set(&changes, "apply edits")?;
+        changes.after = self.revision();
+        Ok(changes)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a document with two edits made since the returned revision.
+    fn edited() -> (Document, u64) {
+        let mut document = Document::from("fn main() {\n    let x = 1;\n}");
+        let start = document.revision();
+        document.insert("\n    let y = x;", &InsertOptions::exact_at(&Range::from(1, 14, 1, 14))).unwrap();
+        document.remove(&RemoveOptions::exact_at(&Range::from(0, 3, 0, 7))).unwrap();
+        document.set_cursor_and_mark(&Position::from(0, 0)).unwrap();
+        (document, start)
+    }
+
+    #[test]
+    fn change_sets_replay() {
+        let (mut document, start) = edited();
+        let edited = document.text();
+
+        let whole = document.changes_since(start).unwrap();
+        assert_eq!(whole.before, start);
+        assert_eq!(whole.after, document.revision());
+        assert_eq!(whole.edits.len(), 2);
+        assert_eq!(whole.edits[0].inserted_range(), Range::from(1, 14, 2, 14));
+        assert_eq!(whole.edits[1].removed, "main");
+
+        let wrong = ChangeSet {
+            before: document.revision(),
+            after: document.revision(),
+            edits: vec![TextEdit { range: Range::from(0, 0, 0, 2), removed: String::from("no"), inserted: String::new() }]
+        };
+        assert!(document.apply_change_set(&wrong, "wrong").is_err());
+        assert_eq!(document.text(), edited);
+        assert_eq!(document.changes_since(document.revision()), Ok(ChangeSet::empty(document.revision())));
+    }
+
+    #[test]
+    fn change_sets_invert() {
+        let (mut document, start) = edited();
+        let edited = document.text();
+
+        let whole = document.changes_since(start).unwrap();
+        document.apply_change_set(&whole.invert(), "revert").unwrap();
+        assert_eq!(document.text(), "fn main() {\n    let x = 1;\n}");
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), edited);
+        assert!(document.changes_since(start).is_err());
+    }
+
+    #[test]
+    fn change_sets_follow_undo_and_redo() {
+        let mut document = Document::from("ab");
+        let start = document.revision();
+        document.insert("W", &InsertOptions::exact_at(&Range::from(0, 1, 0, 1))).unwrap();
+        let edited = document.revision();
+        document.undo_once().unwrap();
+        let undone = document.revision();
+        document.redo_once().unwrap();
+        let redone = document.revision();
+
+        assert_eq!(document.changes_since(redone), Ok(ChangeSet::empty(redone)));
+        let since_undo = document.changes_since(undone).unwrap();
+        assert_eq!(since_undo.edits.len(), 1);
+        assert_eq!((since_undo.edits[0].removed.as_str(), since_undo.edits[0].inserted.as_str()), ("", "W"));
+        // The edit and its undo are no longer on the undo stack.
+        for revision in start..undone {
+            assert!(document.changes_since(revision).is_err());
+        }
+        assert!(edited > start && edited < undone);
+
+        let mut document = Document::from("ab");
+        document.insert("W", &InsertOptions::exact_at(&Range::from(0, 1, 0, 1))).unwrap();
+        document.undo_once().unwrap();
+        document.redo_once().unwrap();
+        document.checkpoint();
+        let redone = document.revision();
+        document.insert("X", &InsertOptions::exact_at(&Range::from(0, 0, 0, 0))).unwrap();
+        let changes = document.changes_since(redone - 1).unwrap();
+        let inserted: Vec<&str> = changes.edits.iter().map(|edit| edit.inserted.as_str()).collect();
+        assert_eq!(inserted, vec!["W", "X"]);
+        assert_eq!(document.changes_since(redone).unwrap().edits.len(), 1);
+    }
+
+    #[test]
+    fn change_sets_compose() {
+        let mut document = Document::from("ab\ncd");
+        let before = document.revision();
+        let edits = vec![
+            (Range::from(1, 1, 1, 1), String::from("2")),
+            (Range::from(0, 1, 1, 1), String::from("-")),
+            (Range::from(1, 1, 1, 1), String::from("3")),
+            (Range::from(0, 0, 0, 0), String::from("1\n"))
+        ];
+        let changes = document.apply_edits(edits).unwrap();
+        assert_eq!(document.text(), "1\na-23d");
+        let ranges: Vec<Range> = changes.edits.iter().map(|edit| edit.range).collect();
+        assert_eq!(ranges, vec![Range::from(0, 0, 0, 0), Range::from(1, 1, 2, 1), Range::from(1, 2, 1, 2), Range::from(1, 3, 1, 3)]);
+        assert_eq!((changes.before, changes.after), (before, document.revision()));
+        assert!(document.apply_edits(vec![(Range::from(0, 0, 9, 0), String::new())]).is_err());
+    }
+}
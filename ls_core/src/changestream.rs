@@ -0,0 +1,134 @@
+//! A compact binary encoding for streams of [`ChangePacket`]s, for IPC
+//! between the wasm core and a host process and for persistent undo logs.
+//!
+//! Only available with the `serialization` feature.
+//!
+//! # Format
+//!
+//! A stream begins with a six byte header: the magic bytes [`MAGIC`]
+//! followed by [`FORMAT_VERSION`] as a little-endian `u16`. Each packet
+//! follows as a frame: its length in bytes as a little-endian `u32`, then
+//! the packet encoded with [postcard](https://docs.rs/postcard). Because
+//! frames are self-delimiting, a log can be extended by appending frames
+//! without rewriting what came before.
+
+use crate::document::ChangePacket;
+use crate::util::Oops;
+
+/// The bytes every change stream starts with.
+pub const MAGIC: [u8; 4] = *b"LSCS";
+
+/// The version of the encoding written by this crate. Streams with other
+/// versions are rejected.
+pub const FORMAT_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = 6;
+
+/// Writes a stream header to `out`.
+pub fn write_header(out: &mut Vec<u8>) {
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+}
+
+/// Appends `packet` to `out` as a single frame.
+pub fn append_packet(out: &mut Vec<u8>, packet: &ChangePacket) -> Result<(), Oops> {
+    let body = postcard::to_allocvec(packet)
+        .map_err(|_| Oops::InvalidEncoding("append_packet - cannot encode"))?;
+
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+    Ok(())
+}
+
+/// Returns a complete stream containing `packets`, in order.
+///
+/// # Examples
+/// ```
+/// use ls_core::document::*;
+/// use ls_core::changestream::*;
+/// let mut document = Document::from("");
+/// document.checkpoint_labeled("greet");
+/// document.insert("hello", &InsertOptions::exact()).unwrap();
+///
+/// let bytes = encode_packets(document.undo_redo().undo_packets()).unwrap();
+/// let packets = decode_packets(&bytes).unwrap();
+/// assert_eq!(packets.len(), 1);
+/// assert_eq!(packets[0].label(), Some("greet"));
+/// ```
+pub fn encode_packets<'a, I>(packets: I) -> Result<Vec<u8>, Oops>
+    where I: IntoIterator<Item = &'a ChangePacket>
+{
+    let mut out = vec![];
+    write_header(&mut out);
+    for packet in packets {
+        append_packet(&mut out, packet)?;
+    }
+    Ok(out)
+}
+
+/// Reads every packet in the stream `bytes`. Returns `Err` if the header is
+/// missing or has the wrong version, or if any frame is truncated or
+/// malformed.
+pub fn decode_packets(bytes: &[u8]) -> Result<Vec<ChangePacket>, Oops> {
+    if bytes.len() < HEADER_LEN || bytes[0..4] != MAGIC {
+        return Err(Oops::InvalidEncoding("decode_packets - missing header"));
+    }
+    if u16::from_le_bytes([bytes[4], bytes[5]]) != FORMAT_VERSION {
+        return Err(Oops::InvalidEncoding("decode_packets - unsupported version"));
+    }
+
+    let mut packets = vec![];
+    let mut offset = HEADER_LEN;
+
+    while offset < bytes.len() {
+        if offset + 4 > bytes.len() {
+            return Err(Oops::InvalidEncoding("decode_packets - truncated frame length"));
+        }
+        let mut length = [0u8; 4];
+        length.copy_from_slice(&bytes[offset..offset + 4]);
+        let length = u32::from_le_bytes(length) as usize;
+        offset += 4;
+
+        if offset + length > bytes.len() {
+            return Err(Oops::InvalidEncoding("decode_packets - truncated frame"));
+        }
+        let packet = postcard::from_bytes(&bytes[offset..offset + length])
+            .map_err(|_| Oops::InvalidEncoding("decode_packets - malformed packet"))?;
+        packets.push(packet);
+        offset += length;
+    }
+
+    Ok(packets)
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Document, InsertOptions, Indentation};
+
+    #[test]
+    fn round_trip() {
+        let mut document = Document::from("abc\ndef");
+        document.insert("🦄\nx", &InsertOptions::exact()).unwrap();
+        document.checkpoint();
+        document.set_indentation(&Indentation::tabs(8)).unwrap();
+        document.set_language("py").unwrap();
+
+        let packets: Vec<ChangePacket> = document.undo_redo().undo_packets().cloned().collect();
+        let mut bytes = encode_packets(packets.iter()).unwrap();
+        assert_eq!(decode_packets(&bytes).unwrap(), packets);
+
+        append_packet(&mut bytes, &packets[0]).unwrap();
+        assert_eq!(decode_packets(&bytes).unwrap().len(), packets.len() + 1);
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(decode_packets(truncated).is_err());
+
+        let mut future = bytes.clone();
+        future[4] = 2;
+        assert!(decode_packets(&future).is_err());
+        assert!(decode_packets(b"LS").is_err());
+    }
+}
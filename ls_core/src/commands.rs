@@ -0,0 +1,430 @@
+//! The spoken command interpreter: turns recognized phrases into
+//! [`Command`]s and executes them against a document.
+
+use crate::ambiguity::{Ambiguity, Resolution};
+use crate::bookmarks::Bookmark;
+use crate::doc_comments::DocComment;
+use crate::document::{Document, Position, Range};
+use crate::duplicate::DuplicateTarget;
+use crate::extract::{Extract, Extraction};
+use crate::folds::FoldTarget;
+use crate::motions::Motion;
+use crate::operators::{Operation, Operator};
+use crate::snippets::Snippet;
+use crate::spoken;
+use crate::util::Oops;
+
+/// A command which can be spoken.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Command {
+    /// "line 42", "down three", "80 percent", "column 12". See
+    /// [`Motion::parse`].
+    Move(Motion),
+
+    /// "where am I": report the cursor's position.
+    WhereAmI,
+
+    /// "delete three words", "comment paragraph". See [`Operation::parse`].
+    Operate(Operation),
+
+    /// "mark alpha": drop a placeholder named by a phonetic alphabet word at
+    /// the cursor.
+    MarkPlace(String),
+
+    /// "go alpha", "go to alpha": jump to a placeholder.
+    GoToPlace(String),
+
+    /// "list marks": report every placeholder.
+    ListPlaces,
+
+    /// "clear alpha": remove one placeholder.
+    ClearPlace(String),
+
+    /// "clear marks", "clear all marks": remove every placeholder.
+    ClearPlaces,
+
+    /// "bookmark parser setup": bookmark the cursor with a label.
+    AddBookmark(String),
+
+    /// "next bookmark" or, if false, "previous bookmark".
+    NextBookmark(bool),
+
+    /// "list bookmarks": report every bookmark.
+    ListBookmarks,
+
+    /// "doc comment", "document function": insert a doc comment skeleton
+    /// for the function at the cursor.
+    DocComment,
+
+    /// "fold all functions", "fold comments", "fold level two". See
+    /// [`FoldTarget::parse`].
+    Fold(FoldTarget),
+
+    /// "unfold all", "unfold everything": remove every fold.
+    UnfoldAll,
+
+    /// "duplicate that", "duplicate this function below". See
+    /// [`DuplicateTarget::parse`].
+    Duplicate(DuplicateTarget),
+
+    /// "extract variable", "extract that to a function". See
+    /// [`Extract::parse`].
+    Extract(Extract),
+
+    /// "inline variable", "inline that": replace the variable bound at the
+    /// cursor with its value.
+    InlineVariable,
+
+    /// "last change": go to where the most recent edit was made.
+    LastChange,
+
+    /// "previous change" (false) or "next change" (true): step through the
+    /// changelist.
+    NextChange(bool),
+
+    /// "increment", "increment by five", "decrement by two": add to the
+    /// number at the cursor. See [`Document::increment`].
+    Increment(i64),
+
+    /// "insert today's date", "insert a UUID": insert generated text. See
+    /// [`Snippet::parse`].
+    InsertSnippet(Snippet),
+
+    /// "insert sorted delta": add the spoken words to the list around the
+    /// cursor in alphabetical order. See [`Document::insert_sorted`].
+    InsertSorted(String),
+
+    /// "resume dictation": go back to where dictation left off. See
+    /// [`Document::resume_dictation`].
+    ResumeDictation,
+
+    /// "select total width": select the text matching the spoken words.
+    /// See [`Document::select_text`].
+    SelectText(String),
+
+    /// "choose two", "pick three": finish an ambiguous command. See
+    /// [`Document::choose`].
+    Choose(usize),
+
+    /// "scratch that", "undo that": undo the last utterance.
+    ScratchThat
+}
+
+/// What executing a [`Command`] did, for the UI to display or speak.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Outcome {
+    /// The cursor moved to this position.
+    Moved(Position),
+
+    /// The cursor is at `position`, this far through the document and its
+    /// line. See [`Document::position_percentages`].
+    Located { position: Position, document_percent: usize, line_percent: usize },
+
+    /// The operator acted on this range. See [`Document::operate`].
+    Operated(Operator, Range),
+
+    /// A placeholder or bookmark was dropped at this position.
+    Marked(String, Position),
+
+    /// The placeholders, in alphabetical order.
+    Listed(Vec<(String, Position)>),
+
+    /// This many placeholders were removed.
+    Cleared(usize),
+
+    /// The bookmarks, in document order.
+    Bookmarks(Vec<Bookmark>),
+
+    /// A doc comment skeleton was inserted.
+    Documented(DocComment),
+
+    /// This many folds were made.
+    Folded(usize),
+
+    /// A syntax node was copied to this range.
+    Duplicated(Range),
+
+    /// The selection was extracted into a new variable or function.
+    Extracted(Extraction),
+
+    /// A variable was inlined at this many uses.
+    Inlined(usize),
+
+    /// The number at the cursor was rewritten, and is now in this range.
+    Incremented(Range),
+
+    /// A snippet or list element was inserted at this range.
+    Inserted(Range),
+
+    /// This range was selected.
+    Selected(Range),
+
+    /// Several places matched; say "choose" and a number to pick one.
+    Ambiguous(Ambiguity),
+
+    /// A phrase which was not a command was dictated into this range.
+    Dictated(Range),
+
+    /// The last utterance was undone. Its text changes, if any, are now in
+    /// this range.
+    Scratched(Option<Range>)
+}
+
+impl Command {
+    /// Parses a spoken phrase, or returns `None` if it is not a command.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::commands::*;
+    /// assert_eq!(Command::parse("Mark Alpha"), Some(Command::MarkPlace(String::from("alpha"))));
+    /// assert_eq!(Command::parse("go to x-ray"), Some(Command::GoToPlace(String::from("xray"))));
+    /// assert_eq!(Command::parse("clear all marks"), Some(Command::ClearPlaces));
+    /// assert_eq!(Command::parse("mark banana"), None);
+    /// ```
+    pub fn parse(phrase: &str) -> Option<Command> {
+        let lowered = phrase.to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+
+        let place = |word: &str| spoken::phonetic_letter(word).map(|c| String::from(spoken::PHONETIC_ALPHABET[c as usize - 'a' as usize]));
+
+        match words.as_slice() {
+            ["mark", word] => place(word).map(Command::MarkPlace),
+            ["go", word] | ["go", "to", word] if place(word).is_some() => place(word).map(Command::GoToPlace),
+            ["list", "marks"] | ["list", "places"] => Some(Command::ListPlaces),
+            ["clear", "marks"] | ["clear", "all", "marks"] | ["clear", "places"] => Some(Command::ClearPlaces),
+            ["clear", word] => place(word).map(Command::ClearPlace),
+            ["where", "am", "i"] => Some(Command::WhereAmI),
+            ["next", "bookmark"] => Some(Command::NextBookmark(true)),
+            ["previous", "bookmark"] | ["last", "bookmark"] => Some(Command::NextBookmark(false)),
+            ["list", "bookmarks"] => Some(Command::ListBookmarks),
+            ["doc", "comment"] | ["add", "doc", "comment"] | ["document", "function"] => Some(Command::DocComment),
+            ["unfold", "all"] | ["unfold", "everything"] => Some(Command::UnfoldAll),
+            ["fold", ..] => FoldTarget::parse(&lowered).map(Command::Fold),
+            ["duplicate", ..] => DuplicateTarget::parse(&lowered).map(Command::Duplicate),
+            ["extract", ..] => Extract::parse(&lowered).map(Command::Extract),
+            ["last", "change"] | ["go", "to", "last", "change"] => Some(Command::LastChange),
+            ["previous", "change"] | ["older", "change"] => Some(Command::NextChange(false)),
+            ["next", "change"] | ["newer", "change"] => Some(Command::NextChange(true)),
+            ["increment" | "decrement", rest @ ..] => {
+                let sign = if words[0] == "increment" { 1 } else { -1 };
+                match rest {
+                    [] => Some(Command::Increment(sign)),
+                    ["by", amount @ ..] => match spoken::parse_number(amount) {
+                        Some((n, used)) if used == amount.len() => Some(Command::Increment(sign * n as i64)),
+                        _ => None
+                    },
+                    _ => None
+                }
+            },
+            ["insert", "sorted", element @ ..] if !element.is_empty() => Some(Command::InsertSorted(element.join(" "))),
+            ["insert", ..] => Snippet::parse(&lowered).map(Command::InsertSnippet),
+            ["resume", "dictation"] | ["continue", "dictation"] | ["back", "to", "dictation"] => Some(Command::ResumeDictation),
+            ["choose" | "pick", number @ ..] => match spoken::parse_number(number) {
+                Some((n, used)) if used == number.len() => Some(Command::Choose(n)),
+                _ => None
+            },
+            ["select", text @ ..] if !text.is_empty() && Operation::parse(&lowered).is_none() => {
+                Some(Command::SelectText(text.join(" ")))
+            },
+            ["scratch", "that"] | ["undo", "that"] => Some(Command::ScratchThat),
+            ["inline", "variable"] | ["inline", "that"] | ["inline", "this"] => Some(Command::InlineVariable),
+            ["bookmark", label @ ..] if !label.is_empty() => Some(Command::AddBookmark(label.join(" "))),
+            _ => Operation::parse(&lowered).map(Command::Operate)
+                .or_else(|| Motion::parse(&lowered).map(Command::Move))
+        }
+    }
+}
+
+impl Document {
+    /// Executes `command`. Returns `Err` when jumping to or clearing a
+    /// placeholder which does not exist, or when an operation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::commands::*;
+    /// let mut document = Document::from("one\ntwo\nthree");
+    /// document.set_cursor_and_mark(&Position::from(1, 2)).unwrap();
+    /// document.execute_phrase("mark bravo").unwrap();
+    /// document.execute_phrase("last line").unwrap();
+    /// assert_eq!(document.execute_phrase("go bravo"), Ok(Outcome::Moved(Position::from(1, 2))));
+    /// assert_eq!(
+    ///     document.execute_phrase("list marks"),
+    ///     Ok(Outcome::Listed(vec![(String::from("bravo"), Position::from(1, 2))]))
+    /// );
+    /// ```
+    pub fn execute(&mut self, command: &Command) -> Result<Outcome, Oops> {
+        match command {
+            Command::Move(motion) => self.apply_motion(motion).map(Outcome::Moved),
+            Command::WhereAmI => {
+                let (document_percent, line_percent) = self.position_percentages();
+                Ok(Outcome::Located { position: self.cursor().position, document_percent, line_percent })
+            },
+            Command::Operate(operation) => self.operate(operation).map(|range| Outcome::Operated(operation.operator, range)),
+            Command::MarkPlace(name) => {
+                let position = self.cursor().position;
+                self.set_named_anchor(name, &position)?;
+                Ok(Outcome::Marked(name.clone(), position))
+            },
+            Command::GoToPlace(name) => {
+                let position = self.named_anchor(name).ok_or(Oops::Ouch("no such mark"))?;
+                self.set_cursor_and_mark(&position)?;
+                Ok(Outcome::Moved(position))
+            },
+            Command::ListPlaces => Ok(Outcome::Listed(self.named_anchors())),
+            Command::ClearPlace(name) => {
+                if self.remove_named_anchor(name) {
+                    Ok(Outcome::Cleared(1))
+                } else {
+                    Err(Oops::Ouch("no such mark"))
+                }
+            },
+            Command::ClearPlaces => Ok(Outcome::Cleared(self.clear_named_anchors())),
+            Command::AddBookmark(label) => {
+                let position = self.cursor().position;
+                self.add_bookmark(label, &position, None)?;
+                Ok(Outcome::Marked(label.clone(), position))
+            },
+            Command::NextBookmark(forward) => self.go_to_next_bookmark(*forward).map(|b| Outcome::Moved(b.position)),
+            Command::ListBookmarks => Ok(Outcome::Bookmarks(self.bookmarks())),
+            Command::DocComment => self.insert_doc_comment().map(Outcome::Documented),
+            Command::Fold(target) => Ok(Outcome::Folded(self.fold_all(target))),
+            Command::UnfoldAll => {
+                let count = self.folds().len();
+                self.unfold_all();
+                Ok(Outcome::Cleared(count))
+            },
+            Command::Duplicate(target) => self.duplicate(*target).map(Outcome::Duplicated),
+            Command::Extract(what) => self.extract(*what).map(Outcome::Extracted),
+            Command::InlineVariable => self.inline_variable().map(Outcome::Inlined),
+            Command::LastChange => self.go_to_last_change().map(Outcome::Moved),
+            Command::NextChange(forward) => self.go_to_next_change(*forward).map(Outcome::Moved),
+            Command::Increment(delta) => self.increment(*delta).map(Outcome::Incremented),
+            Command::InsertSnippet(snippet) => self.insert_snippet(*snippet).map(Outcome::Inserted),
+            Command::InsertSorted(element) => self.insert_sorted(element).map(Outcome::Inserted),
+            Command::ResumeDictation => self.resume_dictation().map(Outcome::Moved),
+            Command::SelectText(text) => self.select_text(text).map(|resolution| match resolution {
+                Resolution::Done(range) => Outcome::Selected(range),
+                Resolution::Ambiguous(ambiguity) => Outcome::Ambiguous(ambiguity)
+            }),
+            Command::Choose(number) => {
+                let action = self.pending_ambiguity().map(|a| a.action);
+                let range = self.choose(*number)?;
+                Ok(match action {
+                    Some(crate::ambiguity::ChoiceAction::GoTo) => Outcome::Moved(range.beginning),
+                    _ => Outcome::Selected(range)
+                })
+            },
+            Command::ScratchThat => self.undo_once().map(Outcome::Scratched)
+        }
+    }
+
+    /// Parses and executes `phrase`. Returns `Err` if it is not a command.
+    pub fn execute_phrase(&mut self, phrase: &str) -> Result<Outcome, Oops> {
+        let command = Command::parse(phrase).ok_or(Oops::Ouch("unrecognized command"))?;
+        self.execute(&command)
+    }
+
+    /// Executes `utterance` if it is a command, and otherwise dictates it
+    /// (see [`Document::dictate`]), as one change packet. The utterance is
+    /// recorded in the [session log](crate::session_log), if one is being
+    /// kept.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::commands::*;
+    /// let mut document = Document::from("");
+    /// assert_eq!(document.execute_utterance("hello"), Ok(Outcome::Dictated(Range::from(0, 0, 0, 5))));
+    /// assert_eq!(document.execute_utterance("first line"), Ok(Outcome::Moved(Position::from(0, 0))));
+    /// document.execute_utterance("well").unwrap();
+    /// assert_eq!(document.text(), "wellhello");
+    /// assert_eq!(document.execute_utterance("scratch that"), Ok(Outcome::Scratched(Some(Range::from(0, 0, 0, 0)))));
+    /// assert_eq!(document.text(), "hello");
+    /// ```
+    pub fn execute_utterance(&mut self, utterance: &str) -> Result<Outcome, Oops> {
+        let command = Command::parse(utterance);
+        self.act_on_utterance(utterance, command.as_ref())
+    }
+
+    /// Executes `command`, or dictates `utterance` if there is none, and
+    /// logs the utterance. Everything the utterance changes is recorded as
+    /// one change packet labeled with it, so undoing once, or saying
+    /// "scratch that", takes back the whole utterance.
+    pub(crate) fn act_on_utterance(&mut self, utterance: &str, command: Option<&Command>) -> Result<Outcome, Oops> {
+        let revision = self.revision();
+        let result = match command {
+            // Undoing inside a packet would not be grouped.
+            Some(Command::ScratchThat) => self.execute(&Command::ScratchThat),
+            Some(command) => self.with_packet(utterance, |document| document.execute(command)),
+            None => self.with_packet(utterance, |document| {
+                let beginning = document.selection().beginning;
+                document.dictate(utterance)
+                    .map(|_| Outcome::Dictated(Range { beginning, ending: document.cursor().position }))
+            })
+        };
+
+        let interpretation = command.map_or(String::from(crate::session_log::DICTATION), |c| format!("{:?}", c));
+        self.log_utterance(utterance, interpretation, revision, &result);
+        result
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::*;
+
+    #[test]
+    fn placeholders_follow_edits() {
+        let mut document = Document::from("fn f() {}\n");
+        document.set_cursor_and_mark(&Position::from(0, 5)).unwrap();
+        document.execute_phrase("mark alpha").unwrap();
+        document.set_cursor_and_mark(&Position::from(0, 8)).unwrap();
+        document.execute_phrase("mark juliet").unwrap();
+
+        document.set_cursor_and_mark(&Position::from(0, 0)).unwrap();
+        document.insert("pub ", &InsertOptions::exact()).unwrap();
+        assert_eq!(document.execute_phrase("go to alpha"), Ok(Outcome::Moved(Position::from(0, 9))));
+        assert_eq!(document.execute_phrase("line 2"), Ok(Outcome::Moved(Position::from(1, 0))));
+        assert_eq!(
+            document.execute_phrase("where am I"),
+            Ok(Outcome::Located { position: Position::from(1, 0), document_percent: 100, line_percent: 0 })
+        );
+
+        assert_eq!(document.execute_phrase("clear alpha"), Ok(Outcome::Cleared(1)));
+        assert!(document.execute_phrase("go alpha").is_err());
+        assert!(document.execute_phrase("clear alpha").is_err());
+        assert_eq!(document.execute_phrase("clear marks"), Ok(Outcome::Cleared(1)));
+        assert_eq!(document.anchors().len(), 2);
+        assert!(document.execute_phrase("sing a song").is_err());
+    }
+
+    #[test]
+    fn operations_are_phrases() {
+        let mut document = Document::from("pub fn f() {}\n");
+        assert_eq!(
+            document.execute_phrase("select two words"),
+            Ok(Outcome::Operated(Operator::Select, Range::from(0, 0, 0, 6)))
+        );
+    }
+
+    #[test]
+    fn bookmarks_are_phrases() {
+        let mut document = Document::from("pub fn f() {}\n");
+        document.set_cursor_and_mark(&Position::from(0, 6)).unwrap();
+        document.execute_phrase("bookmark Parser Setup").unwrap();
+        assert_eq!(document.execute_phrase("line 2"), Ok(Outcome::Moved(Position::from(1, 0))));
+        assert_eq!(document.execute_phrase("next bookmark"), Ok(Outcome::Moved(Position::from(0, 6))));
+        assert_eq!(
+            document.execute_phrase("list bookmarks"),
+            Ok(Outcome::Bookmarks(vec![Bookmark {
+                label: String::from("parser setup"),
+                position: Position::from(0, 6),
+                note: None
+            }]))
+        );
+    }
+}
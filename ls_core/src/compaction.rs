@@ -0,0 +1,257 @@
+//! History compaction: collapsing old undo packets into coarse ones, so
+//! long editing sessions keep a bounded history to replay.
+//!
+//! [`Document::compact_history`] leaves the newest packets alone, so recent
+//! edits still undo one at a time. The older packets are grouped into
+//! windows of [`CompactionPolicy::period`] milliseconds by their
+//! timestamps, and each window becomes one packet which undoes the whole
+//! window at once. Most are coarse diffs, replacing only the text which
+//! differs between the start and end of the window; every
+//! [`CompactionPolicy::snapshot_every`]th, starting with the oldest, is a
+//! full-text snapshot, replacing the whole text. Undoing a snapshot puts
+//! back the text as it was regardless of what came before, so a
+//! [persistent undo log](crate::changestream) can be cut at any snapshot.
+//!
+//! Compacted packets keep the timestamp, revision, and
+//! [id](ChangePacket::id) of the newest packet in their window, and the
+//! [base revision](ChangePacket::base_revision) of the oldest, so
+//! [`Document::undo_to_time`], [`Document::jump_to_node`], and
+//! [`Document::changes_since`] still stop between windows, though no longer
+//! inside one. Anchor changes are kept, but
+//! anchors without them move with the coarse diff rather than the edits it
+//! replaced.
+
+use std::collections::HashSet;
+
+use crate::document::{Document, Position, Range, Change, ChangePacket};
+use crate::timeline::Scratch;
+
+/// The label of a compacted packet which is a coarse diff.
+pub const COMPACTED_LABEL: &str = "compacted history";
+
+/// The label of a compacted packet which is a full-text snapshot.
+pub const SNAPSHOT_LABEL: &str = "history snapshot";
+
+/// How [`Document::compact_history`] compacts.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub struct CompactionPolicy {
+    /// How many of the newest undo packets to leave alone. The newest
+    /// packet, which edits may still be added to, is always left alone.
+    pub keep: usize,
+
+    /// How long each window of older packets is, in milliseconds, or 0 to
+    /// collapse them all into one.
+    pub period: u64,
+
+    /// How often a window becomes a full-text snapshot rather than a coarse
+    /// diff, or 0 for never.
+    pub snapshot_every: usize
+}
+
+impl Default for CompactionPolicy {
+    fn default() -> CompactionPolicy {
+        CompactionPolicy {
+            keep: 100,
+            period: 60_000,
+            snapshot_every: 10
+        }
+    }
+}
+
+/// Undoes the text changes of `packet` on `scratch`.
+fn undo_text(scratch: &mut Scratch, packet: &ChangePacket) {
+    for change in packet.changes().iter().rev() {
+        match change {
+            Change::Remove { range } => { scratch.remove(range); },
+            Change::Insert { text, position } => { scratch.insert(text, position); },
+            _ => ()
+        }
+    }
+}
+
+/// Returns the position `offset` codepoints into `lines` joined by `\n`.
+fn position_at(lines: &[String], mut offset: usize) -> Position {
+    for (row, line) in lines.iter().enumerate() {
+        let length = line.chars().count();
+        if offset <= length {
+            return Position::from(row, offset);
+        }
+        offset -= length + 1;
+    }
+    Position::from(lines.len() - 1, lines[lines.len() - 1].chars().count())
+}
+
+/// Returns the changes which, applied in reverse order as an undo applies
+/// them, turn the text `after` back into `before`: the whole text if
+/// `snapshot`, or else only the text between their common beginning and
+/// ending.
+fn text_inverse(after: &[String], before: &[String], snapshot: bool) -> Vec<Change> {
+    let a: Vec<char> = after.join("\n").chars().collect();
+    let b: Vec<char> = before.join("\n").chars().collect();
+
+    let (prefix, suffix) = if snapshot {
+        (0, 0)
+    } else {
+        let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+        let limit = a.len().min(b.len()) - prefix;
+        let suffix = a.iter().rev().zip(b.iter().rev()).take(limit).take_while(|(x, y)| x == y).count();
+        (prefix, suffix)
+    };
+
+    let mut changes = vec![];
+    let removed = Range { beginning: position_at(after, prefix), ending: position_at(after, a.len() - suffix) };
+    let inserted: String = b[prefix..b.len() - suffix].iter().collect();
+    if !inserted.is_empty() || snapshot {
+        let text = inserted.split('\n').map(String::from).collect();
+        changes.push(Change::Insert { text, position: removed.beginning });
+    }
+    if !removed.empty() {
+        changes.push(Change::Remove { range: removed });
+    }
+    changes
+}
+
+/// Returns the changes in `packets`, oldest first, other than changes to
+/// the text. If no anchor was added or removed, only the change applied
+/// last by an undo is kept for each anchor.
+fn other_changes(packets: &[ChangePacket]) -> Vec<Change> {
+    let changes = packets.iter().flat_map(|packet| packet.changes().iter());
+    let reshaped = changes.clone().any(|c| matches!(c, Change::AnchorInsert { .. } | Change::AnchorRemove { .. }));
+
+    let mut seen = HashSet::new();
+    changes
+        .filter(|c| !matches!(c, Change::Insert { .. } | Change::Remove { .. }))
+        .filter(|c| match c {
+            Change::AnchorSet { handle, .. } if !reshaped => seen.insert(*handle),
+            _ => true
+        })
+        .cloned()
+        .collect()
+}
+
+impl Document {
+    /// Collapses undo packets older than the newest [`CompactionPolicy::keep`]
+    /// into one packet per window (see the [module documentation](self)).
+    /// Returns how many fewer packets the undo stack has. The redo stack is
+    /// left alone. This cannot be undone!
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::compaction::*;
+    /// let mut document = Document::from("");
+    /// for word in ["one ", "two ", "three"].iter() {
+    ///     document.insert(word, &InsertOptions::exact()).unwrap();
+    ///     document.checkpoint();
+    /// }
+    ///
+    /// let policy = CompactionPolicy { keep: 1, period: 0, snapshot_every: 0 };
+    /// assert_eq!(document.compact_history(&policy), 1);
+    /// assert_eq!(document.undo_redo().undo_packets().nth(1).unwrap().label(), Some(COMPACTED_LABEL));
+    ///
+    /// document.undo_once().unwrap();
+    /// assert_eq!(document.text(), "one two ");
+    /// document.undo_once().unwrap();
+    /// assert_eq!(document.text(), "");
+    /// ```
+    pub fn compact_history(&mut self, policy: &CompactionPolicy) -> usize {
+        let old = self.undo_redo.undo_stack.len().saturating_sub(policy.keep.max(1));
+        if old == 0 {
+            return 0;
+        }
+
+        // Undo the packets being kept on a copy of the text, leaving the text
+        // as the old packets left it.
+        let mut scratch = Scratch { lines: self.lines().iter().map(|l| l.content.clone()).collect() };
+        for packet in self.undo_redo.undo_stack[old..].iter().rev() {
+            undo_text(&mut scratch, packet);
+        }
+
+        let window = |packet: &ChangePacket| packet.timestamp().checked_div(policy.period).unwrap_or(0);
+        let mut windows: Vec<Vec<ChangePacket>> = vec![];
+        for packet in self.undo_redo.undo_stack.drain(..old) {
+            match windows.last_mut() {
+                Some(last) if window(&last[0]) == window(&packet) => last.push(packet),
+                _ => windows.push(vec![packet])
+            }
+        }
+
+        let count = windows.len();
+        let mut compacted = Vec::with_capacity(count);
+        for (index, packets) in windows.iter().enumerate().rev() {
+            let after = scratch.lines.clone();
+            for packet in packets.iter().rev() {
+                undo_text(&mut scratch, packet);
+            }
+
+            let snapshot = policy.snapshot_every > 0 && index % policy.snapshot_every == 0;
+            let mut changes = other_changes(packets);
+            changes.extend(text_inverse(&after, &scratch.lines, snapshot));
+
+            let newest = packets.last().unwrap();
+            let mut packet = ChangePacket::from(changes).labeled(if snapshot { SNAPSHOT_LABEL } else { COMPACTED_LABEL });
+            packet.revision = newest.revision();
+            packet.base_revision = packets[0].base_revision();
+            packet.timestamp = newest.timestamp();
+            packet.id = newest.id();
+            compacted.push(packet);
+        }
+
+        compacted.reverse();
+        self.undo_redo.undo_stack.splice(0..0, compacted);
+        old - count
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use crate::document::{InsertOptions, RemoveOptions};
+
+    #[test]
+    fn compacted_windows_undo_to_their_boundaries() {
+        static TIME: AtomicU64 = AtomicU64::new(0);
+        fn clock() -> u64 { TIME.load(Ordering::SeqCst) }
+
+        let mut document = Document::from("base\ntext");
+        document.set_clock(clock);
+        let edits: [(u64, &str, Range); 6] = [
+            (100, "A", Range::from(0, 0, 0, 0)),
+            (200, "B\n", Range::from(1, 0, 1, 0)),
+            (1100, "", Range::from(0, 1, 0, 3)),
+            (1200, "C", Range::from(2, 4, 2, 4)),
+            (2100, "D", Range::from(0, 0, 0, 1)),
+            (3100, "E", Range::from(0, 0, 0, 0))
+        ];
+        let mut texts = vec![document.text()];
+        for (time, text, range) in edits.iter() {
+            TIME.store(*time, Ordering::SeqCst);
+            if text.is_empty() {
+                document.remove(&RemoveOptions::exact_at(range)).unwrap();
+            } else {
+                document.insert(text, &InsertOptions::exact_at(range)).unwrap();
+            }
+            document.checkpoint();
+            texts.push(document.text());
+        }
+
+        let policy = CompactionPolicy { keep: 2, period: 1000, snapshot_every: 2 };
+        assert_eq!(document.compact_history(&policy), 2);
+        assert_eq!(document.compact_history(&policy), 0);
+
+        let labels: Vec<Option<&str>> = document.undo_redo().undo_packets().map(|p| p.label()).collect();
+        assert_eq!(labels, vec![None, None, Some(COMPACTED_LABEL), Some(SNAPSHOT_LABEL)]);
+        let stamps: Vec<u64> = document.undo_redo().undo_packets().map(|p| p.timestamp()).collect();
+        assert_eq!(stamps, vec![3100, 2100, 1200, 200]);
+
+        for (undone, text) in [(1, &texts[5]), (1, &texts[4]), (1, &texts[2]), (1, &texts[0])].iter() {
+            assert_eq!(document.undo(*undone).count, 1);
+            assert_eq!(document.text(), **text);
+        }
+        assert_eq!(document.undo_to_time(1500), Ok((0, 2)));
+        assert_eq!(document.text(), texts[4]);
+    }
+}
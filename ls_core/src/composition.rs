@@ -0,0 +1,124 @@
+//! Composition (pre-edit) text, as produced by input method editors and
+//! some dictation flows.
+//!
+//! While a composition is in progress, its text is shown at the insertion
+//! point as [virtual text](crate::virtual_text) with style
+//! [`COMPOSITION_STYLE`], but the document is not changed and nothing is
+//! recorded on the undo stack. Committing inserts the final text as a single
+//! [`ChangePacket`](crate::document::ChangePacket).
+
+use crate::document::{Document, Position, Range, InsertOptions};
+use crate::virtual_text::VirtualText;
+use crate::util::Oops;
+
+/// The virtual text style class given to composition text.
+pub const COMPOSITION_STYLE: &str = "composition";
+
+impl Document {
+    /// Starts a composition at the cursor. Returns `Err` if one is already
+    /// in progress.
+    pub fn begin_composition(&mut self) -> Result<(), Oops> {
+        if self.composition.is_some() {
+            return Err(Oops::Ouch("composition already in progress"));
+        }
+
+        let position = self.cursor().position;
+        self.composition = Some(self.add_virtual_text(&position, "", COMPOSITION_STYLE)?);
+        Ok(())
+    }
+
+    /// Replaces the pending composition text with `text`. Returns `Err` if no
+    /// composition is in progress.
+    pub fn update_composition(&mut self, text: &str) -> Result<(), Oops> {
+        match self.composition {
+            None => Err(Oops::Ouch("no composition in progress")),
+            Some(handle) => self.set_virtual_text(handle, Some(VirtualText::from(text, COMPOSITION_STYLE)))
+        }
+    }
+
+    /// Returns the position and pending text of the composition in progress.
+    pub fn composition(&self) -> Option<(Position, &str)> {
+        let handle = self.composition?;
+        Some((self.anchor(handle)?.position, &self.virtual_text(handle)?.text))
+    }
+
+    /// Ends the composition, inserting its text as a single undoable packet.
+    /// Committing empty text just ends the composition. Returns `Err` if no
+    /// composition is in progress, or if the text can't be inserted, in which
+    /// case the composition is left in progress.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("");
+    /// document.begin_composition().unwrap();
+    /// document.update_composition("ni").unwrap();
+    /// document.update_composition("你").unwrap();
+    /// assert_eq!(document.text(), "");
+    /// assert_eq!(document.undo_redo().depth(), (0, 0));
+    ///
+    /// document.commit_composition().unwrap();
+    /// assert_eq!(document.text(), "你");
+    /// assert_eq!(document.undo_redo().depth(), (1, 0));
+    /// ```
+    pub fn commit_composition(&mut self) -> Result<(), Oops> {
+        let (position, text) = match self.composition() {
+            None => return Err(Oops::Ouch("no composition in progress")),
+            Some((p, t)) => (p, String::from(t))
+        };
+
+        // The composition stays in progress if the insert fails, so its text
+        // isn't lost.
+        if !text.is_empty() {
+            self.checkpoint();
+            self.insert(&text, &InsertOptions::exact_at(&Range { beginning: position, ending: position }))?;
+            self.checkpoint();
+        }
+
+        self.cancel_composition();
+        Ok(())
+    }
+
+    /// Ends the composition without changing the document.
+    pub fn cancel_composition(&mut self) {
+        if let Some(handle) = self.composition.take() {
+            let _ = self.remove_virtual_text(handle);
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composition_is_rendered_but_not_tracked() {
+        let mut document = Document::from("ab");
+        document.set_cursor_and_mark(&Position::from(0, 1)).unwrap();
+        document.checkpoint();
+
+        document.begin_composition().unwrap();
+        assert!(document.begin_composition().is_err());
+        document.update_composition("xy").unwrap();
+
+        let viewport = document.viewport(0, 1);
+        assert_eq!(viewport.lines[0].virtual_text[0].text, "xy");
+        assert_eq!(viewport.lines[0].virtual_text[0].style, COMPOSITION_STYLE);
+        assert_eq!(viewport.lines[0].virtual_text[0].column, 1);
+        assert_eq!(document.undo_redo().depth(), (1, 0));
+
+        document.cancel_composition();
+        assert_eq!(document.composition(), None);
+        assert!(document.viewport(0, 1).lines[0].virtual_text.is_empty());
+        assert!(document.update_composition("z").is_err());
+
+        document.begin_composition().unwrap();
+        document.update_composition("Q").unwrap();
+        document.commit_composition().unwrap();
+        assert_eq!(document.text(), "aQb");
+        assert_eq!(document.cursor().position, Position::from(0, 2));
+        assert_eq!(document.undo_redo().depth(), (2, 0));
+    }
+}
@@ -0,0 +1,284 @@
+//! Per-document configuration, layered over workspace-wide defaults.
+//!
+//! The workspace defaults are shared by every document on the current
+//! thread. Each document holds [`ConfigOverrides`], and its effective
+//! [`DocumentConfig`] is the defaults with those overrides applied, so
+//! changing a default reaches every document which doesn't override it.
+//!
+//! Indentation is the exception: it is document state in its own right (see
+//! [`Document::set_indentation`]), so the configured tab settings are copied
+//! into it when a document is created and when its overrides change.
+
+use std::cell::RefCell;
+
+use crate::document::{Document, Indentation};
+use crate::snippets::DateFormat;
+
+/// When a document keeps a parse tree.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum ParsePolicy {
+    Always,
+    Never,
+
+    /// Only while the document has at most this many lines, since parsing
+    /// huge files on every edit is slow.
+    MaxLines(usize)
+}
+
+/// The line break written out when a document is saved. Documents always
+/// use `\n` internally.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr
+}
+
+impl LineEnding {
+    /// Returns the characters of this line ending.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r"
+        }
+    }
+}
+
+/// Settings which control how a document behaves.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub struct DocumentConfig {
+    /// How many columns a tab or indentation level takes up.
+    pub tab_width: usize,
+
+    /// Should indentation be made of spaces rather than tabs?
+    pub use_spaces: bool,
+
+    /// Should typing opening brackets and quotes insert their closing
+    /// counterparts? See [`crate::autopair`].
+    pub auto_pairs: bool,
+
+    /// Should [`Document::prepare_save`] reindent and run formatters? Other
+    /// cleanups in the save pipeline run regardless.
+    pub format_on_save: bool,
+
+    /// See [`crate::ruler`].
+    pub max_line_length: Option<usize>,
+
+    pub parse_policy: ParsePolicy,
+
+    /// See [`Document::output_text`].
+    pub end_of_line: LineEnding,
+
+    /// Should saving trim trailing whitespace, as if the save pipeline's
+    /// step were switched on?
+    pub trim_trailing_whitespace: bool,
+
+    /// Should saving ensure a final newline, as if the save pipeline's step
+    /// were switched on?
+    pub insert_final_newline: bool,
+
+    /// How inserted dates are written. See [`crate::snippets`].
+    pub date_format: DateFormat
+}
+
+/// Settings which differ from the workspace defaults for one document.
+/// `None` fields follow the defaults.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default)]
+pub struct ConfigOverrides {
+    pub tab_width: Option<usize>,
+    pub use_spaces: Option<bool>,
+    pub auto_pairs: Option<bool>,
+    pub format_on_save: Option<bool>,
+    pub max_line_length: Option<Option<usize>>,
+    pub parse_policy: Option<ParsePolicy>,
+    pub end_of_line: Option<LineEnding>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    pub date_format: Option<DateFormat>
+}
+
+impl DocumentConfig {
+    /// Returns the built-in defaults: four spaces, auto-pairs and
+    /// format-on-save on, no maximum line length, always parsing, and
+    /// `\n` line endings with no extra cleanups on save, and ISO dates.
+    pub fn new() -> DocumentConfig {
+        DocumentConfig {
+            tab_width: 4,
+            use_spaces: true,
+            auto_pairs: true,
+            format_on_save: true,
+            max_line_length: None,
+            parse_policy: ParsePolicy::Always,
+            end_of_line: LineEnding::Lf,
+            trim_trailing_whitespace: false,
+            insert_final_newline: false,
+            date_format: DateFormat::Iso
+        }
+    }
+
+    /// Returns this configuration with `overrides` applied.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::config::*;
+    /// let overrides = ConfigOverrides { tab_width: Some(2), max_line_length: Some(Some(80)), ..Default::default() };
+    /// let config = DocumentConfig::new().overlay(&overrides);
+    /// assert_eq!(config.tab_width, 2);
+    /// assert_eq!(config.max_line_length, Some(80));
+    /// assert!(config.auto_pairs);
+    /// ```
+    pub fn overlay(&self, overrides: &ConfigOverrides) -> DocumentConfig {
+        DocumentConfig {
+            tab_width: overrides.tab_width.unwrap_or(self.tab_width),
+            use_spaces: overrides.use_spaces.unwrap_or(self.use_spaces),
+            auto_pairs: overrides.auto_pairs.unwrap_or(self.auto_pairs),
+            format_on_save: overrides.format_on_save.unwrap_or(self.format_on_save),
+            max_line_length: overrides.max_line_length.unwrap_or(self.max_line_length),
+            parse_policy: overrides.parse_policy.unwrap_or(self.parse_policy),
+            end_of_line: overrides.end_of_line.unwrap_or(self.end_of_line),
+            trim_trailing_whitespace: overrides.trim_trailing_whitespace.unwrap_or(self.trim_trailing_whitespace),
+            insert_final_newline: overrides.insert_final_newline.unwrap_or(self.insert_final_newline),
+            date_format: overrides.date_format.unwrap_or(self.date_format)
+        }
+    }
+
+    /// Returns the indentation policy these settings describe.
+    pub fn indentation(&self) -> Indentation {
+        if self.use_spaces {
+            Indentation::spaces(self.tab_width)
+        } else {
+            Indentation::tabs(self.tab_width)
+        }
+    }
+}
+
+impl ConfigOverrides {
+    /// Returns these overrides with every setting in `other` taking
+    /// precedence.
+    pub fn merge(&self, other: &ConfigOverrides) -> ConfigOverrides {
+        ConfigOverrides {
+            tab_width: other.tab_width.or(self.tab_width),
+            use_spaces: other.use_spaces.or(self.use_spaces),
+            auto_pairs: other.auto_pairs.or(self.auto_pairs),
+            format_on_save: other.format_on_save.or(self.format_on_save),
+            max_line_length: other.max_line_length.or(self.max_line_length),
+            parse_policy: other.parse_policy.or(self.parse_policy),
+            end_of_line: other.end_of_line.or(self.end_of_line),
+            trim_trailing_whitespace: other.trim_trailing_whitespace.or(self.trim_trailing_whitespace),
+            insert_final_newline: other.insert_final_newline.or(self.insert_final_newline),
+            date_format: other.date_format.or(self.date_format)
+        }
+    }
+}
+
+impl Default for DocumentConfig {
+    fn default() -> DocumentConfig {
+        DocumentConfig::new()
+    }
+}
+
+impl ParsePolicy {
+    /// Returns whether a document with `rows` lines should be parsed.
+    pub fn allows(&self, rows: usize) -> bool {
+        match self {
+            ParsePolicy::Always => true,
+            ParsePolicy::Never => false,
+            ParsePolicy::MaxLines(max) => rows <= *max
+        }
+    }
+
+    /// Returns whether a document this policy will not parse on every edit
+    /// should be parsed when the host goes idle (see [`Document::tick`]).
+    pub fn allows_when_idle(&self) -> bool {
+        *self != ParsePolicy::Never
+    }
+}
+
+thread_local! {
+    static WORKSPACE_DEFAULTS: RefCell<DocumentConfig> = RefCell::new(DocumentConfig::new());
+}
+
+/// Returns the workspace defaults.
+pub fn workspace_defaults() -> DocumentConfig {
+    WORKSPACE_DEFAULTS.with(|defaults| *defaults.borrow())
+}
+
+/// Replaces the workspace defaults. Existing documents pick up the new
+/// settings, except for indentation, which applies to documents created
+/// afterward.
+pub fn set_workspace_defaults(config: DocumentConfig) {
+    WORKSPACE_DEFAULTS.with(|defaults| *defaults.borrow_mut() = config);
+}
+
+impl Document {
+    /// Returns this document's effective configuration.
+    pub fn config(&self) -> DocumentConfig {
+        workspace_defaults().overlay(&self.config_overrides)
+    }
+
+    /// Returns the settings this document overrides.
+    pub fn config_overrides(&self) -> &ConfigOverrides {
+        &self.config_overrides
+    }
+
+    /// Replaces this document's overrides. The configured indentation
+    /// replaces the document's, and the parse tree is rebuilt or dropped if
+    /// the parse policy requires.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::config::*;
+    /// let mut document = Document::from_with_language("fn f(", "rs");
+    /// document.set_config_overrides(ConfigOverrides {
+    ///     use_spaces: Some(false),
+    ///     auto_pairs: Some(false),
+    ///     ..Default::default()
+    /// });
+    /// assert_eq!(document.indentation(), &Indentation::tabs(4));
+    /// document.type_char('(').unwrap();
+    /// assert_eq!(document.text(), "(fn f(");
+    /// ```
+    pub fn set_config_overrides(&mut self, overrides: ConfigOverrides) {
+        self.config_overrides = overrides;
+        let indentation = self.config().indentation();
+        self.set_indentation_untracked(&indentation);
+        self.update_parse_all();
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_and_overrides() {
+        let document = Document::from("a");
+        assert_eq!(document.config(), DocumentConfig::new());
+
+        set_workspace_defaults(DocumentConfig { tab_width: 2, max_line_length: Some(100), ..DocumentConfig::new() });
+        let mut document = Document::from("a\n  b");
+        assert_eq!(document.indentation(), &Indentation::spaces(2));
+        assert_eq!(document.max_line_length(), Some(100));
+
+        document.set_max_line_length(None).unwrap();
+        assert_eq!(document.max_line_length(), None);
+        assert_eq!(document.config_overrides().max_line_length, Some(None));
+
+        #[cfg(feature = "parsing")]
+        {
+            let mut document = Document::from_with_language("fn f() {}\n", "rs");
+            assert!(document.parse_tree().is_some());
+            document.set_config_overrides(ConfigOverrides { parse_policy: Some(ParsePolicy::MaxLines(1)), ..Default::default() });
+            assert!(document.parse_tree().is_none());
+            document.remove(&crate::document::RemoveOptions::exact_at(&crate::document::Range::from(0, 9, 1, 0))).unwrap();
+            document.update_parse_all();
+            assert!(document.parse_tree().is_some());
+        }
+
+        set_workspace_defaults(DocumentConfig::new());
+    }
+}
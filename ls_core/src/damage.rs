@@ -0,0 +1,167 @@
+//! Damage tracking: which rows of the render model changed, so a frontend
+//! can repaint only those instead of the whole viewport each frame.
+//!
+//! Every change which can alter what [`Document::viewport`] shows is logged
+//! as a span of damaged rows under a new *render revision*: text edits,
+//! highlights which the parser re-derived, folds, virtual text, annotations,
+//! and anchor moves, including the cursor and mark (and so the selection).
+//! Render revisions are not undo revisions; folding and virtual text never
+//! touch the undo stack but still need repainting.
+//!
+//! Rows are not remapped as lines come and go. Instead, any change which
+//! moves later rows (an edit adding or removing lines, a fold, a full
+//! re-parse) damages everything from its first row down, past the end of
+//! the document, so rows which became blank are repainted as well.
+
+use std::collections::VecDeque;
+
+use crate::document::Document;
+
+/// How many changes are remembered. Asking about older revisions damages
+/// everything.
+const DAMAGE_LOG_LIMIT: usize = 1024;
+
+/// The rows damaged since some render revision.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Damage {
+    /// The damaged document rows, as sorted, disjoint, inclusive spans. A
+    /// span ending at `usize::MAX` runs past the end of the document.
+    pub rows: Vec<(usize, usize)>,
+
+    /// The render revision the damage runs up to. Pass it to the next call
+    /// to [`Document::damage_since`].
+    pub revision: u64
+}
+
+impl Damage {
+    /// Does `row` need repainting?
+    pub fn contains(&self, row: usize) -> bool {
+        self.rows.iter().any(|&(first, last)| first <= row && row <= last)
+    }
+
+    /// Is there nothing to repaint?
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+/// A document's recent damage, as `(revision, first_row, last_row)`.
+#[derive(Default)]
+pub(crate) struct DamageLog {
+    revision: u64,
+
+    /// Damage at or before this revision has been forgotten.
+    forgotten: u64,
+
+    entries: VecDeque<(u64, usize, usize)>
+}
+
+impl DamageLog {
+    /// Logs damage to rows `first..=last` under a new render revision.
+    pub(crate) fn record(&mut self, first: usize, last: usize) {
+        self.revision += 1;
+        self.entries.push_back((self.revision, first.min(last), first.max(last)));
+
+        if self.entries.len() > DAMAGE_LOG_LIMIT {
+            if let Some((revision, _, _)) = self.entries.pop_front() {
+                self.forgotten = revision;
+            }
+        }
+    }
+
+    /// Logs damage to every row from `first` down.
+    pub(crate) fn record_from(&mut self, first: usize) {
+        self.record(first, usize::MAX);
+    }
+}
+
+impl Document {
+    /// Returns the current render revision, which advances whenever
+    /// something visible changes. See [`Document::damage_since`].
+    pub fn render_revision(&self) -> u64 {
+        self.damage.revision
+    }
+
+    /// Returns the rows damaged after render revision `revision`, usually
+    /// the [`Damage::revision`] of the previous call or the
+    /// [`Viewport::revision`](crate::render::Viewport::revision) last painted.
+    /// If `revision` is too old to be remembered, or from the future,
+    /// every row is damaged.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("a\nb\nc\nd");
+    /// let painted = document.viewport(0, 10).revision;
+    /// assert!(document.damage_since(painted).is_empty());
+    ///
+    /// document.insert("!", &InsertOptions::exact_at(&Range::from(2, 1, 2, 1))).unwrap();
+    /// let damage = document.damage_since(painted);
+    /// assert_eq!(damage.rows, vec![(2, 2)]);
+    /// assert!(!damage.contains(1));
+    ///
+    /// document.fold_rows(0, 1).unwrap();
+    /// assert_eq!(document.damage_since(damage.revision).rows, vec![(0, usize::MAX)]);
+    /// ```
+    pub fn damage_since(&self, revision: u64) -> Damage {
+        let log = &self.damage;
+        let mut spans: Vec<(usize, usize)> = if revision < log.forgotten || revision > log.revision {
+            vec![(0, usize::MAX)]
+        } else {
+            log.entries.iter()
+                .filter(|(r, _, _)| *r > revision)
+                .map(|&(_, first, last)| (first, last))
+                .collect()
+        };
+
+        spans.sort();
+        let mut rows: Vec<(usize, usize)> = vec![];
+        for (first, last) in spans {
+            match rows.last_mut() {
+                Some(previous) if first <= previous.1.saturating_add(1) => {
+                    previous.1 = previous.1.max(last);
+                },
+                _ => rows.push((first, last))
+            }
+        }
+
+        Damage { rows, revision: log.revision }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::document::*;
+    use crate::annotations::LineAnnotation;
+
+    #[test]
+    fn damage_covers_edits_and_view_changes() {
+        let mut document = Document::from("zero\none\ntwo\nthree\nfour");
+        let start = document.render_revision();
+
+        document.set_cursor_and_mark(&Position::from(3, 1)).unwrap();
+        assert_eq!(document.damage_since(start).rows, vec![(0, 3)]);
+
+        let revision = document.render_revision();
+        document.add_virtual_text(&Position::from(1, 0), "hint", "hint").unwrap();
+        document.set_line_annotation("blame", 4, Some(LineAnnotation::from("a", "author"))).unwrap();
+        assert_eq!(document.damage_since(revision).rows, vec![(1, 1), (4, 4)]);
+
+        let revision = document.render_revision();
+        document.remove(&RemoveOptions::exact_at(&Range::from(1, 3, 2, 0))).unwrap();
+        let damage = document.damage_since(revision);
+        assert_eq!(damage.rows, vec![(1, usize::MAX)]);
+        assert!(damage.contains(40));
+
+        document.undo_once().unwrap();
+        assert!(!document.damage_since(damage.revision).is_empty());
+        assert_eq!(document.damage_since(document.render_revision() + 1).rows, vec![(0, usize::MAX)]);
+
+        for column in 0..1100 {
+            document.set_cursor(&Position::from(0, column % 2)).unwrap();
+        }
+        assert_eq!(document.damage_since(start).rows, vec![(0, usize::MAX)]);
+    }
+}
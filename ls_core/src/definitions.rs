@@ -0,0 +1,295 @@
+//! "Go to definition" for spoken names, within a single document.
+//!
+//! A spoken name is matched against the document's identifiers with
+//! [fuzzy matching](crate::fuzzy). Each match's definitions are then found
+//! in the parse tree (an identifier is a definition when it is the name of
+//! the item, declaration, parameter, or assignment around it) and ranked by
+//! scope: definitions whose scope contains the cursor beat those elsewhere,
+//! and innermost scopes beat outer ones, so shadowing works as expected.
+//!
+//! Without a parse tree, each identifier's first occurrence is taken as its
+//! definition.
+
+use crate::ambiguity::ChoiceAction;
+use crate::document::{Document, Position, Range};
+#[cfg(feature = "parsing")]
+use crate::syntax;
+use crate::util::Oops;
+
+/// How many identifiers a spoken name is matched against.
+pub const DEFINITION_CANDIDATES: usize = 10;
+
+/// A definition of an identifier.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Definition {
+    pub name: String,
+
+    /// The range of the defining identifier.
+    pub range: Range,
+
+    /// The range of the scope in which the definition is visible.
+    pub scope: Range,
+
+    /// How deeply the scope is nested in the parse tree. 0 is the root.
+    pub depth: usize,
+
+    /// The kind of the parse tree node the name belongs to, such as
+    /// `function_item`. Empty without a parse tree.
+    pub kind: String
+}
+
+/// The outcome of looking up a spoken name.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum DefinitionLookup {
+    /// One definition is clearly the best match.
+    Found(Definition),
+
+    /// Several definitions match equally well, best guess first; the user
+    /// should be asked which one they meant.
+    Ambiguous(Vec<Definition>),
+
+    NotFound
+}
+
+/// Node kinds which delimit the scope of the definitions inside them.
+#[cfg(feature = "parsing")]
+const SCOPE_KINDS: &[&str] = &[
+    "source_file", "program", "module", "translation_unit",
+    "block", "statement_block", "compound_statement", "class_body", "declaration_list",
+    "function_item", "function_definition", "function_declaration", "method_declaration",
+    "method_definition", "constructor_declaration", "arrow_function", "closure_expression",
+    "lambda", "class_definition"
+];
+
+/// Node kinds whose names appear in a document's [outline](Document::outline).
+const OUTLINE_KINDS: &[&str] = &[
+    "function_item", "struct_item", "enum_item", "union_item", "trait_item", "mod_item",
+    "type_item", "const_item", "static_item", "macro_definition", "function_signature_item",
+    "function_definition", "function_declaration", "generator_function_declaration",
+    "class_definition", "class_declaration", "interface_declaration", "enum_declaration",
+    "method_definition", "method_declaration", "constructor_declaration", "struct_specifier"
+];
+
+/// Returns whether `node` is the name defined by its parent.
+#[cfg(feature = "parsing")]
+fn is_definition(node: &syntax::Node) -> bool {
+    let parent = match node.parent() {
+        Some(p) => p,
+        None => return false
+    };
+    let field = |name: &str| parent.child_by_field_name(name) == Some(*node);
+    let kind = parent.kind();
+
+    field("name")
+        || (kind == "let_declaration" && field("pattern"))
+        || (kind.ends_with("parameter") && field("pattern"))
+        || kind == "parameters"
+        || kind == "formal_parameters"
+        || (kind == "assignment" && field("left"))
+        || (kind.ends_with("declarator") && field("declarator"))
+}
+
+impl Document {
+    /// Returns every definition in this document, in document order.
+    pub fn definitions(&self) -> Vec<Definition> {
+        #[cfg(feature = "parsing")]
+        {
+            if let Some(tree) = self.parse_tree() {
+                let mut result = vec![];
+                let mut cursor = tree.walk();
+
+                'walk: loop {
+                    let node = cursor.node();
+                    if node.child_count() == 0 && node.kind().ends_with("identifier") && is_definition(&node) {
+                        let mut scope = node.parent().and_then(|p| p.parent());
+                        while let Some(s) = scope {
+                            if SCOPE_KINDS.contains(&s.kind()) {
+                                break;
+                            }
+                            scope = s.parent();
+                        }
+                        let scope = scope.unwrap_or_else(|| tree.root_node());
+
+                        let mut depth = 0;
+                        let mut ancestor = scope.parent();
+                        while let Some(a) = ancestor {
+                            depth += 1;
+                            ancestor = a.parent();
+                        }
+
+                        let range = self.range_from_ts(&node.range());
+                        if let Some(name) = self.text_range(&range) {
+                            result.push(Definition {
+                                name,
+                                range,
+                                scope: self.range_from_ts(&scope.range()),
+                                depth,
+                                kind: node.parent().map(|p| String::from(p.kind())).unwrap_or_default()
+                            });
+                        }
+                    }
+
+                    if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                        continue;
+                    }
+                    loop {
+                        if !cursor.goto_parent() {
+                            break 'walk;
+                        }
+                        if cursor.goto_next_sibling() {
+                            break;
+                        }
+                    }
+                }
+
+                return result;
+            }
+        }
+
+        let whole = Range {
+            beginning: Position::from(0, 0),
+            ending: Position::from(self.rows() - 1, self.lines()[self.rows() - 1].length)
+        };
+        let mut result: Vec<Definition> = self.identifier_index()
+            .iter()
+            .map(|(name, ranges)| Definition { name: name.clone(), range: ranges[0], scope: whole, depth: 0, kind: String::new() })
+            .collect();
+        result.sort_by_key(|d| d.range.beginning);
+        result
+    }
+
+    /// Returns the definitions of this document's functions, types, modules,
+    /// constants and the like, in document order, leaving out variables and
+    /// parameters. Empty without a parse tree.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "parsing")] {
+    /// use ls_core::document::*;
+    /// let document = Document::from_with_language("struct Line;\nfn parse_line(s: &str) {\n    let n = 1;\n}", "rs");
+    /// let names: Vec<String> = document.outline().into_iter().map(|d| d.name).collect();
+    /// assert_eq!(names, vec!["Line", "parse_line"]);
+    /// # }
+    /// ```
+    pub fn outline(&self) -> Vec<Definition> {
+        self.definitions()
+            .into_iter()
+            .filter(|d| OUTLINE_KINDS.contains(&d.kind.as_str()))
+            .collect()
+    }
+
+    /// Looks up the definition of spoken name `query`, as seen from the
+    /// cursor. Candidates are ranked by how well their names match, then by
+    /// whether their scope contains the cursor, then by how deeply nested
+    /// that scope is, then by closeness to the cursor.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::definitions::*;
+    /// let mut document = Document::from_with_language(
+    ///     "fn parse_file() {}\nfn main() {\n    parse_file();\n}", "rs");
+    /// document.set_cursor_and_mark(&Position::from(2, 4)).unwrap();
+    ///
+    /// match document.find_definition("parse file") {
+    ///     DefinitionLookup::Found(d) => assert_eq!(d.range, Range::from(0, 3, 0, 13)),
+    ///     other => panic!("{:?}", other)
+    /// }
+    /// assert_eq!(document.find_definition("zebra"), DefinitionLookup::NotFound);
+    /// ```
+    pub fn find_definition(&self, query: &str) -> DefinitionLookup {
+        let cursor = self.cursor().position;
+        let candidates = self.identifier_index().fuzzy_find(query, DEFINITION_CANDIDATES);
+        let definitions = self.definitions();
+
+        let distance = |r: &Range| (r.beginning.row as isize - cursor.row as isize).unsigned_abs();
+        let visible = |d: &Definition| d.scope.beginning <= cursor && cursor <= d.scope.ending;
+
+        let mut ranked: Vec<(u32, bool, usize, Definition)> = candidates.iter()
+            .flat_map(|c| definitions.iter()
+                .filter(move |d| d.name == c.name)
+                .map(move |d| (c.score, visible(d), d.depth, d.clone())))
+            .collect();
+
+        ranked.sort_by(|a, b| b.0.cmp(&a.0)
+            .then_with(|| b.1.cmp(&a.1))
+            .then_with(|| b.2.cmp(&a.2))
+            .then_with(|| distance(&a.3.range).cmp(&distance(&b.3.range))));
+
+        match ranked.len() {
+            0 => DefinitionLookup::NotFound,
+            1 => DefinitionLookup::Found(ranked.remove(0).3),
+            _ if (ranked[0].0, ranked[0].1, ranked[0].2) != (ranked[1].0, ranked[1].1, ranked[1].2) => {
+                DefinitionLookup::Found(ranked.remove(0).3)
+            },
+            _ => DefinitionLookup::Ambiguous(ranked.into_iter().map(|r| r.3).collect())
+        }
+    }
+
+    /// Like [`Document::find_definition`], but also moves the cursor and mark
+    /// to the start of the definition when one is found. When several are,
+    /// they are left pending for [`Document::choose`].
+    pub fn go_to_definition(&mut self, query: &str) -> Result<DefinitionLookup, Oops> {
+        let lookup = self.find_definition(query);
+        match &lookup {
+            DefinitionLookup::Found(definition) => self.set_cursor_and_mark(&definition.range.beginning)?,
+            DefinitionLookup::Ambiguous(all) => {
+                self.make_ambiguous(ChoiceAction::GoTo, all.iter().map(|d| d.range).collect());
+            },
+            DefinitionLookup::NotFound => ()
+        }
+        Ok(lookup)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "parsing"))]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"
+fn count(items: &[u8]) -> usize {
+    let total = items.len();
+    total
+}
+fn other() {
+    let total = 0;
+    let items = 1;
+}
+"#;
+
+    #[test]
+    fn definitions_resolve_by_scope() {
+        let mut document = Document::from_with_language(SOURCE, "rs");
+
+        document.set_cursor_and_mark(&Position::from(3, 4)).unwrap();
+        assert_eq!(
+            document.go_to_definition("total"),
+            Ok(DefinitionLookup::Found(document.definitions()[2].clone()))
+        );
+        assert_eq!(document.cursor().position, Position::from(2, 8));
+
+        document.set_cursor_and_mark(&Position::from(2, 16)).unwrap();
+        match document.find_definition("items") {
+            DefinitionLookup::Found(d) => assert_eq!(d.range, Range::from(1, 9, 1, 14)),
+            other => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn definitions_outside_scopes_are_ambiguous() {
+        let mut document = Document::from_with_language(SOURCE, "rs");
+        document.set_cursor_and_mark(&Position::from(0, 0)).unwrap();
+        match document.find_definition("total") {
+            DefinitionLookup::Ambiguous(all) => {
+                assert_eq!(all.len(), 2);
+                assert_eq!(all[0].range, Range::from(2, 8, 2, 13));
+            },
+            other => panic!("{:?}", other)
+        }
+        document.go_to_definition("total").unwrap();
+        assert_eq!(document.choose(2), Ok(Range::from(6, 8, 6, 13)));
+        assert_eq!(document.cursor().position, Position::from(6, 8));
+    }
+}
@@ -0,0 +1,198 @@
+//! Diagnostics (errors, warnings, and hints) reported against a document by
+//! external tools like language servers and linters.
+//!
+//! Each diagnostic's range is held by a pair of floating anchors, so
+//! diagnostics keep pointing at the right text while the user edits,
+//! until the tool that produced them reports again.
+
+use crate::document::{Document, Position, Range, AnchorHandle};
+use crate::util::Oops;
+
+/// How serious a [`Diagnostic`] is. Sorts from most to least severe.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint
+}
+
+/// A message about a range of a document.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    pub message: String,
+
+    /// The tool which produced this diagnostic, e.g. `"rust-analyzer"`.
+    pub source: String
+}
+
+/// A [`Diagnostic`] as stored in a document: its range is tracked by two
+/// floating anchors.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub(crate) struct AnchoredDiagnostic {
+    beginning: AnchorHandle,
+    ending: AnchorHandle,
+    severity: Severity,
+    message: String,
+    source: String
+}
+
+impl Diagnostic {
+    /// Returns a diagnostic at `range` with no source.
+    pub fn from(range: &Range, severity: Severity, message: &str) -> Diagnostic {
+        Diagnostic {
+            range: *range,
+            severity,
+            message: String::from(message),
+            source: String::new()
+        }
+    }
+}
+
+impl Document {
+    /// Replaces every diagnostic previously reported by `source` with
+    /// `diagnostics`, whose `source` fields are overwritten with `source`.
+    ///
+    /// Returns `Err` without changing anything if any range is invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::diagnostics::*;
+    /// let mut document = Document::from("let x = 1;\nlet y = z;");
+    /// document.set_diagnostics("rustc", vec![
+    ///     Diagnostic::from(&Range::from(1, 8, 1, 9), Severity::Error, "cannot find value `z`")
+    /// ]).unwrap();
+    ///
+    /// document.insert("\n", &InsertOptions::exact()).unwrap();
+    /// assert_eq!(document.diagnostics()[0].range, Range::from(2, 8, 2, 9));
+    /// assert_eq!(document.diagnostics_at(&Position::from(2, 9)).len(), 1);
+    /// ```
+    pub fn set_diagnostics(&mut self, source: &str, diagnostics: Vec<Diagnostic>) -> Result<(), Oops> {
+        for d in diagnostics.iter() {
+            if !self.range_valid(&d.range) {
+                return Err(Oops::InvalidRange(d.range, "set_diagnostics"));
+            }
+        }
+
+        self.clear_diagnostics(source);
+
+        for d in diagnostics {
+            let beginning = self.create_floating_anchor(&d.range.beginning)?;
+            let ending = self.create_floating_anchor(&d.range.ending)?;
+
+            self.diagnostics.push(AnchoredDiagnostic {
+                beginning,
+                ending,
+                severity: d.severity,
+                message: d.message,
+                source: String::from(source)
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Removes every diagnostic reported by `source`.
+    pub fn clear_diagnostics(&mut self, source: &str) {
+        let (removed, kept): (Vec<AnchoredDiagnostic>, Vec<AnchoredDiagnostic>) = 
+            self.diagnostics.drain(..).partition(|d| d.source == source);
+        self.diagnostics = kept;
+
+        for d in removed {
+            let _ = self.remove_floating_anchor(d.beginning);
+            let _ = self.remove_floating_anchor(d.ending);
+        }
+    }
+
+    /// Returns all diagnostics in document order (by range), most severe first
+    /// among diagnostics which start at the same position.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut result: Vec<Diagnostic> = self.diagnostics
+            .iter()
+            .map(|d| Diagnostic {
+                range: Range {
+                    beginning: self.anchor(d.beginning).unwrap().position,
+                    ending: self.anchor(d.ending).unwrap().position
+                },
+                severity: d.severity,
+                message: d.message.clone(),
+                source: d.source.clone()
+            })
+            .collect();
+
+        result.sort_by_key(|d| (d.range.beginning, d.severity, d.range.ending));
+        result
+    }
+
+    /// Returns the diagnostics whose ranges contain `position`, in order.
+    /// Range endpoints count as inside the range.
+    pub fn diagnostics_at(&self, position: &Position) -> Vec<Diagnostic> {
+        self.diagnostics()
+            .into_iter()
+            .filter(|d| d.range.beginning <= *position && *position <= d.range.ending)
+            .collect()
+    }
+
+    /// Returns the first diagnostic starting after `position`, wrapping around
+    /// to the start of the document, or `None` if there are no diagnostics.
+    pub fn next_diagnostic(&self, position: &Position) -> Option<Diagnostic> {
+        let all = self.diagnostics();
+        let index = all.iter().position(|d| d.range.beginning > *position).unwrap_or(0);
+        all.into_iter().nth(index)
+    }
+
+    /// Returns the last diagnostic starting before `position`, wrapping around
+    /// to the end of the document, or `None` if there are no diagnostics.
+    pub fn previous_diagnostic(&self, position: &Position) -> Option<Diagnostic> {
+        let all = self.diagnostics();
+        match all.iter().rposition(|d| d.range.beginning < *position) {
+            Some(index) => all.into_iter().nth(index),
+            None => all.into_iter().last()
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::*;
+
+    #[test]
+    fn diagnostics_by_source() {
+        let mut document = Document::from("aaa\nbbb\nccc");
+        document.set_diagnostics("lint", vec![
+            Diagnostic::from(&Range::from(2, 0, 2, 3), Severity::Warning, "c"),
+            Diagnostic::from(&Range::from(0, 0, 0, 3), Severity::Hint, "a"),
+        ]).unwrap();
+        document.set_diagnostics("lsp", vec![
+            Diagnostic::from(&Range::from(0, 0, 0, 1), Severity::Error, "a!"),
+        ]).unwrap();
+
+        let all = document.diagnostics();
+        assert_eq!(all.iter().map(|d| d.message.as_str()).collect::<Vec<&str>>(), vec!["a!", "a", "c"]);
+        assert_eq!(all[0].source, "lsp");
+
+        assert_eq!(document.next_diagnostic(&Position::from(0, 0)).unwrap().message, "c");
+        assert_eq!(document.next_diagnostic(&Position::from(2, 0)).unwrap().message, "a!");
+        assert_eq!(document.previous_diagnostic(&Position::from(0, 0)).unwrap().message, "c");
+
+        document.remove(&RemoveOptions::exact_at(&Range::from(0, 2, 1, 3))).unwrap();
+        assert_eq!(document.text(), "aa\nccc");
+        assert_eq!(document.diagnostics()[1].range, Range::from(0, 0, 0, 2));
+        assert_eq!(document.diagnostics()[2].range, Range::from(1, 0, 1, 3));
+
+        document.clear_diagnostics("lint");
+        assert_eq!(document.diagnostics().len(), 1);
+        assert_eq!(document.anchors().len(), 4);
+
+        assert!(document.set_diagnostics("lsp", vec![
+            Diagnostic::from(&Range::from(5, 0, 5, 1), Severity::Error, "bad"),
+        ]).is_err());
+        assert_eq!(document.diagnostics().len(), 1);
+    }
+}
@@ -0,0 +1,124 @@
+//! Formatting dictated phrases for where they land.
+//!
+//! The same spoken words mean different text in different places: "parse
+//! the file" is prose inside a comment or string, but an identifier like
+//! `parse_the_file` in code. The parse tree decides which, via
+//! [`Document::is_in_comment`] and [`Document::is_in_string`]. Without a parse tree, everything is code.
+
+use crate::document::{Document, Position, InsertOptions};
+pub use crate::casing::Casing;
+use crate::util::{self, Oops};
+use crate::casing::capitalize;
+
+/// What kind of text surrounds a position.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum DictationContext {
+    Code,
+    String,
+    Comment
+}
+
+/// Formats `phrase` for `context`. In code, its words become one identifier
+/// in `casing`. In prose, whitespace is normalized and, if `sentence_start`,
+/// the first letter is capitalized; otherwise the words are kept as spoken.
+///
+/// # Examples
+/// ```
+/// use ls_core::dictation::*;
+/// assert_eq!(format_dictation("max  width", DictationContext::Code, Casing::Snake, false), "max_width");
+/// assert_eq!(format_dictation("max  width", DictationContext::Comment, Casing::Snake, true), "Max width");
+/// assert_eq!(format_dictation("max  width", DictationContext::String, Casing::Snake, false), "max width");
+/// ```
+pub fn format_dictation(phrase: &str, context: DictationContext, casing: Casing, sentence_start: bool) -> String {
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+
+    match context {
+        DictationContext::Code => casing.apply(&words),
+        _ => {
+            let prose = words.join(" ");
+            if sentence_start { capitalize(&prose) } else { prose }
+        }
+    }
+}
+
+impl Document {
+    /// Returns whether `position` is in code, a string, or a comment.
+    pub fn dictation_context_at(&self, position: &Position) -> DictationContext {
+        if self.is_in_comment(position) {
+            DictationContext::Comment
+        } else if self.is_in_string(position) {
+            DictationContext::String
+        } else {
+            DictationContext::Code
+        }
+    }
+
+    /// Inserts `phrase` at the cursor, replacing the selection, formatted for
+    /// the context there (see [`format_dictation`]). Code uses the
+    /// language's [`Casing`]. Prose is separated from preceding text by a
+    /// space, and capitalized at the start of a comment or after a full stop.
+    ///
+    /// Like [`Document::insert`], this does not checkpoint. Where the text
+    /// ends is remembered for [`Document::resume_dictation`].
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language("let  = 1; // ", "rs");
+    /// document.set_cursor_and_mark(&Position::from(0, 4)).unwrap();
+    /// document.dictate("max width").unwrap();
+    /// assert_eq!(document.text(), "let max_width = 1; // ");
+    /// ```
+    pub fn dictate(&mut self, phrase: &str) -> Result<(), Oops> {
+        let position = self.selection().beginning;
+        let context = self.dictation_context_at(&position);
+
+        let before = util::slice(&self.lines()[position.row].content, ..position.column);
+        let previous = before.trim_end();
+        let sentence_start = context == DictationContext::Comment && (
+            previous.is_empty()
+            || previous.ends_with(['.', '!', '?'])
+            || previous.split_whitespace().last().is_none_or(|w| w.chars().all(|c| !c.is_alphanumeric()))
+        );
+
+        let casing = self.casing();
+        let mut text = format_dictation(phrase, context, casing, sentence_start);
+
+        if context != DictationContext::Code
+            && before.chars().next_back().is_some_and(|c| !c.is_whitespace() && c != '"' && c != '\'' && c != '`')
+        {
+            text.insert(0, ' ');
+        }
+
+        self.insert(&text, &InsertOptions::exact())?;
+        let ending = self.cursor().position;
+        self.record_dictation(&ending);
+        Ok(())
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "parsing"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dictation_follows_context() {
+        let mut document = Document::from_with_language("let s = \"\"; //", "rs");
+        document.set_cursor_and_mark(&Position::from(0, 14)).unwrap();
+        assert_eq!(document.dictation_context_at(&Position::from(0, 14)), DictationContext::Comment);
+        document.dictate("fix this. later").unwrap();
+        assert_eq!(document.text(), "let s = \"\"; // Fix this. later");
+
+        document.set_cursor_and_mark(&Position::from(0, 9)).unwrap();
+        assert_eq!(document.dictation_context_at(&Position::from(0, 9)), DictationContext::String);
+        document.dictate("hello world").unwrap();
+        assert_eq!(document.text(), "let s = \"hello world\"; // Fix this. later");
+
+        let mut document = Document::from_with_language("const  = 1;", "js");
+        document.set_cursor_and_mark(&Position::from(0, 6)).unwrap();
+        document.dictate("Max Width").unwrap();
+        assert_eq!(document.text(), "const maxWidth = 1;");
+    }
+}
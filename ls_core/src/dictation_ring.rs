@@ -0,0 +1,132 @@
+//! Where dictation left off, so "resume dictation" can go back there after
+//! the user has moved around to look at or fix something else.
+//!
+//! Each [`Document::dictate`] records where its text ended in a small ring
+//! of the most recent [`DICTATION_RING_SIZE`] spots. The ring is separate
+//! from the changelist: commands, motions, and other edits never add to it,
+//! though its entries follow edits like the changelist's do. Dictating on
+//! from the newest spot moves that spot along instead of adding another.
+
+use std::collections::VecDeque;
+
+use crate::document::{Document, Position, Range, Anchor};
+use crate::util::Oops;
+
+/// How many dictation spots are remembered.
+pub const DICTATION_RING_SIZE: usize = 8;
+
+/// A document's recent dictation spots.
+#[derive(Default)]
+pub(crate) struct DictationRing {
+    /// The spots, oldest first.
+    entries: VecDeque<Anchor>,
+
+    /// The spot last resumed at, while cycling through the ring. New
+    /// dictation starts over from the newest.
+    current: Option<usize>
+}
+
+impl DictationRing {
+    /// Moves every spot after `text` was inserted at `position`.
+    pub(crate) fn after_insert(&mut self, position: &Position, text: &[String]) {
+        for entry in self.entries.iter_mut() {
+            entry.position = entry.position.after_insert(position, text);
+        }
+    }
+
+    /// Moves every spot after `range` was removed.
+    pub(crate) fn after_remove(&mut self, range: &Range) {
+        for entry in self.entries.iter_mut() {
+            entry.position = entry.position.after_remove(range);
+        }
+    }
+}
+
+impl Document {
+    /// Records that dictation ended at `ending`. If it began at the newest
+    /// spot, the insert has carried that spot along to `ending` already.
+    pub(crate) fn record_dictation(&mut self, ending: &Position) {
+        let ring = &mut self.dictation_ring;
+        ring.current = None;
+
+        if ring.entries.back().is_some_and(|newest| newest.position == *ending) {
+            return;
+        }
+        ring.entries.push_back(Anchor { position: *ending });
+        if ring.entries.len() > DICTATION_RING_SIZE {
+            ring.entries.pop_front();
+        }
+    }
+
+    /// Returns where the remembered dictation spots are now, oldest first.
+    pub fn dictation_spots(&self) -> Vec<Position> {
+        self.dictation_ring.entries.iter().map(|a| a.position).collect()
+    }
+
+    /// Moves the cursor and mark back to where dictation last left off, or,
+    /// said again straight away, to the spot before that, wrapping around
+    /// to the newest after the oldest. Returns where the spot is, or `Err`
+    /// if nothing has been dictated.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("a\nb\nc");
+    /// document.set_cursor_and_mark(&Position::from(0, 1)).unwrap();
+    /// document.dictate("one").unwrap();
+    /// document.dictate("two").unwrap();
+    /// document.set_cursor_and_mark(&Position::from(2, 1)).unwrap();
+    /// document.dictate("three").unwrap();
+    /// assert_eq!(document.dictation_spots(), vec![Position::from(0, 7), Position::from(2, 6)]);
+    ///
+    /// document.set_cursor_and_mark(&Position::from(1, 0)).unwrap();
+    /// assert_eq!(document.resume_dictation(), Ok(Position::from(2, 6)));
+    /// assert_eq!(document.resume_dictation(), Ok(Position::from(0, 7)));
+    /// assert_eq!(document.resume_dictation(), Ok(Position::from(2, 6)));
+    /// ```
+    pub fn resume_dictation(&mut self) -> Result<Position, Oops> {
+        let ring = &self.dictation_ring;
+        let index = match (ring.entries.len(), ring.current) {
+            (0, _) => return Err(Oops::Ouch("nothing dictated")),
+            (length, Some(current)) if self.cursor().position == ring.entries[current].position => {
+                (current + length - 1) % length
+            },
+            (length, _) => length - 1
+        };
+
+        let position = self.dictation_ring.entries[index].position;
+        self.set_cursor_and_mark(&position)?;
+        self.dictation_ring.current = Some(index);
+        Ok(position)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{InsertOptions, RemoveOptions};
+
+    #[test]
+    fn spots_follow_edits_and_ignore_other_changes() {
+        let mut document = Document::from("first\nsecond");
+        assert!(document.resume_dictation().is_err());
+
+        document.set_cursor_and_mark(&Position::from(1, 6)).unwrap();
+        document.dictate("line").unwrap();
+        document.insert("zero\n", &InsertOptions::exact_at(&Range::from(0, 0, 0, 0))).unwrap();
+        document.remove(&RemoveOptions::exact_at(&Range::from(2, 0, 2, 3))).unwrap();
+        assert_eq!(document.dictation_spots(), vec![Position::from(2, 7)]);
+
+        document.execute_phrase("first line").unwrap();
+        assert_eq!(document.execute_phrase("resume dictation"), Ok(crate::commands::Outcome::Moved(Position::from(2, 7))));
+        assert_eq!(document.resume_dictation(), Ok(Position::from(2, 7)));
+
+        for row in 0..=DICTATION_RING_SIZE {
+            document.set_cursor_and_mark(&Position::from(0, 0)).unwrap();
+            document.dictate(&format!("x{} ", row)).unwrap();
+        }
+        assert_eq!(document.dictation_spots().len(), DICTATION_RING_SIZE);
+    }
+}
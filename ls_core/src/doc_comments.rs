@@ -0,0 +1,338 @@
+//! Doc-comment skeletons: "doc comment" reads the signature of the function
+//! at the cursor from the parse tree and inserts an empty doc comment in the
+//! language's style, with a slot for the summary, each parameter, and the
+//! return value.
+//!
+//! Each slot is held by a floating anchor, like a
+//! [named anchor](crate::named_anchors), so the user can fill the slots in
+//! any order while the comment is edited around them. The caller owns the
+//! anchors and should remove them with
+//! [`Document::remove_floating_anchor`] once the comment is written.
+
+use crate::document::{Document, Position, Range, AnchorHandle, InsertOptions};
+#[cfg(feature = "parsing")]
+use crate::syntax;
+use crate::util::Oops;
+
+/// What a doc comment needs to know about a function.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Signature {
+    /// The range of the whole function, including any attributes or export
+    /// keyword in front of it.
+    pub range: Range,
+
+    /// Where the function's body begins, if it has one.
+    pub body: Option<Position>,
+
+    /// The names of the parameters, in order, without `self`.
+    pub parameters: Vec<String>,
+
+    /// Does the function return a value? True if it declares a return type
+    /// other than `void` or `()`, or, when it declares none, if its body
+    /// returns a value.
+    pub returns: bool
+}
+
+/// A place in an inserted doc comment for the user to fill in.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct DocSlot {
+    /// "summary", "returns", or a parameter's name.
+    pub name: String,
+
+    /// A floating anchor at the slot.
+    pub anchor: AnchorHandle
+}
+
+/// A doc comment inserted by [`Document::insert_doc_comment`].
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct DocComment {
+    /// The range of the inserted lines.
+    pub range: Range,
+
+    /// The slots, in document order, starting with the summary.
+    pub slots: Vec<DocSlot>
+}
+
+/// Node kinds which are functions with a signature worth documenting.
+#[cfg(feature = "parsing")]
+pub const FUNCTION_KINDS: &[&str] = &[
+    "function_item", "function_signature_item", "function_definition", "function_declaration",
+    "method_declaration", "method_definition", "constructor_declaration", "generator_function_declaration"
+];
+
+/// Parameter names which stand for the receiver rather than an argument.
+#[cfg(feature = "parsing")]
+const RECEIVERS: &[&str] = &["self", "cls"];
+
+/// Returns one line of a doc comment skeleton per element: the text of
+/// the line, after the indentation, and the name of the slot at its end,
+/// if any. Returns `None` if `language` has no doc comment style.
+///
+/// # Examples
+/// ```
+/// use ls_core::doc_comments::*;
+/// let lines = doc_comment_lines("js", &[String::from("a")], true).unwrap();
+/// assert_eq!(lines, vec![
+///     (String::from("/**"), None),
+///     (String::from(" * "), Some(String::from("summary"))),
+///     (String::from(" * @param a "), Some(String::from("a"))),
+///     (String::from(" * @returns "), Some(String::from("returns"))),
+///     (String::from(" */"), None)
+/// ]);
+/// ```
+pub fn doc_comment_lines(language: &str, parameters: &[String], returns: bool) -> Option<Vec<(String, Option<String>)>> {
+    let slot = |text: String, name: &str| (text, Some(String::from(name)));
+    let plain = |text: &str| (String::from(text), None);
+    let mut lines = vec![];
+
+    match language {
+        "rs" => {
+            lines.push(slot(String::from("/// "), "summary"));
+            if !parameters.is_empty() {
+                lines.extend([plain("///"), plain("/// # Arguments")]);
+                lines.extend(parameters.iter().map(|p| slot(format!("/// * `{}` - ", p), p)));
+            }
+            if returns {
+                lines.extend([plain("///"), plain("/// # Returns"), slot(String::from("/// "), "returns")]);
+            }
+        },
+        "py" => {
+            lines.push(slot(String::from("\"\"\""), "summary"));
+            if !parameters.is_empty() {
+                lines.extend([plain(""), plain("Args:")]);
+                lines.extend(parameters.iter().map(|p| slot(format!("    {}: ", p), p)));
+            }
+            if returns {
+                lines.extend([plain(""), plain("Returns:"), slot(String::from("    "), "returns")]);
+            }
+            lines.push(plain("\"\"\""));
+        },
+        "js" | "ts" | "tsx" | "java" | "c" | "h" | "cpp" | "hpp" | "cs" => {
+            let returns_tag = if matches!(language, "js" | "ts" | "tsx") { "@returns" } else { "@return" };
+            lines.extend([plain("/**"), slot(String::from(" * "), "summary")]);
+            lines.extend(parameters.iter().map(|p| slot(format!(" * @param {} ", p), p)));
+            if returns {
+                lines.push(slot(format!(" * {} ", returns_tag), "returns"));
+            }
+            lines.push(plain(" */"));
+        },
+        _ => return None
+    }
+
+    Some(lines)
+}
+
+/// Returns the name bound by the parameter `node`.
+#[cfg(feature = "parsing")]
+fn parameter_name(document: &Document, node: syntax::Node) -> Option<String> {
+    if node.kind().ends_with("identifier") {
+        return document.text_range(&document.range_from_ts(&node.range()));
+    }
+
+    let inner = ["pattern", "name", "left", "declarator"].iter()
+        .find_map(|field| node.child_by_field_name(field))
+        .or_else(|| node.named_child(0))?;
+    parameter_name(document, inner)
+}
+
+/// Does `node` contain a statement which returns a value, outside any
+/// nested function?
+#[cfg(feature = "parsing")]
+fn returns_value(node: syntax::Node) -> bool {
+    if matches!(node.kind(), "return_statement" | "return_expression") && node.named_child_count() > 0 {
+        return true;
+    }
+
+    let mut cursor = node.walk();
+    let children: Vec<syntax::Node> = node.named_children(&mut cursor).collect();
+    children.into_iter().any(|child| {
+        !FUNCTION_KINDS.contains(&child.kind())
+            && !matches!(child.kind(), "closure_expression" | "arrow_function" | "lambda")
+            && returns_value(child)
+    })
+}
+
+impl Document {
+    /// Returns the signature of the innermost function around `position`.
+    /// Returns `Err` if there is no parse tree or no function there.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let document = Document::from_with_language("fn add(&self, a: u32, (b, _): (u32, u8)) -> u32 {\n    a\n}", "rs");
+    /// let signature = document.signature_at(&Position::from(1, 4)).unwrap();
+    /// assert_eq!(signature.parameters, vec!["a", "b"]);
+    /// assert!(signature.returns);
+    /// ```
+    #[cfg(feature = "parsing")]
+    pub fn signature_at(&self, position: &Position) -> Result<Signature, Oops> {
+        let mut node = self.node_at(&Range { beginning: *position, ending: *position })
+            .ok_or(Oops::CannotParse("signature_at"))?;
+        while !FUNCTION_KINDS.contains(&node.kind()) {
+            node = node.parent().ok_or(Oops::Ouch("no function around position"))?;
+        }
+
+        // Include what belongs in front of the function.
+        let mut start = node;
+        if let Some(parent) = node.parent().filter(|p| matches!(p.kind(), "export_statement" | "decorated_definition")) {
+            start = parent;
+        }
+        while let Some(previous) = start.prev_named_sibling().filter(|p| p.kind() == "attribute_item") {
+            start = previous;
+        }
+        let range = Range {
+            beginning: self.range_from_ts(&start.range()).beginning,
+            ending: self.range_from_ts(&node.range()).ending
+        };
+
+        let declarator = node.child_by_field_name("declarator");
+        let parameter_list = node.child_by_field_name("parameters")
+            .or_else(|| declarator.and_then(|d| d.child_by_field_name("parameters")));
+        let mut parameters = vec![];
+        if let Some(list) = parameter_list {
+            let mut cursor = list.walk();
+            for child in list.named_children(&mut cursor) {
+                if child.kind() == "self_parameter" || child.kind() == "comment" {
+                    continue;
+                }
+                if let Some(name) = parameter_name(self, child).filter(|n| !RECEIVERS.contains(&n.as_str())) {
+                    parameters.push(name);
+                }
+            }
+        }
+
+        let body = node.child_by_field_name("body");
+        let declared = node.child_by_field_name("return_type")
+            .or_else(|| node.child_by_field_name("type"))
+            .and_then(|t| self.text_range(&self.range_from_ts(&t.range())));
+        let returns = match declared {
+            Some(declared) => declared != "void" && declared != "()",
+            None => body.is_some_and(returns_value)
+        };
+
+        Ok(Signature {
+            range,
+            body: body.map(|b| self.range_from_ts(&b.range()).beginning),
+            parameters,
+            returns
+        })
+    }
+
+    /// Without the `parsing` feature there is never a parse tree, so this
+    /// always returns an [`Oops`].
+    #[cfg(not(feature = "parsing"))]
+    pub fn signature_at(&self, _position: &Position) -> Result<Signature, Oops> {
+        Err(Oops::CannotParse("signature_at"))
+    }
+
+    /// Inserts a doc comment skeleton for the function around the cursor:
+    /// above it, or for Python, as the first line of its body. Moves the
+    /// cursor to the summary slot. The insertion is one change packet labeled
+    /// "doc comment". Returns `Err` if there is no function at the cursor or
+    /// the language has no doc comment style.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "parsing")] {
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language("class A {\n    int f(int x) { return x; }\n}", "java");
+    /// document.set_cursor_and_mark(&Position::from(1, 20)).unwrap();
+    /// let comment = document.insert_doc_comment().unwrap();
+    /// assert_eq!(document.text(), concat!(
+    ///     "class A {\n",
+    ///     "    /**\n",
+    ///     "     * \n",
+    ///     "     * @param x \n",
+    ///     "     * @return \n",
+    ///     "     */\n",
+    ///     "    int f(int x) { return x; }\n",
+    ///     "}"
+    /// ));
+    /// assert_eq!(comment.slots.len(), 3);
+    /// assert_eq!(document.anchor(comment.slots[1].anchor).unwrap().position, Position::from(3, 16));
+    /// assert_eq!(document.cursor().position, Position::from(2, 7));
+    /// # }
+    /// ```
+    pub fn insert_doc_comment(&mut self) -> Result<DocComment, Oops> {
+        let signature = self.signature_at(&self.cursor().position)?;
+        let lines = doc_comment_lines(self.language(), &signature.parameters, signature.returns)
+            .ok_or(Oops::Ouch("no doc comment style for this language"))?;
+
+        let row = if self.language() == "py" {
+            match signature.body {
+                Some(body) if body.row > signature.range.beginning.row => body.row,
+                _ => return Err(Oops::Ouch("function body must start on its own line"))
+            }
+        } else {
+            signature.range.beginning.row
+        };
+        let margin: String = self.lines()[row].content.chars().take_while(|c| c.is_whitespace()).collect();
+        let margin_columns = margin.chars().count();
+
+        let mut text = String::new();
+        for (line, _) in lines.iter() {
+            if !line.is_empty() {
+                text.push_str(&margin);
+                text.push_str(line);
+            }
+            text.push('\n');
+        }
+
+        self.checkpoint_labeled("doc comment");
+        self.insert(&text, &InsertOptions::exact_at(&Range::from(row, 0, row, 0)))?;
+
+        let mut slots = vec![];
+        for (i, (line, name)) in lines.iter().enumerate() {
+            if let Some(name) = name {
+                let position = Position::from(row + i, margin_columns + line.chars().count());
+                slots.push(DocSlot { name: name.clone(), anchor: self.create_floating_anchor(&position)? });
+            }
+        }
+
+        let last = row + lines.len() - 1;
+        let range = Range::from(row, 0, last, self.lines()[last].length);
+        if let Some(summary) = slots.first() {
+            let position = self.anchor(summary.anchor).unwrap().position;
+            self.set_cursor_and_mark(&position)?;
+        }
+        self.checkpoint();
+
+        Ok(DocComment { range, slots })
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "parsing"))]
+mod tests {
+    use crate::document::*;
+
+    #[test]
+    fn skeletons_follow_the_language() {
+        let mut document = Document::from_with_language("#[inline]\nfn f(a: u8, b: u8) {\n    g(a, b);\n}", "rs");
+        document.set_cursor_and_mark(&Position::from(2, 4)).unwrap();
+        let comment = document.insert_doc_comment().unwrap();
+        assert_eq!(document.text(), concat!(
+            "/// \n///\n/// # Arguments\n/// * `a` - \n/// * `b` - \n",
+            "#[inline]\nfn f(a: u8, b: u8) {\n    g(a, b);\n}"
+        ));
+        assert_eq!(comment.range, Range::from(0, 0, 4, 12));
+        let names: Vec<&str> = comment.slots.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["summary", "a", "b"]);
+
+        let mut python = Document::from_with_language("def f(self, x, *rest, y=1):\n    if x:\n        return y\n", "py");
+        python.set_cursor_and_mark(&Position::from(2, 8)).unwrap();
+        python.insert_doc_comment().unwrap();
+        assert_eq!(python.text(), concat!(
+            "def f(self, x, *rest, y=1):\n",
+            "    \"\"\"\n\n    Args:\n        x: \n        rest: \n        y: \n\n",
+            "    Returns:\n        \n    \"\"\"\n",
+            "    if x:\n        return y\n"
+        ));
+        python.undo_once().unwrap();
+        assert_eq!(python.text(), "def f(self, x, *rest, y=1):\n    if x:\n        return y\n");
+
+        python.set_cursor_and_mark(&Position::from(3, 0)).unwrap();
+        assert!(python.insert_doc_comment().is_err());
+    }
+}
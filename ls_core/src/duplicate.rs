@@ -0,0 +1,280 @@
+//! "Duplicate this function below": copying a syntax node next to itself.
+//!
+//! A node standing on lines of its own (a statement, a function, a field) is
+//! copied onto the lines below it, at the same indentation, separated by a
+//! blank line if it is separated from its neighbors by one. An element of a
+//! comma-separated list on the same line as its neighbors (an argument, a
+//! parameter) is copied right after itself, with a comma between. Either way
+//! the cursor lands at the same spot in the copy, ready to edit it.
+
+use crate::document::{Document, Range};
+#[cfg(feature = "parsing")]
+use crate::document::{Position, InsertOptions};
+#[cfg(feature = "parsing")]
+use crate::syntax;
+use crate::util::Oops;
+
+/// What to duplicate, for [`Document::duplicate`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum DuplicateTarget {
+    /// "duplicate that": the innermost node at the cursor which is on lines
+    /// of its own or is an element of a comma-separated list.
+    Node,
+
+    /// "duplicate function": the function or method around the cursor.
+    Function,
+
+    /// "duplicate statement": the statement around the cursor.
+    Statement,
+
+    /// "duplicate item": the declaration or definition around the cursor,
+    /// such as a struct, class, or field.
+    Item
+}
+
+impl DuplicateTarget {
+    /// Parses a spoken duplicate command.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::duplicate::*;
+    /// assert_eq!(DuplicateTarget::parse("duplicate that"), Some(DuplicateTarget::Node));
+    /// assert_eq!(DuplicateTarget::parse("duplicate this function below"), Some(DuplicateTarget::Function));
+    /// assert_eq!(DuplicateTarget::parse("duplicate statement"), Some(DuplicateTarget::Statement));
+    /// assert_eq!(DuplicateTarget::parse("duplicate the laundry"), None);
+    /// ```
+    pub fn parse(phrase: &str) -> Option<DuplicateTarget> {
+        let lowered = phrase.to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+        let mut words = match words.as_slice() {
+            ["duplicate", rest @ ..] => rest,
+            _ => return None
+        };
+
+        if let [rest @ .., "below"] = words {
+            words = rest;
+        }
+        if let ["this" | "that" | "the", rest @ ..] = words {
+            words = rest;
+        }
+
+        match words {
+            [] | ["node"] => Some(DuplicateTarget::Node),
+            ["function"] | ["method"] => Some(DuplicateTarget::Function),
+            ["statement"] => Some(DuplicateTarget::Statement),
+            ["item"] | ["declaration"] | ["definition"] => Some(DuplicateTarget::Item),
+            _ => None
+        }
+    }
+
+    /// Is `node` what this target asks for?
+    #[cfg(feature = "parsing")]
+    fn wants(&self, node: &syntax::Node) -> bool {
+        let kind = node.kind();
+        match self {
+            DuplicateTarget::Node => true,
+            DuplicateTarget::Function => crate::doc_comments::FUNCTION_KINDS.contains(&kind),
+            DuplicateTarget::Statement => {
+                // Some grammars put expression statements straight into
+                // their blocks, without a statement node around them.
+                kind.ends_with("statement") || kind.ends_with("declaration")
+                    || node.parent().is_some_and(|p| p.kind() == "block")
+            },
+            DuplicateTarget::Item => {
+                kind.ends_with("_item") || kind.ends_with("definition") || kind.ends_with("declaration")
+                    || kind.ends_with("_declarator") || kind == "pair"
+            }
+        }
+    }
+}
+
+/// Node kinds whose elements are separated by commas.
+#[cfg(feature = "parsing")]
+pub(crate) const LIST_KINDS: &[&str] = &[
+    "arguments", "parameters", "argument_list", "parameter_list", "formal_parameters",
+    "array", "array_expression", "list", "tuple", "tuple_expression", "dictionary", "object",
+    "field_declaration_list", "field_initializer_list", "enum_variant_list", "initializer_list"
+];
+
+/// How to copy a node, worked out while the parse tree is borrowed.
+#[cfg(feature = "parsing")]
+struct Duplication {
+    /// The node, and the separator after it on its last line, if any.
+    original: Range,
+
+    /// Where the node begins.
+    node_start: Position,
+
+    /// Where to add a comma, for the last element of a list which has none.
+    comma: Option<Position>,
+
+    /// Where the copy goes, and what goes before it.
+    at: Position,
+    prefix: String
+}
+
+/// Returns where inserting `text` at `position` leaves the end of the text.
+#[cfg(feature = "parsing")]
+pub(crate) fn advance(position: &Position, text: &str) -> Position {
+    match text.rsplit_once('\n') {
+        None => Position::from(position.row, position.column + text.chars().count()),
+        Some((before, last)) => Position::from(position.row + before.matches('\n').count() + 1, last.chars().count())
+    }
+}
+
+impl Document {
+    /// Works out how to duplicate `target` at the cursor.
+    #[cfg(feature = "parsing")]
+    fn duplication(&self, target: DuplicateTarget) -> Result<Duplication, Oops> {
+        let cursor = self.cursor().position;
+        let mut node = self.node_at(&Range { beginning: cursor, ending: cursor })
+            .ok_or(Oops::CannotParse("duplicate"))?;
+
+        loop {
+            let parent = node.parent().ok_or(Oops::Ouch("nothing to duplicate at cursor"))?;
+            let range = self.range_from_ts(&node.range());
+            let separator = node.next_sibling()
+                .filter(|s| crate::tidy_delete::SEPARATORS.contains(&s.kind()))
+                .map(|s| self.range_from_ts(&s.range()))
+                .filter(|s| s.beginning.row == range.ending.row);
+            let listed = LIST_KINDS.contains(&parent.kind())
+                || node.next_sibling().is_some_and(|s| s.kind() == ",")
+                || node.prev_sibling().is_some_and(|s| s.kind() == ",");
+            let end = separator.map_or(range.ending, |s| s.ending);
+
+            let line = &self.lines()[range.beginning.row].content;
+            let before: String = line.chars().take(range.beginning.column).collect();
+            let after: String = self.lines()[end.row].content.chars().skip(end.column).collect();
+            let own_lines = before.trim().is_empty() && after.trim().is_empty();
+
+            if target.wants(&node) && node.is_named() && (own_lines || listed) {
+                if !own_lines {
+                    return Ok(Duplication {
+                        original: range,
+                        node_start: range.beginning,
+                        comma: None,
+                        at: range.ending,
+                        prefix: String::from(", ")
+                    });
+                }
+
+                // Keep the spacing the node already has from its neighbors.
+                let gap = |first: usize, last: usize| (first + 1..last).any(|row| self.lines()[row].content.trim().is_empty());
+                let previous = node.prev_named_sibling().filter(|s| s.end_position().row < range.beginning.row);
+                let next = node.next_named_sibling().filter(|s| s.start_position().row > end.row);
+                let blank = match (previous, next) {
+                    (None, None) => range.beginning.row != range.ending.row && parent.parent().is_none(),
+                    _ => previous.is_some_and(|p| gap(p.end_position().row, range.beginning.row))
+                        || next.is_some_and(|n| gap(end.row, n.start_position().row))
+                };
+
+                return Ok(Duplication {
+                    original: Range { beginning: range.beginning, ending: end },
+                    node_start: range.beginning,
+                    comma: if separator.is_none() && listed {
+                        Some(range.ending)
+                    } else {
+                        None
+                    },
+                    at: Position::from(end.row, self.lines()[end.row].length),
+                    prefix: format!("{}{}", if blank { "\n\n" } else { "\n" }, before)
+                });
+            }
+
+            node = parent;
+        }
+    }
+
+    /// Duplicates the node at the cursor chosen by `target`, as one change
+    /// packet labeled "duplicate", and moves the cursor to the same spot in
+    /// the copy. Returns the range of the copy, or `Err` if there is no
+    /// parse tree or no such node around the cursor.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::duplicate::*;
+    /// let mut document = Document::from_with_language("fn f() {\n    g(a, b);\n}", "rs");
+    /// document.set_cursor_and_mark(&Position::from(1, 6)).unwrap();
+    ///
+    /// document.duplicate(DuplicateTarget::Node).unwrap();
+    /// assert_eq!(document.text(), "fn f() {\n    g(a, a, b);\n}");
+    /// assert_eq!(document.cursor().position, Position::from(1, 9));
+    ///
+    /// let copy = document.duplicate(DuplicateTarget::Statement).unwrap();
+    /// assert_eq!(document.text(), "fn f() {\n    g(a, a, b);\n    g(a, a, b);\n}");
+    /// assert_eq!(copy, Range::from(2, 4, 2, 15));
+    /// assert_eq!(document.cursor().position, Position::from(2, 9));
+    /// ```
+    #[cfg(feature = "parsing")]
+    pub fn duplicate(&mut self, target: DuplicateTarget) -> Result<Range, Oops> {
+        let duplication = self.duplication(target)?;
+        let text = self.text_range(&duplication.original).unwrap();
+        let cursor = self.cursor().position;
+
+        let inserted = format!("{}{}", duplication.prefix, text);
+        let beginning = advance(&duplication.at, &duplication.prefix);
+        let ending = advance(&beginning, &text);
+        let start = duplication.node_start;
+        let in_copy = if cursor.row == start.row {
+            Position::from(beginning.row, beginning.column + cursor.column.saturating_sub(start.column))
+        } else {
+            Position::from(beginning.row + cursor.row - start.row, cursor.column)
+        };
+
+        self.with_packet("duplicate", |document| {
+            let at = Range { beginning: duplication.at, ending: duplication.at };
+            document.insert(&inserted, &InsertOptions::exact_at(&at))?;
+            if let Some(comma) = duplication.comma {
+                document.insert(",", &InsertOptions::exact_at(&Range { beginning: comma, ending: comma }))?;
+            }
+            document.set_cursor_and_mark(&in_copy)
+        })?;
+
+        Ok(Range { beginning, ending })
+    }
+
+    /// Duplicates the node at the cursor.
+    ///
+    /// Without the `parsing` feature there is never a parse tree, so this
+    /// always returns an [`Oops`].
+    #[cfg(not(feature = "parsing"))]
+    pub fn duplicate(&mut self, _target: DuplicateTarget) -> Result<Range, Oops> {
+        Err(Oops::CannotParse("duplicate"))
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "parsing"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_keep_spacing_and_separators() {
+        let text = "struct P {\n    x: u8\n}\n\nfn f() {\n    let v = [\n        1,\n        2\n    ];\n}\n";
+        let mut document = Document::from_with_language(text, "rs");
+
+        document.set_cursor_and_mark(&Position::from(5, 4)).unwrap();
+        let copy = document.duplicate(DuplicateTarget::Function).unwrap();
+        assert_eq!(copy, Range::from(11, 0, 16, 1));
+        assert_eq!(document.cursor().position, Position::from(12, 4));
+        assert_eq!(document.text(), format!("{}\n\n{}", text.trim_end(), &text[text.find("fn").unwrap()..]));
+
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), text);
+
+        document.set_cursor_and_mark(&Position::from(7, 8)).unwrap();
+        document.duplicate(DuplicateTarget::Node).unwrap();
+        assert_eq!(document.lines()[7].content, "        2,");
+        assert_eq!(document.lines()[8].content, "        2");
+        assert_eq!(document.cursor().position, Position::from(8, 8));
+
+        document.set_cursor_and_mark(&Position::from(1, 4)).unwrap();
+        document.duplicate(DuplicateTarget::Item).unwrap();
+        assert_eq!(document.lines()[1].content, "    x: u8,");
+        assert_eq!(document.lines()[2].content, "    x: u8");
+
+        assert!(Document::from("plain").duplicate(DuplicateTarget::Node).is_err());
+    }
+}
@@ -0,0 +1,287 @@
+//! Support for [EditorConfig](https://editorconfig.org) files, so documents
+//! follow a project's conventions without per-user setup.
+//!
+//! The properties `indent_style`, `indent_size`, `tab_width`,
+//! `end_of_line`, `trim_trailing_whitespace`, and `insert_final_newline` are
+//! turned into [`ConfigOverrides`]. Others are parsed but ignored.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::config::{ConfigOverrides, LineEnding};
+use crate::document::Document;
+use crate::util::Oops;
+
+/// The name of EditorConfig files.
+pub const FILE_NAME: &str = ".editorconfig";
+
+/// A parsed EditorConfig file.
+#[derive(Clone, Debug)]
+pub struct EditorConfig {
+    /// Was `root = true` set? If so, files in parent directories are not
+    /// consulted.
+    pub root: bool,
+
+    sections: Vec<Section>
+}
+
+/// A `[glob]` section and its properties.
+#[derive(Clone, Debug)]
+struct Section {
+    matcher: Regex,
+    properties: Vec<(String, String)>
+}
+
+/// Converts an EditorConfig glob into an anchored regular expression over
+/// `/`-separated paths relative to the file's directory. Globs without a
+/// `/` match file names in any directory.
+fn glob_to_regex(glob: &str) -> String {
+    let mut result = String::from("^");
+    if !glob.contains('/') {
+        result += "(?:.*/)?";
+    }
+
+    let chars: Vec<char> = glob.trim_start_matches('/').chars().collect();
+    let mut i = 0;
+    let mut braces = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                result += ".*";
+                i += 1;
+            },
+            '*' => result += "[^/]*",
+            '?' => result += "[^/]",
+            '[' => match chars[i..].iter().position(|c| *c == ']') {
+                Some(end) => {
+                    let class: String = chars[i + 1..i + end].iter().collect();
+                    let class = match class.strip_prefix('!') {
+                        Some(rest) => format!("^{}", rest),
+                        None => class
+                    };
+                    result += &format!("[{}]", class.replace('\\', "\\\\"));
+                    i += end;
+                },
+                None => result += "\\["
+            },
+            '{' => {
+                braces += 1;
+                result += "(?:";
+            },
+            '}' if braces > 0 => {
+                braces -= 1;
+                result += ")";
+            },
+            ',' if braces > 0 => result += "|",
+            '\\' if i + 1 < chars.len() => {
+                result += &regex::escape(&chars[i + 1].to_string());
+                i += 1;
+            },
+            c => result += &regex::escape(&c.to_string())
+        }
+        i += 1;
+    }
+
+    result + "$"
+}
+
+impl EditorConfig {
+    /// Parses the text of an EditorConfig file. Unparseable lines and
+    /// sections with invalid globs are skipped, as the specification asks.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::editorconfig::*;
+    /// let config = EditorConfig::parse("root = true\n[*]\nindent_style = space\n[*.{js,py}]\nindent_size = 2\n");
+    /// assert!(config.root);
+    /// assert_eq!(config.properties_for("src/a.py").get("indent_size").map(String::as_str), Some("2"));
+    /// assert_eq!(config.properties_for("src/a.rs").get("indent_size"), None);
+    /// ```
+    pub fn parse(text: &str) -> EditorConfig {
+        let mut config = EditorConfig { root: false, sections: vec![] };
+        let mut current: Option<Section> = None;
+        let mut valid = true;
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                if let Some(section) = current.take() {
+                    config.sections.push(section);
+                }
+                match Regex::new(&glob_to_regex(&line[1..line.len() - 1])) {
+                    Ok(matcher) => {
+                        current = Some(Section { matcher, properties: vec![] });
+                        valid = true;
+                    },
+                    Err(_) => valid = false
+                }
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) => (k.trim().to_lowercase(), String::from(v.trim())),
+                None => continue
+            };
+
+            match current.as_mut() {
+                Some(section) => section.properties.push((key, value.to_lowercase())),
+                None if valid && key == "root" => config.root = value.eq_ignore_ascii_case("true"),
+                None => ()
+            }
+        }
+
+        if let Some(section) = current {
+            config.sections.push(section);
+        }
+
+        config
+    }
+
+    /// Returns the properties which apply to `path`, relative to this file's
+    /// directory, with later sections taking precedence. Keys and values are
+    /// lowercased.
+    pub fn properties_for(&self, path: &str) -> HashMap<String, String> {
+        let path = path.replace('\\', "/");
+        let mut result = HashMap::new();
+
+        for section in self.sections.iter().filter(|s| s.matcher.is_match(&path)) {
+            for (key, value) in section.properties.iter() {
+                result.insert(key.clone(), value.clone());
+            }
+        }
+
+        result
+    }
+}
+
+/// Turns EditorConfig properties into configuration overrides.
+///
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use ls_core::config::*;
+/// use ls_core::editorconfig::*;
+/// let mut properties = HashMap::new();
+/// properties.insert(String::from("indent_style"), String::from("tab"));
+/// properties.insert(String::from("tab_width"), String::from("8"));
+/// properties.insert(String::from("end_of_line"), String::from("crlf"));
+/// let overrides = overrides_from_properties(&properties);
+/// assert_eq!(overrides.use_spaces, Some(false));
+/// assert_eq!(overrides.tab_width, Some(8));
+/// assert_eq!(overrides.end_of_line, Some(LineEnding::CrLf));
+/// ```
+pub fn overrides_from_properties(properties: &HashMap<String, String>) -> ConfigOverrides {
+    let get = |key: &str| properties.get(key).map(String::as_str);
+    let flag = |key: &str| match get(key) {
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        _ => None
+    };
+    let width = |key: &str| get(key).and_then(|v| v.parse::<usize>().ok()).filter(|w| *w > 0);
+
+    ConfigOverrides {
+        use_spaces: match get("indent_style") {
+            Some("space") => Some(true),
+            Some("tab") => Some(false),
+            _ => None
+        },
+        tab_width: match get("indent_size") {
+            Some("tab") => width("tab_width"),
+            _ => width("indent_size").or_else(|| width("tab_width"))
+        },
+        end_of_line: match get("end_of_line") {
+            Some("lf") => Some(LineEnding::Lf),
+            Some("crlf") => Some(LineEnding::CrLf),
+            Some("cr") => Some(LineEnding::Cr),
+            _ => None
+        },
+        trim_trailing_whitespace: flag("trim_trailing_whitespace"),
+        insert_final_newline: flag("insert_final_newline"),
+        ..Default::default()
+    }
+}
+
+/// Reads the `.editorconfig` files which apply to the file at `path`, from
+/// its directory upward until one sets `root = true`, and returns the
+/// resulting overrides. Files closer to `path` take precedence. Missing
+/// files are skipped; other read errors are returned.
+pub fn load(path: &Path) -> Result<ConfigOverrides, Oops> {
+    let mut overrides = ConfigOverrides::default();
+    let mut directory = path.parent();
+
+    while let Some(dir) = directory {
+        match std::fs::read_to_string(dir.join(FILE_NAME)) {
+            Ok(text) => {
+                let config = EditorConfig::parse(&text);
+                let relative = path.strip_prefix(dir).unwrap_or(path).to_string_lossy();
+                overrides = overrides_from_properties(&config.properties_for(&relative)).merge(&overrides);
+                if config.root {
+                    break;
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(_) => return Err(Oops::Ouch("cannot read .editorconfig"))
+        }
+        directory = dir.parent();
+    }
+
+    Ok(overrides)
+}
+
+impl Document {
+    /// Applies the `.editorconfig` files for the file at `path` on top of
+    /// this document's configuration overrides. See [`load`].
+    pub fn apply_editorconfig(&mut self, path: &Path) -> Result<(), Oops> {
+        let overrides = self.config_overrides().merge(&load(path)?);
+        self.set_config_overrides(overrides);
+        Ok(())
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        let matches = |glob: &str, path: &str| Regex::new(&glob_to_regex(glob)).unwrap().is_match(path);
+        assert!(matches("*.rs", "src/deep/a.rs"));
+        assert!(!matches("*.rs", "a.rsx"));
+        assert!(matches("src/*.rs", "src/a.rs"));
+        assert!(!matches("src/*.rs", "src/deep/a.rs"));
+        assert!(matches("src/**.rs", "src/deep/a.rs"));
+        assert!(matches("/Makefile", "Makefile"));
+        assert!(!matches("/Makefile", "sub/Makefile"));
+        assert!(matches("[!a]?.{c,h}", "bx.h"));
+        assert!(!matches("[!a]?.{c,h}", "ax.h"));
+    }
+
+    #[test]
+    fn load_from_directories() {
+        let base = std::env::temp_dir().join(format!("ls_core_editorconfig_{}", std::process::id()));
+        let nested = base.join("project").join("src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(base.join(FILE_NAME), "root = true\n[*]\nend_of_line = crlf\nindent_size = 8\n").unwrap();
+        std::fs::write(base.join("project").join(FILE_NAME), "[*.rs]\nindent_size = 2\ninsert_final_newline = true\n").unwrap();
+
+        let mut document = Document::from("fn f() {}");
+        document.apply_editorconfig(&nested.join("main.rs")).unwrap();
+        let config = document.config();
+        assert_eq!(config.tab_width, 2);
+        assert_eq!(config.end_of_line, LineEnding::CrLf);
+        assert!(config.insert_final_newline);
+        assert_eq!(document.prepare_save().unwrap(), "fn f() {}\r\n");
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+//! `$` escapes in inserted text, which let a speech client say how text is
+//! laid out along with the text itself.
+//!
+//! When [`InsertOptions::escapes`] is set, [`Document::insert`] interprets:
+//!
+//! * `$n`: a line break. The new line starts at the current indentation.
+//! * `$u`: indent the current line, and those after it, one step more.
+//! * `$d`: dedent the current line, and those after it, one step.
+//! * `$g`: glue: remove the spaces and tabs before this point, so the next
+//!   text joins what came before. Before any other text, this glues to the
+//!   document text before the insert.
+//! * `$$`: a literal `$`.
+//!
+//! Any other `$` is left as it is. Indentation starts from the margin of
+//! the line the insert begins on. Escapes which change text already in the
+//! document (indenting the first line, or gluing to the text before the
+//! insert) do so with ordinary tracked removals and inserts, so they undo
+//! with the rest of the insert's packet. The lines the escapes produce are
+//! then indented or reindented as the other insert options ask.
+
+use crate::document::{Document, Position, Range, InsertOptions, RemoveOptions};
+use crate::util::Oops;
+
+/// Returns whether `c` is glued over.
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+impl Document {
+    /// Replaces the margin of `row` with one `spaces` wide, keeping
+    /// `position` on the same text if it is on that row.
+    fn set_margin(&mut self, row: usize, spaces: usize, position: &mut Position) -> Result<(), Oops> {
+        let (_, bytes) = self.indentation().measure(&self.lines()[row].content);
+        let margin = self.indentation().produce(spaces);
+        if self.lines()[row].content[..bytes] == margin {
+            return Ok(());
+        }
+
+        // Margins are spaces and tabs, so bytes and columns agree.
+        if bytes > 0 {
+            self.remove(&RemoveOptions::exact_at(&Range::from(row, 0, row, bytes)))?;
+        }
+        if !margin.is_empty() {
+            self.insert(&margin, &InsertOptions::exact_at(&Range::from(row, 0, row, 0)))?;
+        }
+        if position.row == row {
+            position.column = if position.column >= bytes {
+                position.column - bytes + margin.len()
+            } else {
+                position.column.min(margin.len())
+            };
+        }
+        Ok(())
+    }
+
+    /// Interprets the escapes in `text` (see the [module documentation](self)),
+    /// returning its lines for inserting at `position`. Changes to text
+    /// already in the document are made here, and `position` is moved to
+    /// follow them.
+    pub(crate) fn interpret_escapes(&mut self, text: &str, position: &mut Position) -> Result<Vec<String>, Oops> {
+        let step = self.indentation().spaces_per_tab;
+        let mut indent = self.indentation().measure(&self.lines()[position.row].content).0;
+
+        let mut lines: Vec<String> = vec![];
+        let mut current = String::new();
+        let mut chars = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' if chars.peek() == Some(&'\n') => (),
+                '\n' | '\r' => {
+                    lines.push(std::mem::take(&mut current));
+                },
+                '$' => match chars.peek().copied() {
+                    Some('n') => {
+                        chars.next();
+                        lines.push(std::mem::replace(&mut current, self.indentation().produce(indent)));
+                    },
+                    Some(escape @ ('u' | 'd')) => {
+                        chars.next();
+                        indent = if escape == 'u' { indent + step } else { indent.saturating_sub(step) };
+                        if lines.is_empty() {
+                            self.set_margin(position.row, indent, position)?;
+                        } else {
+                            let (_, bytes) = self.indentation().measure(&current);
+                            current = self.indentation().produce(indent) + &current[bytes..];
+                        }
+                    },
+                    Some('g') => {
+                        chars.next();
+                        let kept = current.trim_end_matches(is_blank).len();
+                        current.truncate(kept);
+                        if current.is_empty() && lines.is_empty() {
+                            let before = &self.lines()[position.row].content;
+                            let columns = before.chars().take(position.column).collect::<Vec<char>>();
+                            let blanks = columns.iter().rev().take_while(|c| is_blank(**c)).count();
+                            if blanks > 0 {
+                                let start = Position::from(position.row, position.column - blanks);
+                                self.remove(&RemoveOptions::exact_at(&Range { beginning: start, ending: *position }))?;
+                                *position = start;
+                            }
+                        }
+                    },
+                    Some('$') => {
+                        chars.next();
+                        current.push('$');
+                    },
+                    _ => current.push('$')
+                },
+                c => current.push(c)
+            }
+        }
+
+        lines.push(current);
+        Ok(lines)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_lay_out_text_and_undo_together() {
+        let mut document = Document::from("fn f() {\n    x = 1  \n}");
+        document.set_cursor_and_mark(&Position::from(1, 11)).unwrap();
+        document.checkpoint();
+        document.insert("$g;$nif y {$n$uz();$n$d}", &InsertOptions::escaped()).unwrap();
+        assert_eq!(document.text(), "fn f() {\n    x = 1;\n    if y {\n        z();\n    }\n}");
+        assert_eq!(document.cursor().position, Position::from(4, 5));
+
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), "fn f() {\n    x = 1  \n}");
+
+        document.set_cursor_and_mark(&Position::from(1, 4)).unwrap();
+        document.insert("$d$dy$$5 $q ", &InsertOptions::escaped()).unwrap();
+        assert_eq!(document.text(), "fn f() {\ny$5 $q x = 1  \n}");
+
+        document.set_cursor_and_mark(&Position::from(1, 14)).unwrap();
+        document.insert("$g", &InsertOptions::escaped()).unwrap();
+        assert_eq!(document.text(), "fn f() {\ny$5 $q x = 1\n}");
+        assert!(document.insert("", &InsertOptions::escaped()).is_err());
+    }
+
+    #[test]
+    fn escaped_text_is_reindented_like_any_other() {
+        let mut document = Document::from("fn f() {\n    \n}");
+        document.set_cursor_and_mark(&Position::from(1, 4)).unwrap();
+        let options = InsertOptions { reindent: true, ..InsertOptions::escaped() };
+        document.insert("        if x {\n            y($$);\n        }", &options).unwrap();
+        assert_eq!(document.text(), "fn f() {\n    if x {\n        y($);\n    }\n}");
+    }
+}
@@ -0,0 +1,224 @@
+//! Lifecycle events: notifications about a document as a whole, as opposed
+//! to changes to its content.
+//!
+//! Listeners attached to a document with [`Document::add_listener`] hear
+//! about that document. Global listeners, added with [`add_global_listener`],
+//! hear about every document on the current thread, including its creation,
+//! which makes them the place to attach per-document behaviors such as
+//! starting a language server.
+//!
+//! Listeners receive the document mutably, so a [`LifecycleEvent::BeforeSave`]
+//! listener can format the text before the save pipeline runs. Events
+//! raised while a document's listeners are running reach only global
+//! listeners, so listeners never re-enter each other.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::document::Document;
+
+/// Something which happened to a document.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum LifecycleEvent {
+    /// The document was just constructed. Only global listeners hear this.
+    Created,
+
+    /// The document's language changed from `previous`, either directly or
+    /// by undoing or redoing a language change.
+    LanguageChanged { previous: String },
+
+    /// [`Document::prepare_save`] is about to run the save pipeline.
+    BeforeSave,
+
+    /// The embedder reported, via [`Document::notify_saved`], that the
+    /// document was written out.
+    AfterSave
+}
+
+/// A listener attached to one document.
+pub type Listener = Box<dyn FnMut(&mut Document, &LifecycleEvent)>;
+
+/// A listener which hears about every document.
+pub type GlobalListener = Rc<dyn Fn(&mut Document, &LifecycleEvent)>;
+
+/// Identifies a listener so that it can be removed.
+pub type ListenerHandle = u32;
+
+/// A document's listeners.
+#[derive(Default)]
+pub(crate) struct Listeners {
+    entries: Vec<(ListenerHandle, Listener)>,
+    next_handle: ListenerHandle,
+
+    /// Set while the entries are lent out to a dispatch.
+    dispatching: bool,
+
+    /// Handles removed during a dispatch, dropped once it finishes.
+    removed: Vec<ListenerHandle>
+}
+
+thread_local! {
+    static GLOBAL_LISTENERS: RefCell<Vec<(ListenerHandle, GlobalListener)>> = const { RefCell::new(Vec::new()) };
+    static NEXT_GLOBAL_HANDLE: Cell<ListenerHandle> = const { Cell::new(0) };
+}
+
+/// Adds `listener` to hear about every document created afterward on this
+/// thread, and every event of every document. Returns its handle.
+///
+/// # Examples
+/// ```
+/// use std::rc::Rc;
+/// use ls_core::document::*;
+/// use ls_core::events::*;
+/// let handle = add_global_listener(Rc::new(|document, event| {
+///     if *event == LifecycleEvent::Created {
+///         document.set_indentation(&Indentation::tabs(4)).unwrap();
+///     }
+/// }));
+/// assert_eq!(Document::from("x").indentation(), &Indentation::tabs(4));
+/// assert!(remove_global_listener(handle));
+/// assert_eq!(Document::from("x").indentation(), &Indentation::spaces(4));
+/// ```
+pub fn add_global_listener(listener: GlobalListener) -> ListenerHandle {
+    let handle = NEXT_GLOBAL_HANDLE.with(|next| {
+        next.set(next.get() + 1);
+        next.get()
+    });
+    GLOBAL_LISTENERS.with(|listeners| listeners.borrow_mut().push((handle, listener)));
+    handle
+}
+
+/// Removes the global listener `handle`. Returns false if there was none.
+pub fn remove_global_listener(handle: ListenerHandle) -> bool {
+    GLOBAL_LISTENERS.with(|listeners| {
+        let mut listeners = listeners.borrow_mut();
+        let before = listeners.len();
+        listeners.retain(|(h, _)| *h != handle);
+        listeners.len() != before
+    })
+}
+
+impl Document {
+    /// Attaches `listener` to this document and returns its handle.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::rc::Rc;
+    /// use std::cell::RefCell;
+    /// use ls_core::document::*;
+    /// use ls_core::events::*;
+    /// let heard = Rc::new(RefCell::new(vec![]));
+    /// let log = heard.clone();
+    ///
+    /// let mut document = Document::from("x");
+    /// document.add_listener(Box::new(move |_, event| log.borrow_mut().push(event.clone())));
+    /// document.set_language("rs").unwrap();
+    /// document.undo_once().unwrap();
+    /// assert_eq!(*heard.borrow(), vec![
+    ///     LifecycleEvent::LanguageChanged { previous: String::from("") },
+    ///     LifecycleEvent::LanguageChanged { previous: String::from("rs") }
+    /// ]);
+    /// ```
+    pub fn add_listener(&mut self, listener: Listener) -> ListenerHandle {
+        self.listeners.next_handle += 1;
+        let handle = self.listeners.next_handle;
+        self.listeners.entries.push((handle, listener));
+        handle
+    }
+
+    /// Detaches the listener `handle`. Returns false if there was none.
+    /// A listener may remove itself or others while handling an event.
+    pub fn remove_listener(&mut self, handle: ListenerHandle) -> bool {
+        let before = self.listeners.entries.len();
+        self.listeners.entries.retain(|(h, _)| *h != handle);
+
+        if self.listeners.entries.len() != before {
+            true
+        } else if self.listeners.dispatching && handle <= self.listeners.next_handle
+            && !self.listeners.removed.contains(&handle)
+        {
+            self.listeners.removed.push(handle);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Tells this document's listeners that it has been written out. Call
+    /// this after writing the text returned by [`Document::prepare_save`].
+    pub fn notify_saved(&mut self) {
+        self.dispatch(&LifecycleEvent::AfterSave);
+    }
+
+    /// Sends `event` to the global listeners and then to this document's
+    /// listeners. [`LifecycleEvent::Created`] goes only to global listeners.
+    pub(crate) fn dispatch(&mut self, event: &LifecycleEvent) {
+        let globals: Vec<GlobalListener> = GLOBAL_LISTENERS.with(|listeners| {
+            listeners.borrow().iter().map(|(_, l)| l.clone()).collect()
+        });
+        for listener in globals {
+            listener(self, event);
+        }
+
+        if *event == LifecycleEvent::Created || self.listeners.dispatching {
+            return;
+        }
+
+        let mut entries = std::mem::take(&mut self.listeners.entries);
+        self.listeners.dispatching = true;
+
+        for (handle, listener) in entries.iter_mut() {
+            if !self.listeners.removed.contains(handle) {
+                listener(self, event);
+            }
+        }
+
+        self.listeners.dispatching = false;
+        let removed = std::mem::take(&mut self.listeners.removed);
+        let added = std::mem::replace(&mut self.listeners.entries, entries);
+        self.listeners.entries.extend(added);
+        self.listeners.entries.retain(|(h, _)| !removed.contains(h));
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_events_and_reentrancy() {
+        let saves = Rc::new(Cell::new(0));
+        let count = saves.clone();
+
+        let mut document = Document::from("a  ");
+        document.save_pipeline_mut().trim_trailing_whitespace = true;
+
+        document.add_listener(Box::new(|document, event| {
+            if *event == LifecycleEvent::BeforeSave {
+                document.checkpoint();
+                document.insert("b", &crate::document::InsertOptions::exact()).unwrap();
+                // Nested events are not delivered to document listeners.
+                document.set_language("py").unwrap();
+            }
+        }));
+        let counter = document.add_listener(Box::new(move |document, event| {
+            if *event == LifecycleEvent::AfterSave {
+                count.set(count.get() + 1);
+                let handle = document.add_listener(Box::new(|_, _| ()));
+                assert!(document.remove_listener(handle));
+            }
+        }));
+
+        assert_eq!(document.prepare_save().unwrap(), "ba");
+        assert_eq!(document.language(), "py");
+        document.notify_saved();
+        assert_eq!(saves.get(), 1);
+
+        assert!(document.remove_listener(counter));
+        assert!(!document.remove_listener(counter));
+        document.notify_saved();
+        assert_eq!(saves.get(), 1);
+    }
+}
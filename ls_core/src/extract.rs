@@ -0,0 +1,939 @@
+//! "Extract variable" and "extract function": the textual half of two common
+//! refactorings.
+//!
+//! Both check the selection against the parse tree first. Extracting a
+//! variable needs exactly one expression, which is replaced by a new name
+//! bound on a line above its statement. Extracting a function needs one or
+//! more whole statements of the same block, which are moved into a new
+//! function above the one they came from and replaced by a call to it.
+//! Nothing checks that the moved code still compiles; that is left to the
+//! user, who knows what the parameters and return value should be.
+//!
+//! Every edit is one change packet, so one undo takes it all back. The new
+//! name, which starts out as a placeholder, is left as a [`LinkedName`]:
+//! floating anchors around each of its occurrences, so renaming the
+//! definition with [`Document::fill_linked`] renames the use along with it.
+//!
+//! [`Document::inline_variable`] goes the other way, replacing the uses of a
+//! variable with its value and deleting its binding. The value is wrapped in
+//! parentheses wherever the language's operator precedence calls for it.
+
+use crate::document::{Document, Range, Position, AnchorHandle, Anchor, InsertOptions, RemoveOptions};
+#[cfg(feature = "parsing")]
+use crate::syntax;
+use crate::util::Oops;
+
+/// What to extract, for [`Document::extract`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Extract {
+    /// Bind the selected expression to a new variable.
+    Variable,
+
+    /// Move the selected statements into a new function.
+    Function
+}
+
+impl Extract {
+    /// Parses a spoken extract command.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::extract::*;
+    /// assert_eq!(Extract::parse("extract variable"), Some(Extract::Variable));
+    /// assert_eq!(Extract::parse("extract that to a method"), Some(Extract::Function));
+    /// assert_eq!(Extract::parse("extract juice"), None);
+    /// ```
+    pub fn parse(phrase: &str) -> Option<Extract> {
+        let lowered = phrase.to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+        let mut words = match words.as_slice() {
+            ["extract", rest @ ..] => rest,
+            _ => return None
+        };
+
+        if let ["this" | "that", rest @ ..] = words {
+            words = rest;
+        }
+        if let ["to" | "into", rest @ ..] = words {
+            words = rest;
+        }
+        if let ["a" | "an", rest @ ..] = words {
+            words = rest;
+        }
+
+        match words {
+            ["variable"] | ["local"] | ["constant"] => Some(Extract::Variable),
+            ["function"] | ["method"] => Some(Extract::Function),
+            _ => None
+        }
+    }
+
+    /// The name given to the new variable or function until the user
+    /// picks one.
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            Extract::Variable => "value",
+            Extract::Function => "extracted"
+        }
+    }
+}
+
+/// Occurrences of one name which are renamed together, each held by a pair
+/// of floating anchors at its beginning and ending. The caller owns the
+/// anchors and should remove them with [`Document::remove_linked`] once the
+/// name is settled.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct LinkedName {
+    /// The occurrences, definition first.
+    pub anchors: Vec<(AnchorHandle, AnchorHandle)>
+}
+
+/// What [`Document::extract`] did.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Extraction {
+    /// The range of the new binding or function, as inserted.
+    pub definition: Range,
+
+    /// The range of the name or call which replaced the selection, as
+    /// inserted.
+    pub usage: Range,
+
+    /// The placeholder name, in the definition and in the usage.
+    pub name: LinkedName
+}
+
+/// Node kinds which hold a list of statements.
+#[cfg(feature = "parsing")]
+const STATEMENT_LISTS: &[&str] = &[
+    "block", "source_file", "program", "module", "statement_block", "compound_statement",
+    "translation_unit", "declaration_list"
+];
+
+/// Is `node` a whole statement, declaration, or item, rather than part of one?
+#[cfg(feature = "parsing")]
+fn is_statement(node: &syntax::Node) -> bool {
+    let kind = node.kind();
+    kind.ends_with("statement") || kind.ends_with("declaration") || kind.ends_with("_item")
+        || kind.ends_with("definition") || node.parent().is_some_and(|p| STATEMENT_LISTS.contains(&p.kind()))
+}
+
+/// Does `outer` contain all of `inner`?
+#[cfg(feature = "parsing")]
+fn contains(outer: &Range, inner: &Range) -> bool {
+    outer.beginning <= inner.beginning && inner.ending <= outer.ending
+}
+
+/// Returns the text binding `name` to `expression`, and how many characters
+/// come before `name` in it, in `language`.
+#[cfg(feature = "parsing")]
+fn binding(language: &str, name: &str, expression: &str) -> Option<(String, usize)> {
+    let keyword = match language {
+        "rs" => "let ",
+        "js" | "ts" | "tsx" => "const ",
+        "java" => "var ",
+        "cpp" => "auto ",
+        "py" => return Some((format!("{} = {}", name, expression), 0)),
+        _ => return None
+    };
+    Some((format!("{}{} = {};", keyword, name, expression), keyword.len()))
+}
+
+/// Returns the first line of a function named `name` in `language`, how many
+/// characters come before `name` in it, and the last line, if any.
+#[cfg(feature = "parsing")]
+fn function_frame(language: &str, name: &str) -> Option<(String, usize, Option<&'static str>)> {
+    let keyword = match language {
+        "rs" => "fn ",
+        "js" | "ts" | "tsx" => "function ",
+        "java" | "cpp" => "void ",
+        "py" => return Some((format!("def {}():", name), 4, None)),
+        _ => return None
+    };
+    Some((format!("{}{}() {{", keyword, name), keyword.len(), Some("}")))
+}
+
+/// Node kinds which assign to their `left` or `argument` operand.
+#[cfg(feature = "parsing")]
+const ASSIGNMENT_KINDS: &[&str] = &[
+    "assignment_expression", "compound_assignment_expr", "assignment", "augmented_assignment",
+    "augmented_assignment_expression", "update_expression"
+];
+
+/// Node kinds whose operands never need parentheses.
+#[cfg(feature = "parsing")]
+const NEUTRAL_KINDS: &[&str] = &[
+    "arguments", "argument_list", "let_declaration", "variable_declarator", "init_declarator",
+    "return_expression", "return_statement", "expression_statement", "parenthesized_expression",
+    "array_expression", "tuple_expression", "array", "list", "tuple", "expression_list",
+    "block", "statement_block", "assignment", "assignment_expression", "keyword_argument",
+    "pair", "field_initializer", "shorthand_field_initializer", "object", "initializer_list",
+    "token_tree"
+];
+
+/// Returns how tightly the operator `node` binds its operands in
+/// `language`, from 0 for the loosest (conditionals, closures, ranges) up,
+/// or `None` if `node` is not an operator and so never needs parentheses.
+#[cfg(feature = "parsing")]
+fn precedence(language: &str, node: &syntax::Node) -> Option<u8> {
+    let python = language == "py";
+    match node.kind() {
+        "conditional_expression" | "ternary_expression" | "lambda" | "closure_expression"
+            | "arrow_function" | "range_expression" | "assignment_expression" | "yield" => Some(0),
+        "not_operator" => Some(3),
+        "comparison_operator" => Some(4),
+        "type_cast_expression" => Some(11),
+        "unary_expression" | "unary_operator" | "reference_expression" | "pointer_expression"
+            | "await_expression" | "cast_expression" => Some(if python { 11 } else { 12 }),
+        "binary_expression" | "binary_operator" | "boolean_operator" => {
+            let operator = node.child_by_field_name("operator").map_or("", |o| o.kind());
+            Some(match (python, operator) {
+                (true, "or") | (false, "||") | (false, "??") => 1,
+                (true, "and") | (false, "&&") => 2,
+                (true, "|") => 5,
+                (true, "^") => 6,
+                (true, "&") => 7,
+                (false, "|") => 3,
+                (false, "^") => 4,
+                (false, "&") => 5,
+                (false, "==") | (false, "!=") | (false, "===") | (false, "!==") => 6,
+                (false, "<") | (false, ">") | (false, "<=") | (false, ">=") | (false, "instanceof") | (false, "in") => 7,
+                (_, "<<") | (_, ">>") | (_, ">>>") => 8,
+                (_, "+") | (_, "-") => 9,
+                (true, "**") => 12,
+                (false, "**") => 11,
+                _ => 10
+            })
+        },
+        _ => None
+    }
+}
+
+/// Does `value` need parentheses when it replaces `usage`?
+#[cfg(feature = "parsing")]
+fn needs_parentheses(language: &str, value: &syntax::Node, usage: &syntax::Node) -> bool {
+    let inner = match precedence(language, value) {
+        Some(inner) => inner,
+        None => return false
+    };
+    let parent = match usage.parent() {
+        Some(parent) => parent,
+        None => return false
+    };
+
+    match precedence(language, &parent) {
+        Some(outer) => inner < outer || (inner == outer && parent.child_by_field_name("left") != Some(*usage)),
+        None => !NEUTRAL_KINDS.contains(&parent.kind())
+    }
+}
+
+/// If `statement` binds exactly one plain variable, returns the variable's
+/// name and its value.
+#[cfg(feature = "parsing")]
+fn binding_of<'tree>(statement: &syntax::Node<'tree>) -> Option<(syntax::Node<'tree>, syntax::Node<'tree>)> {
+    let mut cursor = statement.walk();
+    let inner: Vec<syntax::Node> = statement.named_children(&mut cursor)
+        .filter(|c| matches!(c.kind(), "variable_declarator" | "init_declarator" | "assignment"))
+        .collect();
+
+    let (name, value) = match (statement.kind(), inner.as_slice()) {
+        ("let_declaration", _) => (statement.child_by_field_name("pattern")?, statement.child_by_field_name("value")?),
+        ("expression_statement", [assignment]) if assignment.kind() == "assignment" => {
+            (assignment.child_by_field_name("left")?, assignment.child_by_field_name("right")?)
+        },
+        (_, [declarator]) if declarator.kind() != "assignment" => (
+            declarator.child_by_field_name("name").or_else(|| declarator.child_by_field_name("declarator"))?,
+            declarator.child_by_field_name("value")?
+        ),
+        _ => return None
+    };
+    Some((name, value)).filter(|(name, _)| name.kind() == "identifier")
+}
+
+/// How to inline a variable, worked out while the parse tree is borrowed.
+#[cfg(feature = "parsing")]
+struct Inlining {
+    /// The binding to delete, with the white space that goes with it.
+    binding: Range,
+
+    /// The uses, in document order, and what replaces each.
+    uses: Vec<(Range, String)>
+}
+
+/// How to extract the selection, worked out while the parse tree is borrowed.
+#[cfg(feature = "parsing")]
+struct ExtractPlan {
+    /// The selection, without surrounding white space.
+    selection: Range,
+
+    /// What replaces the selection.
+    usage: String,
+
+    /// The row the definition goes above.
+    row: usize,
+
+    /// The lines of the definition, ending in a newline.
+    definition: String,
+
+    /// Where the name is in the definition, relative to `row`.
+    name_at: Position
+}
+
+impl Document {
+    /// Returns `range` without the white space at either end.
+    #[cfg(feature = "parsing")]
+    fn trim_range(&self, range: &Range) -> Range {
+        let char_at = |p: &Position| self.lines()[p.row].content.chars().nth(p.column);
+        let (mut beginning, mut ending) = (range.beginning, range.ending);
+
+        while beginning < ending {
+            match char_at(&beginning) {
+                Some(c) if !c.is_whitespace() => break,
+                Some(_) => beginning.column += 1,
+                None => beginning = Position::from(beginning.row + 1, 0)
+            }
+        }
+        while ending > beginning {
+            if ending.column == 0 {
+                ending = Position::from(ending.row - 1, self.lines()[ending.row - 1].length);
+                continue;
+            }
+            match char_at(&Position::from(ending.row, ending.column - 1)) {
+                Some(c) if !c.is_whitespace() => break,
+                _ => ending.column -= 1
+            }
+        }
+
+        Range { beginning, ending }
+    }
+
+    /// Returns the left margin of `row`.
+    #[cfg(feature = "parsing")]
+    fn margin_of(&self, row: usize) -> String {
+        self.lines()[row].content.chars().take_while(|c| c.is_whitespace()).collect()
+    }
+
+    /// Works out how to extract the selection as `what`.
+    #[cfg(feature = "parsing")]
+    fn extract_plan(&self, what: Extract) -> Result<ExtractPlan, Oops> {
+        let selection = self.trim_range(&self.selection());
+        if selection.empty() {
+            return Err(Oops::Ouch("nothing selected to extract"));
+        }
+        let text = self.text_range(&selection).unwrap();
+        let name = what.placeholder();
+
+        match what {
+            Extract::Variable => {
+                let node = self.node_at(&selection)
+                    .filter(|n| n.is_named() && !is_statement(n))
+                    .ok_or(Oops::Ouch("selection is not an expression"))?;
+                let mut statement = node;
+                while !is_statement(&statement) {
+                    statement = statement.parent().ok_or(Oops::Ouch("expression is not in a statement"))?;
+                }
+
+                let (line, before) = binding(self.language(), name, &text)
+                    .ok_or(Oops::Ouch("cannot extract variables in this language"))?;
+                let row = statement.start_position().row;
+                let margin = self.margin_of(row);
+
+                Ok(ExtractPlan {
+                    selection,
+                    usage: String::from(name),
+                    row,
+                    definition: format!("{}{}\n", margin, line),
+                    name_at: Position::from(0, margin.chars().count() + before)
+                })
+            },
+
+            Extract::Function => {
+                let mut list = self.node_at(&Range { beginning: selection.beginning, ending: selection.beginning })
+                    .ok_or(Oops::CannotParse("extract"))?;
+                while !(STATEMENT_LISTS.contains(&list.kind()) && contains(&self.range_from_ts(&list.range()), &selection)) {
+                    list = list.parent().ok_or(Oops::Ouch("selection is not in a block"))?;
+                }
+
+                // The selection must cover whole statements, and nothing else.
+                let mut cursor = list.walk();
+                let mut covered = vec![];
+                for child in list.children(&mut cursor) {
+                    let range = self.range_from_ts(&child.range());
+                    if contains(&selection, &range) {
+                        covered.push(child);
+                    } else if range.ending > selection.beginning && range.beginning < selection.ending {
+                        return Err(Oops::Ouch("selection splits a statement"));
+                    }
+                }
+                let whole = covered.first().is_some_and(|c| c.is_named() && self.range_from_ts(&c.range()).beginning == selection.beginning)
+                    && covered.last().is_some_and(|c| self.range_from_ts(&c.range()).ending == selection.ending)
+                    && covered.iter().all(|c| c.kind() != "{" && c.kind() != "}");
+                if !whole {
+                    return Err(Oops::Ouch("selection is not a list of statements"));
+                }
+
+                let function = self.signature_at(&selection.beginning)?.range;
+                let row = function.beginning.row;
+                let margin = self.margin_of(row);
+                let (first, before, last) = function_frame(self.language(), name)
+                    .ok_or(Oops::Ouch("cannot extract functions in this language"))?;
+
+                // Re-indent the statements one level inside the new function.
+                let indentation = self.indentation();
+                let base = indentation.measure(&self.lines()[selection.beginning.row].content).0;
+                let target = indentation.measure(&margin).0 + indentation.spaces_per_tab;
+                let mut definition = format!("{}{}\n", margin, first);
+                for (i, line) in text.split('\n').enumerate() {
+                    let (spaces, cutoff) = indentation.measure(line);
+                    let spaces = if i == 0 { base } else { spaces };
+                    if cutoff < line.len() {
+                        definition.push_str(&indentation.produce((spaces + target).saturating_sub(base)));
+                        definition.push_str(&line[cutoff..]);
+                    }
+                    definition.push('\n');
+                }
+                if let Some(last) = last {
+                    definition.push_str(&margin);
+                    definition.push_str(last);
+                    definition.push('\n');
+                }
+                definition.push('\n');
+
+                Ok(ExtractPlan {
+                    selection,
+                    usage: if self.language() == "py" { format!("{}()", name) } else { format!("{}();", name) },
+                    row,
+                    definition,
+                    name_at: Position::from(0, margin.chars().count() + before)
+                })
+            }
+        }
+    }
+
+    /// Extracts the selection as `what`: binds the selected expression to a
+    /// new variable on a line above its statement, or moves the selected
+    /// statements into a new function above the one they are in. The
+    /// selection is replaced by the variable or a call to the function, and
+    /// the definition's placeholder name is selected. This is one change
+    /// packet labeled "extract".
+    ///
+    /// Returns `Err` if there is no parse tree, the selection is not a
+    /// whole expression or whole statements, or the language is not
+    /// supported.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::extract::*;
+    /// let mut document = Document::from_with_language("fn f() {\n    g(a + b);\n}", "rs");
+    /// document.set_selection(&Range::from(1, 6, 1, 11)).unwrap();
+    ///
+    /// let extraction = document.extract(Extract::Variable).unwrap();
+    /// assert_eq!(document.text(), "fn f() {\n    let value = a + b;\n    g(value);\n}");
+    /// assert_eq!(extraction.usage, Range::from(2, 6, 2, 11));
+    /// assert_eq!(document.selection(), Range::from(1, 8, 1, 13));
+    ///
+    /// document.fill_linked(&extraction.name, "sum").unwrap();
+    /// assert_eq!(document.text(), "fn f() {\n    let sum = a + b;\n    g(sum);\n}");
+    /// document.remove_linked(&extraction.name).unwrap();
+    /// ```
+    #[cfg(feature = "parsing")]
+    pub fn extract(&mut self, what: Extract) -> Result<Extraction, Oops> {
+        let plan = self.extract_plan(what)?;
+        let inserted_rows = plan.definition.matches('\n').count();
+        let name_length = what.placeholder().chars().count();
+
+        let last = plan.row + inserted_rows - 1;
+        let beginning = Position::from(plan.selection.beginning.row + inserted_rows, plan.selection.beginning.column);
+        let usage = Range {
+            beginning,
+            ending: Position::from(beginning.row, beginning.column + plan.usage.chars().count())
+        };
+        let name_at = Position::from(plan.row + plan.name_at.row, plan.name_at.column);
+        let occurrences = [
+            Range { beginning: name_at, ending: Position::from(name_at.row, name_at.column + name_length) },
+            Range { beginning, ending: Position::from(beginning.row, beginning.column + name_length) }
+        ];
+
+        self.with_packet("extract", |document| {
+            document.remove(&RemoveOptions::exact_at(&plan.selection))?;
+            let at = Range { beginning: plan.selection.beginning, ending: plan.selection.beginning };
+            document.insert(&plan.usage, &InsertOptions::exact_at(&at))?;
+            document.insert(&plan.definition, &InsertOptions::exact_at(&Range::from(plan.row, 0, plan.row, 0)))?;
+            document.set_selection(&occurrences[0])
+        })?;
+
+        let definition = Range::from(plan.row, 0, last, self.lines()[last].length);
+        let mut anchors = vec![];
+        for occurrence in occurrences.iter() {
+            anchors.push((
+                self.create_floating_anchor(&occurrence.beginning)?,
+                self.create_floating_anchor(&occurrence.ending)?
+            ));
+        }
+
+        Ok(Extraction { definition, usage, name: LinkedName { anchors } })
+    }
+
+    /// Extracts the selection.
+    ///
+    /// Without the `parsing` feature there is never a parse tree, so this
+    /// always returns an [`Oops`].
+    #[cfg(not(feature = "parsing"))]
+    pub fn extract(&mut self, _what: Extract) -> Result<Extraction, Oops> {
+        Err(Oops::CannotParse("extract"))
+    }
+
+    /// Returns the text of `node`.
+    #[cfg(feature = "parsing")]
+    fn node_text(&self, node: &syntax::Node) -> String {
+        self.text_range(&self.range_from_ts(&node.range())).unwrap_or_default()
+    }
+
+    /// Collects the uses of the variable `name` in the statements from
+    /// `first` on, stopping where a statement binds `name` again. Returns
+    /// `Err` if the variable is assigned to.
+    #[cfg(feature = "parsing")]
+    fn collect_uses<'tree>(&self, first: Option<syntax::Node<'tree>>, name: &str, uses: &mut Vec<syntax::Node<'tree>>) -> Result<(), Oops> {
+        let mut next = first;
+        while let Some(statement) = next {
+            if let Some((_, value)) = binding_of(&statement).filter(|(bound, _)| self.node_text(bound) == name) {
+                return self.collect_uses_in(value, name, uses);
+            }
+            self.collect_uses_in(statement, name, uses)?;
+            next = statement.next_sibling();
+        }
+        Ok(())
+    }
+
+    /// Collects the uses of the variable `name` in `node`.
+    #[cfg(feature = "parsing")]
+    fn collect_uses_in<'tree>(&self, node: syntax::Node<'tree>, name: &str, uses: &mut Vec<syntax::Node<'tree>>) -> Result<(), Oops> {
+        if STATEMENT_LISTS.contains(&node.kind()) {
+            return self.collect_uses(node.child(0), name, uses);
+        }
+        if !matches!(node.kind(), "identifier" | "shorthand_property_identifier") {
+            let mut cursor = node.walk();
+            let children: Vec<syntax::Node> = node.children(&mut cursor).collect();
+            for child in children {
+                self.collect_uses_in(child, name, uses)?;
+            }
+            return Ok(());
+        }
+        if self.node_text(&node) != name {
+            return Ok(());
+        }
+
+        if let Some(parent) = node.parent() {
+            let is = |field: &str| parent.child_by_field_name(field) == Some(node);
+            if ["attribute", "field", "property", "label"].iter().any(|f| is(f)) || (parent.kind() == "keyword_argument" && is("name")) {
+                return Ok(());
+            }
+            if ASSIGNMENT_KINDS.contains(&parent.kind()) && (is("left") || is("argument")) {
+                return Err(Oops::Ouch("variable is assigned to after its binding"));
+            }
+        }
+        uses.push(node);
+        Ok(())
+    }
+
+    /// Works out how to inline the variable bound around the cursor.
+    #[cfg(feature = "parsing")]
+    fn inlining(&self) -> Result<Inlining, Oops> {
+        let cursor = self.cursor().position;
+        let mut statement = self.node_at(&Range { beginning: cursor, ending: cursor })
+            .ok_or(Oops::CannotParse("inline_variable"))?;
+        let (name, value) = loop {
+            let parent = statement.parent().ok_or(Oops::Ouch("no variable binding at cursor"))?;
+            if STATEMENT_LISTS.contains(&parent.kind()) {
+                if let Some(binding) = binding_of(&statement) {
+                    break binding;
+                }
+            }
+            statement = parent;
+        };
+        let name = self.node_text(&name);
+        let text = self.node_text(&value);
+
+        let mut uses = vec![];
+        self.collect_uses(statement.next_sibling(), &name, &mut uses)?;
+        let uses = uses.iter()
+            .map(|usage| {
+                let mut replacement = if needs_parentheses(self.language(), &value, usage) {
+                    format!("({})", text)
+                } else {
+                    text.clone()
+                };
+                if usage.kind() == "shorthand_property_identifier" || usage.parent().is_some_and(|p| p.kind() == "shorthand_field_initializer") {
+                    replacement = format!("{}: {}", name, replacement);
+                }
+                (self.range_from_ts(&usage.range()), replacement)
+            })
+            .collect();
+
+        // Take the binding's lines with it if it has them to itself.
+        let range = self.range_from_ts(&statement.range());
+        let (beginning, ending) = (range.beginning, range.ending);
+        let before_blank = self.lines()[beginning.row].content.chars().take(beginning.column).all(char::is_whitespace);
+        let after: String = self.lines()[ending.row].content.chars().skip(ending.column).collect();
+        let binding = if before_blank && after.trim().is_empty() {
+            if ending.row + 1 < self.rows() {
+                Range::from(beginning.row, 0, ending.row + 1, 0)
+            } else if beginning.row > 0 {
+                Range::from(beginning.row - 1, self.lines()[beginning.row - 1].length, ending.row, self.lines()[ending.row].length)
+            } else {
+                Range::from(beginning.row, 0, ending.row, self.lines()[ending.row].length)
+            }
+        } else {
+            let trailing = after.chars().take_while(|c| c.is_whitespace()).count();
+            Range { beginning, ending: Position::from(ending.row, ending.column + trailing) }
+        };
+
+        Ok(Inlining { binding, uses })
+    }
+
+    /// Inlines the variable whose binding is around the cursor: replaces
+    /// each use of it up to where it is bound again with its value, wrapped
+    /// in parentheses where precedence requires, and deletes the binding.
+    /// This is one change packet labeled "inline". Returns how many uses
+    /// were replaced, or `Err` if there is no parse tree, the cursor is not
+    /// on a binding of one plain variable, or the variable is assigned to
+    /// later.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language("fn f() {\n    let x = a + b;\n    g(x * 2, x);\n}", "rs");
+    /// document.set_cursor_and_mark(&Position::from(1, 8)).unwrap();
+    ///
+    /// assert_eq!(document.inline_variable().unwrap(), 2);
+    /// assert_eq!(document.text(), "fn f() {\n    g((a + b) * 2, a + b);\n}");
+    /// ```
+    #[cfg(feature = "parsing")]
+    pub fn inline_variable(&mut self) -> Result<usize, Oops> {
+        let inlining = self.inlining()?;
+
+        self.with_packet("inline", |document| {
+            for (range, text) in inlining.uses.iter().rev() {
+                document.remove(&RemoveOptions::exact_at(range))?;
+                document.insert(text, &InsertOptions::exact_at(&Range { beginning: range.beginning, ending: range.beginning }))?;
+            }
+            document.remove(&RemoveOptions::exact_at(&inlining.binding))
+        })?;
+
+        Ok(inlining.uses.len())
+    }
+
+    /// Inlines the variable whose binding is around the cursor.
+    ///
+    /// Without the `parsing` feature there is never a parse tree, so this
+    /// always returns an [`Oops`].
+    #[cfg(not(feature = "parsing"))]
+    pub fn inline_variable(&mut self) -> Result<usize, Oops> {
+        Err(Oops::CannotParse("inline_variable"))
+    }
+
+    /// Returns the current range of each occurrence of `name`, definition
+    /// first, or `Err` if any of its anchors is gone.
+    pub fn linked_ranges(&self, name: &LinkedName) -> Result<Vec<Range>, Oops> {
+        name.anchors.iter()
+            .map(|&(beginning, ending)| {
+                let beginning = self.anchor(beginning).ok_or(Oops::NonexistentAnchor(beginning))?.position;
+                let ending = self.anchor(ending).ok_or(Oops::NonexistentAnchor(ending))?.position;
+                Ok(Range { beginning, ending })
+            })
+            .collect()
+    }
+
+    /// Replaces every occurrence of `name` with `text`, as one change packet
+    /// labeled "rename", and moves the anchors around the new text. Returns
+    /// `Err` if any of the anchors is gone or `text` is more than one line.
+    pub fn fill_linked(&mut self, name: &LinkedName, text: &str) -> Result<(), Oops> {
+        if text.contains('\n') {
+            return Err(Oops::Ouch("a name must be one line"));
+        }
+        let ranges = self.linked_ranges(name)?;
+
+        // Replace from the bottom up so earlier ranges stay put.
+        let mut order: Vec<usize> = (0..ranges.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(ranges[i].beginning));
+        let length = text.chars().count();
+
+        self.with_packet("rename", |document| {
+            for i in order {
+                let range = &ranges[i];
+                document.remove(&RemoveOptions::exact_at(range))?;
+                document.insert(text, &InsertOptions::exact_at(&Range { beginning: range.beginning, ending: range.beginning }))?;
+
+                let (beginning, ending) = name.anchors[i];
+                document.set_anchor(beginning, &Anchor { position: range.beginning })?;
+                document.set_anchor(ending, &Anchor {
+                    position: Position::from(range.beginning.row, range.beginning.column + length)
+                })?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes the anchors of `name`. Returns `Err` if any was already gone,
+    /// after removing the rest.
+    pub fn remove_linked(&mut self, name: &LinkedName) -> Result<(), Oops> {
+        let mut result = Ok(());
+        for &(beginning, ending) in name.anchors.iter() {
+            for handle in [beginning, ending].iter() {
+                if let Err(oops) = self.remove_floating_anchor(*handle) {
+                    result = Err(oops);
+                }
+            }
+        }
+        result
+    }
+}
+
+//-----------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "parsing")]
+    const METHOD: &str = "impl A {\n    fn f(&self) {\n        let x = 1;\n        if x > 0 {\n            g(x);\n        }\n    }\n}";
+
+    #[test]
+    fn extract_commands_parse() {
+        assert_eq!(Extract::parse("Extract Into A Function"), Some(Extract::Function));
+        assert_eq!(Extract::parse("extract local"), Some(Extract::Variable));
+        assert_eq!(Extract::parse("extract"), None);
+        assert_eq!(Extract::parse(""), None);
+        assert_eq!(Extract::parse("variable extract"), None);
+    }
+
+    #[test]
+    fn extracts_need_a_tree() {
+        let mut document = Document::from("g(a + b);");
+        document.set_selection(&Range::from(0, 2, 0, 7)).unwrap();
+        assert!(document.extract(Extract::Variable).is_err());
+        assert!(document.inline_variable().is_err());
+        assert_eq!(document.text(), "g(a + b);");
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn extract_function_moves_statements_above_their_function() {
+        let mut document = Document::from_with_language(METHOD, "rs");
+        document.set_selection(&Range::from(2, 4, 5, 9)).unwrap();
+
+        let extraction = document.extract(Extract::Function).unwrap();
+        assert_eq!(document.text(), concat!(
+            "impl A {\n",
+            "    fn extracted() {\n",
+            "        let x = 1;\n",
+            "        if x > 0 {\n",
+            "            g(x);\n",
+            "        }\n",
+            "    }\n",
+            "\n",
+            "    fn f(&self) {\n",
+            "        extracted();\n",
+            "    }\n",
+            "}"
+        ));
+        assert_eq!(extraction.definition, Range::from(1, 0, 7, 0));
+        assert_eq!(extraction.usage, Range::from(9, 8, 9, 20));
+        assert_eq!(document.selection(), Range::from(1, 7, 1, 16));
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn filling_a_linked_name_renames_every_occurrence() {
+        let mut document = Document::from_with_language(METHOD, "rs");
+        document.set_selection(&Range::from(2, 4, 5, 9)).unwrap();
+        let extraction = document.extract(Extract::Function).unwrap();
+
+        document.fill_linked(&extraction.name, "check").unwrap();
+        assert_eq!(document.lines()[1].content, "    fn check() {");
+        assert_eq!(document.lines()[9].content, "        check();");
+        assert_eq!(document.linked_ranges(&extraction.name).unwrap(), vec![Range::from(1, 7, 1, 12), Range::from(9, 8, 9, 13)]);
+
+        assert!(document.fill_linked(&extraction.name, "two\nlines").is_err());
+        assert_eq!(document.lines()[1].content, "    fn check() {");
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn extract_and_rename_undo_separately() {
+        let mut document = Document::from_with_language(METHOD, "rs");
+        document.set_selection(&Range::from(2, 4, 5, 9)).unwrap();
+        let extraction = document.extract(Extract::Function).unwrap();
+        document.fill_linked(&extraction.name, "check").unwrap();
+
+        document.undo_once().unwrap();
+        assert_eq!(document.lines()[1].content, "    fn extracted() {");
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), METHOD);
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn removed_linked_names_are_gone() {
+        let mut document = Document::from_with_language(METHOD, "rs");
+        document.set_selection(&Range::from(2, 4, 5, 9)).unwrap();
+        let extraction = document.extract(Extract::Function).unwrap();
+
+        document.remove_linked(&extraction.name).unwrap();
+        assert!(document.remove_linked(&extraction.name).is_err());
+        assert!(document.linked_ranges(&extraction.name).is_err());
+        assert!(document.fill_linked(&extraction.name, "check").is_err());
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn extracts_refuse_partial_selections() {
+        let mut document = Document::from_with_language(METHOD, "rs");
+
+        // Part of a statement, or part of an expression, cannot be extracted.
+        document.set_selection(&Range::from(2, 8, 3, 10)).unwrap();
+        assert!(document.extract(Extract::Function).is_err());
+        document.set_selection(&Range::from(3, 11, 3, 14)).unwrap();
+        assert!(document.extract(Extract::Variable).is_err());
+        assert_eq!(document.text(), METHOD);
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn extracts_refuse_empty_selections() {
+        let mut document = Document::from_with_language("", "rs");
+        assert!(document.extract(Extract::Variable).is_err());
+        assert!(document.extract(Extract::Function).is_err());
+
+        let mut document = Document::from_with_language(METHOD, "rs");
+        document.set_selection(&Range::from(4, 12, 4, 12)).unwrap();
+        assert!(document.extract(Extract::Variable).is_err());
+
+        // White space alone trims down to nothing.
+        document.set_selection(&Range::from(1, 17, 2, 8)).unwrap();
+        assert!(document.extract(Extract::Function).is_err());
+        assert_eq!(document.text(), METHOD);
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn extract_variable_in_python() {
+        let mut document = Document::from_with_language("def f():\n    print(1 + 2)\n", "py");
+        document.set_selection(&Range::from(1, 10, 1, 15)).unwrap();
+        document.extract(Extract::Variable).unwrap();
+        assert_eq!(document.text(), "def f():\n    value = 1 + 2\n    print(value)\n");
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn extract_variable_at_the_end_of_the_document() {
+        let mut document = Document::from_with_language("x = 1\nprint(x + 2)", "py");
+        document.set_selection(&Range::from(1, 6, 1, 11)).unwrap();
+
+        let extraction = document.extract(Extract::Variable).unwrap();
+        assert_eq!(document.text(), "x = 1\nvalue = x + 2\nprint(value)");
+        assert_eq!(extraction.definition, Range::from(1, 0, 1, 13));
+        assert_eq!(extraction.usage, Range::from(2, 6, 2, 11));
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn extract_variable_counts_characters() {
+        let mut document = Document::from_with_language("fn f() {\n    g(\"é\", h(\"ü\"));\n}", "rs");
+        document.set_selection(&Range::from(1, 11, 1, 17)).unwrap();
+
+        let extraction = document.extract(Extract::Variable).unwrap();
+        assert_eq!(document.text(), "fn f() {\n    let value = h(\"ü\");\n    g(\"é\", value);\n}");
+        assert_eq!(extraction.usage, Range::from(2, 11, 2, 16));
+        assert_eq!(document.selection(), Range::from(1, 8, 1, 13));
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn extracts_refuse_unsupported_languages() {
+        let mut document = Document::from_with_language("echo hello", "sh");
+        document.set_selection(&Range::from(0, 5, 0, 10)).unwrap();
+        assert!(document.extract(Extract::Variable).is_err());
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn inlining_respects_precedence() {
+        let text = "fn f() {\n    let x = a || b;\n    g(!x, x + c, x);\n}";
+        let mut document = Document::from_with_language(text, "rs");
+        document.set_cursor_and_mark(&Position::from(1, 4)).unwrap();
+
+        assert_eq!(document.inline_variable().unwrap(), 3);
+        assert_eq!(document.text(), "fn f() {\n    g(!(a || b), (a || b) + c, a || b);\n}");
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), text);
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn inlining_stops_where_the_name_is_bound_again() {
+        let text = "fn f() {\n    let x = a || b;\n    if !x && c {\n        let x = x || d;\n        g(x);\n    }\n    h(S { x }, x.0);\n}";
+        let mut document = Document::from_with_language(text, "rs");
+        document.set_cursor_and_mark(&Position::from(1, 4)).unwrap();
+
+        assert_eq!(document.inline_variable().unwrap(), 4);
+        assert_eq!(document.text(), concat!(
+            "fn f() {\n",
+            "    if !(a || b) && c {\n",
+            "        let x = a || b || d;\n",
+            "        g(x);\n",
+            "    }\n",
+            "    h(S { x: a || b }, (a || b).0);\n",
+            "}"
+        ));
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn inlining_refuses_assigned_variables() {
+        let text = "def f():\n    y = 1\n    y += 1\n";
+        let mut document = Document::from_with_language(text, "py");
+        document.set_cursor_and_mark(&Position::from(1, 4)).unwrap();
+        assert!(document.inline_variable().is_err());
+        assert_eq!(document.text(), text);
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn inlining_skips_keyword_names() {
+        let mut document = Document::from_with_language("def f():\n    y = a if b else c\n    return y + g(y=y)\n", "py");
+        document.set_cursor_and_mark(&Position::from(1, 4)).unwrap();
+        assert_eq!(document.inline_variable().unwrap(), 2);
+        assert_eq!(document.text(), "def f():\n    return (a if b else c) + g(y=a if b else c)\n");
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn inlining_needs_a_binding_at_the_cursor() {
+        let mut document = Document::from_with_language("", "py");
+        assert!(document.inline_variable().is_err());
+
+        let text = "fn f() {\n    g(x);\n}";
+        let mut document = Document::from_with_language(text, "rs");
+        document.set_cursor_and_mark(&Position::from(1, 6)).unwrap();
+        assert!(document.inline_variable().is_err());
+        assert_eq!(document.text(), text);
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn inlining_a_binding_on_the_last_line() {
+        let mut document = Document::from_with_language("print(1)\ny = 2", "py");
+        document.set_cursor_and_mark(&Position::from(1, 0)).unwrap();
+        assert_eq!(document.inline_variable().unwrap(), 0);
+        assert_eq!(document.text(), "print(1)");
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn inlining_counts_characters() {
+        let mut document = Document::from_with_language("fn f() {\n    let x = \"ö\";\n    g(\"ü\", x, \"é\", x);\n}", "rs");
+        document.set_cursor_and_mark(&Position::from(1, 8)).unwrap();
+        assert_eq!(document.inline_variable().unwrap(), 2);
+        assert_eq!(document.text(), "fn f() {\n    g(\"ü\", \"ö\", \"é\", \"ö\");\n}");
+    }
+}
@@ -0,0 +1,199 @@
+//! Spoken feedback: short descriptions of what an edit did, such as
+//! "inserted 12 characters on line 8" or "deleted the if statement spanning
+//! lines 3 to 5", for text-to-speech or a status line.
+//!
+//! Descriptions are built from a packet's text changes; anchor and other
+//! changes say nothing. Line numbers count from one, as spoken line targets
+//! do. Edits on one line are counted in characters, and edits across lines
+//! by the lines they span. When a [`Chain`] from where the edit happened is
+//! given, and one of its regions spans the same lines as the edit, the edit
+//! is described by the region's kind instead.
+
+use crate::document::{Document, Change, Chain, Range};
+
+/// A text edit, reduced to what feedback mentions.
+struct Edit {
+    inserted: bool,
+    range: Range
+}
+
+/// Returns the edits among `changes`. If `inverse`, the changes are the
+/// inverses of the edits, as on the undo stack.
+fn edits(changes: &[Change], inverse: bool) -> Vec<Edit> {
+    changes.iter()
+        .filter_map(|change| match change {
+            Change::Insert { text, position } => Some(Edit {
+                inserted: !inverse,
+                range: Range { beginning: *position, ending: position.after_insert(position, text) }
+            }),
+            Change::Remove { range } => Some(Edit { inserted: inverse, range: *range }),
+            _ => None
+        })
+        .collect()
+}
+
+/// Returns how `kind`, a syntax node kind, is said aloud.
+fn spoken_kind(kind: &str) -> String {
+    let words = kind.replace('_', " ");
+    match words.strip_suffix(" item") {
+        Some(item) => String::from(item),
+        None => words
+    }
+}
+
+/// Returns the one-based numbers of the first and last lines `range` covers.
+fn line_span(range: &Range) -> (usize, usize) {
+    let last = if range.ending.row > range.beginning.row && range.ending.column == 0 {
+        range.ending.row - 1
+    } else {
+        range.ending.row
+    };
+    (range.beginning.row + 1, last + 1)
+}
+
+/// Returns "N characters", or "1 character".
+fn characters(count: usize) -> String {
+    format!("{} character{}", count, if count == 1 { "" } else { "s" })
+}
+
+/// Returns a description of `edits`, or `None` if there are none.
+fn describe(edits: &[Edit], chain: Option<&Chain>) -> Option<String> {
+    let beginning = edits.iter().map(|e| e.range.beginning).min()?;
+    let ending = edits.iter().map(|e| e.range.ending).max()?;
+    let span = Range { beginning, ending };
+    let (first, last) = line_span(&span);
+
+    let inserted = edits.iter().any(|e| e.inserted);
+    let removed = edits.iter().any(|e| !e.inserted);
+    let verb = match (inserted, removed) {
+        (true, false) => "inserted",
+        (false, true) => "deleted",
+        _ => "changed"
+    };
+
+    // The outermost region the edit covers, or what it wraps exactly, since
+    // an expression statement is better named by its expression.
+    let region = chain.and_then(|chain| {
+        let regions = &chain.regions;
+        let mut index = (1..regions.len()).find(|&i| {
+            let range = &regions[i].range;
+            regions[i].kind.contains('_') && span.beginning <= range.beginning && range.ending <= span.ending
+                && line_span(range) == (first, last)
+        })?;
+        while regions.get(index + 1).is_some_and(|r| r.range == regions[index].range && r.kind.contains('_')) {
+            index += 1;
+        }
+        Some(&regions[index])
+    });
+    let lines = if first == last {
+        format!("on line {}", first)
+    } else {
+        format!("spanning lines {} to {}", first, last)
+    };
+
+    Some(match region {
+        Some(region) => format!("{} the {} {}", verb, spoken_kind(&region.kind), lines),
+        None if first != last => format!("{} lines {} to {}", verb, first, last),
+        None if inserted && removed => format!("changed line {}", first),
+        None => {
+            // An edit reaching the start of the next line took a line break.
+            let count: usize = edits.iter()
+                .map(|e| if e.range.ending.row > e.range.beginning.row {
+                    1
+                } else {
+                    e.range.ending.column - e.range.beginning.column
+                })
+                .sum();
+            format!("{} {} {}", verb, characters(count), lines)
+        }
+    })
+}
+
+/// Returns a description of `changes`, applied in order, naming a region of
+/// `chain` where one matches. Returns `None` if no text changed.
+///
+/// # Examples
+/// ```
+/// use ls_core::document::*;
+/// use ls_core::feedback::*;
+/// let insert = Change::Insert { text: vec![String::from("hello world!")], position: Position::from(7, 4) };
+/// assert_eq!(describe_changes(&[insert], None).unwrap(), "inserted 12 characters on line 8");
+///
+/// let remove = Change::Remove { range: Range::from(2, 0, 5, 0) };
+/// assert_eq!(describe_changes(&[remove], None).unwrap(), "deleted lines 3 to 5");
+/// assert_eq!(describe_changes(&[], None), None);
+/// ```
+pub fn describe_changes(changes: &[Change], chain: Option<&Chain>) -> Option<String> {
+    describe(&edits(changes, false), chain)
+}
+
+impl Document {
+    /// Returns a description of the most recent change packet on the undo
+    /// stack (see [`describe_changes`]), or `None` if there is none or it
+    /// changed no text. For a description naming what was deleted, take
+    /// `chain` from [`Document::get_context_at`] before deleting.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "parsing")] {
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language("fn f() {\n    if a {\n        b();\n    }\n}\n", "rs");
+    /// let chain = document.get_context_at(&Position::from(1, 4)).unwrap();
+    /// document.checkpoint();
+    /// document.remove(&RemoveOptions::exact_at(&Range::from(1, 0, 4, 0))).unwrap();
+    /// assert_eq!(
+    ///     document.describe_last_edit(Some(&chain)).unwrap(),
+    ///     "deleted the if expression spanning lines 2 to 4"
+    /// );
+    /// # }
+    /// ```
+    pub fn describe_last_edit(&self, chain: Option<&Chain>) -> Option<String> {
+        let packet = self.undo_redo().undo_packets().next()?;
+        let changes: Vec<Change> = packet.changes().iter().rev().cloned().collect();
+        describe(&edits(&changes, true), chain)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Position, InsertOptions, RemoveOptions};
+
+    #[test]
+    fn descriptions_count_characters_or_lines() {
+        let mut document = Document::from("alpha\nbeta\ngamma");
+        assert_eq!(document.describe_last_edit(None), None);
+
+        document.insert("x", &InsertOptions::exact_at(&Range::from(1, 0, 1, 0))).unwrap();
+        assert_eq!(document.describe_last_edit(None).unwrap(), "inserted 1 character on line 2");
+
+        document.checkpoint();
+        document.set_selection(&Range::from(0, 1, 0, 3)).unwrap();
+        document.insert("LPH", &InsertOptions::exact()).unwrap();
+        assert_eq!(document.describe_last_edit(None).unwrap(), "changed line 1");
+
+        document.checkpoint();
+        document.remove(&RemoveOptions::exact_at(&Range::from(1, 2, 2, 1))).unwrap();
+        assert_eq!(document.describe_last_edit(None).unwrap(), "deleted lines 2 to 3");
+
+        let removals = [
+            Change::Remove { range: Range::from(0, 4, 0, 6) },
+            Change::Remove { range: Range::from(0, 0, 0, 1) }
+        ];
+        assert_eq!(describe_changes(&removals, None).unwrap(), "deleted 3 characters on line 1");
+
+        let chain = Chain { regions: vec![
+            crate::document::ChainRegion { kind: String::from("source_file"), range: Range::from(0, 0, 9, 0) },
+            crate::document::ChainRegion { kind: String::from("function_item"), range: Range::from(2, 0, 4, 1) }
+        ] };
+        let insert = Change::Insert { text: vec![String::new(), String::from("fn g() {}"), String::new()], position: Position::from(4, 1) };
+        let remove = Change::Remove { range: Range::from(2, 0, 5, 0) };
+        assert_eq!(describe_changes(&[remove], Some(&chain)).unwrap(), "deleted the function spanning lines 3 to 5");
+        assert_eq!(describe_changes(&[insert], Some(&chain)).unwrap(), "inserted lines 5 to 6");
+
+        let newline = Change::Insert { text: vec![String::new(), String::new()], position: Position::from(0, 2) };
+        assert_eq!(describe_changes(&[newline], None).unwrap(), "inserted 1 character on line 1");
+    }
+}
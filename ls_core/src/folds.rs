@@ -0,0 +1,380 @@
+//! Folded (collapsed) runs of lines.
+//!
+//! A fold hides the lines after its first row, up to and including its last
+//! row; the first row stays visible as the fold's header. Both ends are held
+//! by floating anchors, so folds follow the text as it is edited. Folding is
+//! a view concern and is not recorded on the undo stack.
+//!
+//! Folds can also be made structurally, from the parse tree: "fold all
+//! functions", "fold all comments", or "fold level 2" fold every
+//! [`FoldingRange`] of a kind or nesting level. See [`FoldTarget`].
+
+use crate::document::{Document, Position, AnchorHandle};
+use crate::spoken;
+use crate::util::Oops;
+
+/// A fold covering rows `first_row..=last_row`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Fold {
+    pub first_row: usize,
+    pub last_row: usize
+}
+
+/// A [`Fold`] as stored in a document: its rows are tracked by two floating
+/// anchors. The handle of the first anchor identifies the fold.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub(crate) struct AnchoredFold {
+    beginning: AnchorHandle,
+    ending: AnchorHandle
+}
+
+/// A run of rows which could be folded: a syntax node spanning several
+/// lines, or a run of adjacent comment lines.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct FoldingRange {
+    pub first_row: usize,
+    pub last_row: usize,
+
+    /// The kind of the syntax node, or `"comment"` for comment runs.
+    pub kind: String,
+
+    /// How many other folding ranges enclose this one. Top-level items are
+    /// at level 0.
+    pub level: usize
+}
+
+/// Which folding ranges to fold, for [`Document::fold_all`].
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum FoldTarget {
+    /// "fold all functions": every function and method.
+    Functions,
+
+    /// "fold all comments": every comment spanning several lines, and every
+    /// run of line comments.
+    Comments,
+
+    /// "fold level 2": every range at this nesting level, hiding everything
+    /// below it.
+    Level(usize),
+
+    /// Every range whose kind is one of these.
+    Kinds(Vec<String>)
+}
+
+impl Fold {
+    /// Returns the number of rows hidden by this fold.
+    pub fn hidden_rows(&self) -> usize {
+        self.last_row - self.first_row
+    }
+}
+
+impl FoldTarget {
+    /// Parses a spoken fold command.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::folds::*;
+    /// assert_eq!(FoldTarget::parse("fold all functions"), Some(FoldTarget::Functions));
+    /// assert_eq!(FoldTarget::parse("fold comments"), Some(FoldTarget::Comments));
+    /// assert_eq!(FoldTarget::parse("fold below level two"), Some(FoldTarget::Level(2)));
+    /// assert_eq!(FoldTarget::parse("fold the laundry"), None);
+    /// ```
+    pub fn parse(phrase: &str) -> Option<FoldTarget> {
+        let lowered = phrase.to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+        let words = match words.as_slice() {
+            ["fold", "all", rest @ ..] | ["fold", rest @ ..] => rest,
+            _ => return None
+        };
+
+        match words {
+            ["functions"] | ["methods"] | ["function", "bodies"] => Some(FoldTarget::Functions),
+            ["comments"] => Some(FoldTarget::Comments),
+            ["level", rest @ ..] | ["below", "level", rest @ ..] => match spoken::parse_number(rest)? {
+                (level, used) if used == rest.len() => Some(FoldTarget::Level(level)),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    /// Does `target` include `range`?
+    fn includes(&self, range: &FoldingRange) -> bool {
+        match self {
+            FoldTarget::Functions => is_function_kind(&range.kind),
+            FoldTarget::Comments => range.kind.contains("comment"),
+            FoldTarget::Level(level) => range.level == *level,
+            FoldTarget::Kinds(kinds) => kinds.contains(&range.kind)
+        }
+    }
+}
+
+/// Is `kind` the kind of a function node?
+pub(crate) fn is_function_kind(kind: &str) -> bool {
+    #[cfg(feature = "parsing")]
+    return crate::doc_comments::FUNCTION_KINDS.contains(&kind);
+
+    #[cfg(not(feature = "parsing"))]
+    {
+        let _ = kind;
+        false
+    }
+}
+
+impl Document {
+    /// Folds rows `first_row..=last_row`, returning a handle identifying the
+    /// fold. Returns `Err` if either row does not exist or the fold would not
+    /// hide anything.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("fn f() {\n    1\n}\nfn g() {}");
+    /// let fold = document.fold_rows(0, 2).unwrap();
+    /// assert!(document.row_hidden(1));
+    /// assert!(!document.row_hidden(0));
+    ///
+    /// document.insert("\n", &InsertOptions::exact()).unwrap();
+    /// assert_eq!(document.fold(fold).unwrap().first_row, 1);
+    /// ```
+    pub fn fold_rows(&mut self, first_row: usize, last_row: usize) -> Result<AnchorHandle, Oops> {
+        if last_row >= self.rows() {
+            return Err(Oops::InvalidIndex(last_row, "fold_rows"));
+        }
+        if first_row >= last_row {
+            return Err(Oops::InvalidIndex(first_row, "fold_rows"));
+        }
+
+        let beginning = self.create_floating_anchor(&Position::from(first_row, 0))?;
+        let ending = self.create_floating_anchor(&Position::from(last_row, 0))?;
+        self.folds.push(AnchoredFold { beginning, ending });
+        self.damage.record_from(first_row);
+
+        Ok(beginning)
+    }
+
+    /// Removes the fold identified by `handle`. Returns `Err` if there is no
+    /// such fold.
+    pub fn unfold(&mut self, handle: AnchorHandle) -> Result<(), Oops> {
+        match self.folds.iter().position(|f| f.beginning == handle) {
+            None => Err(Oops::NonexistentAnchor(handle)),
+            Some(index) => {
+                let fold = self.folds.remove(index);
+                if let Some(anchor) = self.anchor(fold.beginning) {
+                    self.damage.record_from(anchor.position.row);
+                }
+                self.remove_floating_anchor(fold.beginning)?;
+                self.remove_floating_anchor(fold.ending)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes every fold.
+    pub fn unfold_all(&mut self) {
+        if let Some(first) = self.folds.iter().filter_map(|f| self.anchor(f.beginning)).map(|a| a.position.row).min() {
+            self.damage.record_from(first);
+        }
+        for fold in std::mem::take(&mut self.folds) {
+            let _ = self.remove_floating_anchor(fold.beginning);
+            let _ = self.remove_floating_anchor(fold.ending);
+        }
+    }
+
+    /// Returns the rows covered by the fold identified by `handle`.
+    pub fn fold(&self, handle: AnchorHandle) -> Option<Fold> {
+        self.folds.iter()
+            .find(|f| f.beginning == handle)
+            .map(|f| self.resolve_fold(f))
+    }
+
+    /// Returns every fold, with its handle, sorted by first row. Folds whose
+    /// hidden rows have all been deleted are omitted.
+    pub fn folds(&self) -> Vec<(AnchorHandle, Fold)> {
+        let mut result: Vec<(AnchorHandle, Fold)> = self.folds.iter()
+            .map(|f| (f.beginning, self.resolve_fold(f)))
+            .filter(|(_, f)| f.hidden_rows() > 0)
+            .collect();
+
+        result.sort_by_key(|(handle, f)| (f.first_row, *handle));
+        result
+    }
+
+    /// Returns the fold whose header is `row`, if any. If several folds
+    /// share a header, the largest is returned.
+    pub fn fold_at_row(&self, row: usize) -> Option<(AnchorHandle, Fold)> {
+        self.folds().into_iter()
+            .filter(|(_, f)| f.first_row == row)
+            .max_by_key(|(_, f)| f.last_row)
+    }
+
+    /// Returns whether `row` is hidden inside a fold.
+    pub fn row_hidden(&self, row: usize) -> bool {
+        self.folds().iter().any(|(_, f)| f.first_row < row && row <= f.last_row)
+    }
+
+    /// Returns every folding range, sorted by first row, outermost first.
+    /// Nodes spanning the same rows as an enclosing node are left out, as
+    /// are comment lines standing alone. Empty without a parse tree.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "parsing")] {
+    /// use ls_core::document::*;
+    /// let document = Document::from_with_language("// a\n// b\nfn f() {\n    if x {\n        y();\n    }\n}\n", "rs");
+    /// let ranges: Vec<(usize, usize, String, usize)> = document.folding_ranges().into_iter()
+    ///     .map(|r| (r.first_row, r.last_row, r.kind, r.level))
+    ///     .collect();
+    /// assert_eq!(ranges, vec![
+    ///     (0, 1, String::from("comment"), 0),
+    ///     (2, 6, String::from("function_item"), 0),
+    ///     (3, 5, String::from("if_expression"), 1)
+    /// ]);
+    /// # }
+    /// ```
+    pub fn folding_ranges(&self) -> Vec<FoldingRange> {
+        #[cfg(feature = "parsing")]
+        {
+            let tree = match self.parse_tree() {
+                Some(tree) => tree,
+                None => return vec![]
+            };
+
+            let mut result: Vec<FoldingRange> = vec![];
+            let mut comments: Vec<(usize, usize)> = vec![];
+            let mut enclosing: Vec<usize> = vec![];
+            let mut cursor = tree.walk();
+
+            'walk: loop {
+                let node = cursor.node();
+                let (first_row, last_row) = (node.start_position().row, node.end_position().row);
+                let last_row = if node.end_position().column == 0 && last_row > first_row { last_row - 1 } else { last_row };
+
+                if node.kind().contains("comment") {
+                    match comments.last_mut() {
+                        Some(run) if first_row <= run.1 + 1 => run.1 = run.1.max(last_row),
+                        _ => comments.push((first_row, last_row))
+                    }
+                } else if node.is_named() && node.parent().is_some() && last_row > first_row {
+                    while enclosing.last().is_some_and(|&i| result[i].last_row < first_row) {
+                        enclosing.pop();
+                    }
+                    let duplicate = enclosing.last()
+                        .is_some_and(|&i| result[i].first_row == first_row && result[i].last_row == last_row);
+                    if !duplicate {
+                        enclosing.push(result.len());
+                        result.push(FoldingRange {
+                            first_row,
+                            last_row,
+                            kind: String::from(node.kind()),
+                            level: enclosing.len() - 1
+                        });
+                    }
+                }
+
+                if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                    continue;
+                }
+                loop {
+                    if !cursor.goto_parent() {
+                        break 'walk;
+                    }
+                    if cursor.goto_next_sibling() {
+                        break;
+                    }
+                }
+            }
+
+            for (first_row, last_row) in comments.into_iter().filter(|(first, last)| last > first) {
+                let level = result.iter().filter(|r| r.first_row < first_row && last_row <= r.last_row).count();
+                result.push(FoldingRange { first_row, last_row, kind: String::from("comment"), level });
+            }
+
+            result.sort_by_key(|r| (r.first_row, std::cmp::Reverse(r.last_row)));
+            result
+        }
+
+        #[cfg(not(feature = "parsing"))]
+        vec![]
+    }
+
+    /// Folds every folding range `target` includes which is not folded
+    /// already, returning how many folds were made.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "parsing")] {
+    /// use ls_core::document::*;
+    /// use ls_core::folds::*;
+    /// let mut document = Document::from_with_language("fn f() {\n    1\n}\n\nfn g() {\n    2\n}\n", "rs");
+    /// assert_eq!(document.fold_all(&FoldTarget::Functions), 2);
+    /// assert_eq!(document.fold_all(&FoldTarget::Functions), 0);
+    /// assert!(document.row_hidden(5));
+    /// # }
+    /// ```
+    pub fn fold_all(&mut self, target: &FoldTarget) -> usize {
+        let folded: Vec<(usize, usize)> = self.folds().iter().map(|(_, f)| (f.first_row, f.last_row)).collect();
+        let wanted: Vec<(usize, usize)> = self.folding_ranges().into_iter()
+            .filter(|r| target.includes(r) && !folded.contains(&(r.first_row, r.last_row)))
+            .map(|r| (r.first_row, r.last_row))
+            .collect();
+
+        wanted.iter().filter(|(first, last)| self.fold_rows(*first, *last).is_ok()).count()
+    }
+
+    fn resolve_fold(&self, fold: &AnchoredFold) -> Fold {
+        let first_row = self.anchor(fold.beginning).unwrap().position.row;
+        let last_row = self.anchor(fold.ending).unwrap().position.row;
+        Fold { first_row, last_row: std::cmp::max(first_row, last_row) }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{RemoveOptions, Range};
+
+    #[test]
+    fn folds_follow_edits() {
+        let mut document = Document::from("a\nb\nc\nd\ne");
+        assert!(document.fold_rows(2, 2).is_err());
+        assert!(document.fold_rows(2, 5).is_err());
+
+        let outer = document.fold_rows(0, 3).unwrap();
+        let inner = document.fold_rows(1, 2).unwrap();
+        assert_eq!(document.fold_at_row(0).unwrap().0, outer);
+        assert_eq!(document.folds().len(), 2);
+
+        document.remove(&RemoveOptions::exact_at(&Range::from(1, 0, 3, 0))).unwrap();
+        assert_eq!(document.fold(outer), Some(Fold { first_row: 0, last_row: 1 }));
+        assert_eq!(document.fold(inner).unwrap().hidden_rows(), 0);
+        assert_eq!(document.folds().len(), 1);
+
+        document.unfold(outer).unwrap();
+        assert!(document.unfold(outer).is_err());
+        assert!(!document.row_hidden(1));
+
+        document.unfold_all();
+        assert_eq!(document.fold(inner), None);
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn structural_folds_by_speech() {
+        use crate::commands::Outcome;
+
+        let text = "/* one\n   two */\nfn f() {\n    // a\n    // b\n    g(|| {\n        h()\n    });\n}\n";
+        let mut document = Document::from_with_language(text, "rs");
+
+        assert_eq!(document.execute_phrase("fold all comments"), Ok(Outcome::Folded(2)));
+        assert_eq!(document.folds().iter().map(|(_, f)| (f.first_row, f.last_row)).collect::<Vec<_>>(), vec![(0, 1), (3, 4)]);
+        assert_eq!(document.execute_phrase("unfold all"), Ok(Outcome::Cleared(2)));
+
+        assert_eq!(document.fold_all(&FoldTarget::Level(1)), 2);
+        assert_eq!(document.fold_at_row(5).unwrap().1, Fold { first_row: 5, last_row: 7 });
+        assert_eq!(document.fold_all(&FoldTarget::Kinds(vec![String::from("function_item")])), 1);
+        assert!(document.execute_phrase("fold banana").is_err());
+    }
+}
@@ -0,0 +1,267 @@
+//! Formatting part of a document with a pluggable formatter.
+//!
+//! A [`RangeFormatter`] is handed the document's text and a range and
+//! returns new text for that range. [`Document::format_range`] applies only
+//! the lines and characters which actually changed, so anchors (the cursor
+//! among them) in untouched text stay where they were.
+//!
+//! With the `command_formatter` feature, [`CommandFormatter`] runs a
+//! command-line formatter such as rustfmt or prettier.
+
+use crate::changeset::{ChangeSet, TextEdit};
+use crate::document::{Document, Position, Range};
+use crate::util::Oops;
+
+/// Something which can reformat part of a document.
+pub trait RangeFormatter {
+    /// Returns the replacement for the text in `range`, given the whole
+    /// document's `text` for context.
+    fn format(&self, text: &str, range: &Range) -> Result<String, Oops>;
+}
+
+impl<F: Fn(&str, &Range) -> Result<String, Oops>> RangeFormatter for F {
+    fn format(&self, text: &str, range: &Range) -> Result<String, Oops> {
+        self(text, range)
+    }
+}
+
+/// A span of lines which differ between the old and new text: old lines
+/// `old.0..old.1` are replaced by new lines `new.0..new.1`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct Hunk {
+    old: (usize, usize),
+    new: (usize, usize)
+}
+
+/// Returns the hunks which turn `old` into `new`, in order, by way of
+/// their longest common subsequence.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut hunks = vec![];
+    let (mut i, mut j) = (0, 0);
+    let mut start: Option<(usize, usize)> = None;
+
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            if let Some((si, sj)) = start.take() {
+                hunks.push(Hunk { old: (si, i), new: (sj, j) });
+            }
+            i += 1;
+            j += 1;
+            continue;
+        }
+
+        start.get_or_insert((i, j));
+        if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+            j += 1;
+        } else {
+            i += 1;
+        }
+    }
+
+    if let Some((si, sj)) = start {
+        hunks.push(Hunk { old: (si, i), new: (sj, j) });
+    }
+    hunks
+}
+
+/// Returns the position just after `text` when it starts at `start`.
+fn advance(start: &Position, text: &str) -> Position {
+    let lines: Vec<String> = text.split('\n').map(String::from).collect();
+    start.after_insert(start, &lines)
+}
+
+impl Document {
+    /// Replaces the text in `range` with `formatter`'s output as a single
+    /// undoable packet labeled "format", touching only what changed.
+    /// Returns how many separate edits were made.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::formatting::*;
+    /// let mut document = Document::from("a=1\nb = 2\nc=3");
+    /// document.set_cursor_and_mark(&Position::from(1, 5)).unwrap();
+    ///
+    /// let spaced = |text: &str, range: &Range| -> Result<String, ls_core::util::Oops> {
+    ///     Ok(text.replace(" = ", "=").replace('=', " = "))
+    /// };
+    /// let whole = Range::from(0, 0, 2, 3);
+    /// assert_eq!(document.format_range(&spaced, &whole), Ok(2));
+    /// assert_eq!(document.text(), "a = 1\nb = 2\nc = 3");
+    /// assert_eq!(document.cursor().position, Position::from(1, 5));
+    /// ```
+    pub fn format_range(&mut self, formatter: &dyn RangeFormatter, range: &Range) -> Result<usize, Oops> {
+        if !self.range_valid(range) {
+            return Err(Oops::InvalidRange(*range, "format_range"));
+        }
+        let new = formatter.format(&self.text(), range)?;
+        self.replace_changed(range, &new, "format")
+    }
+
+    /// Replaces the text in `range` with `new` as a single undoable packet
+    /// labeled `label`, touching only the lines and characters which
+    /// changed. Returns how many separate edits were made.
+    pub(crate) fn replace_changed(&mut self, range: &Range, new: &str, label: &str) -> Result<usize, Oops> {
+        let old = self.text_range(range).ok_or(Oops::InvalidRange(*range, "replace_changed"))?;
+
+        let old_lines: Vec<&str> = old.split('\n').collect();
+        let new_lines: Vec<&str> = new.split('\n').collect();
+        let hunks = diff_lines(&old_lines, &new_lines);
+        if hunks.is_empty() {
+            return Ok(0);
+        }
+
+        // Each hunk covers its lines and their line breaks. A hunk at the end
+        // takes the line break before it instead, since the last line has none.
+        let offsets = |lines: &[&str]| -> Vec<usize> {
+            let mut result = vec![0];
+            for line in lines.iter() {
+                result.push(result[result.len() - 1] + line.chars().count() + 1);
+            }
+            result
+        };
+        let span = |offsets: &[usize], (a, b): (usize, usize)| -> (usize, usize) {
+            let count = offsets.len() - 1;
+            if b < count {
+                (offsets[a], offsets[b])
+            } else {
+                (offsets[a].saturating_sub(1), offsets[count] - 1)
+            }
+        };
+        let old_offsets = offsets(&old_lines);
+        let new_offsets = offsets(&new_lines);
+        let old_chars: Vec<char> = old.chars().collect();
+        let new_chars: Vec<char> = new.chars().collect();
+        let text = |chars: &[char]| chars.iter().collect::<String>();
+
+        let mut changes = ChangeSet::empty(self.revision());
+        for hunk in hunks.iter().rev() {
+            let (old_start, old_end) = span(&old_offsets, hunk.old);
+            let (new_start, new_end) = span(&new_offsets, hunk.new);
+            let before = &old_chars[old_start..old_end];
+            let after = &new_chars[new_start..new_end];
+
+            let prefix = before.iter().zip(after.iter()).take_while(|(a, b)| a == b).count();
+            let suffix = before[prefix..].iter().rev()
+                .zip(after[prefix..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            let beginning = advance(&range.beginning, &text(&old_chars[..old_start + prefix]));
+            let removed = text(&before[prefix..before.len() - suffix]);
+            let ending = advance(&beginning, &removed);
+            changes.edits.push(TextEdit {
+                range: Range { beginning, ending },
+                removed,
+                inserted: text(&after[prefix..after.len() - suffix])
+            });
+        }
+
+        self.apply_change_set(&changes, label)?;
+        Ok(hunks.len())
+    }
+}
+
+/// Formats text by piping it through an external command, which must read
+/// the text on standard input and write the result to standard output.
+///
+/// Only the text of the range is sent, so the range should be something the
+/// formatter can handle on its own, such as a whole item or the whole
+/// document.
+#[cfg(feature = "command_formatter")]
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct CommandFormatter {
+    pub program: String,
+    pub args: Vec<String>
+}
+
+#[cfg(feature = "command_formatter")]
+impl CommandFormatter {
+    pub fn new(program: &str, args: &[&str]) -> CommandFormatter {
+        CommandFormatter {
+            program: String::from(program),
+            args: args.iter().map(|a| String::from(*a)).collect()
+        }
+    }
+
+    /// Returns a formatter which runs `rustfmt`.
+    pub fn rustfmt() -> CommandFormatter {
+        CommandFormatter::new("rustfmt", &["--emit", "stdout", "--quiet"])
+    }
+
+    /// Returns a formatter which runs `prettier`, choosing a parser from
+    /// `file_name`'s extension.
+    pub fn prettier(file_name: &str) -> CommandFormatter {
+        CommandFormatter::new("prettier", &["--stdin-filepath", file_name])
+    }
+}
+
+#[cfg(feature = "command_formatter")]
+impl RangeFormatter for CommandFormatter {
+    fn format(&self, text: &str, range: &Range) -> Result<String, Oops> {
+        use std::io::Write;
+        use std::process::{Command, Stdio};
+
+        let input = Document::from(text).text_range(range)
+            .ok_or(Oops::InvalidRange(*range, "CommandFormatter::format"))?;
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|_| Oops::Ouch("cannot run formatter"))?;
+
+        child.stdin.take().unwrap().write_all(input.as_bytes())
+            .map_err(|_| Oops::Ouch("cannot write to formatter"))?;
+        let output = child.wait_with_output().map_err(|_| Oops::Ouch("formatter did not finish"))?;
+        if !output.status.success() {
+            return Err(Oops::Ouch("formatter failed"));
+        }
+
+        let mut result = String::from_utf8(output.stdout).map_err(|_| Oops::Ouch("formatter output is not UTF-8"))?;
+        if !input.ends_with('\n') && result.ends_with('\n') {
+            result.pop();
+        }
+        Ok(result)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hunks_are_minimal() {
+        let hunks = diff_lines(&["a", "b", "c", "d"], &["a", "x", "c", "d", "e"]);
+        assert_eq!(hunks, vec![Hunk { old: (1, 2), new: (1, 2) }, Hunk { old: (4, 4), new: (4, 5) }]);
+
+        let mut document = Document::from("keep\n  drop\nfix(x)\nkeep");
+        let anchor = document.create_anchor(&crate::document::Anchor::from(3, 2)).unwrap();
+        let formatter = |_: &str, _: &Range| -> Result<String, Oops> { Ok(String::from("keep\nfix( x )\nnew\nkeep")) };
+        assert_eq!(document.format_range(&formatter, &Range::from(0, 0, 3, 4)), Ok(1));
+        assert_eq!(document.text(), "keep\nfix( x )\nnew\nkeep");
+        assert_eq!(document.anchor(anchor).unwrap().position, Position::from(3, 2));
+
+        document.undo(1);
+        assert_eq!(document.text(), "keep\n  drop\nfix(x)\nkeep");
+
+        let append = |text: &str, _: &Range| -> Result<String, Oops> { Ok(format!("{}\nmore", text)) };
+        assert_eq!(document.format_range(&append, &Range::from(0, 0, 3, 4)), Ok(1));
+        assert_eq!(document.text(), "keep\n  drop\nfix(x)\nkeep\nmore");
+    }
+}
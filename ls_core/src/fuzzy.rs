@@ -0,0 +1,309 @@
+//! Fuzzy matching of spoken phrases against identifiers.
+//!
+//! Speech recognition rarely produces identifiers verbatim: "set anchor
+//! untracked" needs to find `set_anchor_untracked`, and so does
+//! "sat anchor un tracked". Matching combines three signals:
+//!
+//! - the phrase and identifier spell the same letters once word breaks are
+//!   ignored,
+//! - the words line up in order, exactly, phonetically, or by prefix,
+//! - the phrase's letters form a subsequence of the identifier's letters.
+
+use crate::document::Range;
+use crate::identifiers::IdentifierIndex;
+
+/// An identifier which matched a spoken phrase, with the places it occurs.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct FuzzyCandidate {
+    pub name: String,
+    pub score: u32,
+    pub ranges: Vec<Range>
+}
+
+/// The score given when phrase and identifier spell exactly the same letters.
+pub const EXACT_SCORE: u32 = 1000;
+
+/// Splits `s` into lowercase words on non-alphanumeric characters and
+/// lower-to-upper case transitions.
+fn words(s: &str) -> Vec<String> {
+    let mut result: Vec<String> = vec![];
+    let mut current = String::new();
+    let mut previous_lower = false;
+
+    for c in s.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                result.push(current);
+                current = String::new();
+            }
+            previous_lower = false;
+            continue;
+        }
+
+        if c.is_uppercase() && previous_lower && !current.is_empty() {
+            result.push(current);
+            current = String::new();
+        }
+
+        previous_lower = c.is_lowercase() || c.is_numeric();
+        current.extend(c.to_lowercase());
+    }
+
+    if !current.is_empty() {
+        result.push(current);
+    }
+
+    result
+}
+
+/// Returns a Soundex-like phonetic key for `word`: its first letter followed by
+/// consonant class digits, with vowels dropped and repeated classes collapsed.
+/// Unlike Soundex, the key is not truncated, and all leading vowels are
+/// equivalent.
+///
+/// # Examples
+/// ```
+/// use ls_core::fuzzy::*;
+/// assert_eq!(phonetic_key("set"), phonetic_key("sat"));
+/// assert_eq!(phonetic_key("anchor"), phonetic_key("anker"));
+/// assert_eq!(phonetic_key("untracked"), phonetic_key("ontracked"));
+/// assert_ne!(phonetic_key("set"), phonetic_key("get"));
+/// ```
+pub fn phonetic_key(word: &str) -> String {
+    fn class(c: char) -> Option<char> {
+        match c {
+            'b' | 'f' | 'p' | 'v' => Some('1'),
+            'c' | 'g' | 'j' | 'k' | 'q' | 's' | 'x' | 'z' => Some('2'),
+            'd' | 't' => Some('3'),
+            'l' => Some('4'),
+            'm' | 'n' => Some('5'),
+            'r' => Some('6'),
+            _ => None
+        }
+    }
+
+    let lower = word.to_lowercase();
+    let mut chars = lower.chars();
+    let mut key = String::new();
+
+    let first = match chars.next() {
+        None => return key,
+        Some(c) => c
+    };
+
+    // Leading vowels are easily confused ("untracked", "on tracked"),
+    // so they all share one key.
+    key.push(if "aeiouy".contains(first) { '*' } else { first });
+
+    let mut last = class(first);
+    for c in chars {
+        let current = class(c);
+        if let Some(digit) = current {
+            if current != last {
+                key.push(digit);
+            }
+        }
+        if c != 'h' && c != 'w' {
+            last = current;
+        }
+    }
+
+    key
+}
+
+/// Returns the points for matching query word `q` against candidate word `c`:
+/// exactly, phonetically, or by prefix.
+fn word_score(q: &str, c: &str) -> u32 {
+    if q == c {
+        100
+    } else if phonetic_key(q) == phonetic_key(c) {
+        70
+    } else if q.len() >= 2 && c.len() >= 2 && (c.starts_with(q) || q.starts_with(c)) {
+        50
+    } else {
+        0
+    }
+}
+
+/// Scores how well the words of a phrase line up, in order, with the words
+/// of an identifier. A query word which matches nothing may be joined with
+/// the next one, since recognizers often split words ("un tracked").
+///
+/// Returns `(points, unmatched query words, matched candidate words)`.
+fn alignment_score(query: &[String], candidate: &[String]) -> (u32, usize, usize) {
+    let mut points = 0;
+    let mut unmatched = 0;
+    let mut matched = 0;
+    let mut next = 0;
+    let mut i = 0;
+
+    let find = |q: &str, from: usize| -> Option<(usize, u32)> {
+        candidate.iter()
+            .enumerate()
+            .skip(from)
+            .map(|(j, c)| (j, word_score(q, c)))
+            .find(|(_, p)| *p > 0)
+    };
+
+    while i < query.len() {
+        let single = find(&query[i], next);
+        let joined = if single.is_none() && i + 1 < query.len() {
+            find(&(query[i].clone() + &query[i + 1]), next)
+        } else {
+            None
+        };
+
+        match (single, joined) {
+            (Some((j, p)), _) => {
+                points += p;
+                matched += 1;
+                next = j + 1;
+                i += 1;
+            },
+            (None, Some((j, p))) => {
+                points += p;
+                matched += 1;
+                next = j + 1;
+                i += 2;
+            },
+            (None, None) => {
+                unmatched += 1;
+                i += 1;
+            }
+        }
+    }
+
+    (points, unmatched, matched)
+}
+
+/// Scores `query`'s letters as a subsequence of `candidate`'s, rewarding
+/// consecutive runs and matches at the starts of words. Returns `None` if
+/// they are not a subsequence.
+fn subsequence_score(query: &[String], candidate: &[String]) -> Option<u32> {
+    let q: Vec<char> = query.concat().chars().collect();
+    let mut starts: Vec<bool> = vec![];
+    let mut c: Vec<char> = vec![];
+    for word in candidate {
+        for (i, ch) in word.chars().enumerate() {
+            c.push(ch);
+            starts.push(i == 0);
+        }
+    }
+
+    let mut points = 0;
+    let mut qi = 0;
+    let mut previous: Option<usize> = None;
+
+    for (ci, ch) in c.iter().enumerate() {
+        if qi < q.len() && *ch == q[qi] {
+            points += 10;
+            if starts[ci] {
+                points += 15;
+            }
+            if previous.is_some() && previous.unwrap() + 1 == ci {
+                points += 5;
+            }
+            previous = Some(ci);
+            qi += 1;
+        }
+    }
+
+    if qi == q.len() { Some(points) } else { None }
+}
+
+/// Returns how well spoken `query` matches `identifier`, or `None` if it does
+/// not match at all. Higher is better; [`EXACT_SCORE`] is a perfect match.
+///
+/// # Examples
+/// ```
+/// use ls_core::fuzzy::*;
+/// assert_eq!(score("set anchor untracked", "set_anchor_untracked"), Some(EXACT_SCORE));
+/// assert_eq!(score("set anchor un tracked", "setAnchorUntracked"), Some(EXACT_SCORE));
+/// assert!(score("sat anchor untracked", "set_anchor_untracked").is_some());
+/// assert!(
+///     score("sat anchor untracked", "set_anchor_untracked") >
+///     score("sat anchor untracked", "get_anchor")
+/// );
+/// assert_eq!(score("banana", "set_anchor"), None);
+/// ```
+pub fn score(query: &str, identifier: &str) -> Option<u32> {
+    let q = words(query);
+    let c = words(identifier);
+
+    if q.is_empty() || c.is_empty() {
+        return None;
+    }
+
+    if q.concat() == c.concat() {
+        return Some(EXACT_SCORE);
+    }
+
+    let (aligned, unmatched, matched) = alignment_score(&q, &c);
+    let aligned = if aligned > 0 {
+        let penalty = 40 * unmatched as u32 + 10 * (c.len() - matched) as u32;
+        Some(aligned.saturating_sub(penalty))
+    } else {
+        None
+    };
+
+    let subsequence = subsequence_score(&q, &c).map(|s| s / 2);
+
+    match (aligned, subsequence) {
+        (None, None) => None,
+        (a, s) => Some(std::cmp::min(
+            EXACT_SCORE - 1,
+            std::cmp::max(a.unwrap_or(0), s.unwrap_or(0))
+        )).filter(|s| *s > 0)
+    }
+}
+
+impl IdentifierIndex {
+    /// Returns up to `limit` identifiers matching spoken `query`, best first.
+    /// Ties are broken alphabetically.
+    pub fn fuzzy_find(&self, query: &str, limit: usize) -> Vec<FuzzyCandidate> {
+        let mut result: Vec<FuzzyCandidate> = self.iter()
+            .filter_map(|(name, ranges)| score(query, name).map(|s| FuzzyCandidate {
+                name: name.clone(),
+                score: s,
+                ranges: ranges.clone()
+            }))
+            .collect();
+
+        result.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        result.truncate(limit);
+        result
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::*;
+
+    #[test]
+    fn spoken_lookup() {
+        let document = Document::from_with_language(r#"
+fn set_anchor_untracked() {}
+fn set_anchor() {}
+fn get_anchor() {}
+fn main() { set_anchor_untracked(); }
+"#, "rs");
+
+        let index = document.identifier_index();
+
+        let found = index.fuzzy_find("set anchor untracked", 3);
+        assert_eq!(found[0].name, "set_anchor_untracked");
+        assert_eq!(found[0].score, EXACT_SCORE);
+        assert_eq!(found[0].ranges, vec![Range::from(1, 3, 1, 23), Range::from(4, 12, 4, 32)]);
+
+        let noisy = index.fuzzy_find("sat anker on tracked", 3);
+        assert_eq!(noisy[0].name, "set_anchor_untracked");
+
+        let short = index.fuzzy_find("get anchor", 3);
+        assert_eq!(short[0].name, "get_anchor");
+
+        assert!(index.fuzzy_find("zebra", 3).is_empty());
+    }
+}
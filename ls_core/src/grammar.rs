@@ -0,0 +1,272 @@
+//! A grammar of the spoken commands which make sense in a document right
+//! now, for a speech engine to load and constrain recognition with.
+//!
+//! Rules are phrases whose words may name slots in angle brackets, such as
+//! `mark <letter>`, and each slot lists the words or phrases which may fill
+//! it. Rules which cannot apply are left out: "fold functions" needs a
+//! function in the parse tree, "choose <choice>" needs a pending
+//! [ambiguity](crate::ambiguity), and `<symbol>` offers only the
+//! definitions whose scope holds the cursor, said as words. Without a parse
+//! tree, rules needing particular node kinds are all offered. Commands
+//! which take free text, such as bookmark labels, are left to dictation.
+//!
+//! What the grammar takes from the text (node kinds, vocabulary, and
+//! definitions) is kept between calls and gathered again only after an
+//! edit, so asking for the grammar after every command is cheap.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::definitions::Definition;
+use crate::document::Document;
+use crate::folds::is_function_kind;
+use crate::segmentation::{self, Segmentation};
+use crate::snippets::SNIPPET_NAMES;
+use crate::spoken::{PHONETIC_ALPHABET, ONES, TENS};
+
+/// Rules offered in every document.
+const COMMON_RULES: &[&str] = &[
+    "mark <letter>", "list marks", "where am i", "list bookmarks", "unfold all", "fold level <number>",
+    "<operator> <object>", "<operator> <number> <object>",
+    "first line", "last line", "top", "bottom", "middle of the file", "middle of the line",
+    "line <number>", "up <number> lines", "down <number> lines", "column <number>",
+    "<number> percent", "<number> percent of the line",
+    "next tab stop", "previous tab stop", "next hump", "previous hump",
+    "insert <snippet>", "extract variable", "extract function", "inline variable", "scratch that"
+];
+
+/// Words which start an [operation](crate::operators).
+const OPERATORS: &[&str] = &["delete", "change", "select", "copy", "indent", "dedent", "comment", "uncomment"];
+
+/// Text objects an operation can act on, singular and plural.
+const OBJECTS: &[&str] = &[
+    "word", "words", "subword", "subwords", "line", "lines", "paragraph", "paragraphs",
+    "that", "node", "brackets", "selection"
+];
+
+/// What the grammar takes from a document's text, kept until it changes.
+pub(crate) struct GrammarFacts {
+    /// Was there a parse tree to take node kinds from?
+    parsed: bool,
+    kinds: BTreeSet<String>,
+    vocabulary: Vec<String>,
+    definitions: Vec<Definition>
+}
+
+/// The commands valid in a document at one revision.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct CommandGrammar {
+    /// The document revision the grammar was made at.
+    pub revision: u64,
+
+    /// Phrases which are commands, naming slots in angle brackets.
+    pub rules: Vec<String>,
+
+    /// What may fill each slot the rules name.
+    pub slots: BTreeMap<String, Vec<String>>,
+
+    /// The words the document's identifiers are made of, for dictation.
+    pub vocabulary: Vec<String>
+}
+
+/// Returns `n` as a spoken word, if it has one of its own.
+fn number_word(n: usize) -> String {
+    ONES.get(n).map_or_else(|| n.to_string(), |word| String::from(*word))
+}
+
+impl CommandGrammar {
+    /// Returns the grammar in the Java Speech Grammar Format, with one public
+    /// rule, `<command>`, and a rule for each slot.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("");
+    /// let jsgf = document.command_grammar().to_jsgf();
+    /// assert!(jsgf.starts_with("#JSGF V1.0;\ngrammar commands;\npublic <command> = mark <letter> | "));
+    /// assert!(jsgf.contains("\n<letter> = alpha | bravo | "));
+    /// ```
+    pub fn to_jsgf(&self) -> String {
+        let mut lines = vec![
+            String::from("#JSGF V1.0;"),
+            String::from("grammar commands;"),
+            format!("public <command> = {};", self.rules.join(" | "))
+        ];
+        for (name, fillers) in &self.slots {
+            lines.push(format!("<{}> = {};", name, fillers.join(" | ")));
+        }
+        lines.join("\n")
+    }
+
+    /// Returns every word the grammar and vocabulary use, sorted, without
+    /// duplicates, for engines which take only a word list.
+    pub fn words(&self) -> Vec<String> {
+        let rules = self.rules.iter().flat_map(|rule| rule.split_whitespace()).filter(|w| !w.starts_with('<'));
+        let fillers = self.slots.values().flatten().flat_map(|filler| filler.split_whitespace());
+        let words: BTreeSet<String> = rules.chain(fillers)
+            .map(String::from)
+            .chain(self.vocabulary.iter().cloned())
+            .collect();
+        words.into_iter().collect()
+    }
+}
+
+impl Document {
+    /// Gathers what the grammar takes from the text, unless it is still
+    /// current.
+    fn grammar_facts(&mut self) -> &GrammarFacts {
+        if self.grammar_facts.is_none() {
+            #[cfg(feature = "parsing")]
+            let (parsed, kinds) = match self.parse_tree() {
+                Some(tree) => {
+                    let mut kinds = BTreeSet::new();
+                    let mut cursor = tree.walk();
+                    'walk: loop {
+                        if cursor.node().is_named() {
+                            kinds.insert(String::from(cursor.node().kind()));
+                        }
+                        if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                            continue;
+                        }
+                        while cursor.goto_parent() {
+                            if cursor.goto_next_sibling() {
+                                continue 'walk;
+                            }
+                        }
+                        break;
+                    }
+                    (true, kinds)
+                },
+                None => (false, BTreeSet::new())
+            };
+
+            #[cfg(not(feature = "parsing"))]
+            let (parsed, kinds) = (false, BTreeSet::new());
+
+            self.grammar_facts = Some(GrammarFacts {
+                parsed,
+                kinds,
+                vocabulary: self.identifier_index().vocabulary(&Segmentation::default()),
+                definitions: self.definitions()
+            });
+        }
+        self.grammar_facts.as_ref().unwrap()
+    }
+
+    /// Returns the commands valid in this document at the cursor (see the
+    /// [module documentation](self)).
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "parsing")] {
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language("fn area(width: u32) -> u32 {\n    width * 2\n}", "rs");
+    /// document.set_cursor_and_mark(&Position::from(1, 4)).unwrap();
+    /// let grammar = document.command_grammar();
+    /// assert!(grammar.rules.contains(&String::from("fold functions")));
+    /// assert!(grammar.rules.contains(&String::from("increment")));
+    /// assert!(!grammar.rules.contains(&String::from("fold comments")));
+    /// assert_eq!(grammar.slots["symbol"], vec!["area", "width"]);
+    /// # }
+    /// ```
+    pub fn command_grammar(&mut self) -> CommandGrammar {
+        let cursor = self.cursor().position;
+        let places: Vec<String> = self.named_anchors().into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| PHONETIC_ALPHABET.contains(&name.as_str()))
+            .collect();
+        let bookmarked = !self.bookmarks().is_empty();
+        let changed = !self.changes().is_empty();
+        let dictated = !self.dictation_spots().is_empty();
+        let choices = self.pending_ambiguity().map_or(0, |ambiguity| ambiguity.candidates.len());
+        let revision = self.revision();
+
+        let facts = self.grammar_facts();
+        let has = |wanted: fn(&str) -> bool| !facts.parsed || facts.kinds.iter().any(|k| wanted(k));
+
+        let mut rules: Vec<&str> = COMMON_RULES.to_vec();
+        let mut add = |condition: bool, extra: &[&'static str]| if condition {
+            rules.extend_from_slice(extra);
+        };
+        add(!places.is_empty(), &["go to <place>", "clear <place>", "clear marks"]);
+        add(bookmarked, &["next bookmark", "previous bookmark"]);
+        add(changed, &["last change", "previous change", "next change"]);
+        add(dictated, &["resume dictation"]);
+        add(choices > 0, &["choose <choice>"]);
+        add(facts.parsed, &["duplicate"]);
+        add(has(is_function_kind), &["fold functions", "duplicate function", "doc comment"]);
+        add(has(|k| k.contains("comment")), &["fold comments"]);
+        add(has(|k| k.ends_with("statement") || k.ends_with("declaration")), &["duplicate statement"]);
+        add(has(|k| k.ends_with("_item") || k.ends_with("definition") || k.ends_with("declaration")), &["duplicate item"]);
+        add(has(|k| k.contains("integer") || k.contains("float") || k.contains("number")), &[
+            "increment", "decrement", "increment by <number>", "decrement by <number>"
+        ]);
+
+        let symbols: BTreeSet<String> = facts.definitions.iter()
+            .filter(|d| d.scope.beginning <= cursor && cursor <= d.scope.ending)
+            .map(|d| segmentation::split_identifier(&d.name).join(" ").to_lowercase())
+            .filter(|spoken| !spoken.is_empty())
+            .collect();
+        add(!symbols.is_empty(), &["select <symbol>"]);
+
+        let owned = |words: &[&str]| words.iter().map(|w| String::from(*w)).collect::<Vec<String>>();
+        let numbers: Vec<&str> = ONES[1..].iter().chain(TENS.iter()).chain(["hundred", "thousand"].iter()).copied().collect();
+
+        let mut slots = BTreeMap::new();
+        slots.insert(String::from("letter"), owned(&PHONETIC_ALPHABET));
+        slots.insert(String::from("number"), owned(&numbers));
+        slots.insert(String::from("operator"), owned(OPERATORS));
+        slots.insert(String::from("object"), owned(OBJECTS));
+        slots.insert(String::from("snippet"), owned(SNIPPET_NAMES));
+        if !places.is_empty() {
+            slots.insert(String::from("place"), places);
+        }
+        if choices > 0 {
+            slots.insert(String::from("choice"), (1..=choices).map(number_word).collect());
+        }
+        if !symbols.is_empty() {
+            slots.insert(String::from("symbol"), symbols.into_iter().collect());
+        }
+
+        CommandGrammar {
+            revision,
+            rules: rules.into_iter().map(String::from).collect(),
+            slots,
+            vocabulary: facts.vocabulary.clone()
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::Command;
+    use crate::document::{Position, InsertOptions};
+
+    #[test]
+    fn grammar_follows_context_and_edits() {
+        let mut document = Document::from("let total = 1;\nlet tally = total;");
+        let grammar = document.command_grammar();
+        assert!(!grammar.rules.contains(&String::from("go to <place>")));
+        assert!(!grammar.rules.contains(&String::from("choose <choice>")));
+        assert!(grammar.words().contains(&String::from("total")));
+
+        document.execute_phrase("mark charlie").unwrap();
+        document.select_text("total").unwrap();
+        let grammar = document.command_grammar();
+        assert_eq!(grammar.slots["place"], vec!["charlie"]);
+        assert_eq!(grammar.slots["choice"], vec!["one", "two"]);
+        assert!(grammar.to_jsgf().contains("\n<choice> = one | two;"));
+
+        document.insert("let widthInPixels = 2;\n", &InsertOptions::exact_at(&crate::document::Range::from(0, 0, 0, 0))).unwrap();
+        document.set_cursor_and_mark(&Position::from(0, 0)).unwrap();
+        let grammar = document.command_grammar();
+        assert!(grammar.vocabulary.contains(&String::from("pixels")));
+        assert_eq!(grammar.revision, document.revision());
+
+        for snippet in SNIPPET_NAMES {
+            assert!(Command::parse(&format!("insert {}", snippet)).is_some(), "{}", snippet);
+        }
+    }
+}
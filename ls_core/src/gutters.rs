@@ -0,0 +1,270 @@
+//! Gutters: the line numbers and signs drawn beside the text, computed once
+//! in the core so every frontend and the [terminal renderer](crate::terminal)
+//! shows the same ones.
+//!
+//! [`Document::gutter`] walks the screen rows from a first document row as
+//! [`Document::viewport`] does, skipping rows hidden by folds. With a wrap
+//! width, a line too wide for it takes several screen rows, and only the
+//! first is numbered. Relative numbers count lines as they are shown: a
+//! folded block is one line, and a wrapped line is one line however many
+//! screen rows it takes.
+//!
+//! Each numbered row carries signs, in order: the most severe
+//! [diagnostic](crate::diagnostics) starting on it, its
+//! [bookmarks](crate::bookmarks) by label, and its
+//! [annotations](crate::annotations) (like version control status) by
+//! channel. Signs for rows hidden in a fold are shown on the fold's header.
+
+use std::collections::BTreeMap;
+
+use crate::diagnostics::Severity;
+use crate::document::Document;
+use crate::folds::Fold;
+
+/// How lines are numbered.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default)]
+pub enum LineNumbering {
+    /// Each line shows its row, counting from 1.
+    #[default]
+    Absolute,
+
+    /// Each line shows how many lines away from the cursor it is.
+    Relative,
+
+    /// As `Relative`, but the cursor's line shows its row, counting from 1.
+    Hybrid
+}
+
+/// How [`Document::gutter`] lays out the gutter.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default)]
+pub struct GutterOptions {
+    pub numbering: LineNumbering,
+
+    /// The number of columns lines wrap at, or `None` if they don't wrap.
+    /// Tabs are counted to the document's tab stops, and inline virtual text
+    /// by its characters.
+    pub wrap_width: Option<usize>
+}
+
+/// Where a [`Sign`] comes from.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum SignKind {
+    Diagnostic(Severity),
+
+    /// A bookmark, by label.
+    Bookmark(String),
+
+    /// A line annotation, by channel.
+    Annotation(String)
+}
+
+/// An entry in the sign column. `style` is an opaque class name handed to
+/// the renderer, as for [virtual text](crate::virtual_text::VirtualText).
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Sign {
+    pub kind: SignKind,
+    pub text: String,
+    pub style: String
+}
+
+/// The gutter beside one screen row.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct GutterRow {
+    /// The document row shown on this screen row.
+    pub row: usize,
+
+    /// The line number to show, or `None` if this screen row continues a
+    /// wrapped line.
+    pub number: Option<usize>,
+    pub signs: Vec<Sign>,
+
+    /// If this row is the header of a fold, the number of rows it hides.
+    pub folded: Option<usize>
+}
+
+/// Returns the sign text and style for a diagnostic of `severity`.
+fn severity_sign(severity: Severity) -> (&'static str, &'static str) {
+    match severity {
+        Severity::Error => ("E", "error"),
+        Severity::Warning => ("W", "warning"),
+        Severity::Information => ("I", "information"),
+        Severity::Hint => ("H", "hint")
+    }
+}
+
+/// The rows hidden by a document's folds.
+struct Hidden {
+    folds: Vec<Fold>
+}
+
+impl Hidden {
+    /// Is `row` hidden?
+    fn contains(&self, row: usize) -> bool {
+        self.folds.iter().any(|f| f.first_row < row && row <= f.last_row)
+    }
+
+    /// Returns the row shown in place of `row`: the header of the outermost
+    /// fold hiding it, or `row` itself if it isn't hidden.
+    fn shown_as(&self, mut row: usize) -> usize {
+        while let Some(header) = self.folds.iter()
+            .filter(|f| f.first_row < row && row <= f.last_row)
+            .map(|f| f.first_row)
+            .min()
+        {
+            row = header;
+        }
+        row
+    }
+
+    /// Returns how many rows before `row` are shown.
+    fn shown_before(&self, row: usize) -> usize {
+        let mut hidden = 0;
+        let mut covered = 0;
+        for fold in self.folds.iter() {
+            let first = (fold.first_row + 1).max(covered);
+            let last = (fold.last_row + 1).min(row);
+            if first < last {
+                hidden += last - first;
+            }
+            covered = covered.max(fold.last_row + 1);
+        }
+        row - hidden
+    }
+}
+
+impl Document {
+    /// Returns the gutter for up to `rows` screen rows starting at document
+    /// row `first_row` (see the [module documentation](self)). Without a
+    /// wrap width, these are the rows [`Document::viewport`] shows.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::gutters::*;
+    /// let mut document = Document::from("one\ntwo\nthree\nfour\nfive and more");
+    /// document.fold_rows(1, 2).unwrap();
+    /// document.set_cursor_and_mark(&Position::from(4, 0)).unwrap();
+    ///
+    /// let options = GutterOptions { numbering: LineNumbering::Hybrid, wrap_width: Some(8) };
+    /// let numbers: Vec<(usize, Option<usize>)> = document.gutter(0, 10, &options).iter()
+    ///     .map(|row| (row.row, row.number))
+    ///     .collect();
+    /// assert_eq!(numbers, vec![(0, Some(3)), (1, Some(2)), (3, Some(1)), (4, Some(5)), (4, None)]);
+    /// ```
+    pub fn gutter(&self, first_row: usize, rows: usize, options: &GutterOptions) -> Vec<GutterRow> {
+        let hidden = Hidden { folds: self.folds().into_iter().map(|(_, fold)| fold).collect() };
+        let cursor_row = hidden.shown_as(self.cursor().position.row);
+        let cursor_line = hidden.shown_before(cursor_row);
+        let mut line = hidden.shown_before(first_row);
+
+        let mut signs: BTreeMap<usize, Vec<Sign>> = BTreeMap::new();
+        let mut severities: BTreeMap<usize, Severity> = BTreeMap::new();
+        for diagnostic in self.diagnostics() {
+            let row = hidden.shown_as(diagnostic.range.beginning.row);
+            let severity = severities.entry(row).or_insert(diagnostic.severity);
+            *severity = (*severity).min(diagnostic.severity);
+        }
+        for (row, severity) in severities {
+            let (text, style) = severity_sign(severity);
+            let kind = SignKind::Diagnostic(severity);
+            signs.entry(row).or_default().push(Sign { kind, text: String::from(text), style: String::from(style) });
+        }
+        for bookmark in self.bookmarks() {
+            let text = bookmark.label.chars().take(1).collect();
+            let kind = SignKind::Bookmark(bookmark.label);
+            let row = hidden.shown_as(bookmark.position.row);
+            signs.entry(row).or_default().push(Sign { kind, text, style: String::from("bookmark") });
+        }
+
+        let mut result = vec![];
+        let mut row = first_row;
+        while result.len() < rows && row < self.rows() {
+            if hidden.contains(row) {
+                row += 1;
+                continue;
+            }
+
+            let distance = line.abs_diff(cursor_line);
+            let number = match options.numbering {
+                LineNumbering::Absolute => row + 1,
+                LineNumbering::Hybrid if distance == 0 => row + 1,
+                LineNumbering::Relative | LineNumbering::Hybrid => distance
+            };
+
+            let mut row_signs = signs.remove(&row).unwrap_or_default();
+            row_signs.extend(self.annotations_on_row(row).into_iter().map(|(channel, annotation)| Sign {
+                kind: SignKind::Annotation(String::from(channel)),
+                text: annotation.text.clone(),
+                style: annotation.style.clone()
+            }));
+
+            let folded = self.fold_at_row(row).map(|(_, fold)| fold.hidden_rows());
+            result.push(GutterRow { row, number: Some(number), signs: row_signs, folded });
+
+            let continuations = options.wrap_width.map_or(0, |width| self.screen_rows_of(row, width) - 1);
+            for _ in 0..continuations {
+                if result.len() == rows {
+                    break;
+                }
+                result.push(GutterRow { row, number: None, signs: vec![], folded: None });
+            }
+
+            line += 1;
+            row += folded.map_or(1, |hidden| hidden + 1);
+        }
+        result
+    }
+
+    /// Returns how many screen rows `row` takes when wrapped at `width`
+    /// columns, counting at least one.
+    fn screen_rows_of(&self, row: usize, width: usize) -> usize {
+        let tab = self.indentation().spaces_per_tab;
+        let mut columns = self.lines()[row].content.chars()
+            .fold(0, |columns, c| if c == '\t' { (columns / tab + 1) * tab } else { columns + 1 });
+        columns += self.virtual_text_on_row(row).iter().map(|(_, _, vt)| vt.text.chars().count()).sum::<usize>();
+
+        if width == 0 {
+            1
+        } else {
+            columns.div_ceil(width).max(1)
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations::LineAnnotation;
+    use crate::diagnostics::Diagnostic;
+    use crate::document::{Position, Range};
+
+    #[test]
+    fn signs_roll_up_into_folds_and_numbers_follow_the_cursor() {
+        let mut document = Document::from("a\nb\nc\nd\ne\nf");
+        document.fold_rows(1, 3).unwrap();
+        document.fold_rows(2, 3).unwrap();
+        document.set_diagnostics("lint", vec![
+            Diagnostic::from(&Range::from(2, 0, 2, 1), Severity::Warning, "w"),
+            Diagnostic::from(&Range::from(3, 0, 3, 1), Severity::Error, "e"),
+            Diagnostic::from(&Range::from(5, 0, 5, 1), Severity::Hint, "h")
+        ]).unwrap();
+        document.add_bookmark("mine", &Position::from(3, 0), None).unwrap();
+        document.set_line_annotation("vcs", 1, Some(LineAnnotation::from("+", "added"))).unwrap();
+        document.set_cursor_and_mark(&Position::from(2, 0)).unwrap();
+
+        let gutter = document.gutter(0, 10, &GutterOptions { numbering: LineNumbering::Relative, ..Default::default() });
+        let rows: Vec<(usize, Option<usize>, Option<usize>)> = gutter.iter().map(|r| (r.row, r.number, r.folded)).collect();
+        assert_eq!(rows, vec![(0, Some(1), None), (1, Some(0), Some(2)), (4, Some(1), None), (5, Some(2), None)]);
+
+        let texts: Vec<&str> = gutter[1].signs.iter().map(|sign| sign.text.as_str()).collect();
+        assert_eq!(texts, vec!["E", "m", "+"]);
+        assert_eq!(gutter[1].signs[1].kind, SignKind::Bookmark(String::from("mine")));
+        assert_eq!(gutter[3].signs[0].style, "hint");
+        assert!(gutter[0].signs.is_empty());
+
+        let absolute = document.gutter(4, 1, &GutterOptions::default());
+        assert_eq!((absolute.len(), absolute[0].number), (1, Some(5)));
+    }
+}
@@ -0,0 +1,315 @@
+//! A randomized test harness: generates valid sequences of operations on a
+//! [`Document`] and checks the document's invariants after each one.
+//!
+//! Only available with the `harness` feature. Embedders can run the same
+//! oracle against documents set up the way their integration uses them,
+//! adding their own checks with [`run_with`].
+//!
+//! After every operation, the harness checks that
+//!
+//! * every line's cached length matches its content,
+//! * every anchor is at a valid position,
+//! * undoing the operation restores the text and tracked anchors exactly,
+//!   and redoing it restores the state after the operation.
+//!
+//! Failures report the seed and step so they can be reproduced.
+
+use std::collections::BTreeMap;
+
+use crate::document::{Document, Position, Range, Anchor, Anchors, AnchorHandle, InsertOptions, RemoveOptions};
+use crate::util::Oops;
+
+/// A small, deterministic pseudo-random number generator (xorshift64*), so
+/// runs can be reproduced from their seed without extra dependencies.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64
+}
+
+/// A single operation performed by the harness.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Operation {
+    Insert { text: String, range: Range },
+    Remove { range: Range },
+    CreateAnchor { position: Position },
+    SetAnchor { handle: AnchorHandle, position: Position },
+    RemoveAnchor { handle: AnchorHandle },
+    Undo(usize),
+    Redo(usize),
+}
+
+/// Describes an invariant violation.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Failure {
+    pub seed: u64,
+
+    /// The index of the operation after which the violation was found.
+    pub step: usize,
+
+    /// Every operation performed, up to and including the failing one.
+    pub operations: Vec<Operation>,
+    pub message: String
+}
+
+/// The state compared by the undo and redo checks: text, plus the position
+/// of every tracked anchor.
+#[derive(PartialEq, Eq, Debug)]
+struct Snapshot {
+    text: String,
+    anchors: BTreeMap<AnchorHandle, Position>
+}
+
+/// Characters inserted by generated operations. Includes line breaks, tabs,
+/// and multi-byte characters to exercise codepoint/byte conversions.
+const ALPHABET: &[char] = &['a', 'b', 'z', ' ', '\t', '\n', '\n', '(', ')', 'é', '火', '🦄'];
+
+impl Rng {
+    /// Returns a generator seeded with `seed`.
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: (seed ^ 0x9e37_79b9_7f4a_7c15) | 1 }
+    }
+
+    /// Returns the next pseudo-random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Returns a number in `0..n`. `n` must not be 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+impl Operation {
+    /// Returns a random operation which is valid for `document`.
+    pub fn generate(document: &Document, rng: &mut Rng) -> Operation {
+        let anchors: Vec<AnchorHandle> = {
+            let mut handles: Vec<AnchorHandle> = document.anchors()
+                .map(|(h, _)| *h)
+                .filter(|h| !document.anchor_is_floating(*h))
+                .collect();
+            handles.sort_unstable();
+            handles
+        };
+        let removable: Vec<AnchorHandle> = anchors.iter()
+            .cloned()
+            .filter(|h| *h != Anchors::CURSOR && *h != Anchors::MARK)
+            .collect();
+        let (undos, redos) = document.undo_redo().depth();
+
+        loop {
+            match rng.below(10) {
+                0..=3 => {
+                    let length = 1 + rng.below(6);
+                    let text = (0..length).map(|_| ALPHABET[rng.below(ALPHABET.len())]).collect();
+                    let range = if rng.below(3) == 0 {
+                        random_range(document, rng)
+                    } else {
+                        let p = random_position(document, rng);
+                        Range { beginning: p, ending: p }
+                    };
+                    return Operation::Insert { text, range };
+                },
+                4 | 5 => {
+                    let range = random_range(document, rng);
+                    if !range.empty() {
+                        return Operation::Remove { range };
+                    }
+                },
+                6 => return Operation::CreateAnchor { position: random_position(document, rng) },
+                7 => {
+                    let handle = anchors[rng.below(anchors.len())];
+                    return Operation::SetAnchor { handle, position: random_position(document, rng) };
+                },
+                8 if !removable.is_empty() => {
+                    return Operation::RemoveAnchor { handle: removable[rng.below(removable.len())] };
+                },
+                9 if undos > 0 && rng.below(2) == 0 => return Operation::Undo(1 + rng.below(undos)),
+                9 if redos > 0 => return Operation::Redo(1 + rng.below(redos)),
+                _ => ()
+            }
+        }
+    }
+
+    /// Performs this operation on `document` as a new undoable packet
+    /// (except for undo and redo themselves).
+    pub fn apply(&self, document: &mut Document) -> Result<(), Oops> {
+        if !self.is_history() {
+            document.checkpoint();
+        }
+
+        match self {
+            Operation::Insert { text, range } => document.insert(text, &InsertOptions::exact_at(range)),
+            Operation::Remove { range } => document.remove(&RemoveOptions::exact_at(range)),
+            Operation::CreateAnchor { position } => document.create_anchor(&Anchor { position: *position }).map(|_| ()),
+            Operation::SetAnchor { handle, position } => document.set_anchor(*handle, &Anchor { position: *position }),
+            Operation::RemoveAnchor { handle } => document.remove_anchor(*handle),
+            Operation::Undo(n) => match document.undo(*n) {
+                outcome if outcome.complete() => Ok(()),
+                outcome => Err(Oops::NoMoreUndos(outcome.count))
+            },
+            Operation::Redo(n) => match document.redo(*n) {
+                outcome if outcome.complete() => Ok(()),
+                outcome => Err(Oops::NoMoreRedos(outcome.count))
+            }
+        }
+    }
+
+    /// Returns true for [`Operation::Undo`] and [`Operation::Redo`].
+    pub fn is_history(&self) -> bool {
+        matches!(self, Operation::Undo(_) | Operation::Redo(_))
+    }
+}
+
+fn random_position(document: &Document, rng: &mut Rng) -> Position {
+    let row = rng.below(document.rows());
+    let column = rng.below(document.lines()[row].length + 1);
+    Position::from(row, column)
+}
+
+fn random_range(document: &Document, rng: &mut Rng) -> Range {
+    let a = random_position(document, rng);
+    let b = random_position(document, rng);
+    if a <= b {
+        Range { beginning: a, ending: b }
+    } else {
+        Range { beginning: b, ending: a }
+    }
+}
+
+fn snapshot(document: &Document) -> Snapshot {
+    Snapshot {
+        text: document.text(),
+        anchors: document.anchors()
+            .filter(|(h, _)| !document.anchor_is_floating(**h))
+            .map(|(h, a)| (*h, a.position))
+            .collect()
+    }
+}
+
+/// Checks the structural invariants of `document`: cached line lengths and
+/// anchor positions. Returns a description of the first violation found.
+pub fn check_invariants(document: &Document) -> Result<(), String> {
+    if document.rows() == 0 {
+        return Err(String::from("document has no lines"));
+    }
+
+    for (row, line) in document.lines().iter().enumerate() {
+        let length = line.content.chars().count();
+        if line.length != length {
+            return Err(format!("line {} has cached length {} but {} characters", row, line.length, length));
+        }
+        if line.content.contains('\n') {
+            return Err(format!("line {} contains a line break", row));
+        }
+    }
+
+    for (handle, anchor) in document.anchors() {
+        if !document.position_valid(&anchor.position) {
+            return Err(format!("anchor {} is out of bounds at {:?}", handle, anchor.position));
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `steps` random operations from `seed` on a fresh document.
+///
+/// # Examples
+/// ```
+/// use ls_core::harness::*;
+/// for seed in 0..10 {
+///     run(seed, 100).unwrap();
+/// }
+/// ```
+pub fn run(seed: u64, steps: usize) -> Result<(), Failure> {
+    let mut document = Document::from("");
+    run_with(&mut document, seed, steps, &|_| Ok(()))
+}
+
+/// Runs `steps` random operations from `seed` on `document`, applying
+/// `check` along with the built-in invariants after every operation.
+pub fn run_with(
+    document: &mut Document,
+    seed: u64,
+    steps: usize,
+    check: &dyn Fn(&Document) -> Result<(), String>
+) -> Result<(), Failure> {
+    let mut rng = Rng::new(seed);
+    let mut operations = vec![];
+
+    let fail = |operations: &Vec<Operation>, message: String| Failure {
+        seed,
+        step: operations.len() - 1,
+        operations: operations.clone(),
+        message
+    };
+
+    for _ in 0..steps {
+        let operation = Operation::generate(document, &mut rng);
+        operations.push(operation.clone());
+
+        let before = snapshot(document);
+        let revision = document.revision();
+
+        if let Err(oops) = operation.apply(document) {
+            return Err(fail(&operations, format!("operation failed: {:?}", oops)));
+        }
+
+        if !operation.is_history() && document.revision() != revision {
+            let after = snapshot(document);
+
+            if let Err(oops) = document.undo_once() {
+                return Err(fail(&operations, format!("undo failed: {:?}", oops)));
+            }
+            if snapshot(document) != before {
+                return Err(fail(&operations, String::from("undo did not restore the previous state")));
+            }
+
+            if let Err(oops) = document.redo_once() {
+                return Err(fail(&operations, format!("redo failed: {:?}", oops)));
+            }
+            if snapshot(document) != after {
+                return Err(fail(&operations, String::from("redo did not restore the following state")));
+            }
+        }
+
+        if let Err(message) = check_invariants(document).and_then(|_| check(document)) {
+            return Err(fail(&operations, message));
+        }
+    }
+
+    Ok(())
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn many_seeds() {
+        for seed in 0..50 {
+            if let Err(failure) = run(seed, 200) {
+                panic!("{:#?}", failure);
+            }
+        }
+    }
+
+    #[test]
+    fn custom_checks_and_failures() {
+        let mut document = Document::from("abc\ndef");
+        let result = run_with(&mut document, 7, 50, &|d| {
+            if d.text().contains('z') { Err(String::from("no z allowed")) } else { Ok(()) }
+        });
+
+        let failure = result.unwrap_err();
+        assert_eq!(failure.message, "no z allowed");
+        assert_eq!(failure.step + 1, failure.operations.len());
+        assert_eq!(failure.seed, 7);
+    }
+}
@@ -0,0 +1,83 @@
+//! Content hashing, for noticing when a file changed on disk, checking that
+//! a persistent undo log still matches the text it was recorded against,
+//! and comparing snapshots without comparing their text.
+//!
+//! Hashes are 64-bit [XXH3](https://xxhash.com) hashes of the text with
+//! `\n` line breaks, as [`Document::text`] returns it. The algorithm is
+//! stable, so hashes may be stored and compared across runs.
+
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::document::Document;
+use crate::util;
+
+/// Returns the hash of `text`, whose line breaks may be `\n` or `\r\n`. The
+/// same as the [`Document::content_hash`] of a document holding `text`.
+///
+/// # Examples
+/// ```
+/// use ls_core::document::*;
+/// use ls_core::hashing::*;
+/// assert_eq!(hash_text("a\r\nb"), Document::from("a\nb").content_hash());
+/// assert_ne!(hash_text("a\nb"), hash_text("a\nb\n"));
+/// ```
+pub fn hash_text(text: &str) -> u64 {
+    hash_lines(util::LINE_SPLIT.split(text))
+}
+
+/// Returns the hash of `lines` joined by `\n`.
+fn hash_lines<'a>(lines: impl Iterator<Item = &'a str>) -> u64 {
+    let mut hasher = Xxh3::new();
+    for (i, line) in lines.enumerate() {
+        if i > 0 {
+            hasher.update(b"\n");
+        }
+        hasher.update(line.as_bytes());
+    }
+    hasher.digest()
+}
+
+impl Document {
+    /// Returns the hash of the document's text. This streams over the lines
+    /// rather than building the text, but still reads all of it.
+    pub fn content_hash(&self) -> u64 {
+        hash_lines(self.lines().iter().map(|line| line.content.as_str()))
+    }
+
+    /// Returns whether `text` hashes the same as the document's text, for
+    /// checking whether a file changed on disk since it was loaded or saved.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("fn main() {}\n");
+    /// assert!(document.content_matches("fn main() {}\r\n"));
+    /// document.insert("pub ", &InsertOptions::exact()).unwrap();
+    /// assert!(!document.content_matches("fn main() {}\n"));
+    /// ```
+    pub fn content_matches(&self, text: &str) -> bool {
+        hash_text(text) == self.content_hash()
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::*;
+
+    #[test]
+    fn hash_follows_text_through_undo() {
+        let mut document = Document::from("alpha\nbeta");
+        let original = document.content_hash();
+        assert_eq!(original, hash_text(&document.text()));
+        assert_eq!(Document::new().content_hash(), hash_text(""));
+
+        document.insert("x", &InsertOptions::exact()).unwrap();
+        assert_ne!(document.content_hash(), original);
+        document.set_cursor_and_mark(&Position::from(1, 0)).unwrap();
+        document.undo(2);
+        assert_eq!(document.content_hash(), original);
+    }
+}
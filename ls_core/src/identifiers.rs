@@ -0,0 +1,142 @@
+//! An index of the identifiers appearing in a document.
+//!
+//! When the document has a parse tree, identifiers are the leaf nodes whose
+//! kind ends in `identifier` (`identifier`, `field_identifier`,
+//! `type_identifier`, and so on). Otherwise, a simple word scan is used.
+
+use std::collections::{BTreeMap, BTreeSet};
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::document::{Document, Range};
+use crate::segmentation::Segmentation;
+
+lazy_static! {
+    static ref IDENTIFIER: Regex = Regex::new(r"[\p{Alphabetic}_][\p{Alphabetic}\p{Nd}_]*").unwrap();
+}
+
+/// Maps each identifier in a document to the ranges where it occurs.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct IdentifierIndex {
+    entries: BTreeMap<String, Vec<Range>>
+}
+
+impl IdentifierIndex {
+    /// Returns an empty index.
+    pub fn new() -> IdentifierIndex {
+        IdentifierIndex { entries: BTreeMap::new() }
+    }
+
+    /// Records an occurrence of `name` at `range`.
+    pub fn add(&mut self, name: &str, range: &Range) {
+        self.entries.entry(String::from(name)).or_default().push(*range);
+    }
+
+    /// Returns the ranges where `name` occurs, in document order.
+    pub fn get(&self, name: &str) -> Option<&Vec<Range>> {
+        self.entries.get(name)
+    }
+
+    /// Returns the number of distinct identifiers.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the index holds no identifiers.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates over `(name, ranges)` pairs in alphabetical order.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Vec<Range>)> {
+        self.entries.iter()
+    }
+
+    /// Returns the distinct words the identifiers are made of, split by
+    /// `segmentation`, in alphabetical order: vocabulary to teach a speech
+    /// recognizer. Words are lower-cased, except acronyms. Single letters
+    /// and numbers are left out.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::segmentation::*;
+    /// let document = Document::from("let response = parseHTTPResponse(raw_body, v2);");
+    /// assert_eq!(
+    ///     document.identifier_index().vocabulary(&Segmentation::default()),
+    ///     vec!["HTTP", "body", "let", "parse", "raw", "response"]
+    /// );
+    /// ```
+    pub fn vocabulary(&self, segmentation: &Segmentation) -> Vec<String> {
+        let mut words: BTreeSet<String> = BTreeSet::new();
+        for name in self.entries.keys() {
+            for word in segmentation.split(name) {
+                if word.chars().count() < 2 || word.chars().all(char::is_numeric) {
+                    continue;
+                }
+                let acronym = word.chars().filter(|c| c.is_uppercase()).count() > 1
+                    && word.chars().all(|c| c.is_uppercase() || c == 's');
+                words.insert(if acronym { String::from(word) } else { word.to_lowercase() });
+            }
+        }
+        words.into_iter().collect()
+    }
+}
+
+impl Document {
+    /// Builds an [`IdentifierIndex`] for this document.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "parsing")] {
+    /// use ls_core::document::*;
+    /// let document = Document::from_with_language("fn main() { let x = main; }", "rs");
+    /// let index = document.identifier_index();
+    /// assert_eq!(index.get("main").unwrap().len(), 2);
+    /// assert_eq!(index.get("x").unwrap()[0], Range::from(0, 16, 0, 17));
+    /// assert!(index.get("let").is_none());
+    /// # }
+    /// ```
+    pub fn identifier_index(&self) -> IdentifierIndex {
+        let mut index = IdentifierIndex::new();
+
+        #[cfg(feature = "parsing")]
+        {
+            if let Some(tree) = self.parse_tree() {
+                let mut cursor = tree.walk();
+                'walk: loop {
+                    let node = cursor.node();
+                    if node.child_count() == 0 && node.kind().ends_with("identifier") {
+                        let range = self.range_from_ts(&node.range());
+                        if let Some(name) = self.text_range(&range) {
+                            index.add(&name, &range);
+                        }
+                    }
+
+                    if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                        continue;
+                    }
+                    loop {
+                        if !cursor.goto_parent() {
+                            break 'walk;
+                        }
+                        if cursor.goto_next_sibling() {
+                            break;
+                        }
+                    }
+                }
+
+                return index;
+            }
+        }
+
+        for (row, line) in self.lines().iter().enumerate() {
+            for m in IDENTIFIER.find_iter(&line.content) {
+                let start = line.content[..m.start()].chars().count();
+                let end = start + m.as_str().chars().count();
+                index.add(m.as_str(), &Range::from(row, start, row, end));
+            }
+        }
+
+        index
+    }
+}
@@ -0,0 +1,237 @@
+//! Idle work: what the core does while the host has nothing for it.
+//!
+//! The host calls [`Document::tick`] periodically, from a timer or its
+//! event loop, and the document decides what deferred work is due, so the
+//! host needn't know about each subsystem. Once edits have paused for long
+//! enough it
+//!
+//! * requests a checkpoint, so the next edit starts a new undo packet,
+//!   keeping any label already requested for it,
+//! * parses documents the [parse policy](crate::config::ParsePolicy) keeps
+//!   from parsing on every edit,
+//! * forgets undo packets beyond [`IdlePolicy::history_limit`],
+//!
+//! and once idle for longer still it drops the cache kept for the next
+//! [snapshot](crate::snapshot).
+//!
+//! Speech recognition takes time to turn an utterance into edits, and the
+//! longer the utterance, the longer the gap before its edits land. So the
+//! pause counted as idle stretches with the last utterance the host
+//! reported through [`Document::note_utterance`]: a user still dictating a
+//! long sentence isn't checkpointed in the middle of it.
+
+use crate::document::Document;
+
+/// When a document does its idle work.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub struct IdlePolicy {
+    /// How long edits must pause, in milliseconds, before the document
+    /// checkpoints, parses, and trims its history.
+    pub settle_after: u64,
+
+    /// The pause needed after an utterance, as a percentage of how long the
+    /// utterance took to say, if that is longer than `settle_after`.
+    pub utterance_percent: u64,
+
+    /// How many undo packets to keep, or `None` to keep them all.
+    pub history_limit: Option<usize>,
+
+    /// How long edits must pause, in milliseconds, before caches are
+    /// dropped, or `None` to keep them.
+    pub evict_after: Option<u64>
+}
+
+impl Default for IdlePolicy {
+    fn default() -> IdlePolicy {
+        IdlePolicy {
+            settle_after: 1000,
+            utterance_percent: 50,
+            history_limit: None,
+            evict_after: Some(60_000)
+        }
+    }
+}
+
+/// What a call to [`Document::tick`] did.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default)]
+pub struct TickReport {
+    pub checkpointed: bool,
+    pub reparsed: bool,
+
+    /// How many undo packets were forgotten.
+    pub forgotten: usize,
+    pub evicted: bool
+}
+
+impl TickReport {
+    /// Returns true if the tick did anything.
+    pub fn did_work(&self) -> bool {
+        *self != TickReport::default()
+    }
+}
+
+/// A document's idle bookkeeping.
+#[derive(Default)]
+pub(crate) struct IdleState {
+    policy: IdlePolicy,
+
+    /// The revision seen by the last tick, and when it was first seen.
+    revision: u64,
+    since: u64,
+
+    /// How long the last utterance took, in milliseconds.
+    utterance: u64,
+
+    /// True once the work due at `settle_after` is done for `revision`.
+    settled: bool,
+
+    /// True if the parse policy skipped a parse which idle time should make
+    /// up for.
+    #[cfg(feature = "parsing")]
+    pub(crate) reparse_pending: bool
+}
+
+impl Document {
+    /// Returns the policy for idle work.
+    pub fn idle_policy(&self) -> &IdlePolicy {
+        &self.idle.policy
+    }
+
+    /// Sets the policy for idle work.
+    pub fn set_idle_policy(&mut self, policy: &IdlePolicy) {
+        self.idle.policy = *policy;
+    }
+
+    /// Reports that the user just finished an utterance which took
+    /// `duration` milliseconds to say, lengthening the pause needed before
+    /// the document settles (see the [module documentation](self)).
+    pub fn note_utterance(&mut self, duration: u64) {
+        self.idle.utterance = duration;
+    }
+
+    /// Does whatever idle work is due, as of the document's clock (see
+    /// [`Document::set_clock`]).
+    pub fn tick(&mut self) -> TickReport {
+        let now = self.undo_redo().now();
+        self.tick_at(now)
+    }
+
+    /// Does whatever idle work is due at `now`, in milliseconds since the
+    /// Unix epoch.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("");
+    /// document.tick_at(0);
+    /// document.insert("hello", &InsertOptions::exact()).unwrap();
+    ///
+    /// assert!(!document.tick_at(10_000).checkpointed);
+    /// assert!(document.tick_at(11_000).checkpointed);
+    /// assert!(!document.tick_at(12_000).did_work());
+    ///
+    /// document.insert(" world", &InsertOptions::exact()).unwrap();
+    /// document.tick_at(20_000);
+    /// document.tick_at(21_000);
+    /// assert_eq!(document.undo_redo().depth(), (2, 0));
+    /// ```
+    pub fn tick_at(&mut self, now: u64) -> TickReport {
+        let mut report = TickReport::default();
+        let revision = self.revision();
+        if revision != self.idle.revision {
+            self.idle.revision = revision;
+            self.idle.since = now;
+            self.idle.settled = false;
+        }
+
+        let policy = self.idle.policy;
+        let idle = now.saturating_sub(self.idle.since);
+        let settle_after = policy.settle_after.max(self.idle.utterance * policy.utterance_percent / 100);
+
+        if !self.idle.settled && idle >= settle_after {
+            self.idle.settled = true;
+
+            // Checkpointing forgets redos, so it waits for an edit.
+            let (undos, redos) = self.undo_redo().depth();
+            if undos > 0 && redos == 0 {
+                self.undo_redo.close_packet();
+                report.checkpointed = true;
+            }
+
+            #[cfg(feature = "parsing")]
+            if self.idle.reparse_pending {
+                self.parse_everything();
+                self.damage.record_from(0);
+                report.reparsed = true;
+            }
+
+            if let Some(limit) = policy.history_limit {
+                report.forgotten = self.undo_redo.forget_oldest(limit);
+            }
+        }
+
+        if policy.evict_after.is_some_and(|after| idle >= after) && self.snapshot_cache.is_some() {
+            self.snapshot_cache = None;
+            report.evicted = true;
+        }
+        report
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::InsertOptions;
+
+    #[test]
+    fn idle_work_waits_for_utterances_and_forgets() {
+        let mut document = Document::from("");
+        document.set_idle_policy(&IdlePolicy { history_limit: Some(1), evict_after: Some(5000), ..IdlePolicy::default() });
+        document.tick_at(0);
+
+        for (i, word) in ["one ", "two ", "three "].iter().enumerate() {
+            let start = i as u64 * 10_000;
+            document.insert(word, &InsertOptions::exact()).unwrap();
+            document.note_utterance(4000);
+            document.tick_at(start + 1);
+            assert!(!document.tick_at(start + 1500).did_work());
+            let report = document.tick_at(start + 2001);
+            assert!(report.checkpointed);
+            assert_eq!(report.forgotten, usize::from(i > 0));
+        }
+        assert_eq!(document.undo_redo().depth(), (1, 0));
+
+        document.undo_once().unwrap();
+        assert!(!document.tick_at(40_000).checkpointed);
+        assert_eq!(document.undo_redo().depth(), (0, 1));
+
+        document.snapshot();
+        assert!(!document.tick_at(41_000).evicted);
+        assert!(document.tick_at(46_000).evicted);
+
+        #[cfg(feature = "parsing")]
+        {
+            use crate::config::{ConfigOverrides, ParsePolicy};
+            let mut document = Document::from_with_language("fn f() {}\n", "rs");
+            document.set_config_overrides(ConfigOverrides { parse_policy: Some(ParsePolicy::MaxLines(1)), ..Default::default() });
+            assert!(document.parse_tree().is_none());
+            assert!(document.tick_at(u64::MAX).reparsed);
+            assert!(document.parse_tree().is_some());
+        }
+    }
+
+    #[test]
+    fn idle_checkpoints_keep_requested_labels() {
+        let mut document = Document::from("");
+        document.tick_at(0);
+        document.insert("one", &InsertOptions::exact()).unwrap();
+        document.checkpoint_labeled("dictation");
+        document.tick_at(1);
+
+        assert!(document.tick_at(5000).checkpointed);
+        document.insert(" two", &InsertOptions::exact()).unwrap();
+        assert_eq!(document.undo_redo().undo_packets().next().unwrap().label(), Some("dictation"));
+    }
+}
@@ -0,0 +1,337 @@
+//! Adding imports: "import HashMap from std collections" should land in the
+//! file's import section, in order, without the user navigating there.
+//!
+//! The import section is the run of top-level import statements found in
+//! the parse tree. A new import merges into an existing statement for the
+//! same module where the language allows it (Rust use lists, Python
+//! `from ... import`, JavaScript named imports), and is otherwise added as a
+//! new statement in sorted position. Imports already present are left alone.
+
+use crate::document::{Document, Range};
+use crate::util::Oops;
+
+/// Returns the statement which imports `symbol` from `module` in
+/// `language`, or `None` if imports of `language` are not supported.
+///
+/// # Examples
+/// ```
+/// use ls_core::imports::*;
+/// assert_eq!(import_statement("rs", "HashMap", "std::collections").unwrap(), "use std::collections::HashMap;");
+/// assert_eq!(import_statement("py", "path", "os").unwrap(), "from os import path");
+/// assert_eq!(import_statement("js", "join", "path").unwrap(), "import { join } from 'path';");
+/// assert_eq!(import_statement("txt", "x", "y"), None);
+/// ```
+pub fn import_statement(language: &str, symbol: &str, module: &str) -> Option<String> {
+    match language {
+        "rs" => Some(format!("use {}::{};", module, symbol)),
+        "py" => Some(format!("from {} import {}", module, symbol)),
+        "js" | "ts" | "tsx" => Some(format!("import {{ {} }} from '{}';", symbol, module)),
+        "java" => Some(format!("import {}.{};", module, symbol)),
+        _ => None
+    }
+}
+
+/// Node kinds of top-level import statements.
+#[cfg(feature = "parsing")]
+const IMPORT_KINDS: &[&str] = &["use_declaration", "import_statement", "import_from_statement", "import_declaration"];
+
+/// Node kinds which may come before the import section: file comments,
+/// inner attributes, and package declarations.
+#[cfg(feature = "parsing")]
+const HEADER_KINDS: &[&str] = &["comment", "line_comment", "block_comment", "inner_attribute_item", "package_declaration"];
+
+/// The top-level import statements of a document, and the file header
+/// before them.
+#[cfg(feature = "parsing")]
+struct ImportSection {
+    /// Each statement's range and text.
+    statements: Vec<(Range, String)>,
+
+    /// The range of the header's last node, if there is a header.
+    header_end: Option<Range>
+}
+
+/// What adding an import to an existing statement would do.
+#[cfg(feature = "parsing")]
+#[derive(PartialEq, Eq, Debug)]
+enum Merge {
+    /// The statement imports the symbol already.
+    Present,
+
+    /// The statement should be replaced with this text.
+    Into(String),
+
+    /// The statement is for something else.
+    Unrelated
+}
+
+/// Splits `list` at commas outside braces, trimming each item.
+#[cfg(feature = "parsing")]
+fn split_items(list: &str) -> Vec<String> {
+    let mut items = vec![];
+    let mut depth = 0;
+    let mut current = String::new();
+
+    for c in list.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(current.trim().to_string());
+                current.clear();
+                continue;
+            },
+            _ => ()
+        }
+        current.push(c);
+    }
+
+    items.push(current.trim().to_string());
+    items.retain(|item| !item.is_empty());
+    items
+}
+
+/// Returns the name an import list item brings in from its module: the
+/// part before any `as` or `::`.
+#[cfg(feature = "parsing")]
+fn item_name(item: &str) -> &str {
+    let name = item.split_whitespace().next().unwrap_or("");
+    name.split("::").next().unwrap_or(name)
+}
+
+/// Returns `items` with `symbol` added, sorted, with `self` first.
+#[cfg(feature = "parsing")]
+fn with_item(mut items: Vec<String>, symbol: &str) -> Vec<String> {
+    items.push(String::from(symbol));
+    items.sort_by_key(|item| (item != "self", item.clone()));
+    items
+}
+
+/// Works out how adding `symbol` from `module` affects `statement`, an
+/// import statement of `language`.
+#[cfg(feature = "parsing")]
+fn merge(language: &str, statement: &str, symbol: &str, module: &str) -> Merge {
+    let statement = statement.trim();
+
+    match language {
+        "rs" => {
+            let path = match statement.strip_prefix("use ").and_then(|s| s.strip_suffix(';')) {
+                Some(path) => path.trim(),
+                None => return Merge::Unrelated
+            };
+            let rest = match path.strip_prefix(module).and_then(|r| r.strip_prefix("::")) {
+                Some(rest) => rest,
+                None => return Merge::Unrelated
+            };
+
+            let items = match rest.strip_prefix('{').and_then(|r| r.strip_suffix('}')) {
+                Some(list) => split_items(list),
+                None if !rest.contains("::") && !rest.contains('{') && rest != "*" => vec![String::from(rest)],
+                None => return if rest == "*" { Merge::Present } else { Merge::Unrelated }
+            };
+
+            if items.iter().any(|item| item == symbol || item == "*") {
+                Merge::Present
+            } else {
+                Merge::Into(format!("use {}::{{{}}};", module, with_item(items, symbol).join(", ")))
+            }
+        },
+        "py" => {
+            let prefix = format!("from {} import ", module);
+            let list = match statement.strip_prefix(&prefix) {
+                Some(list) => list.trim().trim_start_matches('(').trim_end_matches(')'),
+                None => return Merge::Unrelated
+            };
+
+            let items = split_items(list);
+            if items.iter().any(|item| item_name(item) == symbol || item == "*") {
+                Merge::Present
+            } else {
+                Merge::Into(format!("{}{}", prefix, with_item(items, symbol).join(", ")))
+            }
+        },
+        "js" | "ts" | "tsx" => {
+            let source = statement.rsplit(" from ").next().unwrap_or("").trim_end_matches(';').trim();
+            if source.len() < 2 || &source[1..source.len() - 1] != module {
+                return Merge::Unrelated;
+            }
+            let (open, close) = match (statement.find('{'), statement.find('}')) {
+                (Some(open), Some(close)) if open < close => (open, close),
+                _ => return Merge::Unrelated
+            };
+
+            let items = split_items(&statement[open + 1..close]);
+            if items.iter().any(|item| item_name(item) == symbol) {
+                Merge::Present
+            } else {
+                let list = with_item(items, symbol).join(", ");
+                Merge::Into(format!("{}{{ {} }}{}", &statement[..open], list, &statement[close + 1..]))
+            }
+        },
+        "java" => {
+            let imported = statement.strip_prefix("import ").and_then(|s| s.strip_suffix(';')).map(str::trim);
+            if imported == Some(&format!("{}.{}", module, symbol)) || imported == Some(&format!("{}.*", module)) {
+                Merge::Present
+            } else {
+                Merge::Unrelated
+            }
+        },
+        _ => Merge::Unrelated
+    }
+}
+
+impl Document {
+    /// Imports `symbol` from `module`, such as `HashMap` from
+    /// `std::collections`, returning the range of the import statement
+    /// which now includes it. If it is imported already, nothing changes.
+    /// Edits are one change packet labeled "import". Returns `Err` if there
+    /// is no parse tree or the language's imports are not supported.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language(
+    ///     "//! Tools.\n\nuse std::fmt;\nuse std::io::Read;\n\nfn main() {}\n",
+    ///     "rs"
+    /// );
+    /// assert_eq!(document.add_import("Write", "std::io"), Ok(Range::from(3, 0, 3, 27)));
+    /// assert_eq!(document.add_import("HashMap", "std::collections"), Ok(Range::from(2, 0, 2, 30)));
+    /// assert_eq!(document.add_import("fmt", "std"), Ok(Range::from(3, 0, 3, 13)));
+    /// assert_eq!(document.text(), concat!(
+    ///     "//! Tools.\n\n",
+    ///     "use std::collections::HashMap;\nuse std::fmt;\nuse std::io::{Read, Write};\n\n",
+    ///     "fn main() {}\n"
+    /// ));
+    /// ```
+    #[cfg(feature = "parsing")]
+    pub fn add_import(&mut self, symbol: &str, module: &str) -> Result<Range, Oops> {
+        use crate::document::{Position, InsertOptions};
+
+        let language = String::from(self.language());
+        let statement = import_statement(&language, symbol, module)
+            .ok_or(Oops::Ouch("imports are not supported in this language"))?;
+        let ImportSection { statements: imports, header_end } = self.import_section(&language)?;
+
+        for (range, text) in imports.iter() {
+            match merge(&language, text, symbol, module) {
+                Merge::Present => return Ok(*range),
+                Merge::Into(merged) => {
+                    self.checkpoint_labeled("import");
+                    self.insert(&merged, &InsertOptions::exact_at(range))?;
+                    self.checkpoint();
+                    return Ok(Range { ending: Position::from(range.beginning.row, merged.chars().count()), ..*range });
+                },
+                Merge::Unrelated => ()
+            }
+        }
+
+        let length = statement.chars().count();
+        self.checkpoint_labeled("import");
+
+        let row = match imports.iter().find(|(_, text)| text.as_str() > statement.as_str()) {
+            Some((range, _)) => {
+                let row = range.beginning.row;
+                self.insert(&format!("{}\n", statement), &InsertOptions::exact_at(&Range::from(row, 0, row, 0)))?;
+                row
+            },
+            None => match imports.last() {
+                Some((range, _)) => {
+                    let at = range.ending;
+                    self.insert(&format!("\n{}", statement), &InsertOptions::exact_at(&Range { beginning: at, ending: at }))?;
+                    at.row + 1
+                },
+                None => {
+                    let row = header_end.map_or(0, |h| h.ending.row + 1).min(self.rows() - 1);
+                    let blank_after = self.lines()[row].content.trim().is_empty();
+                    let mut text = format!("{}\n", statement);
+                    if !blank_after {
+                        text.push('\n');
+                    }
+                    if header_end.is_some() && row > 0 && !self.lines()[row - 1].content.trim().is_empty() {
+                        text.insert(0, '\n');
+                    }
+                    self.insert(&text, &InsertOptions::exact_at(&Range::from(row, 0, row, 0)))?;
+                    row + if text.starts_with('\n') { 1 } else { 0 }
+                }
+            }
+        };
+
+        self.checkpoint();
+        Ok(Range::from(row, 0, row, length))
+    }
+
+    /// Finds the import section in the parse tree.
+    #[cfg(feature = "parsing")]
+    fn import_section(&self, language: &str) -> Result<ImportSection, Oops> {
+        let tree = self.parse_tree().ok_or(Oops::CannotParse("add_import"))?;
+        let root = tree.root_node();
+
+        let mut imports: Vec<(Range, String)> = vec![];
+        let mut header_end: Option<Range> = None;
+        let mut cursor = root.walk();
+        for node in root.named_children(&mut cursor) {
+            let range = self.range_from_ts(&node.range());
+            let is_docstring = language == "py"
+                && imports.is_empty()
+                && header_end.is_none()
+                && node.kind() == "expression_statement"
+                && node.named_child(0).is_some_and(|c| c.kind() == "string");
+
+            if IMPORT_KINDS.contains(&node.kind()) {
+                let text = self.text_range(&range).unwrap_or_default();
+                imports.push((range, text));
+            } else if imports.is_empty() && (HEADER_KINDS.contains(&node.kind()) || is_docstring) {
+                header_end = Some(range);
+            } else {
+                break;
+            }
+        }
+
+        Ok(ImportSection { statements: imports, header_end })
+    }
+
+    /// Without the `parsing` feature there is never a parse tree, so this
+    /// always returns an [`Oops`].
+    #[cfg(not(feature = "parsing"))]
+    pub fn add_import(&mut self, _symbol: &str, _module: &str) -> Result<Range, Oops> {
+        Err(Oops::CannotParse("add_import"))
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "parsing"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merges_per_language() {
+        let merged = |language, statement, symbol, module| merge(language, statement, symbol, module);
+        assert_eq!(merged("rs", "use a::{self, c};", "b", "a"), Merge::Into(String::from("use a::{self, b, c};")));
+        assert_eq!(merged("rs", "use a::b::c;", "d", "a"), Merge::Unrelated);
+        assert_eq!(merged("rs", "use a::*;", "d", "a"), Merge::Present);
+        assert_eq!(merged("py", "from os import (sep, path as p)", "path", "os"), Merge::Present);
+        assert_eq!(merged("py", "from os import sep", "path", "os"), Merge::Into(String::from("from os import path, sep")));
+        assert_eq!(
+            merged("ts", "import React, { useState } from \"react\";", "useEffect", "react"),
+            Merge::Into(String::from("import React, { useEffect, useState } from \"react\";"))
+        );
+        assert_eq!(merged("js", "import x from 'react';", "y", "react"), Merge::Unrelated);
+        assert_eq!(merged("java", "import java.util.*;", "List", "java.util"), Merge::Present);
+    }
+
+    #[test]
+    fn new_sections_go_after_headers() {
+        let mut document = Document::from_with_language("\"\"\"Docs.\"\"\"\ndef f():\n    pass\n", "py");
+        assert_eq!(document.add_import("path", "os"), Ok(Range::from(2, 0, 2, 19)));
+        assert_eq!(document.text(), "\"\"\"Docs.\"\"\"\n\nfrom os import path\n\ndef f():\n    pass\n");
+        assert_eq!(document.add_import("path", "os"), Ok(Range::from(2, 0, 2, 19)));
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), "\"\"\"Docs.\"\"\"\ndef f():\n    pass\n");
+
+        let mut java = Document::from_with_language("package p;\n\nimport java.util.Map;\n\nclass A {}\n", "java");
+        assert_eq!(java.add_import("List", "java.util"), Ok(Range::from(2, 0, 2, 22)));
+        assert_eq!(java.text(), "package p;\n\nimport java.util.List;\nimport java.util.Map;\n\nclass A {}\n");
+        assert!(Document::from_with_language("x", "sh").add_import("a", "b").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! "Increment by five": adding to the number at the cursor.
+//!
+//! Numbers are decimal, with an optional minus sign, or hexadecimal with a
+//! `0x` prefix. A minus sign right after a word character or closing
+//! bracket is read as subtraction, not a sign, so `x-1` increments to `x-2`.
+//! The result keeps the number's look: zero-padded numbers stay padded to
+//! the same width, and hexadecimal digits keep their case and width.
+//! Hexadecimal numbers are unsigned and wrap around at 64 bits.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::document::{Document, Position, Range, InsertOptions, RemoveOptions};
+use crate::util::{self, Oops};
+
+lazy_static! {
+    static ref NUMBER: Regex = Regex::new(r"0[xX][0-9a-fA-F]+|-?[0-9]+").unwrap();
+}
+
+/// Returns `number` plus `delta`, written like `number`, or `None` if
+/// `number` is not a number or the result does not fit in 64 bits.
+///
+/// # Examples
+/// ```
+/// use ls_core::increment::*;
+/// assert_eq!(add_to_number("41", 1), Some(String::from("42")));
+/// assert_eq!(add_to_number("007", 5), Some(String::from("012")));
+/// assert_eq!(add_to_number("-3", 5), Some(String::from("2")));
+/// assert_eq!(add_to_number("0x0fF", 1), Some(String::from("0x100")));
+/// assert_eq!(add_to_number("0x00", -1), Some(String::from("0xffffffffffffffff")));
+/// ```
+pub fn add_to_number(number: &str, delta: i64) -> Option<String> {
+    if let Some(digits) = number.strip_prefix("0x").or_else(|| number.strip_prefix("0X")) {
+        let value = u64::from_str_radix(digits, 16).ok()?.wrapping_add(delta as u64);
+        let width = digits.len();
+        let text = if digits.chars().any(|c| c.is_ascii_uppercase()) {
+            format!("{:0width$X}", value, width = width)
+        } else {
+            format!("{:0width$x}", value, width = width)
+        };
+        return Some(format!("{}{}", &number[..2], text));
+    }
+
+    let value = number.parse::<i64>().ok()?.checked_add(delta)?;
+    let digits = number.trim_start_matches('-');
+    let width = if digits.len() > 1 && digits.starts_with('0') { digits.len() } else { 0 };
+    let sign = if value < 0 { "-" } else { "" };
+    Some(format!("{}{:0width$}", sign, value.unsigned_abs(), width = width))
+}
+
+impl Document {
+    /// Returns the range of the number at `position`: the one containing
+    /// the character after `position` or ending at `position`, or else the
+    /// first one after `position` on its line.
+    pub fn number_at(&self, position: &Position) -> Option<Range> {
+        let line = &self.lines().get(position.row)?.content;
+        let row = position.row;
+
+        NUMBER.find_iter(line)
+            .map(|found| {
+                let mut beginning = util::byte_index_to_cp(line, found.start()).unwrap();
+                let ending = beginning + found.as_str().chars().count();
+                let previous = line[..found.start()].chars().next_back();
+                if found.as_str().starts_with('-') && previous.is_some_and(|c| c.is_alphanumeric() || "_)]".contains(c)) {
+                    beginning += 1;
+                }
+                Range::from(row, beginning, row, ending)
+            })
+            .find(|range| range.ending.column >= position.column)
+    }
+
+    /// Adds `delta` to the number at the cursor (see
+    /// [`Document::number_at`]), as one change packet labeled "increment",
+    /// and moves the cursor to its beginning. Returns the range of the new
+    /// number, or `Err` if there is no number or the result overflows.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("let width = x-0009;");
+    /// document.set_cursor_and_mark(&Position::from(0, 4)).unwrap();
+    /// assert_eq!(document.increment(5), Ok(Range::from(0, 14, 0, 18)));
+    /// assert_eq!(document.text(), "let width = x-0014;");
+    ///
+    /// let mut document = Document::from("offset = -3");
+    /// document.increment(5).unwrap();
+    /// assert_eq!(document.text(), "offset = 2");
+    /// ```
+    pub fn increment(&mut self, delta: i64) -> Result<Range, Oops> {
+        let range = self.number_at(&self.cursor().position).ok_or(Oops::Ouch("no number at cursor"))?;
+        let number = self.text_range(&range).unwrap();
+        let text = add_to_number(&number, delta).ok_or(Oops::Ouch("number out of range"))?;
+
+        self.with_packet("increment", |document| {
+            document.remove(&RemoveOptions::exact_at(&range))?;
+            document.insert(&text, &InsertOptions::exact_at(&Range { beginning: range.beginning, ending: range.beginning }))?;
+            document.set_cursor_and_mark(&range.beginning)
+        })?;
+
+        let ending = Position::from(range.beginning.row, range.beginning.column + text.chars().count());
+        Ok(Range { beginning: range.beginning, ending })
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_and_rewrites_numbers() {
+        let mut document = Document::from("a[-1] = f(0x1F, -08) + 3;\nnone here");
+        let number = |document: &Document, column: usize| {
+            document.number_at(&Position::from(0, column)).and_then(|r| document.text_range(&r))
+        };
+        assert_eq!(number(&document, 0).as_deref(), Some("-1"));
+        assert_eq!(number(&document, 4).as_deref(), Some("-1"));
+        assert_eq!(number(&document, 5).as_deref(), Some("0x1F"));
+        assert_eq!(number(&document, 6).as_deref(), Some("0x1F"));
+        assert_eq!(number(&document, 16).as_deref(), Some("-08"));
+        assert_eq!(number(&document, 24).as_deref(), Some("3"));
+        assert_eq!(number(&document, 25), None);
+
+        document.set_cursor_and_mark(&Position::from(0, 11)).unwrap();
+        document.increment(1).unwrap();
+        document.set_cursor_and_mark(&Position::from(0, 17)).unwrap();
+        document.increment(10).unwrap();
+        assert_eq!(document.text(), "a[-1] = f(0x20, 02) + 3;\nnone here");
+        assert_eq!(document.cursor().position, Position::from(0, 16));
+
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), "a[-1] = f(0x20, -08) + 3;\nnone here");
+
+        document.set_cursor_and_mark(&Position::from(0, 23)).unwrap();
+        document.execute_phrase("increment by five").unwrap();
+        document.execute_phrase("decrement").unwrap();
+        assert_eq!(document.lines()[0].content, "a[-1] = f(0x20, -08) + 7;");
+
+        document.set_cursor_and_mark(&Position::from(1, 0)).unwrap();
+        assert!(document.increment(1).is_err());
+        assert_eq!(add_to_number(&i64::MAX.to_string(), 1), None);
+    }
+}
@@ -0,0 +1,626 @@
+//! Keyboard input mapping.
+//!
+//! Frontends translate their native key events into [`KeyEvent`]s and hand
+//! them to [`Document::handle_key`] along with a [`Keymap`]. The keymap
+//! resolves each event to an [`Action`], and the document performs it, so
+//! that every frontend shares the same editing semantics (word deletion,
+//! home/end, shift-selection, and so forth).
+
+use std::collections::HashMap;
+
+use crate::document::{Document, Position, Range, InsertOptions, RemoveOptions};
+use crate::util::Oops;
+
+/// A key, independent of modifiers.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum Key {
+    Char(char),
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Backspace,
+    Delete,
+    Enter,
+    Tab,
+}
+
+/// Modifier keys held during a [`KeyEvent`].
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub meta: bool
+}
+
+/// A key press, as reported by a frontend.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub modifiers: Modifiers
+}
+
+/// An editing operation that a key can be bound to.
+///
+/// Movement actions extend the selection instead of collapsing it when
+/// they are triggered with shift held.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    MoveWordLeft,
+    MoveWordRight,
+    MoveLineStart,
+    MoveLineEnd,
+    MoveDocumentStart,
+    MoveDocumentEnd,
+    DeleteBackward,
+    DeleteForward,
+    DeleteWordBackward,
+    DeleteWordForward,
+    InsertNewline,
+    InsertTab,
+    SelectAll,
+    Undo,
+    Redo,
+}
+
+/// A table from [`KeyEvent`]s to [`Action`]s.
+///
+/// Unmodified (or shift-only) character keys which are not bound insert
+/// themselves, with [auto-pairing](crate::autopair).
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct Keymap {
+    bindings: HashMap<KeyEvent, Action>
+}
+
+//-----------------------------------------------------------------------------
+
+impl Modifiers {
+    /// Returns the empty set of modifiers.
+    pub fn none() -> Modifiers {
+        Modifiers::default()
+    }
+
+    /// Returns just shift.
+    pub fn shift() -> Modifiers {
+        Modifiers { shift: true, ..Modifiers::default() }
+    }
+
+    /// Returns just ctrl.
+    pub fn ctrl() -> Modifiers {
+        Modifiers { ctrl: true, ..Modifiers::default() }
+    }
+
+    /// Returns just alt.
+    pub fn alt() -> Modifiers {
+        Modifiers { alt: true, ..Modifiers::default() }
+    }
+
+    /// Returns just meta (command, super, windows).
+    pub fn meta() -> Modifiers {
+        Modifiers { meta: true, ..Modifiers::default() }
+    }
+}
+
+impl KeyEvent {
+    /// Returns the event for `key` pressed with `modifiers`.
+    pub fn from(key: Key, modifiers: Modifiers) -> KeyEvent {
+        KeyEvent { key, modifiers }
+    }
+
+    /// Returns the event for `key` pressed with no modifiers.
+    pub fn plain(key: Key) -> KeyEvent {
+        KeyEvent { key, modifiers: Modifiers::none() }
+    }
+}
+
+impl Action {
+    /// Returns whether this action moves the cursor without editing.
+    pub fn is_movement(&self) -> bool {
+        matches!(self,
+            Action::MoveLeft | Action::MoveRight | Action::MoveUp | Action::MoveDown
+            | Action::MoveWordLeft | Action::MoveWordRight
+            | Action::MoveLineStart | Action::MoveLineEnd
+            | Action::MoveDocumentStart | Action::MoveDocumentEnd)
+    }
+}
+
+impl Keymap {
+    /// Returns a keymap with no bindings.
+    pub fn new() -> Keymap {
+        Keymap { bindings: HashMap::new() }
+    }
+
+    /// Returns the conventional PC-style keymap: arrows, home/end, ctrl for
+    /// word motions and deletions, ctrl-a/z/y for select all, undo, and redo.
+    pub fn standard() -> Keymap {
+        let mut keymap = Keymap::new();
+        let none = Modifiers::none();
+        let ctrl = Modifiers::ctrl();
+
+        keymap.bind(KeyEvent::from(Key::Left, none), Action::MoveLeft);
+        keymap.bind(KeyEvent::from(Key::Right, none), Action::MoveRight);
+        keymap.bind(KeyEvent::from(Key::Up, none), Action::MoveUp);
+        keymap.bind(KeyEvent::from(Key::Down, none), Action::MoveDown);
+        keymap.bind(KeyEvent::from(Key::Left, ctrl), Action::MoveWordLeft);
+        keymap.bind(KeyEvent::from(Key::Right, ctrl), Action::MoveWordRight);
+        keymap.bind(KeyEvent::from(Key::Home, none), Action::MoveLineStart);
+        keymap.bind(KeyEvent::from(Key::End, none), Action::MoveLineEnd);
+        keymap.bind(KeyEvent::from(Key::Home, ctrl), Action::MoveDocumentStart);
+        keymap.bind(KeyEvent::from(Key::End, ctrl), Action::MoveDocumentEnd);
+        keymap.bind(KeyEvent::from(Key::Backspace, none), Action::DeleteBackward);
+        keymap.bind(KeyEvent::from(Key::Delete, none), Action::DeleteForward);
+        keymap.bind(KeyEvent::from(Key::Backspace, ctrl), Action::DeleteWordBackward);
+        keymap.bind(KeyEvent::from(Key::Delete, ctrl), Action::DeleteWordForward);
+        keymap.bind(KeyEvent::from(Key::Enter, none), Action::InsertNewline);
+        keymap.bind(KeyEvent::from(Key::Tab, none), Action::InsertTab);
+        keymap.bind(KeyEvent::from(Key::Char('a'), ctrl), Action::SelectAll);
+        keymap.bind(KeyEvent::from(Key::Char('z'), ctrl), Action::Undo);
+        keymap.bind(KeyEvent::from(Key::Char('y'), ctrl), Action::Redo);
+
+        keymap
+    }
+
+    /// Binds `event` to `action`, replacing any previous binding.
+    pub fn bind(&mut self, event: KeyEvent, action: Action) {
+        self.bindings.insert(event, action);
+    }
+
+    /// Removes the binding for `event`, returning the action it was bound to.
+    pub fn unbind(&mut self, event: &KeyEvent) -> Option<Action> {
+        self.bindings.remove(event)
+    }
+
+    /// Returns the action bound to `event`. If there is no exact binding and
+    /// shift is held, the binding without shift is used when it is a movement,
+    /// so that shift-selection does not need separate bindings.
+    pub fn lookup(&self, event: &KeyEvent) -> Option<Action> {
+        if let Some(action) = self.bindings.get(event) {
+            return Some(*action);
+        }
+
+        if event.modifiers.shift {
+            let unshifted = KeyEvent {
+                modifiers: Modifiers { shift: false, ..event.modifiers },
+                ..*event
+            };
+            if let Some(action) = self.bindings.get(&unshifted) {
+                if action.is_movement() {
+                    return Some(*action);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Document {
+    /// Handles `event` according to `keymap`. Returns `Ok(true)` if the event
+    /// was consumed, or `Ok(false)` if it has no meaning and the frontend
+    /// should handle it (or ignore it).
+    ///
+    /// Each edit is undoable on its own; movements are grouped with the edit
+    /// that follows them.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::input::*;
+    /// let mut document = Document::from("hello world");
+    /// let keymap = Keymap::standard();
+    ///
+    /// document.handle_key(&KeyEvent::plain(Key::End), &keymap).unwrap();
+    /// document.handle_key(&KeyEvent::from(Key::Backspace, Modifiers::ctrl()), &keymap).unwrap();
+    /// document.handle_key(&KeyEvent::plain(Key::Char('!')), &keymap).unwrap();
+    /// assert_eq!(document.text(), "hello !");
+    /// ```
+    pub fn handle_key(&mut self, event: &KeyEvent, keymap: &Keymap) -> Result<bool, Oops> {
+        if let Some(action) = keymap.lookup(event) {
+            self.perform_action(action, event.modifiers.shift)?;
+            return Ok(true);
+        }
+
+        match event.key {
+            Key::Char(c) if !(event.modifiers.ctrl || event.modifiers.alt || event.modifiers.meta) => {
+                self.checkpoint();
+                self.type_char(c)?;
+                Ok(true)
+            },
+            _ => Ok(false)
+        }
+    }
+
+    /// Performs `action`. Movements extend the selection if `extend` is set
+    /// and collapse it otherwise.
+    pub fn perform_action(&mut self, action: Action, extend: bool) -> Result<(), Oops> {
+        if action.is_movement() {
+            let target = self.action_target(action);
+            return if extend {
+                self.set_cursor(&target)
+            } else {
+                self.set_cursor_and_mark(&target)
+            };
+        }
+
+        match action {
+            Action::DeleteBackward => {
+                self.checkpoint();
+                if self.remove_empty_pair()? {
+                    return Ok(());
+                }
+                self.delete_toward(self.previous_position(&self.cursor().position))
+            },
+            Action::DeleteForward => self.delete_toward(self.next_position(&self.cursor().position)),
+            Action::DeleteWordBackward => self.delete_toward(self.previous_word_position(&self.cursor().position)),
+            Action::DeleteWordForward => self.delete_toward(self.next_word_position(&self.cursor().position)),
+            Action::InsertNewline => {
+                self.checkpoint();
+                self.insert("\n", &InsertOptions::exact())
+            },
+            Action::InsertTab => {
+                let tab = self.indentation().produce(self.indentation().spaces_per_tab);
+                self.checkpoint();
+                self.insert(&tab, &InsertOptions::exact())
+            },
+            Action::SelectAll => {
+                let last = self.rows() - 1;
+                let ending = Position::from(last, self.lines()[last].length);
+                self.set_selection(&Range { beginning: Position::from(0, 0), ending })
+            },
+            Action::Undo => self.undo_once().map(|_| ()),
+            Action::Redo => self.redo_once().map(|_| ()),
+            _ => Ok(())
+        }
+    }
+
+    fn action_target(&self, action: Action) -> Position {
+        let cursor = self.cursor().position;
+        let selection = self.selection();
+        let collapsing = !selection.empty();
+
+        match action {
+            Action::MoveLeft if collapsing => selection.beginning,
+            Action::MoveRight if collapsing => selection.ending,
+            Action::MoveLeft => self.previous_position(&cursor),
+            Action::MoveRight => self.next_position(&cursor),
+            Action::MoveUp if cursor.row == 0 => Position::from(0, 0),
+            Action::MoveUp => self.clamp_column(cursor.row - 1, cursor.column),
+            Action::MoveDown if cursor.row + 1 >= self.rows() => self.line_end(cursor.row),
+            Action::MoveDown => self.clamp_column(cursor.row + 1, cursor.column),
+            Action::MoveWordLeft => self.previous_word_position(&cursor),
+            Action::MoveWordRight => self.next_word_position(&cursor),
+            Action::MoveLineStart => Position::from(cursor.row, 0),
+            Action::MoveLineEnd => self.line_end(cursor.row),
+            Action::MoveDocumentStart => Position::from(0, 0),
+            Action::MoveDocumentEnd => self.line_end(self.rows() - 1),
+            _ => cursor
+        }
+    }
+
+    /// Removes the selection if there is one, or else the text between the
+    /// cursor and `target`.
+    fn delete_toward(&mut self, target: Position) -> Result<(), Oops> {
+        let cursor = self.cursor().position;
+        let range = if !self.selection().empty() {
+            self.selection()
+        } else if target < cursor {
+            Range { beginning: target, ending: cursor }
+        } else {
+            Range { beginning: cursor, ending: target }
+        };
+
+        if range.empty() {
+            return Ok(());
+        }
+
+        self.checkpoint();
+        self.remove(&RemoveOptions::exact_at(&range))
+    }
+
+    fn line_end(&self, row: usize) -> Position {
+        Position::from(row, self.lines()[row].length)
+    }
+
+    fn clamp_column(&self, row: usize, column: usize) -> Position {
+        Position::from(row, column.min(self.lines()[row].length))
+    }
+
+    fn char_at(&self, position: &Position) -> Option<char> {
+        self.lines()[position.row].content.chars().nth(position.column)
+    }
+
+    /// Returns the position one character before `position`, wrapping to the
+    /// end of the previous line.
+    fn previous_position(&self, position: &Position) -> Position {
+        if position.column > 0 {
+            Position::from(position.row, position.column - 1)
+        } else if position.row > 0 {
+            self.line_end(position.row - 1)
+        } else {
+            *position
+        }
+    }
+
+    /// Returns the position one character after `position`, wrapping to the
+    /// beginning of the next line.
+    fn next_position(&self, position: &Position) -> Position {
+        if position.column < self.lines()[position.row].length {
+            Position::from(position.row, position.column + 1)
+        } else if position.row + 1 < self.rows() {
+            Position::from(position.row + 1, 0)
+        } else {
+            *position
+        }
+    }
+
+    /// Returns the beginning of the word before `position`. Line breaks count
+    /// as a single step of their own.
+    fn previous_word_position(&self, position: &Position) -> Position {
+        if position.column == 0 {
+            return self.previous_position(position);
+        }
+
+        let mut p = *position;
+        while p.column > 0 && !self.char_at(&Position::from(p.row, p.column - 1)).is_some_and(|c| self.is_word_char(c)) {
+            p.column -= 1;
+        }
+        while p.column > 0 && self.char_at(&Position::from(p.row, p.column - 1)).is_some_and(|c| self.is_word_char(c)) {
+            p.column -= 1;
+        }
+        p
+    }
+
+    /// Returns the end of the word after `position`. Line breaks count as a
+    /// single step of their own.
+    fn next_word_position(&self, position: &Position) -> Position {
+        let length = self.lines()[position.row].length;
+        if position.column == length {
+            return self.next_position(position);
+        }
+
+        let mut p = *position;
+        while p.column < length && !self.char_at(&p).is_some_and(|c| self.is_word_char(c)) {
+            p.column += 1;
+        }
+        while p.column < length && self.char_at(&p).is_some_and(|c| self.is_word_char(c)) {
+            p.column += 1;
+        }
+        p
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(document: &mut Document, keymap: &Keymap, key: Key, modifiers: Modifiers) {
+        assert_eq!(document.handle_key(&KeyEvent::from(key, modifiers), keymap), Ok(true));
+    }
+
+    #[test]
+    fn shift_extends_movements() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("foo bar\nbaz");
+        let shift_ctrl = Modifiers { shift: true, ctrl: true, ..Modifiers::none() };
+
+        press(&mut document, &keymap, Key::Right, shift_ctrl);
+        assert_eq!(document.selection(), Range::from(0, 0, 0, 3));
+        press(&mut document, &keymap, Key::Down, Modifiers::shift());
+        assert_eq!(document.selection(), Range::from(0, 0, 1, 3));
+    }
+
+    #[test]
+    fn plain_movements_collapse_the_selection() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("foo bar");
+
+        document.set_selection(&Range::from(0, 1, 0, 5)).unwrap();
+        press(&mut document, &keymap, Key::Right, Modifiers::none());
+        assert_eq!(document.selection(), Range::from(0, 5, 0, 5));
+
+        document.set_selection(&Range::from(0, 1, 0, 5)).unwrap();
+        press(&mut document, &keymap, Key::Left, Modifiers::none());
+        assert_eq!(document.selection(), Range::from(0, 1, 0, 1));
+    }
+
+    #[test]
+    fn vertical_movements_clamp_to_the_line() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("foo bar\nbaz\nquux");
+        document.set_cursor_and_mark(&Position::from(0, 6)).unwrap();
+
+        press(&mut document, &keymap, Key::Down, Modifiers::none());
+        assert_eq!(document.cursor().position, Position::from(1, 3));
+        press(&mut document, &keymap, Key::Down, Modifiers::none());
+        assert_eq!(document.cursor().position, Position::from(2, 3));
+
+        // Past the first or last line, up and down go to the document's ends.
+        press(&mut document, &keymap, Key::Down, Modifiers::none());
+        assert_eq!(document.cursor().position, Position::from(2, 4));
+        document.set_cursor_and_mark(&Position::from(0, 2)).unwrap();
+        press(&mut document, &keymap, Key::Up, Modifiers::none());
+        assert_eq!(document.cursor().position, Position::from(0, 0));
+    }
+
+    #[test]
+    fn horizontal_movements_wrap_between_lines() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("ab\ncd");
+        document.set_cursor_and_mark(&Position::from(0, 2)).unwrap();
+
+        press(&mut document, &keymap, Key::Right, Modifiers::none());
+        assert_eq!(document.cursor().position, Position::from(1, 0));
+        press(&mut document, &keymap, Key::Left, Modifiers::none());
+        assert_eq!(document.cursor().position, Position::from(0, 2));
+
+        press(&mut document, &keymap, Key::Home, Modifiers::ctrl());
+        press(&mut document, &keymap, Key::Left, Modifiers::none());
+        assert_eq!(document.cursor().position, Position::from(0, 0));
+        press(&mut document, &keymap, Key::End, Modifiers::ctrl());
+        press(&mut document, &keymap, Key::Right, Modifiers::none());
+        assert_eq!(document.cursor().position, Position::from(1, 2));
+    }
+
+    #[test]
+    fn word_movements_stop_at_line_breaks() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("foo  bar\n  baz");
+        document.set_cursor_and_mark(&Position::from(0, 3)).unwrap();
+
+        press(&mut document, &keymap, Key::Right, Modifiers::ctrl());
+        assert_eq!(document.cursor().position, Position::from(0, 8));
+        press(&mut document, &keymap, Key::Right, Modifiers::ctrl());
+        assert_eq!(document.cursor().position, Position::from(1, 0));
+        press(&mut document, &keymap, Key::Left, Modifiers::ctrl());
+        assert_eq!(document.cursor().position, Position::from(0, 8));
+        press(&mut document, &keymap, Key::Left, Modifiers::ctrl());
+        assert_eq!(document.cursor().position, Position::from(0, 5));
+    }
+
+    #[test]
+    fn word_movements_count_characters() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("héllo wörld");
+        document.set_cursor_and_mark(&Position::from(0, 0)).unwrap();
+
+        press(&mut document, &keymap, Key::Right, Modifiers::ctrl());
+        assert_eq!(document.cursor().position, Position::from(0, 5));
+        press(&mut document, &keymap, Key::End, Modifiers::none());
+        press(&mut document, &keymap, Key::Backspace, Modifiers::ctrl());
+        assert_eq!(document.text(), "héllo ");
+        press(&mut document, &keymap, Key::Backspace, Modifiers::none());
+        press(&mut document, &keymap, Key::Backspace, Modifiers::none());
+        assert_eq!(document.text(), "héll");
+    }
+
+    #[test]
+    fn backspace_joins_lines() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("foo bar\nbaz");
+        document.set_cursor_and_mark(&Position::from(1, 2)).unwrap();
+
+        press(&mut document, &keymap, Key::Home, Modifiers::none());
+        press(&mut document, &keymap, Key::Backspace, Modifiers::none());
+        assert_eq!(document.text(), "foo barbaz");
+        press(&mut document, &keymap, Key::Backspace, Modifiers::ctrl());
+        assert_eq!(document.text(), "foo baz");
+    }
+
+    #[test]
+    fn deletes_remove_the_selection_first() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("foo bar baz");
+
+        document.set_selection(&Range::from(0, 2, 0, 9)).unwrap();
+        press(&mut document, &keymap, Key::Delete, Modifiers::ctrl());
+        assert_eq!(document.text(), "foaz");
+    }
+
+    #[test]
+    fn deletes_at_the_ends_of_the_document_do_nothing() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("ab\ncd");
+
+        press(&mut document, &keymap, Key::Backspace, Modifiers::none());
+        press(&mut document, &keymap, Key::Backspace, Modifiers::ctrl());
+        press(&mut document, &keymap, Key::End, Modifiers::ctrl());
+        press(&mut document, &keymap, Key::Delete, Modifiers::none());
+        press(&mut document, &keymap, Key::Delete, Modifiers::ctrl());
+        assert_eq!(document.text(), "ab\ncd");
+        assert_eq!(document.cursor().position, Position::from(1, 2));
+    }
+
+    #[test]
+    fn each_edit_undoes_on_its_own() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("foo");
+
+        press(&mut document, &keymap, Key::End, Modifiers::none());
+        press(&mut document, &keymap, Key::Enter, Modifiers::none());
+        press(&mut document, &keymap, Key::Tab, Modifiers::none());
+        press(&mut document, &keymap, Key::Char('x'), Modifiers::none());
+        assert_eq!(document.text(), "foo\n    x");
+
+        press(&mut document, &keymap, Key::Char('z'), Modifiers::ctrl());
+        assert_eq!(document.text(), "foo\n    ");
+        press(&mut document, &keymap, Key::Char('z'), Modifiers::ctrl());
+        assert_eq!(document.text(), "foo\n");
+        press(&mut document, &keymap, Key::Char('y'), Modifiers::ctrl());
+        assert_eq!(document.text(), "foo\n    ");
+    }
+
+    #[test]
+    fn undo_and_redo_report_empty_stacks() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("foo");
+
+        let undo = KeyEvent::from(Key::Char('z'), Modifiers::ctrl());
+        let redo = KeyEvent::from(Key::Char('y'), Modifiers::ctrl());
+        assert!(document.handle_key(&undo, &keymap).is_err());
+        assert!(document.handle_key(&redo, &keymap).is_err());
+    }
+
+    #[test]
+    fn typing_replaces_everything_selected() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("foo bar\nbaz");
+
+        press(&mut document, &keymap, Key::Char('a'), Modifiers::ctrl());
+        assert_eq!(document.selection(), Range::from(0, 0, 1, 3));
+        press(&mut document, &keymap, Key::Char('X'), Modifiers::shift());
+        assert_eq!(document.text(), "X");
+    }
+
+    #[test]
+    fn empty_documents() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("");
+
+        for key in [Key::Left, Key::Right, Key::Up, Key::Down, Key::Home, Key::End, Key::Backspace, Key::Delete] {
+            press(&mut document, &keymap, key, Modifiers::none());
+            assert_eq!(document.selection(), Range::from(0, 0, 0, 0));
+        }
+        for key in [Key::Left, Key::Right, Key::Home, Key::End, Key::Backspace, Key::Delete] {
+            press(&mut document, &keymap, key, Modifiers::ctrl());
+            assert_eq!(document.selection(), Range::from(0, 0, 0, 0));
+        }
+        press(&mut document, &keymap, Key::Char('a'), Modifiers::ctrl());
+        assert_eq!(document.selection(), Range::from(0, 0, 0, 0));
+        assert_eq!(document.text(), "");
+    }
+
+    #[test]
+    fn unbound_keys_are_not_consumed() {
+        let keymap = Keymap::standard();
+        let mut document = Document::from("foo");
+
+        let unbound = KeyEvent::from(Key::Char('q'), Modifiers::ctrl());
+        assert_eq!(document.handle_key(&unbound, &keymap), Ok(false));
+        let unbound = KeyEvent::from(Key::Char('q'), Modifiers::alt());
+        assert_eq!(document.handle_key(&unbound, &keymap), Ok(false));
+        assert_eq!(document.handle_key(&KeyEvent::plain(Key::Left), &Keymap::new()), Ok(false));
+        assert_eq!(document.text(), "foo");
+    }
+
+    #[test]
+    fn shift_falls_back_only_to_movements() {
+        let mut keymap = Keymap::standard();
+        assert_eq!(keymap.lookup(&KeyEvent::from(Key::Home, Modifiers::shift())), Some(Action::MoveLineStart));
+        assert_eq!(keymap.lookup(&KeyEvent::from(Key::Backspace, Modifiers::shift())), None);
+        assert_eq!(keymap.lookup(&KeyEvent::from(Key::Char('z'), Modifiers { shift: true, ctrl: true, ..Modifiers::none() })), None);
+
+        keymap.bind(KeyEvent::from(Key::Home, Modifiers::shift()), Action::SelectAll);
+        assert_eq!(keymap.lookup(&KeyEvent::from(Key::Home, Modifiers::shift())), Some(Action::SelectAll));
+        assert_eq!(keymap.unbind(&KeyEvent::from(Key::Home, Modifiers::shift())), Some(Action::SelectAll));
+        assert_eq!(keymap.unbind(&KeyEvent::from(Key::Home, Modifiers::shift())), None);
+    }
+}
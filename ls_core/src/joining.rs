@@ -0,0 +1,147 @@
+//! Joining lines, as a single command.
+//!
+//! [`Document::join_lines`] glues lines onto the one above them the way an
+//! editor's "join" does: the line break, the trailing whitespace before it,
+//! and the indentation after it collapse into the separator
+//! [`join_separator`] picks, which is a single space or nothing. Joining a
+//! line comment onto another drops the continuation's comment token, and
+//! joining after a `\` line continuation drops the backslash.
+
+use crate::document::{Document, Position, Range, InsertOptions, RemoveOptions};
+use crate::operators::line_comment;
+use crate::util::Oops;
+
+/// Returns whether `c` is collapsed by a join.
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+/// Returns whether lines in `language` continue with a trailing `\`.
+fn continues_with_backslash(language: &str) -> bool {
+    matches!(language, "c" | "h" | "cpp" | "hpp" | "py" | "sh" | "bash")
+}
+
+/// Returns what goes between `before`, the end of a line with its trailing
+/// whitespace removed, and `after`, the start of the next with its
+/// indentation removed, when they are joined in `language`.
+///
+/// Nothing separates them if either is empty, if `before` ends with an
+/// opening bracket, or if `after` starts with a closing bracket or `,` or
+/// `;`. In languages with line comments (that is, programming languages), a
+/// continuation starting with `.` or `?.`, as in a method chain, is glued on
+/// too. Otherwise they are separated by a single space.
+///
+/// # Examples
+/// ```
+/// use ls_core::joining::join_separator;
+/// assert_eq!(join_separator("rs", "let x =", "1;"), " ");
+/// assert_eq!(join_separator("rs", "f(", "x"), "");
+/// assert_eq!(join_separator("rs", "items", ".iter()"), "");
+/// assert_eq!(join_separator("", "the end", ".txt"), " ");
+/// ```
+pub fn join_separator(language: &str, before: &str, after: &str) -> &'static str {
+    let opens = before.ends_with(['(', '[', '{']);
+    let closes = after.starts_with([')', ']', '}', ',', ';']);
+    let chains = line_comment(language).is_some() && (after.starts_with('.') || after.starts_with("?."));
+
+    if before.is_empty() || after.is_empty() || opens || closes || chains {
+        ""
+    } else {
+        " "
+    }
+}
+
+impl Document {
+    /// Joins the `count` lines after `row` onto it as one undoable packet,
+    /// collapsing the whitespace around each line break into the separator
+    /// [`join_separator`] picks (see the [module documentation](self)).
+    /// Returns the position of the last join, or the end of `row` if `count`
+    /// is zero, or `Err` if there aren't `count` lines after `row`.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language("let x = f(  \n    a,\n    b\n);  // one\n// two", "rs");
+    ///
+    /// assert_eq!(document.join_lines(0, 3), Ok(Position::from(0, 14)));
+    /// assert_eq!(document.text(), "let x = f(a, b);  // one\n// two");
+    ///
+    /// document.join_lines(0, 1).unwrap();
+    /// assert_eq!(document.text(), "let x = f(a, b);  // one two");
+    ///
+    /// document.undo_once().unwrap();
+    /// assert_eq!(document.text(), "let x = f(a, b);  // one\n// two");
+    /// assert!(document.join_lines(1, 1).is_err());
+    /// ```
+    pub fn join_lines(&mut self, row: usize, count: usize) -> Result<Position, Oops> {
+        if row + count >= self.rows() {
+            return Err(Oops::InvalidIndex(row + count, "join_lines"));
+        }
+
+        self.with_packet("join lines", |document| {
+            let mut joined = Position::from(row, document.lines()[row].length);
+            for _ in 0..count {
+                let (range, separator) = document.join_at(row);
+                if separator.is_empty() {
+                    document.remove(&RemoveOptions::exact_at(&range))?;
+                } else {
+                    document.insert(separator, &InsertOptions::exact_at(&range))?;
+                }
+                joined = range.beginning;
+            }
+            Ok(joined)
+        })
+    }
+
+    /// Returns the range a join of `row` and the line after it replaces, and
+    /// what replaces it.
+    fn join_at(&self, row: usize) -> (Range, &'static str) {
+        let language = self.language();
+        let line = &self.lines()[row].content;
+        let next = &self.lines()[row + 1].content;
+
+        let mut before = line.trim_end_matches(is_blank);
+        if continues_with_backslash(language) && before.ends_with('\\') {
+            before = before[..before.len() - 1].trim_end_matches(is_blank);
+        }
+
+        let mut after = next.trim_start_matches(is_blank);
+        if let Some(token) = line_comment(language) {
+            if line.contains(token) && after.starts_with(token) && !before.ends_with(token) {
+                after = after[token.len()..].trim_start_matches(is_blank);
+            }
+        }
+
+        let kept = before.chars().count();
+        let skipped = next.chars().count() - after.chars().count();
+        let range = Range::from(row, kept, row + 1, skipped);
+        (range, join_separator(language, before, after))
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_collapse_whitespace_and_continuations() {
+        let mut document = Document::from_with_language("x = 1 + \\\n\t2\n\n   \ny", "py");
+        document.set_cursor_and_mark(&Position::from(4, 1)).unwrap();
+        let depth = document.undo_redo().depth().0;
+
+        assert_eq!(document.join_lines(0, 4), Ok(Position::from(0, 9)));
+        assert_eq!(document.text(), "x = 1 + 2 y");
+        assert_eq!(document.cursor().position, Position::from(0, 11));
+        assert_eq!(document.undo_redo().depth().0, depth + 1);
+
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), "x = 1 + \\\n\t2\n\n   \ny");
+        assert_eq!(document.join_lines(4, 0), Ok(Position::from(4, 1)));
+
+        let mut prose = Document::from("The end\n  .txt\n# not a comment");
+        prose.join_lines(0, 2).unwrap();
+        assert_eq!(prose.text(), "The end .txt # not a comment");
+    }
+}
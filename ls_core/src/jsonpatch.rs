@@ -0,0 +1,211 @@
+//! A JSON representation of edits, for thin clients (web views, plugins)
+//! which apply or submit edits without binding to Rust types.
+//!
+//! Only available with the `serialization` feature.
+//!
+//! A patch looks like this:
+//!
+//! ```json
+//! {
+//!   "revision": 12,
+//!   "label": "insert 'x'",
+//!   "edits": [
+//!     { "range": { "beginning": { "row": 0, "column": 3 },
+//!                  "ending": { "row": 0, "column": 3 } },
+//!       "newText": "x" }
+//!   ]
+//! }
+//! ```
+//!
+//! Edits apply in order, so each edit's range refers to the document as
+//! left by the edits before it.
+
+use serde::{Serialize, Deserialize};
+
+use crate::changeset::ChangeSet;
+use crate::document::{Document, Range, Change, ChangePacket};
+use crate::util::Oops;
+
+/// Replaces `range` with `new_text`.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct JsonEdit {
+    pub range: Range,
+    #[serde(rename = "newText")]
+    pub new_text: String
+}
+
+/// A list of [`JsonEdit`]s tagged with a revision.
+///
+/// Patches produced by [`JsonPatch::from_packet`] carry the revision the
+/// packet produced. Patches submitted to [`Document::apply_json_patch`] carry
+/// the revision the client last saw, i.e. the revision its edits are based on.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct JsonPatch {
+    pub revision: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub edits: Vec<JsonEdit>
+}
+
+impl JsonPatch {
+    /// Returns the text edits in `packet`, in the order
+    /// [`Document::apply_packet`] would apply them. Anchor, indentation, and
+    /// language changes are not edits and are left out.
+    ///
+    /// Packets on the undo and redo stacks are applied in reverse; see
+    /// [`Document::last_undo_patch`].
+    pub fn from_packet(packet: &ChangePacket) -> JsonPatch {
+        let edits = packet.changes().iter().filter_map(|change| match change {
+            Change::Insert { text, position } => Some(JsonEdit {
+                range: Range { beginning: *position, ending: *position },
+                new_text: text.join("\n")
+            }),
+            Change::Remove { range } => Some(JsonEdit {
+                range: *range,
+                new_text: String::new()
+            }),
+            _ => None
+        }).collect();
+
+        JsonPatch {
+            revision: packet.revision(),
+            label: packet.label().map(String::from),
+            edits
+        }
+    }
+
+    /// Returns the edits in `changes`, tagged with the revision they
+    /// produced.
+    pub fn from_change_set(changes: &ChangeSet) -> JsonPatch {
+        JsonPatch {
+            revision: changes.after,
+            label: None,
+            edits: changes.edits.iter().map(|edit| JsonEdit {
+                range: edit.range,
+                new_text: edit.inserted.clone()
+            }).collect()
+        }
+    }
+
+    /// Returns the changes which perform this patch's edits, labeled with
+    /// this patch's label.
+    pub fn to_packet(&self) -> ChangePacket {
+        let mut packet = ChangePacket::new();
+
+        for edit in self.edits.iter() {
+            if !edit.range.empty() {
+                packet.push(Change::Remove { range: edit.range });
+            }
+            if !edit.new_text.is_empty() {
+                packet.push(Change::Insert {
+                    text: edit.new_text.split('\n').map(String::from).collect(),
+                    position: edit.range.beginning
+                });
+            }
+        }
+
+        match self.label.as_ref() {
+            Some(label) => packet.labeled(label),
+            None => packet
+        }
+    }
+
+    /// Returns this patch as a JSON string.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    /// Parses a patch from JSON. Returns `Err` if `json` is not a patch.
+    pub fn from_json(json: &str) -> Result<JsonPatch, Oops> {
+        serde_json::from_str(json).map_err(|_| Oops::InvalidEncoding("JsonPatch::from_json"))
+    }
+}
+
+impl Document {
+    /// Applies the JSON patch `json` as a single undoable packet. Returns
+    /// `Err` without changing anything if the patch is malformed, any edit
+    /// is invalid, or the patch is based on a revision other than the
+    /// current one.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::jsonpatch::*;
+    /// let mut document = Document::from("hello");
+    /// let json = r#"{"revision": 0, "edits": [
+    ///     {"range": {"beginning": {"row": 0, "column": 0},
+    ///                "ending": {"row": 0, "column": 1}}, "newText": "J"}
+    /// ]}"#;
+    /// document.apply_json_patch(json).unwrap();
+    /// assert_eq!(document.text(), "Jello");
+    ///
+    /// // The document has moved on, so the same patch is now stale.
+    /// assert!(document.apply_json_patch(json).is_err());
+    /// ```
+    pub fn apply_json_patch(&mut self, json: &str) -> Result<(), Oops> {
+        let patch = JsonPatch::from_json(json)?;
+        if patch.revision != self.revision() {
+            return Err(Oops::Ouch("JSON patch is based on a stale revision"));
+        }
+        self.apply_packet(&patch.to_packet())
+    }
+
+    /// Returns the most recent undoable packet's edits as a JSON patch.
+    /// Since undo packets hold inverse changes, these are the edits a
+    /// client would apply to revert it. Returns `None` if there is nothing
+    /// to undo.
+    pub fn last_undo_patch(&self) -> Option<JsonPatch> {
+        self.undo_redo().undo_packets().next().map(|packet| {
+            let reversed = ChangePacket::from(packet.changes().iter().rev().cloned().collect());
+            JsonPatch {
+                revision: packet.revision(),
+                label: packet.label().map(String::from),
+                ..JsonPatch::from_packet(&reversed)
+            }
+        })
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::InsertOptions;
+
+    #[test]
+    fn round_trip() {
+        let mut document = Document::from("one\ntwo");
+        document.checkpoint_labeled("typing");
+        document.insert("1\n2", &InsertOptions::exact_at(&Range::from(0, 0, 1, 3))).unwrap();
+        assert_eq!(document.text(), "1\n2");
+
+        let patch = document.last_undo_patch().unwrap();
+        assert_eq!(patch.label.as_deref(), Some("typing"));
+        assert_eq!(patch.revision, document.revision());
+        assert_eq!(patch.edits[0].new_text, "");
+        assert_eq!(patch.edits[0].range, Range::from(0, 0, 1, 1));
+        assert_eq!(patch.edits[1].new_text, "one\ntwo");
+
+        let json = patch.to_json();
+        assert!(json.contains("\"newText\""));
+        assert_eq!(JsonPatch::from_json(&json).unwrap(), patch);
+
+        let forward = JsonPatch::from_change_set(&document.changes_since(0).unwrap());
+        assert_eq!(forward.revision, document.revision());
+        assert_eq!(forward.edits[0], JsonEdit { range: Range::from(0, 0, 1, 3), new_text: String::new() });
+
+        let revert = JsonPatch { revision: document.revision(), ..patch };
+        document.apply_json_patch(&revert.to_json()).unwrap();
+        assert_eq!(document.text(), "one\ntwo");
+
+        assert!(document.apply_json_patch("{\"edits\": 3}").is_err());
+        let bad = JsonPatch {
+            revision: document.revision(),
+            label: None,
+            edits: vec![JsonEdit { range: Range::from(9, 0, 9, 0), new_text: String::from("x") }]
+        };
+        assert!(document.apply_json_patch(&bad.to_json()).is_err());
+        assert_eq!(document.text(), "one\ntwo");
+    }
+}
@@ -1,24 +1,33 @@
 //! Support for intelligent parsing / understanding of source code
+//!
+//! Parsing is provided by tree sitter and is only compiled in with the
+//! `parsing` feature (on by default). Without it, no language is supported
+//! and documents never have a parse tree.
+//!
+//! Native builds have their grammars compiled in. Under wasm, parsing goes
+//! through web-tree-sitter (see [`syntax`](crate::syntax)), and languages
+//! are whichever grammars the host has registered with
+//! [`register_language`].
 
 
 extern crate test;
 
-use tree_sitter;
-use tree_sitter_rust;
-use tree_sitter_cpp;
-use tree_sitter_java;
-use tree_sitter_javascript;
-use tree_sitter_python;
-use tree_sitter_typescript;
-use tree_sitter_bash;
+#[cfg(all(feature = "parsing", not(target_arch = "wasm32")))]
 use lazy_static::lazy_static;
+#[cfg(all(feature = "parsing", target_arch = "wasm32"))]
+use std::cell::RefCell;
+#[cfg(all(feature = "parsing", target_arch = "wasm32"))]
+use wasm_bindgen::prelude::*;
 
 use crate::document;
+use crate::syntax;
 
-extern "C" { fn tree_sitter_test() -> tree_sitter::Language; }
+#[cfg(all(feature = "parsing", not(target_arch = "wasm32")))]
+extern "C" { fn tree_sitter_test() -> syntax::Language; }
 
+#[cfg(all(feature = "parsing", not(target_arch = "wasm32")))]
 lazy_static! {
-    static ref LANGUAGES: Vec<(&'static str, tree_sitter::Language)> = vec![
+    static ref LANGUAGES: Vec<(&'static str, syntax::Language)> = vec![
         ("rs", tree_sitter_rust::language()),
         ("cpp", tree_sitter_cpp::language()),
         ("java", tree_sitter_java::language()),
@@ -31,19 +40,227 @@ lazy_static! {
     ];
 }
 
-pub fn get_parser(lang_str: &str) -> Option<tree_sitter::Parser> {
-    for (name, lang) in LANGUAGES.iter() {
-        if name == &lang_str {
-            let mut parser = tree_sitter::Parser::new();
-            parser.set_language(*lang).ok()?;
-            return Some(parser);
+#[cfg(all(feature = "parsing", target_arch = "wasm32"))]
+thread_local! {
+    static LANGUAGES: RefCell<Vec<(&'static str, syntax::Language)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Registers `language`, a grammar the host has loaded with
+/// `TreeSitter.Language.load`, for documents with language `name`,
+/// replacing any grammar registered for it before. Only under wasm, where
+/// grammars are not compiled in.
+#[cfg(all(feature = "parsing", target_arch = "wasm32"))]
+#[wasm_bindgen]
+pub fn register_language(name: &str, language: JsValue) {
+    let language = syntax::Language::from_js(language);
+    LANGUAGES.with(|languages| {
+        let mut languages = languages.borrow_mut();
+        match languages.iter_mut().find(|(known, _)| *known == name) {
+            Some((_, known)) => *known = language,
+            None => languages.push((Box::leak(String::from(name).into_boxed_str()), language))
+        }
+    });
+}
+
+/// Returns the names of the languages which can be parsed, in order of
+/// registration.
+#[cfg(feature = "parsing")]
+fn language_names() -> Vec<&'static str> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return LANGUAGES.iter().map(|(name, _)| *name).collect();
+
+    #[cfg(target_arch = "wasm32")]
+    return LANGUAGES.with(|languages| languages.borrow().iter().map(|(name, _)| *name).collect());
+}
+
+/// Returns the grammar for language `lang_str`, if there is one.
+#[cfg(feature = "parsing")]
+fn find_language(lang_str: &str) -> Option<syntax::Language> {
+    #[cfg(not(target_arch = "wasm32"))]
+    return LANGUAGES.iter().find(|(name, _)| *name == lang_str).map(|(_, lang)| *lang);
+
+    #[cfg(target_arch = "wasm32")]
+    return LANGUAGES.with(|languages| {
+        languages.borrow().iter().find(|(name, _)| *name == lang_str).map(|(_, lang)| lang.clone())
+    });
+}
+
+/// Returns true if documents with language `lang_str` can be parsed.
+pub fn is_supported(lang_str: &str) -> bool {
+    #[cfg(feature = "parsing")]
+    return find_language(lang_str).is_some();
+
+    #[cfg(not(feature = "parsing"))]
+    {
+        let _ = lang_str;
+        false
+    }
+}
+
+/// Returns a parser for language `lang_str`, or `None` if it is not
+/// supported. Always returns `None` when ls_core is built without the
+/// `parsing` feature.
+pub fn get_parser(lang_str: &str) -> Option<syntax::Parser> {
+    #[cfg(feature = "parsing")]
+    {
+        let mut parser = syntax::Parser::new();
+        parser.set_language(find_language(lang_str)?).ok()?;
+        Some(parser)
+    }
+
+    #[cfg(not(feature = "parsing"))]
+    {
+        let _ = lang_str;
+        None
+    }
+}
+
+/// How much of a document [`guess`] parses, in bytes.
+pub const GUESS_SAMPLE_LENGTH: usize = 4096;
+
+/// Scores above this are too broken for [`guess`] to report.
+pub const GUESS_THRESHOLD: f64 = 0.25;
+
+/// Returns the fraction of the nodes in the parse of `text` with language
+/// `lang_str` which are errors or missing, or `None` if the language is not
+/// supported or `text` produces no nodes. Lower scores mean a better fit.
+pub fn score(lang_str: &str, text: &str) -> Option<f64> {
+    #[cfg(feature = "parsing")]
+    {
+        let tree = get_parser(lang_str)?.parse(text, None)?;
+        let mut cursor = tree.walk();
+        let mut nodes = 0usize;
+        let mut errors = 0usize;
+
+        'walk: loop {
+            let node = cursor.node();
+            nodes += 1;
+            if node.is_error() || node.is_missing() {
+                errors += 1;
+            }
+
+            if !node.is_error() && cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    break 'walk;
+                }
+            }
+        }
+
+        if nodes <= 1 {
+            None
+        } else {
+            Some(errors as f64 / nodes as f64)
         }
     }
 
-    None
+    #[cfg(not(feature = "parsing"))]
+    {
+        let _ = (lang_str, text);
+        None
+    }
 }
 
-fn pp_rec(node: &tree_sitter::Node, out: String, depth: i32, doc: &document::Document) -> String {
+/// Guesses the language of `text`, for files whose name doesn't say, by
+/// parsing a sample of it with every supported grammar and picking the one
+/// with the lowest [`score`]. Ties go to the grammar registered first.
+/// Returns `None` if nothing parses well enough.
+///
+/// # Examples
+/// ```
+/// # #[cfg(feature = "parsing")] {
+/// use ls_core::language;
+/// assert_eq!(language::guess("def f(x):\n    return [y for y in x if y]\n"), Some("py"));
+/// assert_eq!(language::guess("fn main() {\n    let v: Vec<u8> = vec![];\n}\n"), Some("rs"));
+/// assert_eq!(language::guess(""), None);
+/// # }
+/// ```
+pub fn guess(text: &str) -> Option<&'static str> {
+    #[cfg(feature = "parsing")]
+    {
+        let mut end = text.len().min(GUESS_SAMPLE_LENGTH);
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        let sample = match text[..end].rfind('\n') {
+            Some(i) if end < text.len() => &text[..i],
+            _ => &text[..end]
+        };
+
+        let mut best: Option<(&'static str, f64)> = None;
+        for name in language_names().into_iter().filter(|name| *name != "test") {
+            if let Some(s) = score(name, sample) {
+                if s <= GUESS_THRESHOLD && best.is_none_or(|(_, b)| s < b) {
+                    best = Some((name, s));
+                }
+            }
+        }
+
+        best.map(|(name, _)| name)
+    }
+
+    #[cfg(not(feature = "parsing"))]
+    {
+        let _ = text;
+        None
+    }
+}
+
+/// Limits on how much of a tree [`pretty_print_limited`] prints.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct PrettyPrintLimits {
+    /// Nodes deeper than this are left out, and their parent notes how
+    /// many children it has. The root is at depth 0.
+    pub max_depth: Option<usize>,
+
+    /// Node text longer than this many characters is cut short with `…`.
+    pub max_text: Option<usize>
+}
+
+impl PrettyPrintLimits {
+    /// Returns limits which print everything.
+    pub fn unlimited() -> PrettyPrintLimits {
+        PrettyPrintLimits { max_depth: None, max_text: None }
+    }
+}
+
+impl Default for PrettyPrintLimits {
+    /// Every node, with text cut short after 60 characters.
+    fn default() -> PrettyPrintLimits {
+        PrettyPrintLimits { max_depth: None, max_text: Some(60) }
+    }
+}
+
+/// Returns the text of `range` in `doc`, line breaks written as `\n`, cut
+/// short after `max_text` characters. Columns which are not on character
+/// boundaries, or rows past the end, give what text there is rather than
+/// panicking.
+#[cfg(feature = "parsing")]
+fn node_text(range: &syntax::Range, doc: &document::Document, max_text: Option<usize>) -> String {
+    let (start, end) = (range.start_point, range.end_point);
+    let mut pieces: Vec<&str> = vec![];
+
+    for row in start.row..=end.row {
+        let line = match doc.line(row) {
+            Some(line) => line.as_str(),
+            None => break
+        };
+        let from = if row == start.row { start.column } else { 0 };
+        let to = if row == end.row { end.column.min(line.len()) } else { line.len() };
+        pieces.push(line.get(from..to).unwrap_or(""));
+    }
+
+    let text = pieces.join("\\n");
+    match max_text {
+        Some(limit) if text.chars().count() > limit => text.chars().take(limit).collect::<String>() + "\u{2026}",
+        _ => text
+    }
+}
+
+#[cfg(feature = "parsing")]
+fn pp_rec(node: &syntax::Node, out: String, depth: usize, doc: &document::Document, limits: &PrettyPrintLimits) -> String {
     let mut result = out;
 
     for _ in 0..depth {
@@ -57,23 +274,51 @@ fn pp_rec(node: &tree_sitter::Node, out: String, depth: i32, doc: &document::Doc
         range.start_point.row, range.start_point.column,
         range.end_point.row, range.end_point.column
     );
+    result += &format!(" \"{}\"", node_text(&range, doc, limits.max_text));
 
-    if range.end_point.row == range.start_point.row {
-        let line = doc.line(range.start_point.row).unwrap();
-        result += &format!(" \"{}\"", 
-            line[range.start_point.column..range.end_point.column].to_string());
+    if limits.max_depth.is_some_and(|max| depth >= max) && node.child_count() > 0 {
+        result += &format!(" ({} children)\n", node.child_count());
+        return result;
     }
 
     result += "\n";
     for i in 0..node.child_count() {
-        result = pp_rec(&node.child(i).unwrap(), result, depth + 1, doc);
+        result = pp_rec(&node.child(i).unwrap(), result, depth + 1, doc, limits);
     }
 
     result
 }
 
-pub fn pretty_print(node: &tree_sitter::Node, doc: &document::Document) -> String {
-    pp_rec(node, String::new(), 0i32, doc)
+/// Returns `node` and its descendants, one per line, indented by depth,
+/// with their kinds, ranges (in bytes), and text, within the
+/// [default limits](PrettyPrintLimits::default).
+pub fn pretty_print(node: &syntax::Node, doc: &document::Document) -> String {
+    pretty_print_limited(node, doc, &PrettyPrintLimits::default())
+}
+
+/// Like [`pretty_print`], within `limits`.
+///
+/// # Examples
+/// ```
+/// # #[cfg(feature = "parsing")] {
+/// use ls_core::document::*;
+/// use ls_core::language::*;
+/// let document = Document::from_with_language("fn f() {\n    \"é\"\n}", "rs");
+/// let limits = PrettyPrintLimits { max_depth: Some(1), max_text: Some(12) };
+/// assert_eq!(document.parse_tree_pretty_print_limited(&limits).unwrap(),
+///     "source_file (0.0 - 2.1) \"fn f() {\\n  \u{2026}\"\n   function_item (0.0 - 2.1) \"fn f() {\\n  \u{2026}\" (4 children)\n");
+/// # }
+/// ```
+pub fn pretty_print_limited(node: &syntax::Node, doc: &document::Document, limits: &PrettyPrintLimits) -> String {
+    #[cfg(feature = "parsing")]
+    return pp_rec(node, String::new(), 0, doc, limits);
+
+    // There are no nodes to print without the parsing feature.
+    #[cfg(not(feature = "parsing"))]
+    {
+        let _ = (doc, limits);
+        match *node {}
+    }
 }
 
 
@@ -86,7 +331,7 @@ pub fn pretty_print(node: &tree_sitter::Node, doc: &document::Document) -> Strin
 
 
 
-#[cfg(test)]
+#[cfg(all(test, feature = "parsing"))]
 mod tests {
     use super::*;
     use test::Bencher;
@@ -114,6 +359,31 @@ string_content (3, 13)-(3, 18)
 "#);
     }
 
+    #[test]
+    fn guess_by_trial_parse() {
+        let script = "#!/bin/sh\nfor f in *.txt; do\n  echo \"$f\" | wc -c\ndone\n";
+        assert_eq!(guess(script), Some("sh"));
+        assert_eq!(guess("class A {\n  public static void main(String[] args) {}\n}\n"), Some("java"));
+        assert_eq!(guess("const f = (x) => x * 2;\n"), Some("js"));
+
+        assert!(score("py", "def f(:\n").unwrap() > score("py", "def f(): pass\n").unwrap());
+        assert_eq!(score("cobol", "x"), None);
+    }
+
+    #[test]
+    fn pretty_print_tolerates_odd_ranges() {
+        let doc = document::Document::from("ᚡ = 1\nx");
+        let range = |start: (usize, usize), end: (usize, usize)| syntax::Range {
+            start_byte: 0,
+            end_byte: 0,
+            start_point: syntax::Point::new(start.0, start.1),
+            end_point: syntax::Point::new(end.0, end.1)
+        };
+        assert_eq!(node_text(&range((0, 1), (0, 5)), &doc, None), "");
+        assert_eq!(node_text(&range((0, 3), (5, 0)), &doc, None), " = 1\\nx");
+        assert_eq!(node_text(&range((0, 0), (1, 1)), &doc, Some(3)), "ᚡ =\u{2026}");
+    }
+
     #[bench]
     fn bench_doc_create(b: &mut Bencher) {
         b.iter(|| {
@@ -0,0 +1,165 @@
+//! Fast answers to "is this position in a string or a comment?".
+//!
+//! Auto-pairs and dictation ask on every keystroke or utterance, and
+//! building a [`Chain`](crate::document::Chain) with
+//! [`Document::get_context_at`] for each question allocates a region per
+//! level of the tree. [`Document::is_in_string`] and
+//! [`Document::is_in_comment`] walk the same path down the parse tree
+//! without allocating, and remember their answers until the tree next
+//! changes.
+//!
+//! A position is in a string or comment if it is strictly inside a node
+//! whose kind contains `string` or `comment`. A comment ending at the end
+//! of a line also holds the position just after it, since line comments
+//! run to the end of the line. Without a parse tree, nothing is.
+
+use crate::document::{Document, Position};
+#[cfg(feature = "parsing")]
+use crate::{syntax, util};
+#[cfg(feature = "parsing")]
+use std::collections::HashMap;
+
+/// Whether a position is in a string or a comment.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default)]
+pub struct LexicalContext {
+    pub string: bool,
+    pub comment: bool
+}
+
+/// How many answers are remembered before they are all forgotten.
+#[cfg(feature = "parsing")]
+const LEXICAL_CACHE_LIMIT: usize = 256;
+
+/// Answers remembered since the parse tree last changed.
+#[cfg(feature = "parsing")]
+#[derive(Default)]
+pub(crate) struct LexicalCache {
+    answers: HashMap<Position, LexicalContext>
+}
+
+#[cfg(feature = "parsing")]
+impl LexicalCache {
+    /// Forgets every answer, as when the parse tree changes.
+    pub(crate) fn clear(&mut self) {
+        self.answers.clear();
+    }
+}
+
+impl Document {
+    /// Returns whether `position` is in a string (see the
+    /// [module documentation](self)).
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "parsing")] {
+    /// use ls_core::document::*;
+    /// let document = Document::from_with_language("let s = \"hi\"; // note", "rs");
+    /// assert!(document.is_in_string(&Position::from(0, 10)));
+    /// assert!(!document.is_in_string(&Position::from(0, 8)));
+    /// assert!(document.is_in_comment(&Position::from(0, 21)));
+    /// assert!(!document.is_in_comment(&Position::from(0, 10)));
+    /// # }
+    /// ```
+    pub fn is_in_string(&self, position: &Position) -> bool {
+        self.lexical_context_at(position).string
+    }
+
+    /// Returns whether `position` is in a comment (see the
+    /// [module documentation](self)).
+    pub fn is_in_comment(&self, position: &Position) -> bool {
+        self.lexical_context_at(position).comment
+    }
+
+    /// Returns whether `position` is in a string, a comment, or both.
+    #[cfg(feature = "parsing")]
+    pub fn lexical_context_at(&self, position: &Position) -> LexicalContext {
+        if let Some(answer) = self.lexical_cache.borrow().answers.get(position) {
+            return *answer;
+        }
+
+        let answer = self.find_lexical_context(position);
+        let mut cache = self.lexical_cache.borrow_mut();
+        if cache.answers.len() >= LEXICAL_CACHE_LIMIT {
+            cache.clear();
+        }
+        cache.answers.insert(*position, answer);
+        answer
+    }
+
+    /// Without the `parsing` feature there is no parse tree, so nothing is
+    /// in a string or comment.
+    #[cfg(not(feature = "parsing"))]
+    pub fn lexical_context_at(&self, _position: &Position) -> LexicalContext {
+        LexicalContext::default()
+    }
+
+    /// Walks down the parse tree to `position` as
+    /// [`Document::get_context_at`] does, noting strings and comments.
+    #[cfg(feature = "parsing")]
+    fn find_lexical_context(&self, position: &Position) -> LexicalContext {
+        let mut context = LexicalContext::default();
+        let (tree, line) = match (self.parse_tree(), self.line(position.row)) {
+            (Some(tree), Some(line)) => (tree, line),
+            _ => return context
+        };
+        let column = match util::cp_index_to_byte(line, position.column) {
+            Some(column) => column,
+            None => return context
+        };
+        let point = syntax::Point::new(position.row, column);
+
+        let mut node = tree.root_node();
+        loop {
+            let (start, end) = (node.start_position(), node.end_position());
+            let at_line_end = self.line(end.row).is_some_and(|line| end.column == line.len());
+            let kind = node.kind();
+
+            if start < point && point < end {
+                context.string |= kind.contains("string");
+                context.comment |= kind.contains("comment");
+            } else if start < point && point == end && at_line_end {
+                context.comment |= kind.contains("comment");
+            }
+
+            match (0..node.child_count())
+                .filter_map(|i| node.child(i))
+                .find(|child| child.start_position() <= point && point <= child.end_position())
+            {
+                Some(child) => node = child,
+                None => return context
+            }
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "parsing"))]
+mod tests {
+    use super::*;
+    use crate::document::{InsertOptions, Range};
+
+    #[test]
+    fn answers_match_context_chains_and_follow_edits() {
+        let mut document = Document::from_with_language("fn f() {\n    let s = \"a b\"; // c\n    /* d */ g();\n}", "rs");
+        for row in 0..document.rows() {
+            for column in 0..=document.lines()[row].length {
+                let position = Position::from(row, column);
+                let chain = document.get_context_at(&position).unwrap();
+                let inside = |word: &str| chain.regions.iter().any(|region| {
+                    let at_line_end = region.range.ending.column == document.lines()[region.range.ending.row].length;
+                    region.kind.contains(word) && region.range.beginning < position
+                        && (position < region.range.ending || (word == "comment" && position == region.range.ending && at_line_end))
+                });
+                assert_eq!(document.is_in_string(&position), inside("string"), "string at {:?}", position);
+                assert_eq!(document.is_in_comment(&position), inside("comment"), "comment at {:?}", position);
+            }
+        }
+
+        let position = Position::from(2, 4);
+        assert!(!document.is_in_comment(&position));
+        document.insert("// ", &InsertOptions::exact_at(&Range::from(2, 4, 2, 4))).unwrap();
+        assert!(document.is_in_comment(&Position::from(2, 8)));
+        assert!(!document.is_in_string(&Position::from(9, 0)));
+    }
+}
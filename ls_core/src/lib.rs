@@ -2,8 +2,91 @@
 #![feature(test)]
 
 pub mod language;
+pub mod syntax;
 pub mod document;
+pub mod escapes;
+pub mod capitalization;
+pub mod casing;
 pub mod util;
+pub mod range_set;
+pub mod virtual_text;
+pub mod diagnostics;
+pub mod identifiers;
+pub mod naming;
+pub mod segmentation;
+pub mod fuzzy;
+pub mod render;
+pub mod gutters;
+pub mod save;
+pub mod composition;
+pub mod input;
+pub mod folds;
+pub mod paste;
+pub mod timeline;
+pub mod undo_tree;
+pub mod compaction;
+pub mod lexical;
+pub mod autopair;
+pub mod search;
+pub mod words;
+pub mod events;
+pub mod ruler;
+pub mod registers;
+pub mod spoken;
+pub mod dictation;
+pub mod dictation_ring;
+pub mod definitions;
+pub mod workspace;
+pub mod named_anchors;
+pub mod bookmarks;
+pub mod anchor_watches;
+pub mod anchor_snapping;
+pub mod annotations;
+pub mod motions;
+pub mod operators;
+pub mod joining;
+pub mod blocks;
+pub mod ambiguity;
+pub mod commands;
+pub mod alternatives;
+pub mod session_log;
+pub mod grammar;
+pub mod config;
+pub mod editorconfig;
+pub mod tidy_delete;
+pub mod formatting;
+pub mod whitespace;
+pub mod line_metrics;
+pub mod hashing;
+pub mod normalization;
+pub mod reflow;
+pub mod doc_comments;
+pub mod imports;
+pub mod duplicate;
+pub mod sorted_insert;
+pub mod increment;
+pub mod snippets;
+pub mod extract;
+pub mod damage;
+pub mod snapshot;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod shared;
+pub mod changelist;
+pub mod idle;
+pub mod changeset;
+pub mod rebase;
+pub mod feedback;
+#[cfg(feature = "serialization")]
+pub mod changestream;
+#[cfg(feature = "serialization")]
+pub mod jsonpatch;
+#[cfg(feature = "serialization")]
+pub mod tree_json;
+#[cfg(feature = "harness")]
+pub mod harness;
+#[cfg(feature = "terminal")]
+pub mod terminal;
+pub mod wasm;
 
 use wasm_bindgen::prelude::*;
 
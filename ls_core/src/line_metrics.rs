@@ -0,0 +1,88 @@
+//! Line lengths in every unit clients count in: characters (codepoints, as
+//! [`Position`](crate::document::Position) columns are), UTF-8 bytes, and
+//! UTF-16 code units, as editors built on JavaScript and the Language Server
+//! Protocol count.
+//!
+//! The character and byte lengths are always at hand. The UTF-16 length is
+//! computed the first time it is asked for, as [`LineWhitespace`] is, and
+//! kept until the line is edited.
+//!
+//! [`LineWhitespace`]: crate::whitespace::LineWhitespace
+
+use crate::document::{Document, Line};
+
+/// The lengths of a line, not counting its line break.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default)]
+pub struct LineMetrics {
+    pub chars: usize,
+    pub bytes: usize,
+    pub utf16: usize
+}
+
+impl LineMetrics {
+    /// Returns true if every character of the line is one byte, so the three
+    /// lengths, and columns in each unit, agree.
+    pub fn is_ascii(&self) -> bool {
+        self.chars == self.bytes
+    }
+}
+
+impl Line {
+    /// Returns the number of characters in the line.
+    pub fn char_len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns the number of UTF-8 bytes in the line.
+    pub fn byte_len(&self) -> usize {
+        self.content.len()
+    }
+
+    /// Returns the number of UTF-16 code units in the line, computing it if
+    /// needed.
+    pub fn utf16_len(&self) -> usize {
+        *self.utf16_length.get_or_init(|| self.content.chars().map(char::len_utf16).sum())
+    }
+
+    /// Returns all of the line's lengths.
+    pub fn metrics(&self) -> LineMetrics {
+        LineMetrics { chars: self.char_len(), bytes: self.byte_len(), utf16: self.utf16_len() }
+    }
+}
+
+impl Document {
+    /// Returns the lengths of the line at `row`, or `None` if there is no
+    /// such row.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::line_metrics::LineMetrics;
+    /// let document = Document::from("plain\nnaïve 🦄");
+    /// assert!(document.line_metrics(0).unwrap().is_ascii());
+    /// assert_eq!(document.line_metrics(1), Some(LineMetrics { chars: 7, bytes: 11, utf16: 8 }));
+    /// assert_eq!(document.line_metrics(2), None);
+    /// ```
+    pub fn line_metrics(&self, row: usize) -> Option<LineMetrics> {
+        self.lines().get(row).map(Line::metrics)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{InsertOptions, Range};
+
+    #[test]
+    fn cached_lengths_follow_edits() {
+        let mut document = Document::from("ab");
+        assert_eq!(document.lines()[0].utf16_len(), 2);
+
+        document.insert("𝄞é", &InsertOptions::exact_at(&Range::from(0, 1, 0, 1))).unwrap();
+        let line = &document.lines()[0];
+        assert_eq!((line.char_len(), line.byte_len(), line.utf16_len()), (4, 8, 5));
+        assert!(!line.metrics().is_ascii());
+    }
+}
@@ -0,0 +1,672 @@
+//! Motions and text objects: the places a command can send the cursor and
+//! the spans of text it can act on, as one vocabulary shared by the command
+//! interpreter and frontends.
+//!
+//! Every target resolves to a [`Range`] through [`Document::resolve`], so
+//! "select X", "delete X", and "go to X" need no range math of their own.
+
+use crate::document::{Document, Position, Range};
+#[cfg(feature = "parsing")]
+use crate::syntax;
+use crate::search::SearchOptions;
+use crate::segmentation::Segmentation;
+use crate::spoken::{self, LineTarget, LineCounting, LineRange};
+use crate::util::Oops;
+
+/// A place to move the cursor to.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Motion {
+    /// A line, counted as the [`LineCounting`] says. See [`LineTarget`].
+    Line(LineTarget, LineCounting),
+
+    /// "80 percent": the line this far through the document, from 0 (the
+    /// first line) to 100 (the last). Larger values are clamped.
+    DocumentPercent(usize),
+
+    /// "column 50": a column of the cursor's line, counting from 1, clamped
+    /// to the end of the line.
+    Column(usize),
+
+    /// "half way along the line", "80 percent of the line": a column this
+    /// far through the cursor's line.
+    LinePercent(usize),
+
+    /// "next tab stop" (true) or "previous tab stop" (false): within the
+    /// leading whitespace of a line, the next or previous multiple of the
+    /// [`Indentation`](crate::document::Indentation) width, so that soft tabs
+    /// behave like tabs. Elsewhere, the next or previous character.
+    TabStop(bool),
+
+    /// "next hump" (true) or "previous hump" (false): the beginning of the
+    /// next or previous sub-word (see [`Document::subword_at`]), on any
+    /// line. Going forward also stops at the end of each word, so "delete
+    /// to next hump" never takes the space after a word.
+    Hump(bool)
+}
+
+/// A span of text to act on.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum TextObject {
+    /// "word": the word at the cursor. See [`Document::word_at`].
+    Word,
+
+    /// "sub-word", "hump": the sub-word at the cursor (see
+    /// [`Document::subword_at`]), with the separators after it, if another
+    /// sub-word of the same word follows them, or else those before it, so
+    /// that deleting it leaves a well-formed identifier.
+    Subword,
+
+    /// "line": the cursor's line, without its line break.
+    Line,
+
+    /// "paragraph": the run of non-blank lines around the cursor, or of
+    /// blank lines if the cursor's line is blank.
+    Paragraph,
+
+    /// "that": the innermost named syntax node at the cursor.
+    Node,
+
+    /// The innermost syntax node of this kind around the cursor, such as
+    /// `"function_item"`.
+    NodeOfKind(String),
+
+    /// The next occurrence of this text after the cursor, wrapping around
+    /// the document and ignoring case and diacritics. See
+    /// [`SearchOptions::spoken`].
+    Search(String),
+
+    /// "brackets": the innermost pair of brackets around the cursor,
+    /// brackets included.
+    Brackets,
+
+    /// "selection": the selection, which may be empty.
+    Selection,
+
+    /// "lines five through nine": whole lines, without the last one's line
+    /// break. See [`Document::resolve_line_range`].
+    Lines(LineRange),
+
+    /// The text between the cursor and where this motion leads.
+    To(Motion)
+}
+
+/// Brackets matched by [`TextObject::Brackets`], as (open, close) pairs.
+pub const BRACKETS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Returns `percent` of `total`, rounded to the nearest whole number.
+/// `percent` is clamped to 100.
+fn percent_of(percent: usize, total: usize) -> usize {
+    (percent.min(100) * total + 50) / 100
+}
+
+/// Returns how far `part` is through `total`, as a rounded percentage.
+/// Zero if `total` is.
+fn as_percent(part: usize, total: usize) -> usize {
+    (part * 100 + total / 2).checked_div(total).unwrap_or(0)
+}
+
+impl Motion {
+    /// Parses a spoken motion. Lines are counted visually.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::motions::*;
+    /// use ls_core::spoken::*;
+    /// assert_eq!(Motion::parse("go to eighty percent"), Some(Motion::DocumentPercent(80)));
+    /// assert_eq!(Motion::parse("50 percent of the line"), Some(Motion::LinePercent(50)));
+    /// assert_eq!(Motion::parse("column twelve"), Some(Motion::Column(12)));
+    /// assert_eq!(Motion::parse("line 3"), Some(Motion::Line(LineTarget::Absolute(3), LineCounting::Visual)));
+    /// assert_eq!(Motion::parse("to previous tab stop"), Some(Motion::TabStop(false)));
+    /// assert_eq!(Motion::parse("next hump"), Some(Motion::Hump(true)));
+    /// assert_eq!(Motion::parse("column"), None);
+    /// ```
+    pub fn parse(phrase: &str) -> Option<Motion> {
+        let lowered = phrase.to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+        let words = match words.as_slice() {
+            ["go", "to", rest @ ..] | ["to", rest @ ..] => rest,
+            all => all
+        };
+
+        match words {
+            ["next", "tab", "stop"] => return Some(Motion::TabStop(true)),
+            ["previous", "tab", "stop"] | ["prior", "tab", "stop"] => return Some(Motion::TabStop(false)),
+            ["next", "hump"] | ["next", "sub-word"] | ["next", "subword"] => return Some(Motion::Hump(true)),
+            ["previous", "hump"] | ["previous", "sub-word"] | ["previous", "subword"] | ["prior", "hump"] => {
+                return Some(Motion::Hump(false))
+            },
+            ["half", "way", "along", "the", "line"] | ["middle", "of", "the", "line"] => {
+                return Some(Motion::LinePercent(50))
+            },
+            ["column", rest @ ..] => {
+                return match spoken::parse_number(rest)? {
+                    (n, used) if used == rest.len() => Some(Motion::Column(n)),
+                    _ => None
+                };
+            },
+            _ => ()
+        }
+
+        if let Some((n, used)) = spoken::parse_number(words) {
+            match &words[used..] {
+                ["percent"] | ["percent", "of", "the", "file"] | ["percent", "of", "file"] => {
+                    return Some(Motion::DocumentPercent(n))
+                },
+                ["percent", "of", "the", "line"] | ["percent", "of", "line"] => return Some(Motion::LinePercent(n)),
+                _ => return None
+            }
+        }
+
+        LineTarget::parse(phrase).map(|target| Motion::Line(target, LineCounting::Visual))
+    }
+}
+
+impl TextObject {
+    /// Parses a spoken text object, falling back on [`Motion::parse`] for
+    /// the text up to a motion's target.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::motions::*;
+    /// assert_eq!(TextObject::parse("paragraph"), Some(TextObject::Paragraph));
+    /// assert_eq!(TextObject::parse("column 4"), Some(TextObject::To(Motion::Column(4))));
+    /// assert_eq!(TextObject::parse("banana"), None);
+    /// ```
+    pub fn parse(phrase: &str) -> Option<TextObject> {
+        let lowered = phrase.to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["word"] => Some(TextObject::Word),
+            ["subword"] | ["sub-word"] | ["sub", "word"] | ["hump"] => Some(TextObject::Subword),
+            ["line"] => Some(TextObject::Line),
+            ["paragraph"] => Some(TextObject::Paragraph),
+            ["that"] | ["node"] => Some(TextObject::Node),
+            ["brackets"] | ["parens"] | ["braces"] => Some(TextObject::Brackets),
+            ["selection"] => Some(TextObject::Selection),
+            _ => LineRange::parse(phrase).map(TextObject::Lines)
+                .or_else(|| Motion::parse(phrase).map(TextObject::To))
+        }
+    }
+}
+
+impl Document {
+    /// Returns the position `motion` leads to from the cursor. Lines reached
+    /// by percentage land at the end of their indentation, like line
+    /// numbers, and on the first line of any fold they are inside.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::motions::*;
+    /// let document = Document::from("0\n1\n2\n3\n  four\n5\n6\n7\n8\n9\n10");
+    /// assert_eq!(document.motion_target(&Motion::DocumentPercent(40)), Position::from(4, 2));
+    /// assert_eq!(document.motion_target(&Motion::DocumentPercent(250)), Position::from(10, 0));
+    /// ```
+    pub fn motion_target(&self, motion: &Motion) -> Position {
+        let cursor = self.cursor().position;
+        let length = self.lines()[cursor.row].length;
+
+        match *motion {
+            Motion::Line(target, counting) => self.line_target_position(target, counting),
+            Motion::DocumentPercent(percent) => {
+                let mut row = percent_of(percent, self.rows() - 1);
+                while self.row_hidden(row) {
+                    row -= 1;
+                }
+                self.indentation_end(row)
+            },
+            Motion::Column(column) => Position::from(cursor.row, column.saturating_sub(1).min(length)),
+            Motion::LinePercent(percent) => Position::from(cursor.row, percent_of(percent, length)),
+            Motion::TabStop(forward) => self.tab_stop_from(&cursor, forward),
+            Motion::Hump(forward) => self.hump_from(&cursor, forward)
+        }
+    }
+
+    /// Returns the next or previous hump from `position`, or `position`
+    /// itself if there is none. See [`Motion::Hump`].
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let document = Document::from("parseHTTPResponse(raw_body)");
+    /// assert_eq!(document.hump_from(&Position::from(0, 0), true), Position::from(0, 5));
+    /// assert_eq!(document.hump_from(&Position::from(0, 9), true), Position::from(0, 17));
+    /// assert_eq!(document.hump_from(&Position::from(0, 18), false), Position::from(0, 9));
+    /// assert_eq!(document.hump_from(&Position::from(0, 0), false), Position::from(0, 0));
+    /// ```
+    pub fn hump_from(&self, position: &Position, forward: bool) -> Position {
+        let stops = |row: usize| -> Vec<Position> {
+            let mut stops = vec![];
+            let mut column = 0;
+            while let Some(word) = self.word_after(&Position::from(row, column)).filter(|w| w.beginning.row == row) {
+                stops.extend(self.subwords_in(&word).iter().map(|s| s.beginning));
+                if forward {
+                    stops.push(word.ending);
+                }
+                column = word.ending.column;
+            }
+            stops
+        };
+
+        let found = if forward {
+            (position.row..self.rows()).find_map(|row| stops(row).into_iter().find(|p| p > position))
+        } else {
+            (0..=position.row).rev().find_map(|row| stops(row).into_iter().rev().find(|p| p < position))
+        };
+        found.unwrap_or(*position)
+    }
+
+    /// Returns the next or previous tab stop from `position`. See
+    /// [`Motion::TabStop`].
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("      x = 1;");
+    /// document.set_indentation(&Indentation::spaces(4)).unwrap();
+    /// assert_eq!(document.tab_stop_from(&Position::from(0, 1), true), Position::from(0, 4));
+    /// assert_eq!(document.tab_stop_from(&Position::from(0, 4), true), Position::from(0, 6));
+    /// assert_eq!(document.tab_stop_from(&Position::from(0, 6), false), Position::from(0, 4));
+    /// assert_eq!(document.tab_stop_from(&Position::from(0, 9), false), Position::from(0, 8));
+    /// ```
+    pub fn tab_stop_from(&self, position: &Position, forward: bool) -> Position {
+        let line = &self.lines()[position.row];
+        let margin = self.indentation_end(position.row).column;
+        let column = position.column;
+
+        let outside = if forward { column >= margin } else { column == 0 || column > margin };
+        if outside {
+            let column = if forward { (column + 1).min(line.length) } else { column.saturating_sub(1) };
+            return Position::from(position.row, column);
+        }
+
+        // The visual width of the margin up to each column, with tabs
+        // advancing to the next stop.
+        let step = self.indentation().spaces_per_tab;
+        let mut widths = vec![0];
+        for c in line.content.chars().take(margin) {
+            let width = widths[widths.len() - 1];
+            widths.push(if c == '\t' { (width / step + 1) * step } else { width + 1 });
+        }
+
+        let width = widths[column];
+        let target = if forward {
+            let stop = (width / step + 1) * step;
+            (column..=margin).find(|&c| widths[c] >= stop).unwrap_or(margin)
+        } else {
+            let stop = (width - 1) / step * step;
+            (0..column).rev().find(|&c| widths[c] <= stop).unwrap_or(0)
+        };
+        Position::from(position.row, target)
+    }
+
+    /// Moves the cursor and mark to [`Document::motion_target`], returning
+    /// the new position.
+    pub fn apply_motion(&mut self, motion: &Motion) -> Result<Position, Oops> {
+        let position = self.motion_target(motion);
+        self.set_cursor_and_mark(&position)?;
+        Ok(position)
+    }
+
+    /// Returns the range of `object` relative to the cursor. Returns `Err`
+    /// if there is no such text, or if `object` needs a parse tree and there
+    /// is none.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::motions::*;
+    /// let mut document = Document::from("f(a, [b])\n\nnext one");
+    /// document.set_cursor_and_mark(&Position::from(0, 7)).unwrap();
+    /// assert_eq!(document.resolve(&TextObject::Brackets), Ok(Range::from(0, 5, 0, 8)));
+    /// assert_eq!(document.resolve(&TextObject::Word), Ok(Range::from(0, 6, 0, 7)));
+    /// assert_eq!(document.resolve(&TextObject::Search(String::from("one"))), Ok(Range::from(2, 5, 2, 8)));
+    /// assert_eq!(document.resolve(&TextObject::To(Motion::Column(2))), Ok(Range::from(0, 1, 0, 7)));
+    /// ```
+    pub fn resolve(&self, object: &TextObject) -> Result<Range, Oops> {
+        self.resolve_counted(object, 1)
+    }
+
+    /// Like [`Document::resolve`], but for `count` of `object`. Words, lines,
+    /// and paragraphs extend forward over the ones that follow; brackets and
+    /// syntax nodes widen outward to enclosing ones; searches find the
+    /// `count`th occurrence. Selections and motions ignore `count`. Counts
+    /// run out quietly at the end of the document or the outermost match.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::motions::*;
+    /// let mut document = Document::from("(one, (two three)) four");
+    /// document.set_cursor_and_mark(&Position::from(0, 8)).unwrap();
+    /// assert_eq!(document.resolve_counted(&TextObject::Word, 2), Ok(Range::from(0, 7, 0, 16)));
+    /// assert_eq!(document.resolve_counted(&TextObject::Brackets, 2), Ok(Range::from(0, 0, 0, 18)));
+    /// assert_eq!(document.resolve_counted(&TextObject::Word, 9), Ok(Range::from(0, 7, 0, 23)));
+    /// ```
+    pub fn resolve_counted(&self, object: &TextObject, count: usize) -> Result<Range, Oops> {
+        let cursor = self.cursor().position;
+
+        let mut range = match object {
+            TextObject::Word => self.word_at(&cursor).ok_or(Oops::Ouch("no word at cursor"))?,
+            TextObject::Subword => self.subword_at(&cursor).ok_or(Oops::Ouch("no sub-word at cursor"))?,
+            TextObject::Line => Range::from(cursor.row, 0, cursor.row, self.lines()[cursor.row].length),
+            TextObject::Paragraph => self.paragraph_at(cursor.row),
+            TextObject::Node | TextObject::NodeOfKind(_) => return self.resolve_node(object, count.max(1)),
+            TextObject::Search(needle) => self.find_next(needle, &cursor, true, &SearchOptions::spoken())
+                .ok_or(Oops::Ouch("no occurrence of search text"))?,
+            TextObject::Brackets => self.brackets_around(&cursor).ok_or(Oops::Ouch("no brackets around cursor"))?,
+            TextObject::Selection => return Ok(self.selection()),
+            TextObject::Lines(lines) => {
+                let (first, last) = self.resolve_line_range(lines);
+                return Ok(Range::from(first, 0, last, self.lines()[last].length));
+            },
+            TextObject::To(motion) => {
+                let target = self.motion_target(motion);
+                return Ok(Range { beginning: cursor.min(target), ending: cursor.max(target) });
+            }
+        };
+
+        for _ in 1..count {
+            let next = match object {
+                TextObject::Word => self.word_after(&range.ending).map(|word| Range { ending: word.ending, ..range }),
+                TextObject::Subword => self.subword_after(&range.ending).map(|subword| Range { ending: subword.ending, ..range }),
+                TextObject::Line if range.ending.row + 1 < self.rows() => {
+                    let row = range.ending.row + 1;
+                    Some(Range { ending: Position::from(row, self.lines()[row].length), ..range })
+                },
+                TextObject::Paragraph if range.ending.row + 1 < self.rows() => {
+                    Some(Range { ending: self.paragraph_at(range.ending.row + 1).ending, ..range })
+                },
+                TextObject::Search(needle) => self.find_next(needle, &range.beginning, true, &SearchOptions::spoken())
+                    .filter(|found| found.beginning > range.beginning),
+                TextObject::Brackets => self.brackets_around(&range.beginning),
+                _ => None
+            };
+
+            match next {
+                Some(next) => range = next,
+                None => break
+            }
+        }
+
+        if *object == TextObject::Subword {
+            range = self.widen_over_separators(&range);
+        }
+        Ok(range)
+    }
+
+    /// Returns the first word beginning at or after `position`.
+    fn word_after(&self, position: &Position) -> Option<Range> {
+        (position.row..self.rows()).find_map(|row| {
+            let skip = if row == position.row { position.column } else { 0 };
+            let column = self.lines()[row].content.chars().skip(skip).position(|c| self.is_word_char(c))?;
+            self.word_at(&Position::from(row, skip + column))
+        })
+    }
+
+    /// Returns the first sub-word beginning at or after `position`.
+    fn subword_after(&self, position: &Position) -> Option<Range> {
+        let here = self.word_at(position)
+            .and_then(|word| self.subwords_in(&word).into_iter().find(|s| s.beginning >= *position));
+        here.or_else(|| self.subwords_in(&self.word_after(position)?).into_iter().next())
+    }
+
+    /// Widens the sub-words in `range` over the separators after them, if
+    /// another sub-word of the same word follows, or else over the ones
+    /// before them, if another sub-word precedes. See [`TextObject::Subword`].
+    fn widen_over_separators(&self, range: &Range) -> Range {
+        let separators = Segmentation::default().separators;
+        let separator = |p: &Position| self.lines()[p.row].content.chars().nth(p.column).is_some_and(|c| separators.contains(&c));
+
+        if let Some(word) = self.word_at(&range.ending) {
+            let mut after = range.ending;
+            while after < word.ending && separator(&after) {
+                after.column += 1;
+            }
+            if after > range.ending && after < word.ending {
+                return Range { ending: after, ..*range };
+            }
+        }
+
+        if let Some(word) = self.word_at(&range.beginning) {
+            let mut before = range.beginning;
+            while before > word.beginning && separator(&Position::from(before.row, before.column - 1)) {
+                before.column -= 1;
+            }
+            if before < range.beginning && before > word.beginning {
+                return Range { beginning: before, ..*range };
+            }
+        }
+
+        *range
+    }
+
+    /// Returns the paragraph containing `row`. See [`TextObject::Paragraph`].
+    fn paragraph_at(&self, row: usize) -> Range {
+        let blank = |r: usize| self.lines()[r].content.trim().is_empty();
+        let kind = blank(row);
+
+        let mut first = row;
+        while first > 0 && blank(first - 1) == kind {
+            first -= 1;
+        }
+        let mut last = row;
+        while last + 1 < self.rows() && blank(last + 1) == kind {
+            last += 1;
+        }
+
+        Range::from(first, 0, last, self.lines()[last].length)
+    }
+
+    /// Returns the innermost pair of [`BRACKETS`] enclosing `position`,
+    /// brackets included. Brackets of every kind nest together, so
+    /// mismatched brackets enclose nothing.
+    fn brackets_around(&self, position: &Position) -> Option<Range> {
+        let chars: Vec<(Position, char)> = self.lines().iter().enumerate()
+            .flat_map(|(row, line)| line.content.chars().enumerate().map(move |(column, c)| (Position::from(row, column), c)))
+            .collect();
+        let opens = |c: char| BRACKETS.iter().any(|(open, _)| *open == c);
+        let closes = |c: char| BRACKETS.iter().any(|(_, close)| *close == c);
+        let at = chars.partition_point(|(p, _)| p < position);
+
+        let mut depth = 0;
+        let opening = (0..at).rev().find(|&i| {
+            let c = chars[i].1;
+            if closes(c) {
+                depth += 1;
+            } else if opens(c) {
+                if depth == 0 {
+                    return true;
+                }
+                depth -= 1;
+            }
+            false
+        })?;
+
+        let mut depth = 0;
+        let closing = (opening + 1..chars.len()).find(|&i| {
+            let c = chars[i].1;
+            if opens(c) {
+                depth += 1;
+            } else if closes(c) {
+                if depth == 0 {
+                    return true;
+                }
+                depth -= 1;
+            }
+            false
+        })?;
+
+        let (open, close) = (chars[opening].1, chars[closing].1);
+        if !BRACKETS.contains(&(open, close)) {
+            return None;
+        }
+
+        let ending = chars[closing].0;
+        Some(Range { beginning: chars[opening].0, ending: Position::from(ending.row, ending.column + 1) })
+    }
+
+    /// Resolves `levels` of [`TextObject::Node`] or
+    /// [`TextObject::NodeOfKind`], each enclosing the last.
+    #[cfg(feature = "parsing")]
+    fn resolve_node(&self, object: &TextObject, levels: usize) -> Result<Range, Oops> {
+        let cursor = self.cursor().position;
+        let mut node = self.node_at(&Range { beginning: cursor, ending: cursor })
+            .ok_or(Oops::CannotParse("resolve"))?;
+        let wanted = |n: &syntax::Node| match object {
+            TextObject::NodeOfKind(kind) => n.kind() == kind,
+            _ => n.is_named()
+        };
+
+        while !wanted(&node) {
+            node = node.parent().ok_or(Oops::Ouch("no such node around cursor"))?;
+        }
+
+        for _ in 1..levels {
+            let mut parent = node.parent();
+            while let Some(p) = parent.filter(|p| !wanted(p) || p.byte_range() == node.byte_range()) {
+                parent = p.parent();
+            }
+            match parent {
+                Some(p) => node = p,
+                None => break
+            }
+        }
+
+        Ok(self.range_from_ts(&node.range()))
+    }
+
+    /// Without the `parsing` feature there is never a parse tree, so this
+    /// always returns an [`Oops`].
+    #[cfg(not(feature = "parsing"))]
+    fn resolve_node(&self, _object: &TextObject, _levels: usize) -> Result<Range, Oops> {
+        Err(Oops::CannotParse("resolve"))
+    }
+
+    /// Returns how far the cursor is through the document and through its
+    /// line, as percentages, using the same scale as
+    /// [`Motion::DocumentPercent`] and [`Motion::LinePercent`].
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("a\nb\nabcd\nd\ne");
+    /// document.set_cursor_and_mark(&Position::from(2, 1)).unwrap();
+    /// assert_eq!(document.position_percentages(), (50, 25));
+    /// ```
+    pub fn position_percentages(&self) -> (usize, usize) {
+        let cursor = self.cursor().position;
+        (
+            as_percent(cursor.row, self.rows() - 1),
+            as_percent(cursor.column, self.lines()[cursor.row].length)
+        )
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Indentation;
+    use crate::operators::Operation;
+
+    #[test]
+    fn percentages_round_trip() {
+        let mut document = Document::from("0\n1\n2\n3\n4\n5\n6\nseven");
+        document.fold_rows(2, 5).unwrap();
+        assert_eq!(document.motion_target(&Motion::DocumentPercent(50)), Position::from(2, 0));
+
+        document.set_cursor_and_mark(&Position::from(7, 0)).unwrap();
+        assert_eq!(document.apply_motion(&Motion::Column(99)), Ok(Position::from(7, 5)));
+        assert_eq!(document.apply_motion(&Motion::LinePercent(60)), Ok(Position::from(7, 3)));
+        assert_eq!(document.position_percentages(), (100, 60));
+        assert_eq!(document.apply_motion(&Motion::Column(0)), Ok(Position::from(7, 0)));
+
+        for percent in [0, 14, 29, 86, 100] {
+            document.apply_motion(&Motion::DocumentPercent(percent)).unwrap();
+            assert_eq!(document.position_percentages().0, percent);
+        }
+    }
+
+    #[test]
+    fn resolve_text_objects() {
+        let mut document = Document::from("one\ntwo (x]\n\n\nthree) { y }");
+        document.set_cursor_and_mark(&Position::from(1, 6)).unwrap();
+        assert!(document.resolve(&TextObject::Brackets).is_err());
+        assert_eq!(document.resolve(&TextObject::Paragraph), Ok(Range::from(0, 0, 1, 7)));
+        assert_eq!(document.resolve(&TextObject::Line), Ok(Range::from(1, 0, 1, 7)));
+
+        document.set_cursor_and_mark(&Position::from(2, 0)).unwrap();
+        assert_eq!(document.resolve(&TextObject::Paragraph), Ok(Range::from(2, 0, 3, 0)));
+        assert!(document.resolve(&TextObject::Word).is_err());
+        assert!(document.resolve(&TextObject::Subword).is_err());
+
+        document.set_cursor_and_mark(&Position::from(4, 10)).unwrap();
+        assert_eq!(document.resolve(&TextObject::Brackets), Ok(Range::from(4, 7, 4, 12)));
+        assert_eq!(document.resolve(&TextObject::To(Motion::DocumentPercent(0))), Ok(Range::from(0, 0, 4, 10)));
+    }
+
+    #[test]
+    fn resolve_subwords() {
+        let mut document = Document::from("parse_http_response(rawHTTPBody)");
+        document.set_cursor_and_mark(&Position::from(0, 7)).unwrap();
+        assert_eq!(document.resolve(&TextObject::Subword), Ok(Range::from(0, 6, 0, 11)));
+        assert_eq!(document.resolve_counted(&TextObject::Subword, 2), Ok(Range::from(0, 5, 0, 19)));
+        assert_eq!(document.resolve_counted(&TextObject::Subword, 3), Ok(Range::from(0, 5, 0, 23)));
+        document.set_cursor_and_mark(&Position::from(0, 25)).unwrap();
+        document.operate(&Operation::parse("delete one sub-word").unwrap()).unwrap();
+        assert_eq!(document.text(), "parse_http_response(rawBody)");
+        document.set_cursor_and_mark(&Position::from(0, 13)).unwrap();
+        document.operate(&Operation::parse("delete subword").unwrap()).unwrap();
+        assert_eq!(document.text(), "parse_http(rawBody)");
+    }
+
+    #[test]
+    fn resolve_humps() {
+        let mut document = Document::from("parseHTTPResponse");
+        document.set_cursor_and_mark(&Position::from(0, 12)).unwrap();
+        document.operate(&Operation::parse("delete hump").unwrap()).unwrap();
+        assert_eq!(document.text(), "parseHTTP");
+        document.set_cursor_and_mark(&Position::from(0, 0)).unwrap();
+        document.operate(&Operation::parse("delete to next hump").unwrap()).unwrap();
+        assert_eq!(document.text(), "HTTP");
+    }
+
+    #[test]
+    fn tab_stops_in_margins() {
+        let mut document = Document::from(" \t  x\n      y");
+        document.set_indentation(&Indentation::tabs(4)).unwrap();
+        assert_eq!(document.tab_stop_from(&Position::from(0, 0), true), Position::from(0, 2));
+        assert_eq!(document.tab_stop_from(&Position::from(0, 2), true), Position::from(0, 4));
+        assert_eq!(document.tab_stop_from(&Position::from(0, 4), false), Position::from(0, 2));
+        assert_eq!(document.tab_stop_from(&Position::from(0, 2), false), Position::from(0, 0));
+        assert_eq!(document.tab_stop_from(&Position::from(0, 5), true), Position::from(0, 5));
+        assert_eq!(document.tab_stop_from(&Position::from(0, 0), false), Position::from(0, 0));
+
+        document.set_indentation(&Indentation::spaces(4)).unwrap();
+        document.set_cursor_and_mark(&Position::from(1, 6)).unwrap();
+        let delete = Operation::parse("delete to previous tab stop").unwrap();
+        assert_eq!(document.operate(&delete), Ok(Range::from(1, 4, 1, 6)));
+        assert_eq!(document.operate(&delete), Ok(Range::from(1, 0, 1, 4)));
+        assert_eq!(document.text(), " \t  x\ny");
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), " \t  x\n    y");
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn resolve_nodes() {
+        let mut document = Document::from_with_language("fn f() {\n    g(1);\n}\n", "rs");
+        document.set_cursor_and_mark(&Position::from(1, 6)).unwrap();
+        assert_eq!(document.resolve(&TextObject::Node), Ok(Range::from(1, 6, 1, 7)));
+        assert_eq!(
+            document.resolve(&TextObject::NodeOfKind(String::from("function_item"))),
+            Ok(Range::from(0, 0, 2, 1))
+        );
+        assert!(document.resolve(&TextObject::NodeOfKind(String::from("struct_item"))).is_err());
+        assert_eq!(document.resolve_counted(&TextObject::Node, 2), Ok(Range::from(1, 5, 1, 8)));
+        assert_eq!(document.resolve_counted(&TextObject::Node, 99), Ok(Range::from(0, 0, 3, 0)));
+    }
+}
@@ -0,0 +1,67 @@
+//! Named anchors: positions the user has labeled so they can return to them
+//! later, such as placeholders dropped by "mark alpha".
+//!
+//! Each name is held by a floating anchor, so it follows the text as it is
+//! edited but setting and clearing names are not undoable.
+
+use std::collections::BTreeMap;
+
+use crate::document::{Document, Position, AnchorHandle};
+use crate::util::Oops;
+
+/// A document's named anchors, by name.
+pub type NamedAnchors = BTreeMap<String, AnchorHandle>;
+
+impl Document {
+    /// Labels `position` with `name`, replacing any position already labeled
+    /// with it. Returns `Err` if `position` is invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("abc");
+    /// document.set_named_anchor("here", &Position::from(0, 2)).unwrap();
+    /// document.insert("xx", &InsertOptions::exact()).unwrap();
+    /// assert_eq!(document.named_anchor("here"), Some(Position::from(0, 4)));
+    /// ```
+    pub fn set_named_anchor(&mut self, name: &str, position: &Position) -> Result<(), Oops> {
+        let handle = self.create_floating_anchor(position)?;
+        if let Some(old) = self.named_anchors.insert(String::from(name), handle) {
+            self.remove_floating_anchor(old)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the position labeled `name`, if any.
+    pub fn named_anchor(&self, name: &str) -> Option<Position> {
+        self.named_anchors.get(name).map(|h| self.anchor(*h).unwrap().position)
+    }
+
+    /// Removes the label `name`. Returns false if there was none.
+    pub fn remove_named_anchor(&mut self, name: &str) -> bool {
+        match self.named_anchors.remove(name) {
+            None => false,
+            Some(handle) => {
+                let _ = self.remove_floating_anchor(handle);
+                true
+            }
+        }
+    }
+
+    /// Removes every label, returning how many there were.
+    pub fn clear_named_anchors(&mut self) -> usize {
+        let names: Vec<String> = self.named_anchors.keys().cloned().collect();
+        for name in names.iter() {
+            self.remove_named_anchor(name);
+        }
+        names.len()
+    }
+
+    /// Returns every label and its position, in alphabetical order.
+    pub fn named_anchors(&self) -> Vec<(String, Position)> {
+        self.named_anchors
+            .iter()
+            .map(|(name, h)| (name.clone(), self.anchor(*h).unwrap().position))
+            .collect()
+    }
+}
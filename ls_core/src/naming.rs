@@ -0,0 +1,162 @@
+//! Identifier style hints: flagging names which don't follow the casing
+//! convention of the document's language, so dictated names stay idiomatic.
+//!
+//! An identifier is flagged if it is in neither the language's
+//! [casing](Document::casing), nor pascal case (types, classes, and enum
+//! variants in most languages), nor screaming snake case (constants). In
+//! Rust, `parseFile` is flagged; in JavaScript, `parse_file` is. Only plain
+//! `identifier` nodes of the parse tree are checked, not fields, types, or
+//! keywords, so without a parse tree nothing is flagged.
+//!
+//! [`Document::check_identifier_style`] reports the hints as informational
+//! diagnostics from [`NAMING_SOURCE`], and [`Document::fix_identifier_style`]
+//! renames a flagged identifier into the language's casing.
+
+use crate::casing::Casing;
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::document::{Document, Position, Range};
+use crate::util::Oops;
+
+/// The diagnostic source used for identifier style hints.
+pub const NAMING_SOURCE: &str = "naming";
+
+/// Returns `identifier` in `casing`, keeping its leading and trailing
+/// underscores.
+fn restyle(identifier: &str, casing: Casing) -> String {
+    let core = identifier.trim_matches('_');
+    if core.is_empty() {
+        return String::from(identifier);
+    }
+    let leading = identifier.len() - identifier.trim_start_matches('_').len();
+    let trailing = identifier.len() - identifier.trim_end_matches('_').len();
+    format!("{}{}{}", &identifier[..leading], casing.convert(core), &identifier[identifier.len() - trailing..])
+}
+
+impl Document {
+    /// Returns the plain identifiers in the parse tree with their ranges, in
+    /// document order.
+    fn plain_identifiers(&self) -> Vec<(String, Range)> {
+        #[allow(unused_mut)]
+        let mut result = vec![];
+
+        #[cfg(feature = "parsing")]
+        if let Some(tree) = self.parse_tree() {
+            let mut cursor = tree.walk();
+            'walk: loop {
+                let node = cursor.node();
+                if node.child_count() == 0 && node.kind() == "identifier" {
+                    let range = self.range_from_ts(&node.range());
+                    if let Some(name) = self.text_range(&range) {
+                        result.push((name, range));
+                    }
+                }
+
+                if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                    continue;
+                }
+                loop {
+                    if !cursor.goto_parent() {
+                        break 'walk;
+                    }
+                    if cursor.goto_next_sibling() {
+                        break;
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns an informational diagnostic for each identifier which doesn't
+    /// follow the language's casing (see the
+    /// [module documentation](self)), suggesting the name it should have.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "parsing")] {
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language("fn parseFile(_rawBody: u8) { let Ok(x) = MAX_LEN; }", "rs");
+    /// let hints = document.identifier_style_hints();
+    /// assert_eq!(hints.len(), 2);
+    /// assert_eq!(hints[0].range, Range::from(0, 3, 0, 12));
+    /// assert_eq!(hints[0].message, "`parseFile` is not snake case: try `parse_file`");
+    ///
+    /// assert_eq!(document.fix_identifier_style(&Position::from(0, 15)), Ok(String::from("_raw_body")));
+    /// assert_eq!(document.text(), "fn parseFile(_raw_body: u8) { let Ok(x) = MAX_LEN; }");
+    /// assert!(document.fix_identifier_style(&Position::from(0, 38)).is_err());
+    /// # }
+    /// ```
+    pub fn identifier_style_hints(&self) -> Vec<Diagnostic> {
+        let casing = self.casing();
+        self.plain_identifiers()
+            .into_iter()
+            .filter(|(name, _)| {
+                ![casing, Casing::Pascal, Casing::ScreamingSnake].iter().any(|c| c.matches(name))
+            })
+            .map(|(name, range)| {
+                let message = format!("`{}` is not {}: try `{}`", name, casing.name(), restyle(&name, casing));
+                let mut diagnostic = Diagnostic::from(&range, Severity::Information, &message);
+                diagnostic.source = String::from(NAMING_SOURCE);
+                diagnostic
+            })
+            .collect()
+    }
+
+    /// Reports [`Document::identifier_style_hints`] as diagnostics from
+    /// [`NAMING_SOURCE`], replacing those reported before.
+    pub fn check_identifier_style(&mut self) -> Result<(), Oops> {
+        let hints = self.identifier_style_hints();
+        self.set_diagnostics(NAMING_SOURCE, hints)
+    }
+
+    /// Renames the flagged identifier at `position`, and every other
+    /// occurrence of it, into the language's casing as one undoable packet,
+    /// refreshing the style diagnostics if they have been reported. Returns
+    /// the new name, or `Err` if no flagged identifier touches `position`.
+    pub fn fix_identifier_style(&mut self, position: &Position) -> Result<String, Oops> {
+        let hint = self.identifier_style_hints()
+            .into_iter()
+            .find(|hint| hint.range.beginning <= *position && *position <= hint.range.ending)
+            .ok_or(Oops::InvalidPosition(*position, "fix_identifier_style"))?;
+
+        let name = self.text_range(&hint.range).ok_or(Oops::InvalidRange(hint.range, "fix_identifier_style"))?;
+        let fixed = restyle(&name, self.casing());
+        let edits: Vec<(Range, String)> = self.plain_identifiers()
+            .into_iter()
+            .filter(|(other, _)| *other == name)
+            .map(|(_, range)| (range, fixed.clone()))
+            .collect();
+
+        self.with_packet("fix identifier style", |document| document.apply_edits(edits))?;
+
+        if self.diagnostics().iter().any(|d| d.source == NAMING_SOURCE) {
+            self.check_identifier_style()?;
+        }
+        Ok(fixed)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "parsing"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hints_follow_the_language_and_fixes_rename_every_use() {
+        let mut document = Document::from_with_language("function go(max_len) {\n    return max_len + maxLen + Thing.OK;\n}", "js");
+        document.check_identifier_style().unwrap();
+        let flagged: Vec<Range> = document.diagnostics().iter().map(|d| d.range).collect();
+        assert_eq!(flagged, vec![Range::from(0, 12, 0, 19), Range::from(1, 11, 1, 18)]);
+        assert!(document.diagnostics().iter().all(|d| d.severity == Severity::Information && d.source == NAMING_SOURCE));
+
+        assert_eq!(document.fix_identifier_style(&Position::from(1, 11)), Ok(String::from("maxLen")));
+        assert_eq!(document.text(), "function go(maxLen) {\n    return maxLen + maxLen + Thing.OK;\n}");
+        assert!(document.diagnostics().is_empty());
+
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), "function go(max_len) {\n    return max_len + maxLen + Thing.OK;\n}");
+        assert_eq!(restyle("__init__", Casing::Camel), "__init__");
+        assert_eq!(restyle("_", Casing::Snake), "_");
+    }
+}
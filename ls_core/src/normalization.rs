@@ -0,0 +1,249 @@
+//! Text normalization: rewriting a range into a canonical Unicode form, or
+//! swapping typographic punctuation for its ASCII look-alikes and back.
+//!
+//! Dictation engines like to emit curly quotes, dashes, and precomposed or
+//! decomposed accents, which read fine as prose but break compilers and
+//! confuse search. Each normalization is recorded as one labeled
+//! [`ChangePacket`](crate::document::ChangePacket) and reports every
+//! replacement it made, so clients can show the user what happened.
+
+use unicode_normalization::{UnicodeNormalization, is_nfc_quick, is_nfd_quick, IsNormalized};
+
+use crate::document::{Document, Position, Range, InsertOptions};
+use crate::util::Oops;
+
+/// A way of normalizing text.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum Normalization {
+    /// Unicode canonical composition: "e" followed by a combining acute
+    /// accent becomes "é".
+    Nfc,
+
+    /// Unicode canonical decomposition: "é" becomes "e" followed by a
+    /// combining acute accent.
+    Nfd,
+
+    /// Curly quotes, primes, dashes, ellipses, and non-breaking spaces
+    /// become their ASCII equivalents. See [`ascii_punctuation`].
+    AsciiPunctuation,
+
+    /// Straight quotes become curly ones, opening or closing depending on
+    /// what precedes them, `--` becomes an em dash, and `...` an ellipsis.
+    SmartPunctuation
+}
+
+/// One replacement made by [`Document::normalize`].
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Normalized {
+    /// Where the replacement text is, after normalizing.
+    pub range: Range,
+
+    pub before: String,
+    pub after: String
+}
+
+/// Returns the ASCII text which stands in for the typographic character `c`
+/// under [`Normalization::AsciiPunctuation`], if any.
+///
+/// # Examples
+/// ```
+/// use ls_core::normalization::*;
+/// assert_eq!(ascii_punctuation('\u{201c}'), Some("\""));
+/// assert_eq!(ascii_punctuation('\u{2014}'), Some("--"));
+/// assert_eq!(ascii_punctuation('a'), None);
+/// ```
+pub fn ascii_punctuation(c: char) -> Option<&'static str> {
+    match c {
+        '\u{2018}' | '\u{2019}' | '\u{201a}' | '\u{201b}' | '\u{2032}' => Some("'"),
+        '\u{201c}' | '\u{201d}' | '\u{201e}' | '\u{201f}' | '\u{2033}' => Some("\""),
+        '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2212}' => Some("-"),
+        '\u{2014}' | '\u{2015}' => Some("--"),
+        '\u{2026}' => Some("..."),
+        '\u{a0}' | '\u{202f}' => Some(" "),
+        _ => None
+    }
+}
+
+/// Could a quote after `previous` open a quotation, rather than close one
+/// or be an apostrophe?
+fn opens_quote(previous: Option<char>) -> bool {
+    match previous {
+        None => true,
+        Some(c) => c.is_whitespace() || "([{<\u{2018}\u{201c}\u{2014}\u{2013}-".contains(c)
+    }
+}
+
+/// Returns the replacements `normalization` makes in `segment`, as
+/// character columns `start..end` within it and their new text.
+fn segment_edits(normalization: Normalization, segment: &str) -> Vec<(usize, usize, String)> {
+    match normalization {
+        Normalization::Nfc | Normalization::Nfd => {
+            let normalized: String = match normalization {
+                Normalization::Nfc if is_nfc_quick(segment.chars()) == IsNormalized::Yes => return vec![],
+                Normalization::Nfd if is_nfd_quick(segment.chars()) == IsNormalized::Yes => return vec![],
+                Normalization::Nfc => segment.nfc().collect(),
+                _ => segment.nfd().collect()
+            };
+            if normalized == segment {
+                return vec![];
+            }
+
+            // Report the smallest span which changed.
+            let old: Vec<char> = segment.chars().collect();
+            let new: Vec<char> = normalized.chars().collect();
+            let prefix = old.iter().zip(new.iter()).take_while(|(a, b)| a == b).count();
+            let suffix = old[prefix..].iter().rev()
+                .zip(new[prefix..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            vec![(prefix, old.len() - suffix, new[prefix..new.len() - suffix].iter().collect())]
+        },
+
+        Normalization::AsciiPunctuation => segment.chars()
+            .enumerate()
+            .filter_map(|(column, c)| ascii_punctuation(c).map(|ascii| (column, column + 1, String::from(ascii))))
+            .collect(),
+
+        Normalization::SmartPunctuation => {
+            let chars: Vec<char> = segment.chars().collect();
+            let mut result = vec![];
+            let mut column = 0;
+
+            while column < chars.len() {
+                let previous = if column > 0 { Some(chars[column - 1]) } else { None };
+                let (length, replacement) = match chars[column] {
+                    '"' => (1, if opens_quote(previous) { '\u{201c}' } else { '\u{201d}' }),
+                    '\'' => (1, if opens_quote(previous) { '\u{2018}' } else { '\u{2019}' }),
+                    '-' if chars.get(column + 1) == Some(&'-') => (2, '\u{2014}'),
+                    '.' if chars[column..].starts_with(&['.', '.', '.']) => (3, '\u{2026}'),
+                    _ => {
+                        column += 1;
+                        continue;
+                    }
+                };
+                result.push((column, column + length, String::from(replacement)));
+                column += length;
+            }
+
+            result
+        }
+    }
+}
+
+impl Document {
+    /// Applies `normalization` to the text in `range`, as one change packet
+    /// labeled "normalize". Returns every replacement made, in document
+    /// order, or `Err` if `range` is invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::normalization::*;
+    /// let mut document = Document::from("let s = \u{201c}it\u{2019}s\u{201d};");
+    /// let changes = document.normalize(Normalization::AsciiPunctuation, &Range::from(0, 0, 0, 14)).unwrap();
+    /// assert_eq!(document.text(), "let s = \"it's\";");
+    /// assert_eq!(changes.len(), 3);
+    /// assert_eq!(changes[1], Normalized {
+    ///     range: Range::from(0, 11, 0, 12),
+    ///     before: String::from("\u{2019}"),
+    ///     after: String::from("'")
+    /// });
+    ///
+    /// document.undo_once().unwrap();
+    /// assert_eq!(document.text(), "let s = \u{201c}it\u{2019}s\u{201d};");
+    /// ```
+    pub fn normalize(&mut self, normalization: Normalization, range: &Range) -> Result<Vec<Normalized>, Oops> {
+        if !self.range_valid(range) {
+            return Err(Oops::InvalidRange(*range, "normalize"));
+        }
+
+        let mut edits = vec![];
+        for row in range.beginning.row..=range.ending.row {
+            let line = &self.lines()[row];
+            let start = if row == range.beginning.row { range.beginning.column } else { 0 };
+            let end = if row == range.ending.row { range.ending.column } else { line.length };
+            let segment: String = line.content.chars().skip(start).take(end - start).collect();
+
+            for (from, to, after) in segment_edits(normalization, &segment) {
+                let before: String = segment.chars().skip(from).take(to - from).collect();
+                edits.push((Range::from(row, start + from, row, start + to), before, after));
+            }
+        }
+
+        if edits.is_empty() {
+            return Ok(vec![]);
+        }
+
+        self.checkpoint_labeled("normalize");
+        for (range, _, after) in edits.iter().rev() {
+            self.insert(after, &InsertOptions::exact_at(range))?;
+        }
+        self.checkpoint();
+
+        // Later replacements on a row moved by the length change of earlier
+        // ones.
+        let mut shift = 0isize;
+        let mut shift_row = None;
+        let result = edits.into_iter()
+            .map(|(range, before, after)| {
+                if shift_row != Some(range.beginning.row) {
+                    shift_row = Some(range.beginning.row);
+                    shift = 0;
+                }
+                let column = (range.beginning.column as isize + shift) as usize;
+                let length = after.chars().count();
+                shift += length as isize - before.chars().count() as isize;
+
+                let beginning = Position::from(range.beginning.row, column);
+                let ending = Position::from(range.beginning.row, column + length);
+                Normalized { range: Range { beginning, ending }, before, after }
+            })
+            .collect();
+
+        Ok(result)
+    }
+
+    /// Applies `normalization` to the whole document. See
+    /// [`Document::normalize`].
+    pub fn normalize_all(&mut self, normalization: Normalization) -> Result<Vec<Normalized>, Oops> {
+        let last = self.rows() - 1;
+        let range = Range::from(0, 0, last, self.lines()[last].length);
+        self.normalize(normalization, &range)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicode_forms_and_smart_punctuation() {
+        let mut document = Document::from("cafe\u{301} ok\nr\u{e9}sum\u{e9}");
+        document.set_cursor_and_mark(&Position::from(0, 7)).unwrap();
+
+        let changes = document.normalize_all(Normalization::Nfc).unwrap();
+        assert_eq!(document.text(), "caf\u{e9} ok\nr\u{e9}sum\u{e9}");
+        assert_eq!(changes, vec![Normalized {
+            range: Range::from(0, 3, 0, 4),
+            before: String::from("e\u{301}"),
+            after: String::from("\u{e9}")
+        }]);
+        assert_eq!(document.cursor().position, Position::from(0, 6));
+        assert!(document.normalize_all(Normalization::Nfc).unwrap().is_empty());
+
+        let changes = document.normalize(Normalization::Nfd, &Range::from(1, 0, 1, 2)).unwrap();
+        assert_eq!(document.text(), "caf\u{e9} ok\nre\u{301}sum\u{e9}");
+        assert_eq!(changes[0].range, Range::from(1, 1, 1, 3));
+
+        let mut prose = Document::from("He said \"don't -- wait...\" and left.");
+        let changes = prose.normalize_all(Normalization::SmartPunctuation).unwrap();
+        assert_eq!(prose.text(), "He said \u{201c}don\u{2019}t \u{2014} wait\u{2026}\u{201d} and left.");
+        assert_eq!(changes.iter().map(|c| c.range.beginning.column).collect::<Vec<_>>(), vec![8, 12, 15, 21, 22]);
+        prose.normalize_all(Normalization::AsciiPunctuation).unwrap();
+        assert_eq!(prose.text(), "He said \"don't -- wait...\" and left.");
+
+        assert!(prose.normalize(Normalization::Nfc, &Range::from(3, 0, 3, 1)).is_err());
+    }
+}
@@ -0,0 +1,410 @@
+//! Operators: the verbs of spoken editing ("delete", "select", "indent"...),
+//! which combine with a [`TextObject`] and a count into an [`Operation`],
+//! like "delete three words" or "comment paragraph".
+//!
+//! The text an operation acts on always comes from
+//! [`Document::resolve_counted`], and every edit it makes is recorded as a
+//! single [`ChangePacket`](crate::document::ChangePacket), so one spoken
+//! command is one undo.
+
+use crate::document::{Document, Position, Range, InsertOptions, RemoveOptions};
+use crate::motions::{Motion, TextObject};
+use crate::registers::{RegisterContent, ClipSource, DEFAULT_REGISTER};
+use crate::spoken;
+use crate::util::Oops;
+
+/// Something to do to a span of text.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum Operator {
+    /// "delete": remove the text tidily. See [`Document::tidy_delete`].
+    Delete,
+
+    /// "change": remove the text exactly, leaving the cursor in its place
+    /// ready for a replacement.
+    Change,
+
+    /// "select": select the text.
+    Select,
+
+    /// "copy": copy the text into the [`DEFAULT_REGISTER`].
+    Copy,
+
+    /// "indent": indent the lines of the text by one level.
+    Indent,
+
+    /// "dedent", "outdent": dedent the lines of the text by one level.
+    Dedent,
+
+    /// "comment": toggle line comments on the lines of the text. See
+    /// [`line_comment`].
+    Comment,
+
+    /// "uncomment": remove line comments from the lines of the text which
+    /// have them, leaving the rest alone.
+    Uncomment,
+
+    /// "sort": sort the lines of the text alphabetically, ignoring case.
+    Sort
+}
+
+/// An [`Operator`] applied to `count` of a [`TextObject`].
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Operation {
+    pub operator: Operator,
+    pub object: TextObject,
+    pub count: usize
+}
+
+/// Returns the token which begins a line comment in `language`, if it has
+/// one.
+pub fn line_comment(language: &str) -> Option<&'static str> {
+    match language {
+        "rs" | "js" | "ts" | "tsx" | "java" | "c" | "h" | "cpp" | "hpp" | "cs" | "go" | "swift" | "kt" => Some("//"),
+        "py" | "sh" | "bash" | "rb" | "pl" | "toml" | "yaml" | "yml" | "r" => Some("#"),
+        "lisp" | "clj" | "el" | "scm" => Some(";"),
+        "sql" | "lua" | "hs" => Some("--"),
+        _ => None
+    }
+}
+
+impl Operator {
+    /// Returns the name of this operator, as spoken and as used to label its
+    /// change packets.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operator::Delete => "delete",
+            Operator::Change => "change",
+            Operator::Select => "select",
+            Operator::Copy => "copy",
+            Operator::Indent => "indent",
+            Operator::Dedent => "dedent",
+            Operator::Comment => "comment",
+            Operator::Uncomment => "uncomment",
+            Operator::Sort => "sort"
+        }
+    }
+}
+
+impl Operation {
+    /// Parses a spoken operation: a verb, an optional count, and a text
+    /// object, which may be plural.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::motions::*;
+    /// use ls_core::operators::*;
+    /// assert_eq!(
+    ///     Operation::parse("delete three words"),
+    ///     Some(Operation { operator: Operator::Delete, object: TextObject::Word, count: 3 })
+    /// );
+    /// assert_eq!(
+    ///     Operation::parse("select 80 percent"),
+    ///     Some(Operation { operator: Operator::Select, object: TextObject::To(Motion::DocumentPercent(80)), count: 1 })
+    /// );
+    /// assert_eq!(Operation::parse("delete"), None);
+    /// assert_eq!(Operation::parse("line 3"), None);
+    /// ```
+    pub fn parse(phrase: &str) -> Option<Operation> {
+        let lowered = phrase.to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+        let (verb, rest) = words.split_first()?;
+
+        let operator = match *verb {
+            "delete" => Operator::Delete,
+            "change" => Operator::Change,
+            "select" => Operator::Select,
+            "copy" => Operator::Copy,
+            "indent" => Operator::Indent,
+            "dedent" | "outdent" => Operator::Dedent,
+            "comment" => Operator::Comment,
+            "uncomment" => Operator::Uncomment,
+            "sort" => Operator::Sort,
+            _ => return None
+        };
+
+        let object = |words: &[&str]| {
+            let phrase = words.join(" ");
+            TextObject::parse(&phrase).or_else(|| match phrase.strip_suffix('s') {
+                Some(singular) if words.len() == 1 => TextObject::parse(singular),
+                _ => None
+            })
+        };
+
+        if let Some((count, used)) = spoken::parse_number(rest) {
+            if let Some(object) = object(&rest[used..]).filter(|_| used < rest.len()) {
+                return Some(Operation { operator, object, count });
+            }
+        }
+
+        if rest.is_empty() {
+            return None;
+        }
+        object(rest).map(|object| Operation { operator, object, count: 1 })
+    }
+}
+
+impl Document {
+    /// Performs `operation`, returning the range it acted on: for deletions,
+    /// the range removed, and for line-wise operators, every line affected.
+    /// Edits are recorded as one change packet labeled with the operator's
+    /// [name](Operator::name). Returns `Err` if the text object cannot be
+    /// resolved, or when commenting in a language without line comments.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::operators::*;
+    /// let mut document = Document::from_with_language("fn f() {\n    a();\n    b();\n}", "rs");
+    /// document.set_cursor_and_mark(&Position::from(1, 4)).unwrap();
+    /// document.operate(&Operation::parse("comment two lines").unwrap()).unwrap();
+    /// assert_eq!(document.text(), "fn f() {\n    // a();\n    // b();\n}");
+    /// document.operate(&Operation::parse("delete line").unwrap()).unwrap();
+    /// assert_eq!(document.text(), "fn f() {\n    // b();\n}");
+    /// document.undo_once().unwrap();
+    /// document.undo_once().unwrap();
+    /// assert_eq!(document.text(), "fn f() {\n    a();\n    b();\n}");
+    /// ```
+    pub fn operate(&mut self, operation: &Operation) -> Result<Range, Oops> {
+        let range = self.resolve_counted(&operation.object, operation.count)?;
+
+        match operation.operator {
+            Operator::Select => {
+                self.set_selection(&range)?;
+                Ok(range)
+            },
+            Operator::Copy => {
+                let text = self.text_range(&range).ok_or(Oops::InvalidRange(range, "operate"))?;
+                self.set_register(DEFAULT_REGISTER, RegisterContent::Text(text.clone()));
+                self.record_clip(RegisterContent::Text(text), ClipSource::Yank, range);
+                Ok(range)
+            },
+            operator => {
+                self.checkpoint_labeled(operator.name());
+                let result = match (operator, &operation.object) {
+                    // Tidying whitespace in a margin could take the whole line.
+                    (Operator::Delete, TextObject::To(Motion::TabStop(_))) => self.edit_with(Operator::Change, &range),
+                    _ => self.edit_with(operator, &range)
+                };
+                self.checkpoint();
+                result
+            }
+        }
+    }
+
+    /// Performs an editing operator on `range`.
+    fn edit_with(&mut self, operator: Operator, range: &Range) -> Result<Range, Oops> {
+        match operator {
+            Operator::Delete => {
+                let widened = self.tidy_delete_range(range);
+                if let Some(text) = self.text_range(&widened) {
+                    self.record_clip(RegisterContent::Text(text), ClipSource::Kill, widened);
+                }
+                let removed = self.tidy_delete(range)?;
+                self.set_cursor_and_mark(&removed.beginning)?;
+                Ok(removed)
+            },
+            Operator::Change => {
+                if let Some(text) = self.text_range(range).filter(|t| !t.is_empty()) {
+                    self.record_clip(RegisterContent::Text(text), ClipSource::Kill, *range);
+                }
+                if !range.empty() {
+                    self.remove(&RemoveOptions::exact_at(range))?;
+                }
+                self.set_cursor_and_mark(&range.beginning)?;
+                Ok(*range)
+            },
+            Operator::Indent => self.indent_rows(range, 1),
+            Operator::Dedent => self.indent_rows(range, -1),
+            Operator::Comment => self.toggle_line_comments(range),
+            Operator::Uncomment => self.uncomment_lines(range),
+            Operator::Sort => self.sort_rows(range),
+            Operator::Select | Operator::Copy => Ok(*range)
+        }
+    }
+
+    /// Returns the first and last rows of the lines `range` touches. A
+    /// multi-line range ending at the start of a line does not touch it.
+    pub(crate) fn operated_rows(&self, range: &Range) -> (usize, usize) {
+        let mut last = range.ending.row;
+        if range.ending.column == 0 && last > range.beginning.row {
+            last -= 1;
+        }
+        (range.beginning.row, last)
+    }
+
+    /// Replaces the left margin of `row` with `margin`.
+    fn replace_margin(&mut self, row: usize, margin: &str) -> Result<(), Oops> {
+        let old_columns = self.lines()[row].content.chars().take_while(|c| c.is_whitespace()).count();
+        if old_columns > 0 {
+            self.remove(&RemoveOptions::exact_at(&Range::from(row, 0, row, old_columns)))?;
+        }
+        if !margin.is_empty() {
+            self.insert(margin, &InsertOptions::exact_at(&Range::from(row, 0, row, 0)))?;
+        }
+        Ok(())
+    }
+
+    /// Indents the non-blank lines `range` touches by `levels`.
+    fn indent_rows(&mut self, range: &Range, levels: isize) -> Result<Range, Oops> {
+        let (first, last) = self.operated_rows(range);
+        let indentation = *self.indentation();
+
+        for row in first..=last {
+            let line = &self.lines()[row].content;
+            if !line.trim().is_empty() {
+                let margin = indentation.indent(line, levels, false);
+                self.replace_margin(row, &margin)?;
+            }
+        }
+
+        Ok(Range::from(first, 0, last, self.lines()[last].length))
+    }
+
+    /// Comments out the non-blank lines `range` touches at their shallowest
+    /// indentation or, if they are all commented out already, uncomments
+    /// them.
+    fn toggle_line_comments(&mut self, range: &Range) -> Result<Range, Oops> {
+        let token = line_comment(self.language()).ok_or(Oops::Ouch("no line comments in this language"))?;
+        let (first, last) = self.operated_rows(range);
+
+        let margins = self.line_margins(first, last);
+        let commented = |row: usize| self.lines()[row].content.trim_start().starts_with(token);
+
+        if !margins.is_empty() && margins.iter().all(|&(row, _)| commented(row)) {
+            for (row, column) in margins {
+                self.remove_line_comment(row, column, token)?;
+            }
+        } else if let Some(column) = margins.iter().map(|&(_, column)| column).min() {
+            let text = format!("{} ", token);
+            for (row, _) in margins {
+                let at = Position::from(row, column);
+                self.insert(&text, &InsertOptions::exact_at(&Range { beginning: at, ending: at }))?;
+            }
+        }
+
+        Ok(Range::from(first, 0, last, self.lines()[last].length))
+    }
+
+    /// Uncomments the lines `range` touches which are commented out.
+    fn uncomment_lines(&mut self, range: &Range) -> Result<Range, Oops> {
+        let token = line_comment(self.language()).ok_or(Oops::Ouch("no line comments in this language"))?;
+        let (first, last) = self.operated_rows(range);
+
+        for (row, column) in self.line_margins(first, last) {
+            if self.lines()[row].content.trim_start().starts_with(token) {
+                self.remove_line_comment(row, column, token)?;
+            }
+        }
+
+        Ok(Range::from(first, 0, last, self.lines()[last].length))
+    }
+
+    /// Returns each non-blank row from `first` to `last` with the width of
+    /// its left margin.
+    fn line_margins(&self, first: usize, last: usize) -> Vec<(usize, usize)> {
+        (first..=last)
+            .filter(|&row| !self.lines()[row].content.trim().is_empty())
+            .map(|row| (row, self.lines()[row].content.chars().take_while(|c| c.is_whitespace()).count()))
+            .collect()
+    }
+
+    /// Removes the comment `token` at `column` of `row`, and the space after
+    /// it if there is one.
+    fn remove_line_comment(&mut self, row: usize, column: usize, token: &str) -> Result<(), Oops> {
+        let token_columns = token.chars().count();
+        let after = self.lines()[row].content.chars().nth(column + token_columns);
+        let length = token_columns + if after == Some(' ') { 1 } else { 0 };
+        self.remove(&RemoveOptions::exact_at(&Range::from(row, column, row, column + length)))
+    }
+
+    /// Sorts the lines `range` touches alphabetically, ignoring case, and
+    /// then by their exact text.
+    fn sort_rows(&mut self, range: &Range) -> Result<Range, Oops> {
+        let (first, last) = self.operated_rows(range);
+        let rows = Range::from(first, 0, last, self.lines()[last].length);
+
+        let mut lines: Vec<String> = (first..=last).map(|row| self.lines()[row].content.clone()).collect();
+        lines.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()).then_with(|| a.cmp(b)));
+        self.replace_changed(&rows, &lines.join("\n"), Operator::Sort.name())?;
+
+        Ok(Range::from(first, 0, last, self.lines()[last].length))
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators_compose_with_objects() {
+        let mut document = Document::from_with_language("def f():\n    x = 1\n\n    # y\n    return x\n", "py");
+        document.set_cursor_and_mark(&Position::from(1, 4)).unwrap();
+        let operation = |phrase| Operation::parse(phrase).unwrap();
+
+        document.operate(&operation("copy two words")).unwrap();
+        assert_eq!(document.register(DEFAULT_REGISTER), Some(&RegisterContent::Text(String::from("x = 1"))));
+
+        let depth = document.undo_redo().depth().0;
+        document.operate(&operation("dedent paragraph")).unwrap();
+        assert_eq!(document.text(), "def f():\nx = 1\n\n    # y\n    return x\n");
+        assert_eq!(document.undo_redo().depth().0, depth + 1);
+
+        document.operate(&operation("indent 3 paragraphs")).unwrap();
+        assert_eq!(document.text(), "    def f():\n    x = 1\n\n        # y\n        return x\n");
+
+        document.set_cursor_and_mark(&Position::from(3, 0)).unwrap();
+        assert_eq!(document.operate(&operation("comment paragraph")), Ok(Range::from(3, 0, 4, 18)));
+        assert_eq!(document.text(), "    def f():\n    x = 1\n\n        # # y\n        # return x\n");
+        document.operate(&operation("uncomment paragraph")).unwrap();
+        document.operate(&operation("uncomment line")).unwrap();
+        assert_eq!(document.text(), "    def f():\n    x = 1\n\n        y\n        return x\n");
+
+        document.set_cursor_and_mark(&Position::from(4, 10)).unwrap();
+        let depth = document.undo_redo().depth().0;
+        assert_eq!(document.operate(&operation("change word")), Ok(Range::from(4, 8, 4, 14)));
+        assert_eq!(document.cursor().position, Position::from(4, 8));
+        assert_eq!(document.undo_redo().depth().0, depth + 1);
+
+        document.set_language("txt").unwrap();
+        assert!(document.operate(&operation("comment line")).is_err());
+    }
+
+    #[test]
+    fn spoken_line_ranges_are_one_packet() {
+        let mut document = Document::from_with_language("b\nd\nC\na\ne\nf", "py");
+        document.set_cursor_and_mark(&Position::from(4, 0)).unwrap();
+        let operation = |phrase| Operation::parse(phrase).unwrap();
+        let depth = document.undo_redo().depth().0;
+
+        assert_eq!(document.operate(&operation("sort lines one through four")), Ok(Range::from(0, 0, 3, 1)));
+        assert_eq!(document.text(), "a\nb\nC\nd\ne\nf");
+        assert_eq!(document.operate(&operation("comment up two to here")), Ok(Range::from(2, 0, 4, 3)));
+        assert_eq!(document.text(), "a\nb\n# C\n# d\n# e\nf");
+        assert_eq!(document.operate(&operation("indent lines 5 to 6")), Ok(Range::from(4, 0, 5, 5)));
+        assert_eq!(document.operate(&operation("delete two through three")).map(|range| range.beginning.row), Ok(1));
+        assert_eq!(document.text(), "a\n# d\n    # e\n    f");
+        assert_eq!(document.undo_redo().depth().0, depth + 4);
+
+        document.undo(4);
+        assert_eq!(document.text(), "b\nd\nC\na\ne\nf");
+    }
+
+    #[test]
+    fn uncomment_leaves_uncommented_lines_alone() {
+        let mut document = Document::from_with_language("// a\nb\n    //c\n\n// d", "rs");
+        document.set_cursor_and_mark(&Position::from(0, 0)).unwrap();
+        assert_eq!(Operation::parse("uncomment three lines").map(|o| o.operator), Some(Operator::Uncomment));
+
+        let depth = document.undo_redo().depth().0;
+        assert_eq!(document.operate(&Operation::parse("uncomment three lines").unwrap()), Ok(Range::from(0, 0, 2, 5)));
+        assert_eq!(document.text(), "a\nb\n    c\n\n// d");
+        assert_eq!(document.undo_redo().depth().0, depth + 1);
+
+        document.operate(&Operation::parse("uncomment three lines").unwrap()).unwrap();
+        assert_eq!(document.text(), "a\nb\n    c\n\n// d");
+        document.operate(&Operation::parse("comment line").unwrap()).unwrap();
+        assert_eq!(document.text(), "// a\nb\n    c\n\n// d");
+    }
+}
@@ -0,0 +1,272 @@
+//! Pasting: inserting large blocks of text from a clipboard or a terminal's
+//! bracketed paste in one step.
+//!
+//! Unlike typing, a paste is never processed character by character. The
+//! text is inserted with a single [`Change::Insert`](crate::document::Change),
+//! so it costs one incremental reparse and one undoable
+//! [`ChangePacket`](crate::document::ChangePacket) regardless of its size.
+
+use crate::document::{Document, Position, Range, InsertOptions, RemoveOptions};
+use crate::util::{self, Oops};
+
+/// Options for [`Document::paste`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct PasteOptions {
+    /// Should lines after the first be re-indented to match the line
+    /// being pasted into, preserving their indentation relative to
+    /// one another?
+    pub reindent: bool,
+
+    /// Should lines after the first be indented to the visual column the
+    /// paste begins at, preserving their indentation relative to one
+    /// another? For aligned struct fields or table rows pasted mid-line.
+    /// Text after a paste ending in a line break is aligned too. Takes
+    /// precedence over `reindent`.
+    pub align: bool,
+
+    /// If `None`, the paste replaces the text between the cursor and mark.
+    /// Otherwise, it replaces this range.
+    pub range: Option<Range>
+}
+
+impl PasteOptions {
+    /// Returns options which paste text exactly as given, over the
+    /// selection.
+    pub fn exact() -> PasteOptions {
+        PasteOptions {
+            reindent: false,
+            align: false,
+            range: None
+        }
+    }
+
+    /// Returns options which paste over the selection, re-indenting to the
+    /// destination.
+    pub fn reindented() -> PasteOptions {
+        PasteOptions {
+            reindent: true,
+            align: false,
+            range: None
+        }
+    }
+
+    /// Returns options which paste over the selection, lining lines after
+    /// the first up under where it begins.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::paste::*;
+    /// let mut document = Document::from("let p = P { };");
+    /// document.set_cursor_and_mark(&Position::from(0, 12)).unwrap();
+    /// document.paste("x: 1,\ny: 2, ", &PasteOptions::aligned()).unwrap();
+    /// assert_eq!(document.lines()[1].content, "            y: 2, };");
+    /// ```
+    pub fn aligned() -> PasteOptions {
+        PasteOptions {
+            reindent: false,
+            align: true,
+            range: None
+        }
+    }
+}
+
+impl Document {
+    /// Pastes `text` according to `options` as a single undoable packet
+    /// labeled `"paste"`. Line endings (`\r\n` and lone `\r`) are normalized.
+    ///
+    /// Returns `Err` if the target range is invalid. Pasting nothing only
+    /// removes the target range.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::paste::*;
+    /// let mut document = Document::from("fn f() {\n    \n}");
+    /// document.set_cursor_and_mark(&Position::from(1, 4)).unwrap();
+    /// document.paste("if x {\r\n  y();\r\n}", &PasteOptions::reindented()).unwrap();
+    /// assert_eq!(document.text(), "fn f() {\n    if x {\n      y();\n    }\n}");
+    ///
+    /// document.undo(1);
+    /// assert_eq!(document.text(), "fn f() {\n    \n}");
+    /// ```
+    pub fn paste(&mut self, text: &str, options: &PasteOptions) -> Result<(), Oops> {
+        let range = match options.range {
+            None => self.selection(),
+            Some(r) => {
+                if !self.range_valid(&r) {
+                    return Err(Oops::InvalidRange(r, "paste"));
+                }
+                r
+            }
+        };
+
+        let mut lines: Vec<String> = util::LINE_SPLIT
+            .split(text)
+            .flat_map(|l| l.split('\r'))
+            .map(String::from)
+            .collect();
+
+        if lines.len() > 1 {
+            if options.align {
+                self.align_pasted_lines(&mut lines, &range);
+            } else if options.reindent {
+                self.reindent_pasted_lines(&mut lines, &range);
+            }
+        }
+
+        let text = lines.join("\n");
+
+        self.checkpoint_labeled("paste");
+        if text.is_empty() {
+            if !range.empty() {
+                self.remove(&RemoveOptions::exact_at(&range))?;
+            }
+        } else {
+            self.insert(&text, &InsertOptions::exact_at(&range))?;
+        }
+        self.checkpoint();
+
+        Ok(())
+    }
+
+    /// Re-indents pasted `lines` to the margin of the row `range` begins on.
+    pub(crate) fn reindent_pasted_lines(&self, lines: &mut [String], range: &Range) {
+        let target = self.indentation().measure(&self.lines()[range.beginning.row].content).0;
+        self.shift_pasted_lines(lines, target);
+    }
+
+    /// Re-indents pasted `lines` to the visual column `range` begins at,
+    /// counting tabs to the next stop, so they line up under the first.
+    fn align_pasted_lines(&self, lines: &mut [String], range: &Range) {
+        let tab = self.indentation().spaces_per_tab;
+        let target = self.lines()[range.beginning.row].content.chars()
+            .take(range.beginning.column)
+            .fold(0, |width, c| if c == '\t' { (width / tab + 1) * tab } else { width + 1 });
+        self.shift_pasted_lines(lines, target);
+
+        // Text after the paste follows its last line, so keep it aligned too.
+        if let Some(last) = lines.last_mut().filter(|l| l.is_empty()) {
+            *last = self.indentation().produce(target);
+        }
+    }
+
+    /// Strips `lines`, about to be inserted at `position`, of their common
+    /// indentation and re-indents them to the indentation level there: the
+    /// visual column of `position` if only white space precedes it, and the
+    /// margin of its row otherwise. The first line's own indentation counts
+    /// towards the common indentation, as for whole lines copied from
+    /// another depth, but is dropped, since the destination provides it.
+    /// A first line without indentation is taken to have been copied from
+    /// mid-line, and only the lines after it are considered.
+    pub(crate) fn reindent_to_destination(&self, lines: &mut [String], position: &Position) {
+        let indentation = *self.indentation();
+        let row = &self.lines()[position.row].content;
+        let before = util::slice(row, ..position.column);
+        let target = if before.trim().is_empty() {
+            indentation.measure(before).0
+        } else {
+            indentation.measure(row).0
+        };
+
+        let (first, bytes) = indentation.measure(&lines[0]);
+        if bytes == 0 || lines[0].trim().is_empty() {
+            self.shift_pasted_lines(lines, target);
+            return;
+        }
+
+        let base = lines.iter()
+            .skip(1)
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| indentation.measure(l).0)
+            .fold(first, usize::min);
+        lines[0].drain(..bytes);
+        self.shift_lines_from(lines, target, base);
+    }
+
+    /// Re-indents `lines` after the first to `target` spaces, keeping
+    /// their indentation relative to each other. Does nothing if they are
+    /// all blank.
+    fn shift_pasted_lines(&self, lines: &mut [String], target: usize) {
+        let indentation = *self.indentation();
+
+        let base = lines.iter()
+            .skip(1)
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| indentation.measure(l).0)
+            .min();
+
+        if let Some(base) = base {
+            self.shift_lines_from(lines, target, base);
+        }
+    }
+
+    /// Adds `target - base` spaces of indentation to each line after the
+    /// first, none of which may be indented less than `base`. Blank lines
+    /// are emptied.
+    fn shift_lines_from(&self, lines: &mut [String], target: usize, base: usize) {
+        let indentation = *self.indentation();
+
+        for line in lines.iter_mut().skip(1) {
+            if line.trim().is_empty() {
+                line.clear();
+                continue;
+            }
+            let (spaces, bytes) = indentation.measure(line);
+            *line = indentation.produce(target + spaces - base) + &line[bytes..];
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_paste_is_one_packet() {
+        let mut document = Document::from("start\nend");
+        document.set_cursor_and_mark(&Position::from(1, 0)).unwrap();
+
+        let text: String = (0..5000).map(|i| format!("line {}\r\n", i)).collect();
+        document.paste(&text, &PasteOptions::exact()).unwrap();
+
+        assert_eq!(document.rows(), 5002);
+        assert_eq!(document.lines()[5000].content, "line 4999");
+        assert_eq!(document.cursor().position, Position::from(5001, 0));
+        assert_eq!(document.undo_redo().depth(), (2, 0));
+        assert_eq!(document.undo_redo().undo_packets().next().unwrap().label(), Some("paste"));
+
+        document.paste("", &PasteOptions {
+            reindent: false,
+            align: false,
+            range: Some(Range::from(0, 0, 5001, 0))
+        }).unwrap();
+        assert_eq!(document.text(), "end");
+
+        assert!(document.paste("x", &PasteOptions {
+            reindent: false,
+            align: false,
+            range: Some(Range::from(3, 0, 3, 0))
+        }).is_err());
+    }
+
+    #[test]
+    fn reindented_insert_strips_common_indentation() {
+        let mut document = Document::from("fn f() {\n    g(\n}");
+        let block = "        if x {\n            y();\n\n        }\n";
+        document.insert(block, &InsertOptions { range: Some(Range::from(1, 0, 1, 0)), ..InsertOptions::reindented() }).unwrap();
+        assert_eq!(document.text(), "fn f() {\nif x {\n    y();\n\n}\n    g(\n}");
+        document.insert("a,\n        b", &InsertOptions { range: Some(Range::from(5, 6, 5, 6)), ..InsertOptions::reindented() }).unwrap();
+        assert_eq!(document.lines()[6].content, "    b");
+    }
+
+    #[test]
+    fn aligned_paste_follows_the_cursor_column() {
+        let mut document = Document::from("\tname | age");
+        document.set_cursor_and_mark(&Position::from(0, 1)).unwrap();
+        document.paste("ann  | 31\nbo   | 4\n", &PasteOptions::aligned()).unwrap();
+        assert_eq!(document.text(), "\tann  | 31\n    bo   | 4\n    name | age");
+    }
+}
@@ -0,0 +1,242 @@
+//! Sets of text ranges, with union, intersection, and subtraction.
+//!
+//! A [`RangeSet`] is the text covered by some ranges, kept as sorted,
+//! disjoint ranges: overlapping or touching ranges are merged as they are
+//! added, reversed ranges (ending before they begin, as a selection made
+//! backwards does) are flipped, and empty ranges, which cover no text, are
+//! dropped. Selections, search matches, and regions which shouldn't be
+//! edited can then be combined without each caller redoing the interval
+//! arithmetic.
+
+use std::iter::FromIterator;
+
+use crate::document::{Position, Range};
+
+/// Some ranges of text, sorted and disjoint (see the
+/// [module documentation](self)).
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct RangeSet {
+    ranges: Vec<Range>
+}
+
+/// Returns `range` with its ends in order.
+fn ordered(range: &Range) -> Range {
+    if range.ending < range.beginning {
+        Range { beginning: range.ending, ending: range.beginning }
+    } else {
+        *range
+    }
+}
+
+impl RangeSet {
+    /// Returns an empty set.
+    pub fn new() -> RangeSet {
+        RangeSet::default()
+    }
+
+    /// Returns the set's ranges, sorted, disjoint, and none touching another.
+    pub fn ranges(&self) -> &[Range] {
+        &self.ranges
+    }
+
+    /// Returns true if the set covers no text.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Returns how many disjoint ranges the set has.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Returns the range from the beginning of the first range to the end of
+    /// the last, if there are any.
+    pub fn span(&self) -> Option<Range> {
+        match (self.ranges.first(), self.ranges.last()) {
+            (Some(first), Some(last)) => Some(Range { beginning: first.beginning, ending: last.ending }),
+            _ => None
+        }
+    }
+
+    /// Returns the range in the set containing `position`, ends included.
+    pub fn range_containing(&self, position: &Position) -> Option<&Range> {
+        let index = self.ranges.partition_point(|range| range.ending < *position);
+        self.ranges.get(index).filter(|range| range.beginning <= *position)
+    }
+
+    /// Returns true if `position` is in one of the set's ranges, ends
+    /// included.
+    pub fn contains(&self, position: &Position) -> bool {
+        self.range_containing(position).is_some()
+    }
+
+    /// Returns true if `range` shares any text with the set. An empty `range`
+    /// overlaps if it is strictly inside one of the set's ranges.
+    pub fn overlaps(&self, range: &Range) -> bool {
+        let range = ordered(range);
+        let index = self.ranges.partition_point(|r| r.ending <= range.beginning);
+        self.ranges.get(index).is_some_and(|r| {
+            r.beginning < range.ending || (range.empty() && r.beginning < range.beginning)
+        })
+    }
+
+    /// Adds `range` to the set, merging it with any ranges it overlaps or
+    /// touches.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::Range;
+    /// use ls_core::range_set::RangeSet;
+    /// let mut set = RangeSet::new();
+    /// set.insert(&Range::from(0, 5, 0, 8));
+    /// set.insert(&Range::from(1, 2, 0, 0));
+    /// set.insert(&Range::from(2, 0, 2, 0));
+    /// assert_eq!(set.ranges(), &[Range::from(0, 0, 1, 2)]);
+    /// ```
+    pub fn insert(&mut self, range: &Range) {
+        let mut range = ordered(range);
+        if range.empty() {
+            return;
+        }
+
+        let first = self.ranges.partition_point(|r| r.ending < range.beginning);
+        let last = self.ranges.partition_point(|r| r.beginning <= range.ending);
+        if first < last {
+            range.beginning = range.beginning.min(self.ranges[first].beginning);
+            range.ending = range.ending.max(self.ranges[last - 1].ending);
+        }
+        self.ranges.splice(first..last, std::iter::once(range));
+    }
+
+    /// Returns the text covered by either set.
+    pub fn union(&self, other: &RangeSet) -> RangeSet {
+        self.ranges.iter().chain(other.ranges.iter()).collect()
+    }
+
+    /// Returns the text covered by both sets.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::Range;
+    /// use ls_core::range_set::RangeSet;
+    /// let a: RangeSet = [Range::from(0, 0, 0, 5), Range::from(1, 0, 1, 5)].iter().collect();
+    /// let b: RangeSet = [Range::from(0, 3, 1, 2)].iter().collect();
+    /// assert_eq!(a.intersection(&b).ranges(), &[Range::from(0, 3, 0, 5), Range::from(1, 0, 1, 2)]);
+    /// assert_eq!(a.subtract(&b).ranges(), &[Range::from(0, 0, 0, 3), Range::from(1, 2, 1, 5)]);
+    /// assert_eq!(a.union(&b).ranges(), &[Range::from(0, 0, 1, 5)]);
+    /// ```
+    pub fn intersection(&self, other: &RangeSet) -> RangeSet {
+        let mut ranges = vec![];
+        let (mut i, mut j) = (0, 0);
+
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            let beginning = a.beginning.max(b.beginning);
+            let ending = a.ending.min(b.ending);
+            if beginning < ending {
+                ranges.push(Range { beginning, ending });
+            }
+
+            if a.ending < b.ending {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        RangeSet { ranges }
+    }
+
+    /// Returns the text covered by this set but not by `other`.
+    pub fn subtract(&self, other: &RangeSet) -> RangeSet {
+        let mut ranges = vec![];
+        let mut j = 0;
+
+        for range in self.ranges.iter() {
+            let mut rest = *range;
+            while j < other.ranges.len() && other.ranges[j].ending <= rest.beginning {
+                j += 1;
+            }
+
+            let mut k = j;
+            while k < other.ranges.len() && other.ranges[k].beginning < rest.ending {
+                let cut = &other.ranges[k];
+                if rest.beginning < cut.beginning {
+                    ranges.push(Range { beginning: rest.beginning, ending: cut.beginning });
+                }
+                rest.beginning = rest.beginning.max(cut.ending);
+                k += 1;
+            }
+
+            if rest.beginning < rest.ending {
+                ranges.push(rest);
+            }
+        }
+        RangeSet { ranges }
+    }
+}
+
+impl<'a> FromIterator<&'a Range> for RangeSet {
+    /// Collects ranges into a set, merging and dropping them as
+    /// [`RangeSet::insert`] does.
+    fn from_iter<I: IntoIterator<Item = &'a Range>>(iter: I) -> RangeSet {
+        let mut ranges: Vec<Range> = iter.into_iter()
+            .map(ordered)
+            .filter(|range| !range.empty())
+            .collect();
+        ranges.sort_by_key(|range| range.beginning);
+
+        let mut merged: Vec<Range> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.beginning <= last.ending => last.ending = last.ending.max(range.ending),
+                _ => merged.push(range)
+            }
+        }
+        RangeSet { ranges: merged }
+    }
+}
+
+impl FromIterator<Range> for RangeSet {
+    fn from_iter<I: IntoIterator<Item = Range>>(iter: I) -> RangeSet {
+        iter.into_iter().collect::<Vec<Range>>().iter().collect()
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn algebra_agrees_with_covered_positions() {
+        let a: RangeSet = vec![
+            Range::from(0, 0, 0, 4), Range::from(0, 6, 1, 1), Range::from(0, 3, 0, 5),
+            Range::from(2, 2, 2, 2), Range::from(3, 4, 2, 5)
+        ].into_iter().collect();
+        let b: RangeSet = vec![Range::from(0, 2, 0, 7), Range::from(2, 1, 3, 0), Range::from(3, 3, 3, 9)].into_iter().collect();
+
+        assert_eq!(a.ranges(), &[Range::from(0, 0, 0, 5), Range::from(0, 6, 1, 1), Range::from(2, 5, 3, 4)]);
+        assert_eq!(a.span(), Some(Range::from(0, 0, 3, 4)));
+        assert!(a.overlaps(&Range::from(1, 0, 1, 0)));
+        assert!(!a.overlaps(&Range::from(0, 5, 0, 6)));
+        assert_eq!(a.range_containing(&Position::from(0, 5)), Some(&Range::from(0, 0, 0, 5)));
+
+        // A character at (row, column) is covered if a range spans it.
+        let covers = |set: &RangeSet, p: Position| set.ranges().iter().any(|r| r.beginning <= p && p < r.ending);
+        for row in 0..4 {
+            for column in 0..10 {
+                let p = Position::from(row, column);
+                assert_eq!(covers(&a.union(&b), p), covers(&a, p) || covers(&b, p), "union at {:?}", p);
+                assert_eq!(covers(&a.intersection(&b), p), covers(&a, p) && covers(&b, p), "intersection at {:?}", p);
+                assert_eq!(covers(&a.subtract(&b), p), covers(&a, p) && !covers(&b, p), "subtract at {:?}", p);
+            }
+        }
+
+        for set in [a.union(&b), a.intersection(&b), a.subtract(&b), b.subtract(&a)].iter() {
+            assert!(set.ranges().windows(2).all(|pair| pair[0].ending < pair[1].beginning));
+            assert!(set.ranges().iter().all(|range| !range.empty()));
+        }
+        assert!(a.subtract(&a).is_empty());
+    }
+}
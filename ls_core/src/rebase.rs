@@ -0,0 +1,242 @@
+//! Neutral edits: changes the user didn't make as part of their own work,
+//! like formatter output requested between an undo and a redo, which
+//! shouldn't cost them their redos.
+//!
+//! Every other edit splits the redo stack off into a
+//! [branch](crate::undo_tree), since the redos were recorded against text
+//! which no longer exists. [`Document::apply_neutral_edits`]
+//! instead rebases it: each redo is moved past the neutral edits, and the
+//! neutral edits past it, the way concurrent edits are merged in
+//! collaborative editors. Where the neutral edits and a redo touch the same
+//! text, there is no sensible merge, so the redos become a branch as usual.
+
+use crate::changeset::ChangeSet;
+use crate::document::{Document, Position, Range, Change, ChangePacket};
+use crate::undo_tree::Branch;
+use crate::util::{self, Oops};
+
+/// What [`Document::apply_neutral_edits`] did.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct NeutralOutcome {
+    /// The change set applied.
+    pub changes: ChangeSet,
+
+    /// True if the redo stack was kept, rebased onto the new text; false if
+    /// a redo touched text the edits changed, and the redo stack became a
+    /// branch forking from before the edits.
+    pub redos_kept: bool
+}
+
+/// Returns `change`, made on the same text as `past`, moved to apply after
+/// `past`. Where both insert at the same position, `change`'s text goes
+/// first if `first`. Returns `None` if they touch the same text.
+fn transform(change: &Change, past: &Change, first: bool) -> Option<Change> {
+    use Change::*;
+
+    let moved = match (change, past) {
+        (Insert { text, position }, Insert { text: inserted, position: at }) => {
+            if *position < *at || (*position == *at && first) {
+                change.clone()
+            } else {
+                Insert { text: text.clone(), position: position.after_insert(at, inserted) }
+            }
+        },
+        (Remove { range }, Insert { text: inserted, position: at }) => {
+            if *at <= range.beginning {
+                Remove { range: Range {
+                    beginning: range.beginning.after_insert(at, inserted),
+                    ending: range.ending.after_insert(at, inserted)
+                } }
+            } else if *at >= range.ending {
+                change.clone()
+            } else {
+                return None;
+            }
+        },
+        (Insert { text, position }, Remove { range: removed }) => {
+            if *position <= removed.beginning {
+                change.clone()
+            } else if *position >= removed.ending {
+                Insert { text: text.clone(), position: position.after_remove(removed) }
+            } else {
+                return None;
+            }
+        },
+        (Remove { range }, Remove { range: removed }) => {
+            if removed.empty() || range.ending <= removed.beginning {
+                change.clone()
+            } else if range.beginning >= removed.ending {
+                Remove { range: Range {
+                    beginning: range.beginning.after_remove(removed),
+                    ending: range.ending.after_remove(removed)
+                } }
+            } else {
+                return None;
+            }
+        },
+        _ => change.clone()
+    };
+    Some(moved)
+}
+
+/// Returns `position` moved along with the text by `edits`.
+fn shift(position: &Position, edits: &[Change]) -> Position {
+    edits.iter().fold(*position, |position, edit| match edit {
+        Change::Insert { text, position: at } => position.after_insert(at, text),
+        Change::Remove { range } => position.after_remove(range),
+        _ => position
+    })
+}
+
+/// Returns `change` moved past `edits`, made on the same text, and `edits`
+/// moved past `change`, or `None` if they touch the same text. Where both
+/// insert at the same position, the text of `edits` goes first.
+fn transform_past(change: &Change, edits: &[Change]) -> Option<(Change, Vec<Change>)> {
+    let mut change = change.clone();
+    let mut rest = Vec::with_capacity(edits.len());
+
+    match change {
+        Change::Insert { .. } | Change::Remove { .. } => {
+            for edit in edits {
+                rest.push(transform(edit, &change, true)?);
+                change = transform(&change, edit, false)?;
+            }
+        },
+        Change::AnchorSet { ref mut value, .. } | Change::AnchorInsert { ref mut value, .. } => {
+            value.position = shift(&value.position, edits);
+            rest.extend_from_slice(edits);
+        },
+        _ => rest.extend_from_slice(edits)
+    }
+    Some((change, rest))
+}
+
+/// Returns `redos`, a redo stack recorded against text which `edits` then
+/// changed, rebased to apply after them, or `None` if one touches text they
+/// changed.
+fn rebase(redos: &[ChangePacket], mut edits: Vec<Change>) -> Option<Vec<ChangePacket>> {
+    let mut rebased = Vec::with_capacity(redos.len());
+
+    // The top of the stack is redone first, and each packet's changes are
+    // applied last to first.
+    for packet in redos.iter().rev() {
+        let mut changes = Vec::with_capacity(packet.changes().len());
+        for change in packet.changes().iter().rev() {
+            let (moved, rest) = transform_past(change, &edits)?;
+            changes.push(moved);
+            edits = rest;
+        }
+        changes.reverse();
+
+        let mut packet = packet.clone();
+        packet.changes = changes;
+        rebased.push(packet);
+    }
+
+    rebased.reverse();
+    Some(rebased)
+}
+
+impl Document {
+    /// Applies `edits` as [`Document::apply_edits`] does, as an undoable
+    /// packet labeled `label`, but rebases the redo stack onto the result
+    /// rather than splitting it off (see the [module documentation](self)).
+    /// Returns `Err` without changing anything, redos included, if the
+    /// edits are invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("let x=1;");
+    /// document.insert(" // one", &InsertOptions::exact_at(&Range::from(0, 8, 0, 8))).unwrap();
+    /// document.undo_once().unwrap();
+    ///
+    /// let outcome = document.apply_neutral_edits("format", vec![(Range::from(0, 5, 0, 6), String::from(" = "))]).unwrap();
+    /// assert!(outcome.redos_kept);
+    /// assert_eq!(document.text(), "let x = 1;");
+    ///
+    /// document.redo_once().unwrap();
+    /// assert_eq!(document.text(), "let x = 1; // one");
+    /// document.undo(2);
+    /// assert_eq!(document.text(), "let x=1;");
+    /// ```
+    pub fn apply_neutral_edits(&mut self, label: &str, edits: Vec<(Range, String)>) -> Result<NeutralOutcome, Oops> {
+        let mut sorted = edits.clone();
+        sorted.sort_by_key(|(range, _)| range.beginning);
+
+        // The edits as made from last to first, so each is on the text as
+        // it was.
+        let mut primitive = vec![];
+        for (range, text) in sorted.iter().rev() {
+            if !range.empty() {
+                primitive.push(Change::Remove { range: *range });
+            }
+            if !text.is_empty() {
+                let text = util::LINE_SPLIT.split(text).map(String::from).collect();
+                primitive.push(Change::Insert { text, position: range.beginning });
+            }
+        }
+
+        let base = self.undo_redo.current_node();
+        let redos = std::mem::take(&mut self.undo_redo.redo_stack);
+        let changes = match self.with_packet(label, |document| document.apply_edits(edits)) {
+            Ok(changes) => changes,
+            Err(oops) => {
+                self.undo_redo.redo_stack = redos;
+                return Err(oops);
+            }
+        };
+
+        let redos_kept = match rebase(&redos, primitive) {
+            Some(rebased) => {
+                self.undo_redo.redo_stack = rebased;
+                true
+            },
+            None => {
+                self.undo_redo.branches.push(Branch { base, packets: redos });
+                false
+            }
+        };
+        Ok(NeutralOutcome { changes, redos_kept })
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{InsertOptions, RemoveOptions};
+
+    #[test]
+    fn redos_survive_neutral_edits_unless_they_collide() {
+        let mut document = Document::from("fn f(a,b) {\n}\n");
+        document.insert("    a\n", &InsertOptions::exact_at(&Range::from(1, 0, 1, 0))).unwrap();
+        document.checkpoint();
+        document.remove(&RemoveOptions::exact_at(&Range::from(0, 0, 0, 3))).unwrap();
+        document.checkpoint();
+        document.insert("pub ", &InsertOptions::exact_at(&Range::from(0, 0, 0, 0))).unwrap();
+        document.undo(3);
+
+        let edits = vec![
+            (Range::from(0, 6, 0, 7), String::from(", ")),
+            (Range::from(0, 0, 0, 0), String::from("// f\n"))
+        ];
+        let outcome = document.apply_neutral_edits("format", edits).unwrap();
+        assert!(outcome.redos_kept);
+        assert_eq!(document.undo_redo().depth(), (1, 3));
+        assert_eq!(document.text(), "// f\nfn f(a, b) {\n}\n");
+
+        assert_eq!(document.redo(3).count, 3);
+        assert_eq!(document.text(), "// f\npub f(a, b) {\n    a\n}\n");
+        document.undo(4);
+        assert_eq!(document.text(), "fn f(a,b) {\n}\n");
+
+        document.redo(2);
+        let outcome = document.apply_neutral_edits("format", vec![(Range::from(1, 1, 1, 5), String::new())]).unwrap();
+        assert!(!outcome.redos_kept);
+        assert_eq!(document.undo_redo().depth(), (3, 0));
+        assert_eq!(document.undo_redo().branches()[0].nodes().len(), 2);
+        assert!(document.apply_neutral_edits("format", vec![(Range::from(9, 0, 9, 1), String::new())]).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! Reflowing prose and comment blocks to a target width.
+//!
+//! Dictated comments arrive as one long line. Reflowing splits the lines of
+//! a range into paragraphs, each a run of lines sharing the same prefix (the
+//! indentation plus any comment leader), and re-wraps each paragraph's words
+//! under that prefix. Blank lines and lines with a different prefix separate
+//! paragraphs and are left alone.
+
+use crate::document::{Document, Range};
+use crate::operators::line_comment;
+use crate::util::Oops;
+
+/// Returns the comment leaders recognized in `language`, longest first: its
+/// line comment token and, for C-like languages, doc comment variants and
+/// the `*` which continues a block comment.
+///
+/// # Examples
+/// ```
+/// use ls_core::reflow::*;
+/// assert_eq!(comment_leaders("rs"), vec!["///", "//!", "//", "*"]);
+/// assert_eq!(comment_leaders("py"), vec!["#"]);
+/// assert!(comment_leaders("txt").is_empty());
+/// ```
+pub fn comment_leaders(language: &str) -> Vec<&'static str> {
+    match line_comment(language) {
+        Some("//") => vec!["///", "//!", "//", "*"],
+        Some(token) => vec![token],
+        None => vec![]
+    }
+}
+
+/// Splits `line` into its prefix, which is its indentation followed by any
+/// of `leaders` and the spaces after it, and the rest.
+fn split_prefix<'a>(line: &'a str, leaders: &[&str]) -> (&'a str, &'a str) {
+    let body = line.trim_start();
+    let mut prefix = line.len() - body.len();
+
+    if let Some(leader) = leaders.iter().find(|leader| body.starts_with(*leader)) {
+        let after = &body[leader.len()..];
+        prefix += leader.len() + after.len() - after.trim_start().len();
+    }
+
+    line.split_at(prefix)
+}
+
+/// Returns the width of `prefix` in columns, with tabs `tab` wide.
+fn prefix_width(prefix: &str, tab: usize) -> usize {
+    prefix.chars().map(|c| if c == '\t' { tab } else { 1 }).sum()
+}
+
+/// Returns `words` wrapped into lines which, after `prefix`, fit in `width`
+/// columns where possible. Words too long to fit get a line of their own.
+fn wrap(words: &[&str], prefix: &str, width: usize, tab: usize) -> Vec<String> {
+    let room = width.saturating_sub(prefix_width(prefix, tab.max(1)));
+    let mut lines: Vec<String> = vec![];
+    let mut current = String::new();
+
+    for word in words {
+        let needed = current.chars().count() + 1 + word.chars().count();
+        if !current.is_empty() && needed > room {
+            lines.push(format!("{}{}", prefix, current));
+            current.clear();
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    lines.push(format!("{}{}", prefix, current));
+    lines
+}
+
+impl Document {
+    /// Re-wraps the prose and comment paragraphs on the lines `range`
+    /// touches so each line fits in `width` columns, keeping each
+    /// paragraph's indentation and comment leader, as one change packet
+    /// labeled "reflow". Only what changed is edited, so anchors in lines
+    /// which were already wrapped stay put. Returns the range of the
+    /// reflowed lines, or `Err` if `range` is invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language(
+    ///     "fn f() {\n    // Returns the answer, which is forty-two, to every question.\n}",
+    ///     "rs"
+    /// );
+    /// let reflowed = document.reflow(&Range::from(1, 0, 1, 0), 30).unwrap();
+    /// assert_eq!(document.text(), concat!(
+    ///     "fn f() {\n",
+    ///     "    // Returns the answer,\n",
+    ///     "    // which is forty-two, to\n",
+    ///     "    // every question.\n",
+    ///     "}"
+    /// ));
+    /// assert_eq!(reflowed, Range::from(1, 0, 3, 22));
+    /// ```
+    pub fn reflow(&mut self, range: &Range, width: usize) -> Result<Range, Oops> {
+        if !self.range_valid(range) {
+            return Err(Oops::InvalidRange(*range, "reflow"));
+        }
+
+        let (first, last) = self.operated_rows(range);
+        let leaders = comment_leaders(self.language());
+        let tab = self.indentation().spaces_per_tab;
+
+        let mut output: Vec<String> = vec![];
+        let mut paragraph: Option<(&str, Vec<&str>)> = None;
+
+        for line in self.lines()[first..=last].iter() {
+            let (prefix, body) = split_prefix(&line.content, &leaders);
+
+            match paragraph.as_mut() {
+                Some((current, words)) if *current == prefix && !body.trim().is_empty() => {
+                    words.extend(body.split_whitespace());
+                    continue;
+                },
+                _ => ()
+            }
+
+            if let Some((prefix, words)) = paragraph.take() {
+                output.extend(wrap(&words, prefix, width, tab));
+            }
+            if body.trim().is_empty() {
+                output.push(line.content.clone());
+            } else {
+                paragraph = Some((prefix, body.split_whitespace().collect()));
+            }
+        }
+
+        if let Some((prefix, words)) = paragraph.take() {
+            output.extend(wrap(&words, prefix, width, tab));
+        }
+
+        let old = Range::from(first, 0, last, self.lines()[last].length);
+        let new = output.join("\n");
+        let ending_row = first + output.len() - 1;
+        let reflowed = Range::from(first, 0, ending_row, output[output.len() - 1].chars().count());
+
+        self.replace_changed(&old, &new, "reflow")?;
+        Ok(reflowed)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::document::*;
+
+    #[test]
+    fn paragraphs_keep_their_prefixes() {
+        let text = "# one two three four five\n# six\n#\n#   seven eight\nnine ten eleven";
+        let mut document = Document::from_with_language(text, "py");
+
+        let reflowed = document.reflow(&Range::from(0, 0, 4, 3), 12).unwrap();
+        assert_eq!(document.text(), concat!(
+            "# one two\n# three four\n# five six\n#\n",
+            "#   seven\n#   eight\n",
+            "nine ten\neleven"
+        ));
+        assert_eq!(reflowed, Range::from(0, 0, 7, 6));
+
+        let depth = document.undo_redo().depth().0;
+        assert_eq!(document.reflow(&reflowed, 12), Ok(reflowed));
+        assert_eq!(document.undo_redo().depth().0, depth);
+
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), text);
+        assert!(document.reflow(&Range::from(9, 0, 9, 0), 12).is_err());
+    }
+}
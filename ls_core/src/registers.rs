@@ -0,0 +1,355 @@
+//! Registers: named slots holding yanked text, for spoken commands like
+//! "yank into alpha" and "paste alpha".
+//!
+//! A register holds either ordinary text or a rectangular block. Blocks are
+//! yanked from the rectangle whose opposite corners are the cursor and mark,
+//! and are pasted column-wise, one piece per line, starting at the cursor.
+//!
+//! Every yank, and every deletion by an [operator](crate::operators), is
+//! also kept in a clipboard history of the last [`CLIPBOARD_HISTORY_SIZE`]
+//! clips, with when and where each came from, so "paste the thing I copied
+//! before the function name" can be found with a [`ClipQuery`]. Source
+//! ranges are where the text was when it was taken, and do not follow
+//! later edits.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::document::{Document, Position, Range, InsertOptions};
+use crate::paste::PasteOptions;
+use crate::util::{self, Oops};
+
+/// The register used when none is named.
+pub const DEFAULT_REGISTER: char = '"';
+
+/// The contents of a register.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum RegisterContent {
+    /// Text which is pasted like any other.
+    Text(String),
+
+    /// A column-shaped payload: one piece per line, each covering `width`
+    /// columns. Pieces from lines which ended inside the block are shorter.
+    Block { lines: Vec<String>, width: usize }
+}
+
+/// A document's registers, by name.
+pub type Registers = HashMap<char, RegisterContent>;
+
+/// How many clips the clipboard history keeps.
+pub const CLIPBOARD_HISTORY_SIZE: usize = 32;
+
+/// Words a spoken [`ClipQuery`] may include which do not have to match.
+const FILLER_WORDS: &[&str] = &["the", "a", "an"];
+
+/// How a clip was taken.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ClipSource {
+    /// Copied, leaving the text in place.
+    Yank,
+
+    /// Deleted or changed.
+    Kill
+}
+
+/// An entry in the clipboard history.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Clip {
+    pub content: RegisterContent,
+    pub source: ClipSource,
+
+    /// Where the text was taken from.
+    pub range: Range,
+
+    /// When it was taken, by the document's [clock](Document::set_clock).
+    pub timestamp: u64
+}
+
+/// A document's clipboard history, oldest first.
+pub(crate) type ClipboardHistory = VecDeque<Clip>;
+
+/// A way to pick a clip from the history.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum ClipQuery {
+    /// The clip this many places back: 0 is the newest.
+    Recent(usize),
+
+    /// The newest clip whose text has every spoken word, ignoring case,
+    /// punctuation, and how the words are joined.
+    Matching(String),
+
+    /// The clip taken just before the newest one [matching](ClipQuery::Matching).
+    Before(String)
+}
+
+/// Returns `text` lowercased with only its letters and digits.
+fn squash(text: &str) -> String {
+    text.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+impl RegisterContent {
+    /// Returns the text, with a block's pieces on separate lines.
+    pub fn text(&self) -> String {
+        match self {
+            RegisterContent::Text(text) => text.clone(),
+            RegisterContent::Block { lines, .. } => lines.join("\n")
+        }
+    }
+}
+
+impl Clip {
+    /// Does this clip's text have every word of `spoken`?
+    fn matches(&self, spoken: &str) -> bool {
+        let text = squash(&self.content.text());
+        spoken.split_whitespace()
+            .filter(|w| !FILLER_WORDS.contains(&w.to_lowercase().as_str()))
+            .all(|w| text.contains(&squash(w)))
+    }
+}
+
+impl Document {
+    /// Returns the contents of register `name`, if it has been set.
+    pub fn register(&self, name: char) -> Option<&RegisterContent> {
+        self.registers.get(&name)
+    }
+
+    /// Sets register `name` to `content`.
+    pub fn set_register(&mut self, name: char, content: RegisterContent) {
+        self.registers.insert(name, content);
+    }
+
+    /// Adds `content`, taken from `range`, to the clipboard history.
+    pub(crate) fn record_clip(&mut self, content: RegisterContent, source: ClipSource, range: Range) {
+        let timestamp = self.undo_redo().now();
+        self.clipboard_history.push_back(Clip { content, source, range, timestamp });
+        if self.clipboard_history.len() > CLIPBOARD_HISTORY_SIZE {
+            self.clipboard_history.pop_front();
+        }
+    }
+
+    /// Returns the clipboard history, newest first.
+    pub fn clipboard_history(&self) -> Vec<&Clip> {
+        self.clipboard_history.iter().rev().collect()
+    }
+
+    /// Returns the clips whose text has every word of `spoken` (see
+    /// [`ClipQuery::Matching`]), newest first.
+    pub fn search_clipboard(&self, spoken: &str) -> Vec<&Clip> {
+        self.clipboard_history.iter().rev().filter(|clip| clip.matches(spoken)).collect()
+    }
+
+    /// Returns the clip `query` picks, if there is one.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::registers::*;
+    /// let mut document = Document::from("let total_width = measure(frame);");
+    /// for range in [Range::from(0, 26, 0, 31), Range::from(0, 4, 0, 15), Range::from(0, 18, 0, 25)] {
+    ///     document.set_selection(&range).unwrap();
+    ///     document.yank(DEFAULT_REGISTER).unwrap();
+    /// }
+    ///
+    /// let text = |clip: Option<&Clip>| clip.map(|c| c.content.text());
+    /// assert_eq!(text(document.find_clip(&ClipQuery::Recent(0))).unwrap(), "measure");
+    /// assert_eq!(text(document.find_clip(&ClipQuery::Matching(String::from("the total width")))).unwrap(), "total_width");
+    /// assert_eq!(text(document.find_clip(&ClipQuery::Before(String::from("total width")))).unwrap(), "frame");
+    /// assert_eq!(document.find_clip(&ClipQuery::Before(String::from("frame"))), None);
+    /// ```
+    pub fn find_clip(&self, query: &ClipQuery) -> Option<&Clip> {
+        let newest_first = || self.clipboard_history.iter().rev();
+        match query {
+            ClipQuery::Recent(back) => newest_first().nth(*back),
+            ClipQuery::Matching(spoken) => newest_first().find(|clip| clip.matches(spoken)),
+            ClipQuery::Before(spoken) => {
+                let index = newest_first().position(|clip| clip.matches(spoken))?;
+                newest_first().nth(index + 1)
+            }
+        }
+    }
+
+    /// Pastes the clip `query` picks, as [`Document::put`] would from a
+    /// register. Returns `Err` if no clip matches.
+    pub fn put_clip(&mut self, query: &ClipQuery) -> Result<(), Oops> {
+        let content = self.find_clip(query)
+            .map(|clip| clip.content.clone())
+            .ok_or(Oops::Ouch("no such clip"))?;
+        self.put_content(content)
+    }
+
+    /// Returns the rows and the columns spanned by the rectangle with the
+    /// cursor and mark at opposite corners.
+    pub fn selection_block(&self) -> (std::ops::RangeInclusive<usize>, std::ops::Range<usize>) {
+        let cursor = self.cursor().position;
+        let mark = self.mark().position;
+        (
+            cursor.row.min(mark.row)..=cursor.row.max(mark.row),
+            cursor.column.min(mark.column)..cursor.column.max(mark.column)
+        )
+    }
+
+    /// Copies the selection into register `name` as text.
+    pub fn yank(&mut self, name: char) -> Result<(), Oops> {
+        let text = self.text_range(&self.selection())
+            .ok_or(Oops::InvalidRange(self.selection(), "yank"))?;
+        self.set_register(name, RegisterContent::Text(text.clone()));
+        self.record_clip(RegisterContent::Text(text), ClipSource::Yank, self.selection());
+        Ok(())
+    }
+
+    /// Copies the block selection (see [`Document::selection_block`]) into
+    /// register `name`.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::registers::*;
+    /// let mut document = Document::from("abcd\nef\nijkl");
+    /// document.set_mark(&Position::from(0, 1)).unwrap();
+    /// document.set_cursor(&Position::from(2, 3)).unwrap();
+    /// document.yank_block('a').unwrap();
+    /// assert_eq!(document.register('a'), Some(&RegisterContent::Block {
+    ///     lines: vec![String::from("bc"), String::from("f"), String::from("jk")],
+    ///     width: 2
+    /// }));
+    /// ```
+    pub fn yank_block(&mut self, name: char) -> Result<(), Oops> {
+        let (rows, columns) = self.selection_block();
+        let range = Range::from(*rows.start(), columns.start, *rows.end(), columns.end);
+
+        let lines = rows
+            .map(|row| {
+                let line = &self.lines()[row];
+                let start = columns.start.min(line.length);
+                let end = columns.end.min(line.length);
+                String::from(util::slice(&line.content, start..end))
+            })
+            .collect();
+
+        let content = RegisterContent::Block { lines, width: columns.len() };
+        self.set_register(name, content.clone());
+        self.record_clip(content, ClipSource::Yank, range);
+        Ok(())
+    }
+
+    /// Pastes register `name` as a single undoable packet. Text replaces the
+    /// selection, as with [`Document::paste`]. A block is inserted at the
+    /// cursor's column on the cursor's row and the rows below it: short
+    /// lines are padded with spaces, missing lines are added, and pieces are
+    /// padded to the block's width when text follows them. The cursor ends
+    /// at the top left corner of the pasted block.
+    ///
+    /// Returns `Err` if the register is empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::registers::*;
+    /// let mut document = Document::from("1234\n1\n1234");
+    /// document.set_register('a', RegisterContent::Block {
+    ///     lines: vec![String::from("x"), String::from("yy"), String::from("z"), String::from("w")],
+    ///     width: 2
+    /// });
+    /// document.set_cursor_and_mark(&Position::from(0, 2)).unwrap();
+    /// document.put('a').unwrap();
+    /// assert_eq!(document.text(), "12x 34\n1 yy\n12z 34\n  w");
+    ///
+    /// document.undo(1);
+    /// assert_eq!(document.text(), "1234\n1\n1234");
+    /// ```
+    pub fn put(&mut self, name: char) -> Result<(), Oops> {
+        let content = self.register(name)
+            .cloned()
+            .ok_or(Oops::Ouch("register is empty"))?;
+        self.put_content(content)
+    }
+
+    fn put_content(&mut self, content: RegisterContent) -> Result<(), Oops> {
+        match content {
+            RegisterContent::Text(text) => self.paste(&text, &PasteOptions::exact()),
+            RegisterContent::Block { lines, width } => self.put_block(&lines, width)
+        }
+    }
+
+    fn put_block(&mut self, pieces: &[String], width: usize) -> Result<(), Oops> {
+        let origin = self.cursor().position;
+
+        self.checkpoint_labeled("paste block");
+
+        for (i, piece) in pieces.iter().enumerate() {
+            let row = origin.row + i;
+
+            if row == self.rows() {
+                let last = self.rows() - 1;
+                let end = Position::from(last, self.lines()[last].length);
+                self.insert("\n", &InsertOptions::exact_at(&Range { beginning: end, ending: end }))?;
+            }
+
+            let length = self.lines()[row].length;
+            let mut text = String::new();
+
+            if length < origin.column {
+                text += &" ".repeat(origin.column - length);
+            }
+            text += piece;
+            if length > origin.column {
+                text += &" ".repeat(width.saturating_sub(piece.chars().count()));
+            }
+
+            let at = Position::from(row, origin.column.min(length));
+            self.insert(&text, &InsertOptions::exact_at(&Range { beginning: at, ending: at }))?;
+        }
+
+        self.set_cursor_and_mark(&origin)?;
+        self.checkpoint();
+
+        Ok(())
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yank_and_put_round_trip() {
+        let mut document = Document::from("one two\nthree four");
+        document.set_selection(&Range::from(0, 4, 0, 7)).unwrap();
+        document.yank(DEFAULT_REGISTER).unwrap();
+        document.set_cursor_and_mark(&Position::from(1, 10)).unwrap();
+        document.put(DEFAULT_REGISTER).unwrap();
+        assert_eq!(document.text(), "one two\nthree fourtwo");
+
+        document.set_mark(&Position::from(0, 0)).unwrap();
+        document.set_cursor(&Position::from(1, 3)).unwrap();
+        document.yank_block('b').unwrap();
+        document.set_cursor_and_mark(&Position::from(0, 7)).unwrap();
+        document.put('b').unwrap();
+        assert_eq!(document.text(), "one twoone\nthree fthrourtwo");
+        document.undo(1);
+        assert_eq!(document.text(), "one two\nthree fourtwo");
+
+        assert!(document.put('z').is_err());
+
+        let history = document.clipboard_history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].range, Range::from(0, 0, 1, 3));
+        assert_eq!(history[1].content, RegisterContent::Text(String::from("two")));
+
+        document.set_cursor_and_mark(&Position::from(0, 0)).unwrap();
+        document.execute_phrase("delete word").unwrap();
+        let kill = document.find_clip(&ClipQuery::Recent(0)).unwrap();
+        assert_eq!((kill.source, kill.content.text()), (ClipSource::Kill, String::from("one")));
+        assert_eq!(document.search_clipboard("two").len(), 1);
+
+        document.put_clip(&ClipQuery::Before(String::from("one"))).unwrap();
+        assert_eq!(document.text(), "one two\nthrthree fourtwo");
+        assert!(document.put_clip(&ClipQuery::Matching(String::from("nothing"))).is_err());
+
+        for _ in 0..CLIPBOARD_HISTORY_SIZE {
+            document.yank(DEFAULT_REGISTER).unwrap();
+        }
+        assert_eq!(document.clipboard_history().len(), CLIPBOARD_HISTORY_SIZE);
+    }
+}
@@ -0,0 +1,244 @@
+//! A read-only model of the visible region of a [`Document`], for use by
+//! renderers.
+//!
+//! The render model separates content into channels: the document's own
+//! text, syntax highlight spans over that text, and
+//! [virtual text](crate::virtual_text) which is displayed inline but is not
+//! part of the document, and [line annotations](crate::annotations) for the
+//! gutter. Rows hidden by [folds](crate::folds) are skipped.
+
+use crate::annotations::LineAnnotation;
+use crate::document::{Document, Position, AnchorHandle};
+#[cfg(feature = "parsing")]
+use crate::syntax;
+
+/// Virtual text placed within a [`RenderLine`].
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct InlineVirtualText {
+    /// The column (in codepoints) before which the text is displayed.
+    pub column: usize,
+    pub text: String,
+    pub style: String,
+    pub handle: AnchorHandle
+}
+
+/// A syntax highlight class applied to columns `start..end` (in codepoints)
+/// of a [`RenderLine`].
+///
+/// `class` is one of `"comment"`, `"string"`, `"number"`, `"keyword"`, or
+/// `"type"`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct HighlightSpan {
+    pub start: usize,
+    pub end: usize,
+    pub class: &'static str
+}
+
+/// A single document line, prepared for display.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct RenderLine {
+    pub row: usize,
+    pub content: String,
+    pub highlights: Vec<HighlightSpan>,
+    pub virtual_text: Vec<InlineVirtualText>,
+
+    /// The line's annotations with their channels, in alphabetical order of
+    /// channel.
+    pub gutter: Vec<(String, LineAnnotation)>,
+
+    /// If this line is the header of a fold, the number of rows it hides.
+    pub folded: Option<usize>
+}
+
+/// The visible region of a document: a run of lines plus cursor and mark.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Viewport {
+    pub first_row: usize,
+    pub lines: Vec<RenderLine>,
+    pub cursor: Position,
+    pub mark: Position,
+
+    /// The column at which to draw a ruler: the document's
+    /// [maximum line length](crate::ruler), if any.
+    pub ruler: Option<usize>,
+
+    /// The [render revision](crate::damage) this viewport shows. Pass it to
+    /// [`Document::damage_since`] to learn what to repaint next frame.
+    pub revision: u64
+}
+
+impl Document {
+    /// Returns the render model for up to `rows` visible lines starting at
+    /// `first_row`. Rows hidden by folds and rows past the end of the
+    /// document are omitted.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("a\nb\nc");
+    /// document.add_virtual_text(&Position::from(1, 1), " = 2", "result").unwrap();
+    /// let viewport = document.viewport(1, 10);
+    /// assert_eq!(viewport.lines.len(), 2);
+    /// assert_eq!(viewport.lines[0].content, "b");
+    /// assert_eq!(viewport.lines[0].virtual_text[0].text, " = 2");
+    /// ```
+    pub fn viewport(&self, first_row: usize, rows: usize) -> Viewport {
+        let mut lines = vec![];
+        let mut row = first_row;
+
+        while lines.len() < rows && row < self.rows() {
+            if self.row_hidden(row) {
+                row += 1;
+                continue;
+            }
+
+            let folded = self.fold_at_row(row).map(|(_, fold)| fold.hidden_rows());
+
+            lines.push(RenderLine {
+                row,
+                content: self.line(row).unwrap().clone(),
+                highlights: self.highlights_on_row(row),
+                virtual_text: self.virtual_text_on_row(row)
+                    .into_iter()
+                    .map(|(position, handle, vt)| InlineVirtualText {
+                        column: position.column,
+                        text: vt.text.clone(),
+                        style: vt.style.clone(),
+                        handle
+                    })
+                    .collect(),
+                gutter: self.annotations_on_row(row)
+                    .into_iter()
+                    .map(|(channel, annotation)| (String::from(channel), annotation.clone()))
+                    .collect(),
+                folded
+            });
+
+            row += folded.map_or(1, |hidden| hidden + 1);
+        }
+
+        Viewport {
+            first_row,
+            lines,
+            cursor: self.cursor().position,
+            mark: self.mark().position,
+            ruler: self.max_line_length(),
+            revision: self.render_revision()
+        }
+    }
+}
+
+/// Returns the highlight class of a parse tree node, if any. Comments and
+/// strings are highlighted as a whole; everything else only at the leaves.
+#[cfg(feature = "parsing")]
+fn highlight_class(node: &syntax::Node) -> Option<&'static str> {
+    let kind = node.kind();
+
+    if kind.contains("comment") {
+        Some("comment")
+    } else if kind.contains("string") || kind == "char_literal" || kind == "character_literal" {
+        Some("string")
+    } else if node.child_count() > 0 {
+        None
+    } else if kind == "number" || kind.ends_with("integer_literal") || kind.ends_with("float_literal") {
+        Some("number")
+    } else if kind.ends_with("type_identifier") || kind == "primitive_type" {
+        Some("type")
+    } else if !node.is_named() && kind.chars().all(|c| c.is_ascii_lowercase() || c == '_') {
+        Some("keyword")
+    } else {
+        None
+    }
+}
+
+impl Document {
+    /// Returns the syntax highlight spans on `row`, sorted by column. Empty
+    /// without a parse tree.
+    pub fn highlights_on_row(&self, row: usize) -> Vec<HighlightSpan> {
+        let mut spans: Vec<HighlightSpan> = vec![];
+
+        #[cfg(feature = "parsing")]
+        {
+            let tree = match self.parse_tree() {
+                Some(tree) => tree,
+                None => return spans
+            };
+
+            let length = match self.lines().get(row) {
+                Some(line) => line.length,
+                None => return spans
+            };
+
+            let mut cursor = tree.walk();
+            'walk: loop {
+                let node = cursor.node();
+                let in_row = node.start_position().row <= row && row <= node.end_position().row;
+                let class = if in_row { highlight_class(&node) } else { None };
+
+                if let Some(class) = class {
+                    let range = self.range_from_ts(&node.range());
+                    let start = if range.beginning.row == row { range.beginning.column } else { 0 };
+                    let end = if range.ending.row == row { range.ending.column } else { length };
+                    if start < end {
+                        spans.push(HighlightSpan { start, end, class });
+                    }
+                }
+
+                if in_row && class.is_none() && cursor.goto_first_child() {
+                    continue;
+                }
+                if cursor.goto_next_sibling() {
+                    continue;
+                }
+                loop {
+                    if !cursor.goto_parent() {
+                        break 'walk;
+                    }
+                    if cursor.goto_next_sibling() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        #[cfg(not(feature = "parsing"))]
+        let _ = row;
+
+        spans.sort_by_key(|h| h.start);
+        spans
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn viewport_skips_folded_rows() {
+        let mut document = Document::from("a\nb\nc\nd\ne");
+        document.fold_rows(1, 3).unwrap();
+
+        let viewport = document.viewport(0, 3);
+        let rows: Vec<usize> = viewport.lines.iter().map(|l| l.row).collect();
+        assert_eq!(rows, vec![0, 1, 4]);
+        assert_eq!(viewport.lines[1].folded, Some(2));
+        assert_eq!(viewport.lines[0].folded, None);
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn highlights() {
+        let document = Document::from_with_language("let x: u8 = 1; // one\nlet s = \"a\nb\";", "rs");
+        let classes = |row| -> Vec<(usize, usize, &'static str)> {
+            document.highlights_on_row(row).into_iter().map(|h| (h.start, h.end, h.class)).collect()
+        };
+
+        assert_eq!(classes(0), vec![
+            (0, 3, "keyword"), (7, 9, "type"), (12, 13, "number"), (15, 21, "comment")
+        ]);
+        assert_eq!(classes(1), vec![(0, 3, "keyword"), (8, 10, "string")]);
+        assert_eq!(classes(2), vec![(0, 2, "string")]);
+    }
+}
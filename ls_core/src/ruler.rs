@@ -0,0 +1,110 @@
+//! A maximum line length, shown as a ruler column in the render model and
+//! reported as diagnostics on lines which run past it.
+//!
+//! Line length is measured in display columns: tabs advance to the next
+//! multiple of the document's
+//! [`Indentation::spaces_per_tab`](crate::document::Indentation).
+
+use crate::document::{Document, Range};
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::util::Oops;
+
+/// The diagnostic source used for long-line reports.
+pub const RULER_SOURCE: &str = "ruler";
+
+impl Document {
+    /// Returns the maximum line length, if there is one.
+    pub fn max_line_length(&self) -> Option<usize> {
+        self.config().max_line_length
+    }
+
+    /// Overrides the maximum line length for this document, or removes it
+    /// if `length` is `None`, and refreshes the long-line diagnostics. See
+    /// [`crate::config`].
+    pub fn set_max_line_length(&mut self, length: Option<usize>) -> Result<(), Oops> {
+        self.config_overrides.max_line_length = Some(length);
+        self.damage.record_from(0);
+        self.check_line_lengths()
+    }
+
+    /// Returns a warning for every line longer than the maximum, covering
+    /// exactly the overflowing text. Empty if there is no maximum.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("short\n\tindented line");
+    /// document.set_max_line_length(Some(10)).unwrap();
+    /// let long = document.long_lines();
+    /// assert_eq!(long.len(), 1);
+    /// assert_eq!(long[0].range, Range::from(1, 7, 1, 14));
+    /// assert_eq!(document.diagnostics(), long);
+    /// ```
+    pub fn long_lines(&self) -> Vec<Diagnostic> {
+        let limit = match self.max_line_length() {
+            Some(limit) => limit,
+            None => return vec![]
+        };
+        let tab = self.indentation().spaces_per_tab;
+        let mut result = vec![];
+
+        for (row, line) in self.lines().iter().enumerate() {
+            let mut width = 0;
+            let mut overflow = None;
+
+            for (column, c) in line.content.chars().enumerate() {
+                width = if c == '\t' { (width / tab + 1) * tab } else { width + 1 };
+                if width > limit && overflow.is_none() {
+                    overflow = Some(column);
+                }
+            }
+
+            if let Some(column) = overflow {
+                let mut diagnostic = Diagnostic::from(
+                    &Range::from(row, column, row, line.length),
+                    Severity::Warning,
+                    &format!("line is {} columns long; the limit is {}", width, limit)
+                );
+                diagnostic.source = String::from(RULER_SOURCE);
+                result.push(diagnostic);
+            }
+        }
+
+        result
+    }
+
+    /// Replaces the long-line diagnostics with a fresh [`Document::long_lines`].
+    /// Like any diagnostics, they follow edits but are not recomputed until
+    /// this is called again.
+    pub fn check_line_lengths(&mut self) -> Result<(), Oops> {
+        let long = self.long_lines();
+        self.set_diagnostics(RULER_SOURCE, long)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::*;
+
+    #[test]
+    fn ruler_tracks_limit() {
+        let mut document = Document::from("abcdef\nab\n火火火火火火");
+        document.set_max_line_length(Some(4)).unwrap();
+        assert_eq!(document.viewport(0, 10).ruler, Some(4));
+        let ranges: Vec<Range> = document.diagnostics().iter().map(|d| d.range).collect();
+        assert_eq!(ranges, vec![Range::from(0, 4, 0, 6), Range::from(2, 4, 2, 6)]);
+
+        document.set_indentation(&Indentation::tabs(4)).unwrap();
+        document.insert("\t", &InsertOptions::exact_at(&Range::from(1, 0, 1, 0))).unwrap();
+        document.check_line_lengths().unwrap();
+        assert_eq!(document.diagnostics()[1].range, Range::from(1, 1, 1, 3));
+        assert_eq!(document.diagnostics()[1].message, "line is 6 columns long; the limit is 4");
+
+        document.set_max_line_length(None).unwrap();
+        assert!(document.diagnostics().is_empty());
+        assert_eq!(document.viewport(0, 10).ruler, None);
+    }
+}
@@ -0,0 +1,351 @@
+//! The pre-save pipeline: cleanups and formatters applied to a document
+//! just before it is written out.
+//!
+//! Each step runs as its own labeled [`ChangePacket`], so the user can undo
+//! any one of them after saving. The pipeline as a whole is atomic: if a
+//! step fails, every step already applied is rolled back.
+
+use crate::document::{Document, ChangePacket, Position, Range, InsertOptions, RemoveOptions};
+use crate::util::Oops;
+use crate::events::LifecycleEvent;
+use crate::config::LineEnding;
+
+/// A user-registered formatter. Given the document, returns the changes
+/// to apply to it (possibly none).
+pub type Formatter = Box<dyn Fn(&Document) -> ChangePacket>;
+
+/// How the end of a document's saved output is written. This only affects
+/// [`Document::output_text`]; the document's lines are left alone.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum FinalNewline {
+    /// Output the text exactly as it is stored.
+    Preserve,
+
+    /// End with exactly one newline, collapsing any trailing blank lines.
+    Single,
+
+    /// End without a newline, removing any trailing blank lines.
+    Strip
+}
+
+/// A named [`Formatter`] which can be switched on and off.
+struct FormatterEntry {
+    name: String,
+    formatter: Formatter,
+    enabled: bool
+}
+
+/// The steps run by [`Document::prepare_save`], in order: trimming trailing
+/// whitespace, ensuring a final newline, reindenting, and then each enabled
+/// formatter in registration order.
+pub struct SavePipeline {
+    pub trim_trailing_whitespace: bool,
+    pub ensure_final_newline: bool,
+
+    /// Rewrites every line's left margin under the document's
+    /// [`Indentation`](crate::document::Indentation) policy.
+    pub reindent: bool,
+
+    /// Controls how the saved output ends. Applied after every other step.
+    pub final_newline: FinalNewline,
+
+    formatters: Vec<FormatterEntry>
+}
+
+impl SavePipeline {
+    /// Returns a pipeline with every step switched off.
+    pub fn new() -> SavePipeline {
+        SavePipeline {
+            trim_trailing_whitespace: false,
+            ensure_final_newline: false,
+            reindent: false,
+            final_newline: FinalNewline::Preserve,
+            formatters: vec![]
+        }
+    }
+
+    /// Registers `formatter` under `name`, enabled, replacing any formatter
+    /// already registered under that name.
+    pub fn add_formatter(&mut self, name: &str, formatter: Formatter) {
+        self.remove_formatter(name);
+        self.formatters.push(FormatterEntry {
+            name: String::from(name),
+            formatter,
+            enabled: true
+        });
+    }
+
+    /// Unregisters the formatter `name`. Returns false if there was none.
+    pub fn remove_formatter(&mut self, name: &str) -> bool {
+        let before = self.formatters.len();
+        self.formatters.retain(|f| f.name != name);
+        self.formatters.len() != before
+    }
+
+    /// Enables or disables the formatter `name`. Returns false if there is none.
+    pub fn set_formatter_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.formatters.iter_mut().find(|f| f.name == name) {
+            None => false,
+            Some(f) => {
+                f.enabled = enabled;
+                true
+            }
+        }
+    }
+
+    /// Returns the names of the registered formatters, in the order they run.
+    pub fn formatter_names(&self) -> Vec<&str> {
+        self.formatters.iter().map(|f| f.name.as_str()).collect()
+    }
+}
+
+impl Default for SavePipeline {
+    fn default() -> SavePipeline {
+        SavePipeline::new()
+    }
+}
+
+impl Document {
+    /// Returns this document's save pipeline.
+    pub fn save_pipeline(&self) -> &SavePipeline {
+        &self.save_pipeline
+    }
+
+    /// Returns this document's save pipeline for configuration.
+    pub fn save_pipeline_mut(&mut self) -> &mut SavePipeline {
+        &mut self.save_pipeline
+    }
+
+    /// Runs the save pipeline and returns the text to write. Listeners hear
+    /// [`LifecycleEvent::BeforeSave`] first; call [`Document::notify_saved`]
+    /// once the text has been written.
+    ///
+    /// Each step that changes anything becomes its own labeled, undoable
+    /// [`ChangePacket`]. If any step fails, all of them are rolled back and
+    /// the error is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("fn main() {  \n\tlet x = 1;\n}");
+    /// document.save_pipeline_mut().trim_trailing_whitespace = true;
+    /// document.save_pipeline_mut().ensure_final_newline = true;
+    /// document.save_pipeline_mut().reindent = true;
+    ///
+    /// assert_eq!(document.prepare_save().unwrap(), "fn main() {\n    let x = 1;\n}\n");
+    /// assert_eq!(document.undo_redo().depth(), (3, 0));
+    /// ```
+    pub fn prepare_save(&mut self) -> Result<String, Oops> {
+        self.dispatch(&LifecycleEvent::BeforeSave);
+
+        let mark = self.history_mark();
+
+        if let Err(oops) = self.run_save_steps() {
+            self.roll_back_to(&mark);
+            return Err(oops);
+        }
+
+        self.checkpoint();
+        Ok(self.output_text())
+    }
+
+    /// Returns the text of the document as it should be written out, with the
+    /// save pipeline's [`FinalNewline`] policy applied and lines ended as
+    /// [configured](crate::config::DocumentConfig::end_of_line). Blank lines
+    /// are lines holding only whitespace.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::save::*;
+    /// let mut document = Document::from("a\nb\n\n  \n");
+    /// document.save_pipeline_mut().final_newline = FinalNewline::Single;
+    /// assert_eq!(document.output_text(), "a\nb\n");
+    /// document.save_pipeline_mut().final_newline = FinalNewline::Strip;
+    /// assert_eq!(document.output_text(), "a\nb");
+    /// assert_eq!(document.rows(), 5);
+    /// ```
+    pub fn output_text(&self) -> String {
+        let text = self.text();
+
+        let text = match self.save_pipeline.final_newline {
+            FinalNewline::Preserve => text,
+            policy => {
+                let lines = self.lines();
+                let kept = lines.iter().rposition(|l| !l.content.trim().is_empty()).map_or(0, |i| i + 1);
+
+                let mut result = lines[..kept]
+                    .iter()
+                    .map(|l| l.content.as_str())
+                    .collect::<Vec<&str>>()
+                    .join("\n");
+
+                if policy == FinalNewline::Single && kept > 0 {
+                    result.push('\n');
+                }
+
+                result
+            }
+        };
+
+        match self.config().end_of_line {
+            LineEnding::Lf => text,
+            ending => text.replace('\n', ending.as_str())
+        }
+    }
+
+    /// Runs each enabled step of the save pipeline. Reindenting and
+    /// formatters only run if format-on-save is
+    /// [configured](crate::config::DocumentConfig::format_on_save).
+    fn run_save_steps(&mut self) -> Result<(), Oops> {
+        let config = self.config();
+
+        if self.save_pipeline.trim_trailing_whitespace || config.trim_trailing_whitespace {
+            self.checkpoint_labeled("trim trailing whitespace");
+            self.trim_trailing_whitespace()?;
+        }
+
+        if self.save_pipeline.ensure_final_newline || config.insert_final_newline {
+            self.checkpoint_labeled("ensure final newline");
+            let last = self.rows() - 1;
+            if !self.lines()[last].content.is_empty() {
+                let end = Position::from(last, self.lines()[last].length);
+                self.insert("\n", &InsertOptions::exact_at(&Range { beginning: end, ending: end }))?;
+            }
+        }
+
+        if !config.format_on_save {
+            return Ok(());
+        }
+
+        if self.save_pipeline.reindent {
+            self.checkpoint_labeled("reindent");
+            self.reindent()?;
+        }
+
+        // Each formatter sees the text as the formatters before it left it.
+        for index in 0..self.save_pipeline.formatters.len() {
+            let entry = &self.save_pipeline.formatters[index];
+            if !entry.enabled {
+                continue;
+            }
+
+            let mut packet = (entry.formatter)(self);
+            if packet.label().is_none() {
+                packet = ChangePacket::from(packet.changes().clone()).labeled(&entry.name);
+            }
+            if !packet.changes().is_empty() {
+                self.apply_packet(&packet)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes whitespace at the end of every line.
+    pub fn trim_trailing_whitespace(&mut self) -> Result<(), Oops> {
+        for row in 0..self.rows() {
+            let line = &self.lines()[row];
+            let kept = line.content.trim_end().chars().count();
+            if kept < line.length {
+                let length = line.length;
+                self.remove(&RemoveOptions::exact_at(&Range::from(row, kept, row, length)))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rewrites the left margin of every non-blank line under the document's
+    /// indentation policy, without changing its visual width.
+    pub fn reindent(&mut self) -> Result<(), Oops> {
+        let indentation = *self.indentation();
+
+        for row in 0..self.rows() {
+            let line = &self.lines()[row].content;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (_, margin_bytes) = indentation.measure(line);
+            let margin = &line[..margin_bytes];
+            let wanted = indentation.indent(line, 0, false);
+
+            if margin != wanted {
+                let old_columns = margin.chars().count();
+                let range = Range::from(row, 0, row, old_columns);
+                if old_columns > 0 {
+                    self.remove(&RemoveOptions::exact_at(&range))?;
+                }
+                if !wanted.is_empty() {
+                    self.insert(&wanted, &InsertOptions::exact_at(&Range::from(row, 0, row, 0)))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::document::*;
+
+    #[test]
+    fn formatters_run_in_order_and_roll_back() {
+        let mut document = Document::from("b\na");
+
+        document.save_pipeline_mut().add_formatter("sort", Box::new(|doc: &Document| {
+            let mut lines: Vec<String> = doc.lines().iter().map(|l| l.content.clone()).collect();
+            lines.sort();
+            let end = Position::from(doc.rows() - 1, doc.lines()[doc.rows() - 1].length);
+            ChangePacket::from(vec![
+                Change::Remove { range: Range { beginning: Position::from(0, 0), ending: end } },
+                Change::Insert { text: lines, position: Position::from(0, 0) },
+            ])
+        }));
+
+        assert_eq!(document.prepare_save().unwrap(), "a\nb");
+        let labels: Vec<Option<&str>> = document.undo_redo().undo_packets().map(|p| p.label()).collect();
+        assert_eq!(labels, vec![Some("sort")]);
+
+        document.undo(1);
+        assert_eq!(document.text(), "b\na");
+
+        document.save_pipeline_mut().trim_trailing_whitespace = true;
+        document.save_pipeline_mut().add_formatter("broken", Box::new(|_: &Document| {
+            ChangePacket::from(vec![Change::Remove { range: Range::from(9, 0, 9, 1) }])
+        }));
+        document.insert("  ", &InsertOptions::exact_at(&Range::from(0, 1, 0, 1))).unwrap();
+        let depth = document.undo_redo().depth();
+
+        assert!(document.prepare_save().is_err());
+        assert_eq!(document.text(), "b  \na");
+        assert_eq!(document.undo_redo().depth(), (depth.0, 0));
+
+        assert!(document.save_pipeline_mut().set_formatter_enabled("broken", false));
+        assert_eq!(document.prepare_save().unwrap(), "a\nb");
+    }
+
+    #[test]
+    fn formatters_see_earlier_formatters_output() {
+        let mut document = Document::from("ab");
+        document.save_pipeline_mut().add_formatter("semicolon", Box::new(|doc: &Document| {
+            let end = Position::from(0, doc.lines()[0].length);
+            ChangePacket::from(vec![Change::Insert { text: vec![String::from(";")], position: end }])
+        }));
+        document.save_pipeline_mut().add_formatter("parenthesize", Box::new(|doc: &Document| {
+            let end = Position::from(0, doc.lines()[0].length);
+            ChangePacket::from(vec![
+                Change::Insert { text: vec![String::from(")")], position: end },
+                Change::Insert { text: vec![String::from("(")], position: Position::from(0, 0) }
+            ])
+        }));
+
+        assert_eq!(document.prepare_save().unwrap(), "(ab;)");
+        let labels: Vec<Option<&str>> = document.undo_redo().undo_packets().map(|p| p.label()).collect();
+        assert_eq!(labels, vec![Some("parenthesize"), Some("semicolon")]);
+    }
+}
@@ -0,0 +1,417 @@
+//! Text search within a document.
+//!
+//! Spoken search text rarely matches the document's casing or accents, so
+//! matching can fold both away: the needle and the text are compared after
+//! [folding](fold), and matches are mapped back onto the original text.
+
+use std::collections::VecDeque;
+
+use regex::Regex;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+use crate::document::{Document, Position, Range};
+
+/// Options for [`Document::find_all`] and [`Document::find_next`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct SearchOptions {
+    /// Should matches be rejected if they are directly preceded or followed
+    /// by a word character (see [`Document::is_word_char`])?
+    pub whole_word: bool,
+
+    /// Should letters match regardless of case?
+    pub case_insensitive: bool,
+
+    /// Should accented letters match their unaccented forms, so that "cafe"
+    /// finds "café"?
+    pub ignore_diacritics: bool
+}
+
+impl SearchOptions {
+    /// Returns options which match `needle` anywhere, exactly as given.
+    pub fn exact() -> SearchOptions {
+        SearchOptions {
+            whole_word: false,
+            case_insensitive: false,
+            ignore_diacritics: false
+        }
+    }
+
+    /// Returns options which match `needle` only as a whole word.
+    pub fn whole_word() -> SearchOptions {
+        SearchOptions {
+            whole_word: true,
+            ..SearchOptions::exact()
+        }
+    }
+
+    /// Returns options for text from a speech recognizer, which match
+    /// `needle` anywhere, ignoring case and diacritics.
+    pub fn spoken() -> SearchOptions {
+        SearchOptions {
+            case_insensitive: true,
+            ignore_diacritics: true,
+            ..SearchOptions::exact()
+        }
+    }
+
+    /// Does matching fold the text at all?
+    fn folds(&self) -> bool {
+        self.case_insensitive || self.ignore_diacritics
+    }
+}
+
+/// Returns `text` as compared under `options`: lowercased if matching is
+/// case-insensitive, and with combining marks removed from its canonical
+/// decomposition if diacritics are ignored.
+///
+/// # Examples
+/// ```
+/// use ls_core::search::*;
+/// assert_eq!(fold("Café", &SearchOptions::spoken()), "cafe");
+/// assert_eq!(fold("Café", &SearchOptions { case_insensitive: true, ..SearchOptions::exact() }), "café");
+/// ```
+pub fn fold(text: &str, options: &SearchOptions) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        fold_char(c, options, &mut result);
+    }
+    result
+}
+
+/// Appends the folded form of `c` to `out`. See [`fold`].
+fn fold_char(c: char, options: &SearchOptions, out: &mut String) {
+    let mut push = |c: char| {
+        if options.case_insensitive {
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    };
+
+    if options.ignore_diacritics {
+        std::iter::once(c).nfd().filter(|c| !is_combining_mark(*c)).for_each(push);
+    } else {
+        push(c);
+    }
+}
+
+/// A document's text folded for matching, with the way back to the text.
+struct Folded {
+    text: String,
+
+    /// For each byte of `text`, the byte offset in the original text of the
+    /// character it came from.
+    origins: Vec<usize>
+}
+
+impl Folded {
+    fn new(original: &str, options: &SearchOptions) -> Folded {
+        let mut text = String::with_capacity(original.len());
+        let mut origins = Vec::with_capacity(original.len());
+
+        for (offset, c) in original.char_indices() {
+            fold_char(c, options, &mut text);
+            origins.resize(text.len(), offset);
+        }
+
+        Folded { text, origins }
+    }
+
+    /// Returns the byte range of `original` covered by the non-empty range
+    /// `start..end` of the folded text, widened to whole characters and
+    /// over any trailing characters which fold away, like combining marks.
+    fn original(&self, original: &str, start: usize, end: usize) -> (usize, usize) {
+        let last = self.origins[end - 1];
+        let next = self.origins.get(end).copied().unwrap_or(original.len());
+        let ending = if next > last {
+            next
+        } else {
+            last + original[last..].chars().next().map_or(0, char::len_utf8)
+        };
+        (self.origins[start], ending)
+    }
+}
+
+/// A lazy iterator over the occurrences of a needle in a document, in
+/// document order, from [`Document::find_iter`]. Each line is searched only
+/// when the iterator reaches it, so stopping early skips the rest of the
+/// document.
+pub struct Matches<'a> {
+    document: &'a Document,
+    options: SearchOptions,
+
+    /// The folded needle, split into lines. Empty if nothing can match.
+    parts: Vec<String>,
+
+    /// The next row to search.
+    row: usize,
+
+    /// Matches found but not yet yielded.
+    found: VecDeque<Range>,
+
+    /// The end of the last match, which the next must not begin before.
+    last_ending: Position
+}
+
+impl<'a> Matches<'a> {
+    fn new(document: &'a Document, needle: &str, options: &SearchOptions) -> Matches<'a> {
+        let needle = if options.folds() { fold(needle, options) } else { String::from(needle) };
+
+        Matches {
+            document,
+            options: *options,
+            parts: if needle.is_empty() { vec![] } else { needle.split('\n').map(String::from).collect() },
+            row: 0,
+            found: VecDeque::new(),
+            last_ending: Position::default()
+        }
+    }
+
+    /// Returns `line` folded for matching, or `None` if it is matched as is.
+    fn fold_line(&self, line: &str) -> Option<Folded> {
+        self.options.folds().then(|| Folded::new(line, &self.options))
+    }
+
+    /// Finds the matches beginning on the next row, returning `false` if
+    /// there are no rows left.
+    fn search_row(&mut self) -> bool {
+        let lines = self.document.lines();
+        if self.parts.is_empty() || self.row >= lines.len() {
+            return false;
+        }
+
+        let row = self.row;
+        self.row += 1;
+        let line = &lines[row].content;
+        let folded = self.fold_line(line);
+        let haystack = folded.as_ref().map_or(line.as_str(), |f| f.text.as_str());
+
+        if let [needle] = self.parts.as_slice() {
+            let candidates: Vec<(usize, usize)> = haystack.match_indices(needle.as_str())
+                .map(|(start, matched)| original_bytes(&folded, line, start, start + matched.len()))
+                .collect();
+            for (start, end) in candidates {
+                self.accept(row, start, row, end);
+            }
+            return true;
+        }
+
+        let (first, last) = (&self.parts[0], &self.parts[self.parts.len() - 1]);
+        let last_row = row + self.parts.len() - 1;
+        if last_row >= lines.len() || !haystack.ends_with(first.as_str()) {
+            return true;
+        }
+
+        let folds = |r: usize, part: &String| match self.fold_line(&lines[r].content) {
+            Some(f) => f.text == *part,
+            None => lines[r].content == *part
+        };
+        if !(1..self.parts.len() - 1).all(|i| folds(row + i, &self.parts[i])) {
+            return true;
+        }
+
+        let last_line = &lines[last_row].content;
+        let last_folded = self.fold_line(last_line);
+        let last_haystack = last_folded.as_ref().map_or(last_line.as_str(), |f| f.text.as_str());
+        if !last_haystack.starts_with(last.as_str()) {
+            return true;
+        }
+
+        let start = original_bytes(&folded, line, haystack.len() - first.len(), haystack.len()).0;
+        let end = original_bytes(&last_folded, last_line, 0, last.len()).1;
+        self.accept(row, start, last_row, end);
+        true
+    }
+
+    /// Queues the match from byte `start` of `start_row` to byte `end` of
+    /// `end_row`, unless it overlaps the last match or is not a whole word
+    /// when it must be.
+    fn accept(&mut self, start_row: usize, start: usize, end_row: usize, end: usize) {
+        let lines = self.document.lines();
+        let (first, last) = (&lines[start_row].content, &lines[end_row].content);
+
+        if self.options.whole_word {
+            let before = first[..start].chars().next_back();
+            let after = last[end..].chars().next();
+            if before.is_some_and(|c| self.document.is_word_char(c)) || after.is_some_and(|c| self.document.is_word_char(c)) {
+                return;
+            }
+        }
+
+        let range = Range::from(start_row, first[..start].chars().count(), end_row, last[..end].chars().count());
+        if range.beginning >= self.last_ending {
+            self.last_ending = range.ending;
+            self.found.push_back(range);
+        }
+    }
+}
+
+impl<'a> Iterator for Matches<'a> {
+    type Item = Range;
+
+    fn next(&mut self) -> Option<Range> {
+        loop {
+            if let Some(range) = self.found.pop_front() {
+                return Some(range);
+            }
+            if !self.search_row() {
+                return None;
+            }
+        }
+    }
+}
+
+/// Returns the byte range of `line` covered by `start..end` of its folded
+/// form, or `start..end` itself if it was not folded. Empty ranges map to
+/// where they begin.
+fn original_bytes(folded: &Option<Folded>, line: &str, start: usize, end: usize) -> (usize, usize) {
+    match folded {
+        None => (start, end),
+        Some(f) if start == end => {
+            let at = f.origins.get(start).copied().unwrap_or(line.len());
+            (at, at)
+        },
+        Some(f) => f.original(line, start, end)
+    }
+}
+
+impl Document {
+    /// Returns a lazy iterator over every non-overlapping occurrence of
+    /// `needle`, in document order. `needle` may span lines. When `options`
+    /// fold case or diacritics, ranges cover the original text, so their
+    /// lengths may differ from `needle`'s.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::search::*;
+    /// let document = Document::from(&"needle hay\n".repeat(100_000));
+    /// assert_eq!(document.find_iter("needle", &SearchOptions::exact()).take(3).count(), 3);
+    /// assert_eq!(document.find_iter("hay\nneedle", &SearchOptions::exact()).nth(1), Some(Range::from(1, 7, 2, 6)));
+    /// ```
+    pub fn find_iter<'a>(&'a self, needle: &str, options: &SearchOptions) -> Matches<'a> {
+        Matches::new(self, needle, options)
+    }
+
+    /// Returns the ranges of every occurrence of `needle`. See
+    /// [`Document::find_iter`].
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::search::*;
+    /// let document = Document::from("cat concat\ncat");
+    /// assert_eq!(document.find_all("cat", &SearchOptions::exact()).len(), 3);
+    /// assert_eq!(document.find_all("cat", &SearchOptions::whole_word()), vec![
+    ///     Range::from(0, 0, 0, 3), Range::from(1, 0, 1, 3)
+    /// ]);
+    /// assert_eq!(document.find_all("t\nc", &SearchOptions::exact()), vec![Range::from(0, 9, 1, 1)]);
+    /// assert_eq!(document.find_all("CAT", &SearchOptions::spoken()).len(), 3);
+    /// ```
+    pub fn find_all(&self, needle: &str, options: &SearchOptions) -> Vec<Range> {
+        self.find_iter(needle, options).collect()
+    }
+
+    /// Returns a lazy iterator over the non-empty matches of `regex`, in
+    /// document order. Each line is matched separately, so matches never
+    /// span lines.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use regex::Regex;
+    /// let document = Document::from("let 火x = 10;\nx += 200;");
+    /// let numbers = Regex::new(r"[0-9]+").unwrap();
+    /// assert_eq!(document.find_regex(&numbers).collect::<Vec<_>>(), vec![
+    ///     Range::from(0, 9, 0, 11), Range::from(1, 5, 1, 8)
+    /// ]);
+    /// ```
+    pub fn find_regex<'a>(&'a self, regex: &'a Regex) -> impl Iterator<Item = Range> + 'a {
+        self.lines().iter().enumerate().flat_map(move |(row, line)| {
+            regex.find_iter(&line.content)
+                .filter(|found| !found.as_str().is_empty())
+                .map(move |found| Range::from(
+                    row,
+                    line.content[..found.start()].chars().count(),
+                    row,
+                    line.content[..found.end()].chars().count()
+                ))
+        })
+    }
+
+    /// Returns the first occurrence of `needle` beginning after `from` when
+    /// searching `forward`, or the last occurrence beginning before `from`
+    /// otherwise, wrapping around the document. Returns `None` if there is
+    /// no occurrence other than one beginning exactly at `from`.
+    pub fn find_next(&self, needle: &str, from: &Position, forward: bool, options: &SearchOptions)
+        -> Option<Range>
+    {
+        let found = if forward {
+            self.find_iter(needle, options).find(|r| r.beginning > *from)
+                .or_else(|| self.find_iter(needle, options).next())
+        } else {
+            let matches = self.find_all(needle, options);
+            matches.iter().rev().find(|r| r.beginning < *from).or_else(|| matches.last()).copied()
+        };
+
+        found.filter(|r| r.beginning != *from)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_next_wraps() {
+        let document = Document::from("fn a() { a(); }\nlet 火a = a;");
+        let options = SearchOptions::whole_word();
+        assert_eq!(document.find_all("a", &options).len(), 3);
+
+        let first = Position::from(0, 3);
+        let second = document.find_next("a", &first, true, &options).unwrap();
+        assert_eq!(second, Range::from(0, 9, 0, 10));
+        let third = document.find_next("a", &second.beginning, true, &options).unwrap();
+        assert_eq!(third, Range::from(1, 9, 1, 10));
+        assert_eq!(document.find_next("a", &third.beginning, true, &options).unwrap().beginning, first);
+        assert_eq!(document.find_next("a", &first, false, &options).unwrap(), third);
+
+        let lonely = Document::from("a b");
+        assert_eq!(lonely.find_next("a", &Position::from(0, 0), true, &options), None);
+    }
+
+    #[test]
+    fn folding_maps_back_to_original_text() {
+        let document = Document::from("Crème BRÛLÉE\nnaïve e\u{301}te\u{301} STRASSE");
+        let spoken = SearchOptions::spoken();
+
+        assert_eq!(document.find_all("creme brulee", &spoken), vec![Range::from(0, 0, 0, 12)]);
+        assert_eq!(document.find_all("NAIVE", &spoken), vec![Range::from(1, 0, 1, 5)]);
+        assert_eq!(document.find_all("été", &spoken), vec![Range::from(1, 6, 1, 11)]);
+        assert_eq!(document.find_all("ete", &SearchOptions { ignore_diacritics: true, ..SearchOptions::exact() }).len(), 1);
+        assert_eq!(document.find_all("brûlée", &SearchOptions { case_insensitive: true, ..SearchOptions::exact() }).len(), 1);
+        assert!(document.find_all("brulee", &SearchOptions { case_insensitive: true, ..SearchOptions::exact() }).is_empty());
+        assert!(document.find_all("\u{301}", &spoken).is_empty());
+
+        let whole = SearchOptions { whole_word: true, ..spoken };
+        assert_eq!(document.find_all("strasse", &whole), vec![Range::from(1, 12, 1, 19)]);
+        assert!(document.find_all("stras", &whole).is_empty());
+    }
+
+    #[test]
+    fn matches_span_lines_without_overlapping() {
+        let document = Document::from("ab\nab\nab\n\nÀB");
+        assert_eq!(document.find_all("b\na", &SearchOptions::exact()), vec![Range::from(0, 1, 1, 1), Range::from(1, 1, 2, 1)]);
+        assert_eq!(document.find_all("ab\nab", &SearchOptions::exact()), vec![Range::from(0, 0, 1, 2)]);
+        assert_eq!(document.find_all("\n\n", &SearchOptions::exact()), vec![Range::from(2, 2, 4, 0)]);
+        assert_eq!(document.find_all("b\n\na", &SearchOptions::spoken()), vec![Range::from(2, 1, 4, 1)]);
+        assert_eq!(document.find_all("\nab", &SearchOptions::spoken()).len(), 3);
+
+        let mut matches = document.find_iter("ab", &SearchOptions::spoken());
+        assert_eq!(matches.next(), Some(Range::from(0, 0, 0, 2)));
+        assert_eq!(matches.count(), 3);
+    }
+}
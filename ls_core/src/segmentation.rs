@@ -0,0 +1,170 @@
+//! Splitting identifiers into the words they are made of, whatever their
+//! casing: `parseHTTPResponse`, `parse_http_response`, `PARSE_HTTP_RESPONSE`,
+//! and `parse-http-response` all split into `parse`, `HTTP` (or `http`), and
+//! `Response` (or `response`).
+//!
+//! Words end at separators, which belong to no word, and where the case
+//! changes from lower to upper. A run of capitals is one word, an acronym,
+//! except that its last capital starts the next word if a lower-case
+//! letter follows it, as in `HTTPResponse`; a plural `s` stays with the
+//! acronym, as in `URLs`. Digits are words of their own unless
+//! [`Segmentation::split_digits`] is off.
+//!
+//! [`Casing`](crate::casing::Casing) joins words back into identifiers,
+//! so the two together convert between casings.
+
+/// How identifiers are split into words.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub struct Segmentation {
+    /// Characters which separate words and belong to none of them.
+    pub separators: &'static [char],
+
+    /// Are runs of digits words of their own (`utf8` is `utf`, `8`)?
+    /// Otherwise they stay with the word before them.
+    pub split_digits: bool
+}
+
+impl Default for Segmentation {
+    fn default() -> Segmentation {
+        Segmentation {
+            separators: &['_', '-'],
+            split_digits: true
+        }
+    }
+}
+
+/// What a character is, for finding word boundaries.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+enum Class {
+    Separator,
+    Upper,
+    Lower,
+    Digit
+}
+
+impl Segmentation {
+    fn class(&self, c: char) -> Class {
+        if self.separators.contains(&c) {
+            Class::Separator
+        } else if c.is_uppercase() {
+            Class::Upper
+        } else if c.is_numeric() {
+            Class::Digit
+        } else {
+            Class::Lower
+        }
+    }
+
+    /// Returns the words of `identifier` as ranges of character indices.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::segmentation::*;
+    /// assert_eq!(Segmentation::default().spans("get_URLs2"), vec![0..3, 4..8, 8..9]);
+    /// ```
+    pub fn spans(&self, identifier: &str) -> Vec<std::ops::Range<usize>> {
+        let classes: Vec<Class> = identifier.chars().map(|c| self.class(c)).collect();
+        let chars: Vec<char> = identifier.chars().collect();
+        let mut spans = vec![];
+        let mut start: Option<usize> = None;
+
+        for (i, &class) in classes.iter().enumerate() {
+            if class == Class::Separator {
+                if let Some(s) = start.take() {
+                    spans.push(s..i);
+                }
+                continue;
+            }
+
+            let previous = if i > 0 { classes[i - 1] } else { Class::Separator };
+            let next = classes.get(i + 1).copied();
+            let boundary = match (previous, class) {
+                (Class::Separator, _) => false,
+                (Class::Lower, Class::Upper) => true,
+                (Class::Digit, Class::Upper) => true,
+                (Class::Upper, Class::Upper) => {
+                    // The last capital of an acronym starts the next word,
+                    // unless what follows is a plural.
+                    let plural = chars.get(i + 1) == Some(&'s')
+                        && classes.get(i + 2).is_none_or(|c| *c != Class::Lower);
+                    next == Some(Class::Lower) && !plural
+                },
+                (Class::Digit, _) | (_, Class::Digit) => self.split_digits && previous != class,
+                _ => false
+            };
+
+            match start {
+                None => start = Some(i),
+                Some(s) if boundary => {
+                    spans.push(s..i);
+                    start = Some(i);
+                },
+                _ => ()
+            }
+        }
+
+        if let Some(s) = start {
+            spans.push(s..classes.len());
+        }
+        spans
+    }
+
+    /// Returns the words of `identifier`.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::segmentation::*;
+    /// let split = Segmentation::default();
+    /// assert_eq!(split.split("parseHTTPResponse"), vec!["parse", "HTTP", "Response"]);
+    /// assert_eq!(split.split("MAX_LINE-length"), vec!["MAX", "LINE", "length"]);
+    /// assert_eq!(split.split("base64Encode"), vec!["base", "64", "Encode"]);
+    ///
+    /// let split = Segmentation { split_digits: false, ..Segmentation::default() };
+    /// assert_eq!(split.split("base64Encode"), vec!["base64", "Encode"]);
+    /// ```
+    pub fn split<'a>(&self, identifier: &'a str) -> Vec<&'a str> {
+        let offsets: Vec<usize> = identifier.char_indices()
+            .map(|(byte, _)| byte)
+            .chain(std::iter::once(identifier.len()))
+            .collect();
+
+        self.spans(identifier)
+            .into_iter()
+            .map(|span| &identifier[offsets[span.start]..offsets[span.end]])
+            .collect()
+    }
+}
+
+/// Returns the words of `identifier`, split the default way.
+pub fn split_identifier(identifier: &str) -> Vec<&str> {
+    Segmentation::default().split(identifier)
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_every_casing() {
+        let cases: &[(&str, &[&str])] = &[
+            ("parseHTTPResponse", &["parse", "HTTP", "Response"]),
+            ("ParseHttpResponse", &["Parse", "Http", "Response"]),
+            ("parse_http_response", &["parse", "http", "response"]),
+            ("__PARSE__HTTP__", &["PARSE", "HTTP"]),
+            ("parse-http", &["parse", "http"]),
+            ("IOError", &["IO", "Error"]),
+            ("allURLs", &["all", "URLs"]),
+            ("URLsAndIDs", &["URLs", "And", "IDs"]),
+            ("x2y", &["x", "2", "y"]),
+            ("v10Beta", &["v", "10", "Beta"]),
+            ("überGroß", &["über", "Groß"]),
+            ("", &[]),
+            ("_", &[])
+        ];
+        for (identifier, words) in cases {
+            assert_eq!(split_identifier(identifier), *words, "{}", identifier);
+        }
+    }
+}
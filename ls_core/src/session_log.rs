@@ -0,0 +1,235 @@
+//! A log of a dictation session, for debugging misrecognitions and
+//! regression-testing the interpreter.
+//!
+//! While logging is on, each utterance given to
+//! [`Document::execute_utterance`] or [`Document::execute_alternatives`]
+//! is recorded with how it was interpreted, the revisions of the change
+//! packets it left on the undo stack, and the error if it failed. The log
+//! exports as text, one utterance per line, with tab-separated fields
+//! (shown here as `\t`):
+//!
+//! ```txt
+//! next bookmark\tNextBookmark(true)\t\tOuch("no bookmarks")
+//! hello world\tdictation\t4
+//! ```
+//!
+//! Replaying an exported log against a document holding the session's
+//! starting text runs each utterance again and reports where the
+//! interpretation or success differs from the recording.
+
+use crate::commands::Outcome;
+use crate::document::Document;
+use crate::util::Oops;
+
+/// The interpretation recorded for an utterance which was not a command.
+pub const DICTATION: &str = "dictation";
+
+/// One recorded utterance.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct LoggedUtterance {
+    pub text: String,
+
+    /// The command it was interpreted as, in `Debug` form, or [`DICTATION`].
+    pub interpretation: String,
+
+    /// The revisions of the change packets it recorded, oldest first.
+    pub packets: Vec<u64>,
+
+    /// Why it failed, in `Debug` form, if it did.
+    pub error: Option<String>
+}
+
+/// The utterances of a session, oldest first.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct SessionLog {
+    pub utterances: Vec<LoggedUtterance>
+}
+
+/// The result of replaying one logged utterance.
+#[derive(PartialEq, Debug)]
+pub struct ReplayStep {
+    pub logged: LoggedUtterance,
+
+    /// How it was interpreted this time.
+    pub interpretation: String,
+
+    pub result: Result<Outcome, Oops>
+}
+
+impl ReplayStep {
+    /// Was it interpreted differently, or did it fail or succeed
+    /// differently, than when it was logged?
+    pub fn diverged(&self) -> bool {
+        self.interpretation != self.logged.interpretation
+            || self.result.is_err() != self.logged.error.is_some()
+    }
+}
+
+/// Returns `field` with tabs and line breaks replaced by spaces.
+fn flatten(field: &str) -> String {
+    field.replace(['\t', '\n', '\r'], " ")
+}
+
+impl SessionLog {
+    /// Returns the log as text (see the [module documentation](self)).
+    pub fn export(&self) -> String {
+        self.utterances.iter()
+            .map(|u| {
+                let packets: Vec<String> = u.packets.iter().map(u64::to_string).collect();
+                let mut line = format!("{}\t{}\t{}", flatten(&u.text), flatten(&u.interpretation), packets.join(","));
+                if let Some(error) = &u.error {
+                    line.push('\t');
+                    line.push_str(&flatten(error));
+                }
+                line
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Reads a log exported by [`SessionLog::export`]. Returns `Err` if a
+    /// line does not have three or four fields or a packet revision is not
+    /// a number.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::session_log::*;
+    /// let log = SessionLog::import("hello\tdictation\t3,4\nlast change\tLastChange\t\t\"no changes\"").unwrap();
+    /// assert_eq!(log.utterances[0].packets, vec![3, 4]);
+    /// assert_eq!(log.utterances[1].error.as_deref(), Some("\"no changes\""));
+    /// assert_eq!(SessionLog::import(&log.export()), Ok(log));
+    /// assert!(SessionLog::import("just text").is_err());
+    /// ```
+    pub fn import(text: &str) -> Result<SessionLog, Oops> {
+        let mut utterances = vec![];
+
+        for line in text.lines().filter(|l| !l.is_empty()) {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 3 || fields.len() > 4 {
+                return Err(Oops::Ouch("session log line needs three or four fields"));
+            }
+
+            let packets = fields[2].split(',')
+                .filter(|p| !p.is_empty())
+                .map(|p| p.parse::<u64>().map_err(|_| Oops::Ouch("bad packet revision in session log")))
+                .collect::<Result<Vec<u64>, Oops>>()?;
+
+            utterances.push(LoggedUtterance {
+                text: String::from(fields[0]),
+                interpretation: String::from(fields[1]),
+                packets,
+                error: fields.get(3).map(|e| String::from(*e))
+            });
+        }
+
+        Ok(SessionLog { utterances })
+    }
+}
+
+impl Document {
+    /// Starts logging utterances, discarding any log so far.
+    pub fn start_session_log(&mut self) {
+        self.session_log = Some(SessionLog::default());
+    }
+
+    /// Returns the log, if logging is on.
+    pub fn session_log(&self) -> Option<&SessionLog> {
+        self.session_log.as_ref()
+    }
+
+    /// Stops logging and returns the log, if logging was on.
+    pub fn take_session_log(&mut self) -> Option<SessionLog> {
+        self.session_log.take()
+    }
+
+    /// Records that `text`, interpreted as `interpretation`, gave `result`,
+    /// if logging is on. `revision` is the document revision before it.
+    pub(crate) fn log_utterance(&mut self, text: &str, interpretation: String, revision: u64, result: &Result<Outcome, Oops>) {
+        if self.session_log.is_none() {
+            return;
+        }
+
+        let mut packets: Vec<u64> = self.undo_redo().undo_packets()
+            .map(|p| p.revision())
+            .take_while(|r| *r > revision)
+            .collect();
+        packets.reverse();
+
+        let error = result.as_ref().err().map(|oops| format!("{:?}", oops));
+        if let Some(log) = self.session_log.as_mut() {
+            log.utterances.push(LoggedUtterance { text: String::from(text), interpretation, packets, error });
+        }
+    }
+
+    /// Runs each utterance of `log` again through
+    /// [`Document::execute_utterance`], and returns how each went. Replay
+    /// against a document with the text the session started from.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("");
+    /// document.start_session_log();
+    /// document.execute_utterance("hello world").unwrap();
+    /// assert!(document.execute_utterance("last bookmark").is_err());
+    /// let exported = document.take_session_log().unwrap().export();
+    ///
+    /// let log = ls_core::session_log::SessionLog::import(&exported).unwrap();
+    /// let mut fresh = Document::from("");
+    /// let steps = fresh.replay(&log);
+    /// assert!(steps.iter().all(|step| !step.diverged()));
+    /// assert_eq!(fresh.text(), document.text());
+    /// ```
+    pub fn replay(&mut self, log: &SessionLog) -> Vec<ReplayStep> {
+        log.utterances.iter()
+            .map(|logged| {
+                let interpretation = crate::commands::Command::parse(&logged.text)
+                    .map_or(String::from(DICTATION), |command| format!("{:?}", command));
+                let result = self.execute_utterance(&logged.text);
+                ReplayStep { logged: logged.clone(), interpretation, result }
+            })
+            .collect()
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alternatives::Alternative;
+
+    #[test]
+    fn logs_record_packets_and_catch_divergence() {
+        let mut document = Document::from("one\ntwo");
+        document.execute_utterance("before logging").unwrap();
+        let start = document.text();
+        document.start_session_log();
+
+        document.checkpoint();
+        document.execute_utterance("last line").unwrap();
+        let moved = document.revision();
+        document.checkpoint();
+        document.execute_utterance("tail").unwrap();
+        let dictated = document.revision();
+        document.checkpoint();
+        document.execute_alternatives(&[Alternative::from("first line", 1.0)]).unwrap();
+
+        let log = document.session_log().unwrap().clone();
+        assert_eq!(log.utterances.len(), 3);
+        assert_eq!(log.utterances[0].interpretation, "Move(Line(Last, Visual))");
+        assert_eq!(log.utterances[0].packets, vec![moved]);
+        assert_eq!(log.utterances[1].interpretation, DICTATION);
+        assert_eq!(log.utterances[1].packets, vec![dictated]);
+
+        let mut edited = log.clone();
+        edited.utterances[2].interpretation = String::from("Move(Line(First, Document))");
+        let mut fresh = Document::from(&start);
+        let steps = fresh.replay(&edited);
+        assert_eq!(steps.iter().map(ReplayStep::diverged).collect::<Vec<bool>>(), vec![false, false, true]);
+        assert_eq!(fresh.text(), document.text());
+
+        assert!(document.take_session_log().is_some());
+        assert!(document.session_log().is_none());
+    }
+}
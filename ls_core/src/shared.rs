@@ -0,0 +1,191 @@
+//! A document shared between threads.
+//!
+//! A [`Document`] cannot move between threads: its listeners, formatters,
+//! and anchor watches are not `Send`. A [`SharedDocument`] instead keeps its
+//! document on a thread of its own and takes work for it through a
+//! mailbox. The concurrency story is:
+//!
+//! * Editing threads post closures with [`SharedDocument::edit`], which
+//!   returns at once, or [`SharedDocument::call`], which waits for a
+//!   result. Closures run one at a time, in the order they were posted, so
+//!   each sees the effects of those before it.
+//! * Parsing happens on the document's thread as part of each edit, so it
+//!   is in the background as far as every other thread is concerned.
+//! * After each batch of closures, if anything visible changed, the
+//!   document thread takes a [`DocumentSnapshot`] and publishes it.
+//!   Render threads take the latest with [`SharedDocument::snapshot`],
+//!   which never waits for edits, and draw from it while edits go on.
+//!
+//! A closure which panics is abandoned where it panicked: the document
+//! thread carries on with the next, but the document keeps whatever the
+//! closure had done to it, which may leave an undo packet open.
+//!
+//! The document thread stops when every handle to it has been dropped.
+//! [Workspace defaults](crate::config) and [global
+//! listeners](crate::events) belong to the thread they are set on, so set
+//! them for a shared document in the closure which creates it. Threads are
+//! unavailable under wasm, where this module is left out.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use crate::document::Document;
+use crate::snapshot::DocumentSnapshot;
+use crate::util::Oops;
+
+/// Sends a [`SharedDocument::call`] its result.
+type Reply = Box<dyn FnOnce() + Send>;
+
+/// Work posted to the document thread, which may leave a reply to send
+/// once its effects are published.
+type Job = Box<dyn FnOnce(&mut Document) -> Option<Reply> + Send>;
+
+/// Runs `job` on `document`, treating a panic as a job with no reply.
+fn run(job: Job, document: &mut Document) -> Option<Reply> {
+    panic::catch_unwind(AssertUnwindSafe(|| job(document))).unwrap_or(None)
+}
+
+/// A handle to a document living on its own thread. Clones are handles to
+/// the same document.
+#[derive(Clone)]
+pub struct SharedDocument {
+    mailbox: mpsc::Sender<Job>,
+    latest: Arc<Mutex<Arc<DocumentSnapshot>>>
+}
+
+impl SharedDocument {
+    /// Starts a thread holding the document `create` returns.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::shared::*;
+    /// let shared = SharedDocument::spawn(|| Document::from("hello"));
+    /// let editor = shared.clone();
+    /// std::thread::spawn(move || {
+    ///     editor.edit(|document| {
+    ///         document.insert(" world", &InsertOptions::exact_at(&Range::from(0, 5, 0, 5))).unwrap();
+    ///     });
+    /// }).join().unwrap();
+    ///
+    /// assert_eq!(shared.call(|document| document.text()), Ok(String::from("hello world")));
+    /// assert_eq!(shared.snapshot().text(), "hello world");
+    /// ```
+    pub fn spawn<F>(create: F) -> SharedDocument
+        where F: FnOnce() -> Document + Send + 'static
+    {
+        let (mailbox, jobs) = mpsc::channel::<Job>();
+        let (ready, first) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut document = create();
+            let latest = Arc::new(Mutex::new(Arc::new(document.snapshot())));
+            let mut published = document.render_revision();
+            if ready.send(Arc::clone(&latest)).is_err() {
+                return;
+            }
+
+            while let Ok(job) = jobs.recv() {
+                let mut replies: Vec<Reply> = run(job, &mut document).into_iter().collect();
+                for job in jobs.try_iter() {
+                    replies.extend(run(job, &mut document));
+                }
+
+                if document.render_revision() != published {
+                    published = document.render_revision();
+                    let snapshot = Arc::new(document.snapshot());
+                    if let Ok(mut latest) = latest.lock() {
+                        *latest = snapshot;
+                    }
+                }
+                for reply in replies {
+                    reply();
+                }
+            }
+        });
+
+        let latest = first.recv().expect("document thread failed to start");
+        SharedDocument { mailbox, latest }
+    }
+
+    /// Posts `f` to run on the document, and returns without waiting.
+    /// Does nothing if the document thread has stopped.
+    pub fn edit<F>(&self, f: F)
+        where F: FnOnce(&mut Document) + Send + 'static
+    {
+        let _ = self.mailbox.send(Box::new(move |document: &mut Document| {
+            f(document);
+            None
+        }));
+    }
+
+    /// Runs `f` on the document and waits for what it returns, and for a
+    /// snapshot with its effects to be published. Returns `Err` if `f`
+    /// panics, or if the document thread has stopped.
+    pub fn call<F, T>(&self, f: F) -> Result<T, Oops>
+        where F: FnOnce(&mut Document) -> T + Send + 'static,
+              T: Send + 'static
+    {
+        let (reply, result) = mpsc::channel();
+        self.mailbox.send(Box::new(move |document: &mut Document| {
+            let value = panic::catch_unwind(AssertUnwindSafe(|| f(document)))
+                .map_err(|_| Oops::Ouch("closure panicked"));
+            Some(Box::new(move || {
+                let _ = reply.send(value);
+            }) as Reply)
+        })).map_err(|_| Oops::Ouch("document thread stopped"))?;
+        result.recv().map_err(|_| Oops::Ouch("document thread stopped"))?
+    }
+
+    /// Returns the latest published snapshot. Edits posted but not yet
+    /// run are not in it; to wait for them, [`SharedDocument::call`] first.
+    pub fn snapshot(&self) -> Arc<DocumentSnapshot> {
+        match self.latest.lock() {
+            Ok(latest) => Arc::clone(&latest),
+            Err(poisoned) => Arc::clone(&poisoned.into_inner())
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Position, Range, InsertOptions};
+
+    #[test]
+    fn edits_from_many_threads_arrive_in_order() {
+        let shared = SharedDocument::spawn(|| Document::from(""));
+        let first = shared.snapshot();
+
+        let writers: Vec<thread::JoinHandle<()>> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for _ in 0..25 {
+                        shared.edit(|document| {
+                            let end = Position::from(0, document.lines()[0].length);
+                            document.insert("x", &InsertOptions::exact_at(&Range { beginning: end, ending: end })).unwrap();
+                        });
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert_eq!(shared.call(|document| document.lines()[0].length), Ok(100));
+        let last = shared.snapshot();
+        assert_eq!(last.line(0).map(str::len), Some(100));
+        assert_eq!(first.text(), "");
+        assert!(last.revision > first.revision);
+
+        shared.edit(|_| panic!("deliberately"));
+        assert_eq!(shared.call(|_| -> usize { panic!("deliberately") }), Err(Oops::Ouch("closure panicked")));
+        assert_eq!(shared.call(|document| document.lines()[0].length), Ok(100));
+        assert_eq!(shared.snapshot().line(0).map(str::len), Some(100));
+    }
+}
@@ -0,0 +1,200 @@
+//! Immutable snapshots of a document for renderers on another thread.
+//!
+//! A [`DocumentSnapshot`] owns everything needed to draw the document as it
+//! was when taken: its text and highlight spans, in chunks of
+//! [`SNAPSHOT_CHUNK_ROWS`] rows, and its folds and anchors. It is `Send` and
+//! `Sync`, so a render thread can draw from it while edits go on.
+//!
+//! The document has no copy-on-write storage of its own, so snapshots share
+//! structure instead: chunks are reference counted, and the document keeps
+//! the chunks of its last snapshot. Taking another rebuilds only the chunks
+//! with rows [damaged](crate::damage) since, and shares the rest, so a
+//! snapshot after a small edit costs a chunk rather than the whole text.
+
+use std::sync::Arc;
+
+use crate::document::{Document, Position, AnchorHandle};
+use crate::folds::Fold;
+use crate::render::HighlightSpan;
+
+/// How many rows a snapshot chunk holds.
+pub const SNAPSHOT_CHUNK_ROWS: usize = 256;
+
+/// A run of rows in a snapshot.
+#[derive(PartialEq, Eq, Debug)]
+pub struct SnapshotChunk {
+    pub first_row: usize,
+    pub lines: Vec<String>,
+
+    /// The highlight spans on each line, as [`Document::highlights_on_row`]
+    /// gives them.
+    pub highlights: Vec<Vec<HighlightSpan>>
+}
+
+/// A document's chunks as of a render revision, kept for the next snapshot.
+pub(crate) struct SnapshotCache {
+    revision: u64,
+    chunks: Vec<Arc<SnapshotChunk>>
+}
+
+/// A document as it was at one render revision.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct DocumentSnapshot {
+    chunks: Vec<Arc<SnapshotChunk>>,
+
+    pub folds: Vec<Fold>,
+
+    /// Every anchor, cursor and mark included, in order of handle.
+    pub anchors: Vec<(AnchorHandle, Position)>,
+    pub cursor: Position,
+    pub mark: Position,
+    pub language: String,
+
+    /// The [render revision](Document::render_revision) it was taken at.
+    pub revision: u64
+}
+
+impl DocumentSnapshot {
+    /// Returns the number of rows.
+    pub fn rows(&self) -> usize {
+        self.chunks.iter().map(|c| c.lines.len()).sum()
+    }
+
+    /// Returns the chunks, in order.
+    pub fn chunks(&self) -> &[Arc<SnapshotChunk>] {
+        &self.chunks
+    }
+
+    /// Returns the chunk holding `row` and the row's index in it.
+    fn locate(&self, row: usize) -> Option<(&SnapshotChunk, usize)> {
+        let chunk = self.chunks.get(row / SNAPSHOT_CHUNK_ROWS)?;
+        let index = row - chunk.first_row;
+        if index < chunk.lines.len() { Some((chunk, index)) } else { None }
+    }
+
+    /// Returns line `row`, if there is one.
+    pub fn line(&self, row: usize) -> Option<&str> {
+        self.locate(row).map(|(chunk, index)| chunk.lines[index].as_str())
+    }
+
+    /// Returns the highlight spans on `row`, sorted by column.
+    pub fn highlights_on_row(&self, row: usize) -> &[HighlightSpan] {
+        self.locate(row).map_or(&[], |(chunk, index)| &chunk.highlights[index])
+    }
+
+    /// Returns the whole text, lines joined by `\n`.
+    pub fn text(&self) -> String {
+        self.chunks.iter()
+            .flat_map(|c| c.lines.iter().map(String::as_str))
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+}
+
+impl Document {
+    /// Builds the snapshot chunk starting at `first_row`.
+    fn snapshot_chunk(&self, first_row: usize) -> SnapshotChunk {
+        let rows = first_row..self.rows().min(first_row + SNAPSHOT_CHUNK_ROWS);
+        SnapshotChunk {
+            first_row,
+            lines: rows.clone().map(|row| self.lines()[row].content.clone()).collect(),
+            highlights: rows.map(|row| self.highlights_on_row(row)).collect()
+        }
+    }
+
+    /// Takes a snapshot of this document, sharing the chunks not damaged
+    /// since the last one (see the [module documentation](self)).
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "parsing")] {
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language("let x = 1;\nlet y = 2;", "rs");
+    /// let before = document.snapshot();
+    /// document.insert("z", &InsertOptions::exact_at(&Range::from(1, 4, 1, 5))).unwrap();
+    /// let after = document.snapshot();
+    ///
+    /// assert_eq!(before.line(1), Some("let y = 2;"));
+    /// assert_eq!(after.line(1), Some("let z = 2;"));
+    /// assert_eq!(after.highlights_on_row(0)[0].class, "keyword");
+    /// assert_eq!(after.cursor, document.cursor().position);
+    /// # }
+    /// ```
+    pub fn snapshot(&mut self) -> DocumentSnapshot {
+        let revision = self.render_revision();
+        let count = self.rows().div_ceil(SNAPSHOT_CHUNK_ROWS);
+
+        let (damage, mut cached) = match self.snapshot_cache.take() {
+            Some(cache) => (Some(self.damage_since(cache.revision)), cache.chunks),
+            None => (None, vec![])
+        };
+        cached.truncate(count);
+
+        let chunks: Vec<Arc<SnapshotChunk>> = (0..count)
+            .map(|i| {
+                let first = i * SNAPSHOT_CHUNK_ROWS;
+                let last = first + SNAPSHOT_CHUNK_ROWS - 1;
+                let damaged = damage.as_ref().is_none_or(|damage| {
+                    damage.rows.iter().any(|&(from, to)| from <= last && first <= to)
+                });
+                match cached.get(i) {
+                    Some(chunk) if !damaged => Arc::clone(chunk),
+                    _ => Arc::new(self.snapshot_chunk(first))
+                }
+            })
+            .collect();
+
+        let mut anchors: Vec<(AnchorHandle, Position)> = self.anchors()
+            .map(|(handle, anchor)| (*handle, anchor.position))
+            .collect();
+        anchors.sort();
+
+        self.snapshot_cache = Some(SnapshotCache { revision, chunks: chunks.clone() });
+        DocumentSnapshot {
+            chunks,
+            folds: self.folds().into_iter().map(|(_, fold)| fold).collect(),
+            anchors,
+            cursor: self.cursor().position,
+            mark: self.mark().position,
+            language: String::from(self.language()),
+            revision
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Range, InsertOptions};
+
+    #[test]
+    fn snapshots_share_undamaged_chunks() {
+        let text: Vec<String> = (0..SNAPSHOT_CHUNK_ROWS * 2 + 10).map(|i| format!("line {}", i)).collect();
+        let mut document = Document::from(&text.join("\n"));
+        let row = SNAPSHOT_CHUNK_ROWS + 5;
+        document.set_cursor_and_mark(&Position::from(row, 0)).unwrap();
+        document.fold_rows(1, 2).unwrap();
+        let first = document.snapshot();
+        assert_eq!(first.chunks().len(), 3);
+        assert_eq!(first.folds, vec![Fold { first_row: 1, last_row: 2 }]);
+        assert_eq!(first.text(), document.text());
+
+        document.insert("!", &InsertOptions::exact_at(&Range::from(row, 0, row, 0))).unwrap();
+        let second = document.snapshot();
+        assert!(Arc::ptr_eq(&first.chunks()[0], &second.chunks()[0]));
+        assert!(!Arc::ptr_eq(&first.chunks()[1], &second.chunks()[1]));
+        assert_eq!(second.line(row), Some(format!("!line {}", row).as_str()));
+        assert_eq!(first.line(row), Some(format!("line {}", row).as_str()));
+
+        document.remove(&crate::document::RemoveOptions::exact_at(&Range::from(5, 0, row, 0))).unwrap();
+        let third = document.snapshot();
+        assert_eq!(third.rows(), document.rows());
+        assert_eq!(third.text(), document.text());
+        assert_eq!(third.line(third.rows()), None);
+
+        let sent = std::thread::spawn(move || third.rows()).join().unwrap();
+        assert_eq!(sent, document.rows());
+    }
+}
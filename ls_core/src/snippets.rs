@@ -0,0 +1,232 @@
+//! "Insert today's date": text generated at the moment it is inserted.
+//!
+//! Dates and times come from [`util::now`] and are in UTC, since the core
+//! has no time zone database. Dates are written in the document's
+//! configured [`DateFormat`] unless the phrase names one. UUIDs are
+//! version 4 (random), from a generator which is unpredictable enough to
+//! keep identifiers apart but is not cryptographically secure.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::document::{Document, Position, Range, InsertOptions};
+use crate::util::{self, Oops};
+
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December"
+];
+
+/// How dates are written.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum DateFormat {
+    /// 2024-03-09
+    Iso,
+
+    /// 03/09/2024
+    MonthDayYear,
+
+    /// 09/03/2024
+    DayMonthYear,
+
+    /// March 9, 2024
+    Long
+}
+
+/// What can follow "insert" to name a snippet, one spelling of each.
+pub const SNIPPET_NAMES: &[&str] = &[
+    "date", "today's date", "iso date", "american date", "european date", "long date",
+    "time", "timestamp", "uuid"
+];
+
+/// Something which can be generated and inserted.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Snippet {
+    /// Today's date, in this format or the document's configured one.
+    Date(Option<DateFormat>),
+
+    /// The time of day, as `14:05`.
+    Time,
+
+    /// An ISO 8601 timestamp, as `2024-03-09T14:05:30Z`.
+    Timestamp,
+
+    /// A random UUID, as `0f8fad5b-d9cb-469f-a165-70867728950e`.
+    Uuid
+}
+
+impl Snippet {
+    /// Parses a spoken insert command.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::snippets::*;
+    /// assert_eq!(Snippet::parse("insert today's date"), Some(Snippet::Date(None)));
+    /// assert_eq!(Snippet::parse("insert the long date"), Some(Snippet::Date(Some(DateFormat::Long))));
+    /// assert_eq!(Snippet::parse("insert time stamp"), Some(Snippet::Timestamp));
+    /// assert_eq!(Snippet::parse("insert a new UUID"), Some(Snippet::Uuid));
+    /// assert_eq!(Snippet::parse("insert coin"), None);
+    /// ```
+    pub fn parse(phrase: &str) -> Option<Snippet> {
+        let lowered = phrase.to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+        let mut words = match words.as_slice() {
+            ["insert", rest @ ..] => rest,
+            _ => return None
+        };
+
+        while let ["the" | "a" | "an" | "new" | "current" | "today's" | "todays", rest @ ..] = words {
+            words = rest;
+        }
+
+        match words {
+            ["date"] | ["today"] => Some(Snippet::Date(None)),
+            ["iso", "date"] => Some(Snippet::Date(Some(DateFormat::Iso))),
+            ["american", "date"] | ["us", "date"] => Some(Snippet::Date(Some(DateFormat::MonthDayYear))),
+            ["european", "date"] | ["british", "date"] => Some(Snippet::Date(Some(DateFormat::DayMonthYear))),
+            ["long", "date"] => Some(Snippet::Date(Some(DateFormat::Long))),
+            ["time"] => Some(Snippet::Time),
+            ["timestamp"] | ["time", "stamp"] | ["iso", "timestamp"] => Some(Snippet::Timestamp),
+            ["uuid"] | ["guid"] | ["u", "u", "i", "d"] => Some(Snippet::Uuid),
+            _ => None
+        }
+    }
+
+    /// Returns the text of this snippet at `millis` milliseconds since the
+    /// Unix epoch, writing dates in `format` unless the snippet names one,
+    /// and taking a UUID's bits from `random`.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::snippets::*;
+    /// let millis = 1_710_000_000_000;
+    /// assert_eq!(Snippet::Date(None).expand(millis, 0, DateFormat::Iso), "2024-03-09");
+    /// assert_eq!(Snippet::Date(Some(DateFormat::Long)).expand(millis, 0, DateFormat::Iso), "March 9, 2024");
+    /// assert_eq!(Snippet::Time.expand(millis, 0, DateFormat::Iso), "16:00");
+    /// assert_eq!(Snippet::Timestamp.expand(millis, 0, DateFormat::Iso), "2024-03-09T16:00:00Z");
+    /// assert_eq!(Snippet::Uuid.expand(millis, 0, DateFormat::Iso), "00000000-0000-4000-8000-000000000000");
+    /// ```
+    pub fn expand(&self, millis: u64, random: u128, format: DateFormat) -> String {
+        let seconds = millis / 1000;
+        let (year, month, day) = civil_from_days(seconds / 86400);
+        let (hour, minute, second) = (seconds / 3600 % 24, seconds / 60 % 60, seconds % 60);
+
+        match self {
+            Snippet::Date(chosen) => match chosen.unwrap_or(format) {
+                DateFormat::Iso => format!("{:04}-{:02}-{:02}", year, month, day),
+                DateFormat::MonthDayYear => format!("{:02}/{:02}/{:04}", month, day, year),
+                DateFormat::DayMonthYear => format!("{:02}/{:02}/{:04}", day, month, year),
+                DateFormat::Long => format!("{} {}, {}", MONTHS[month as usize - 1], day, year)
+            },
+            Snippet::Time => format!("{:02}:{:02}", hour, minute),
+            Snippet::Timestamp => format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+                year, month, day, hour, minute, second
+            ),
+            Snippet::Uuid => {
+                let bits = (random & !(0xf << 76) & !(0x3 << 62)) | (0x4 << 76) | (0x2 << 62);
+                let hex = format!("{:032x}", bits);
+                format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+            }
+        }
+    }
+}
+
+/// Returns the year, month, and day `days` days after 1970-01-01.
+///
+/// From Howard Hinnant's `civil_from_days`:
+/// <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Returns 128 bits which differ from call to call.
+fn random_bits() -> u128 {
+    static CALLS: AtomicU64 = AtomicU64::new(0);
+    let call = CALLS.fetch_add(1, Ordering::Relaxed);
+    let state = RandomState::new();
+
+    let half = |which: u64| {
+        let mut hasher = state.build_hasher();
+        hasher.write_u64(util::now());
+        hasher.write_u64(call);
+        hasher.write_u64(which);
+        hasher.finish() as u128
+    };
+    half(0) << 64 | half(1)
+}
+
+impl Document {
+    /// Generates `snippet` now and inserts it in place of the selection, as
+    /// one change packet labeled "snippet", leaving the cursor after it.
+    /// Returns the range of the inserted text.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::snippets::*;
+    /// let mut document = Document::from("id = ");
+    /// document.set_cursor_and_mark(&Position::from(0, 5)).unwrap();
+    /// let range = document.insert_snippet(Snippet::Uuid).unwrap();
+    /// assert_eq!(range, Range::from(0, 5, 0, 41));
+    /// assert_eq!(document.text_range(&range).unwrap().chars().nth(14), Some('4'));
+    /// ```
+    pub fn insert_snippet(&mut self, snippet: Snippet) -> Result<Range, Oops> {
+        let text = snippet.expand(util::now(), random_bits(), self.config().date_format);
+        let beginning = self.selection().beginning;
+        let ending = Position::from(beginning.row, beginning.column + text.chars().count());
+
+        self.with_packet("snippet", |document| {
+            document.insert(&text, &InsertOptions::exact())?;
+            document.set_cursor_and_mark(&ending)
+        })?;
+
+        Ok(Range { beginning, ending })
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConfigOverrides;
+
+    #[test]
+    fn dates_follow_config_and_uuids_differ() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(Snippet::Date(None).expand(951_868_799_999, 0, DateFormat::DayMonthYear), "29/02/2000");
+
+        let mut document = Document::from("today: old");
+        document.set_config_overrides(ConfigOverrides { date_format: Some(DateFormat::Long), ..Default::default() });
+        document.set_selection(&Range::from(0, 7, 0, 10)).unwrap();
+        let range = document.insert_snippet(Snippet::Date(None)).unwrap();
+        let date = document.text_range(&range).unwrap();
+        assert!(MONTHS.iter().any(|month| date.starts_with(month)));
+        assert_eq!(document.cursor().position, range.ending);
+
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), "today: old");
+
+        let first = Snippet::Uuid.expand(0, random_bits(), DateFormat::Iso);
+        let second = Snippet::Uuid.expand(0, random_bits(), DateFormat::Iso);
+        assert_ne!(first, second);
+        assert!(first.chars().all(|c| c == '-' || c.is_ascii_hexdigit()));
+        assert!("89ab".contains(first.chars().nth(19).unwrap()));
+
+        document.set_cursor_and_mark(&Position::from(0, 0)).unwrap();
+        document.execute_phrase("insert timestamp").unwrap();
+        assert_eq!(document.lines()[0].content.find('Z'), Some(19));
+    }
+}
@@ -0,0 +1,218 @@
+//! "Insert sorted": adding an element to a list in alphabetical order.
+//!
+//! The list is the innermost one around the cursor: an import list, enum
+//! variants, match arms, arguments, array elements, and so on. The new
+//! element goes before the first element which sorts after it, ignoring
+//! case, so a sorted list stays sorted. A catch-all match arm (`_ =>`) or
+//! `default:` case always stays last.
+//!
+//! The element is laid out like its neighbors: on a line of its own at
+//! their indentation if they are on lines of their own, and otherwise on the
+//! same line. Commas are added where the list uses them, including after
+//! the last element if the list has a trailing comma.
+
+use crate::document::{Document, Range};
+#[cfg(feature = "parsing")]
+use crate::document::{Position, InsertOptions};
+#[cfg(feature = "parsing")]
+use crate::syntax;
+use crate::util::Oops;
+
+/// Node kinds of lists which are not in [`crate::duplicate::LIST_KINDS`].
+#[cfg(feature = "parsing")]
+const SORTED_LIST_KINDS: &[&str] = &[
+    "use_list", "named_imports", "import_from_statement", "match_block",
+    "enum_body", "enumerator_list", "switch_body", "switch_block"
+];
+
+/// Node kinds of elements which must stay at the end of their list.
+#[cfg(feature = "parsing")]
+const LAST_KINDS: &[&str] = &["switch_default", "default_case"];
+
+/// Is `node` a list [`Document::insert_sorted`] can insert into?
+#[cfg(feature = "parsing")]
+fn is_list(node: &syntax::Node) -> bool {
+    crate::duplicate::LIST_KINDS.contains(&node.kind()) || SORTED_LIST_KINDS.contains(&node.kind())
+}
+
+/// Returns the elements of `list`, leaving out comments and attributes.
+#[cfg(feature = "parsing")]
+fn elements<'tree>(list: &syntax::Node<'tree>) -> Vec<syntax::Node<'tree>> {
+    let mut cursor = list.walk();
+    if list.kind() == "import_from_statement" {
+        return list.children_by_field_name("name", &mut cursor).collect();
+    }
+
+    list.named_children(&mut cursor)
+        .filter(|n| !n.kind().contains("comment") && !n.kind().contains("attribute"))
+        .collect()
+}
+
+/// Returns the comma after `node`, or ending it, if there is one.
+#[cfg(feature = "parsing")]
+fn comma_after<'tree>(node: &syntax::Node<'tree>) -> Option<syntax::Node<'tree>> {
+    node.next_sibling()
+        .filter(|s| s.kind() == ",")
+        .or_else(|| node.child(node.child_count().saturating_sub(1)).filter(|c| c.kind() == ","))
+}
+
+/// How to insert an element, worked out while the parse tree is borrowed.
+#[cfg(feature = "parsing")]
+struct SortedInsert {
+    /// Where the text goes.
+    at: Position,
+
+    /// What goes before and after the element.
+    before: String,
+    after: String
+}
+
+impl Document {
+    /// Works out where `element` goes in the list around the cursor.
+    #[cfg(feature = "parsing")]
+    fn sorted_insert(&self, element: &str) -> Result<SortedInsert, Oops> {
+        let cursor = self.cursor().position;
+        let mut list = self.node_at(&Range { beginning: cursor, ending: cursor })
+            .ok_or(Oops::CannotParse("insert_sorted"))?;
+        while !is_list(&list) {
+            list = list.parent().ok_or(Oops::Ouch("no list at cursor"))?;
+        }
+
+        let items = elements(&list);
+        let text_of = |node: &syntax::Node| self.text_range(&self.range_from_ts(&node.range())).unwrap_or_default();
+        let must_be_last = |node: &syntax::Node| LAST_KINDS.contains(&node.kind())
+            || (node.kind() == "match_arm" && node.child_by_field_name("pattern").is_some_and(|p| text_of(&p) == "_"));
+
+        let (first, last) = match (items.first(), items.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => {
+                let open = list.child(0).ok_or(Oops::Ouch("no list at cursor"))?;
+                let at = self.range_from_ts(&open.range()).ending;
+                return Ok(SortedInsert { at, before: String::new(), after: String::new() });
+            }
+        };
+
+        let commas = items.iter().any(|item| comma_after(item).is_some());
+        let multiline = match items.get(1) {
+            Some(second) => second.start_position().row != first.start_position().row,
+            None => first.start_position().row != list.start_position().row
+        };
+        let row = |node: &syntax::Node| self.range_from_ts(&node.range()).beginning.row;
+        let indent = |node: &syntax::Node| -> String {
+            self.lines()[row(node)].content.chars().take_while(|c| c.is_whitespace()).collect()
+        };
+
+        let key = element.to_lowercase();
+        let next = items.iter().find(|item| must_be_last(item) || text_of(item).to_lowercase() > key);
+
+        Ok(match next {
+            Some(next) => {
+                let mut after = String::from(if commas { "," } else { "" });
+                if multiline {
+                    after.push('\n');
+                    after.push_str(&indent(next));
+                } else {
+                    after.push(' ');
+                }
+                SortedInsert { at: self.range_from_ts(&next.range()).beginning, before: String::new(), after }
+            },
+            None => {
+                let trailing = comma_after(&last);
+                let at = self.range_from_ts(&trailing.unwrap_or(last).range()).ending;
+
+                let mut before = String::from(if commas && trailing.is_none() { "," } else { "" });
+                if multiline {
+                    before.push('\n');
+                    before.push_str(&indent(&last));
+                } else {
+                    before.push(' ');
+                }
+                let after = String::from(if multiline && trailing.is_some() { "," } else { "" });
+                SortedInsert { at, before, after }
+            }
+        })
+    }
+
+    /// Inserts `element` into the list around the cursor in alphabetical
+    /// order (see the [module documentation](self)), as one change packet
+    /// labeled "insert sorted", and moves the cursor to the end of it.
+    /// Returns the range of the element, or `Err` if there is no parse tree
+    /// or no list around the cursor.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from_with_language("use std::{fmt, io};\nenum E {\n    A,\n    C,\n}", "rs");
+    /// document.set_cursor_and_mark(&Position::from(0, 11)).unwrap();
+    /// assert_eq!(document.insert_sorted("env"), Ok(Range::from(0, 10, 0, 13)));
+    /// assert_eq!(document.lines()[0].content, "use std::{env, fmt, io};");
+    ///
+    /// document.set_cursor_and_mark(&Position::from(2, 4)).unwrap();
+    /// document.insert_sorted("B").unwrap();
+    /// document.insert_sorted("D").unwrap();
+    /// assert_eq!(document.text(), "use std::{env, fmt, io};\nenum E {\n    A,\n    B,\n    C,\n    D,\n}");
+    /// assert_eq!(document.cursor().position, Position::from(5, 5));
+    /// ```
+    #[cfg(feature = "parsing")]
+    pub fn insert_sorted(&mut self, element: &str) -> Result<Range, Oops> {
+        use crate::duplicate::advance;
+
+        let plan = self.sorted_insert(element)?;
+        let beginning = advance(&plan.at, &plan.before);
+        let ending = advance(&beginning, element);
+        let text = format!("{}{}{}", plan.before, element, plan.after);
+
+        self.with_packet("insert sorted", |document| {
+            document.insert(&text, &InsertOptions::exact_at(&Range { beginning: plan.at, ending: plan.at }))?;
+            document.set_cursor_and_mark(&ending)
+        })?;
+
+        Ok(Range { beginning, ending })
+    }
+
+    /// Inserts `element` into the list around the cursor in alphabetical
+    /// order.
+    ///
+    /// Without the `parsing` feature there is never a parse tree, so this
+    /// always returns an [`Oops`].
+    #[cfg(not(feature = "parsing"))]
+    pub fn insert_sorted(&mut self, _element: &str) -> Result<Range, Oops> {
+        Err(Oops::CannotParse("insert_sorted"))
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "parsing"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elements_land_in_order_with_separators() {
+        let text = "fn f(x: E) -> u8 {\n    match x {\n        E::B => 1,\n        E::D => { 2 }\n        _ => 3\n    }\n}\n";
+        let mut document = Document::from_with_language(text, "rs");
+        document.set_cursor_and_mark(&Position::from(2, 8)).unwrap();
+        assert_eq!(document.insert_sorted("E::E => 5"), Ok(Range::from(4, 8, 4, 17)));
+        assert_eq!(document.lines()[4].content, "        E::E => 5,");
+        assert_eq!(document.lines()[5].content, "        _ => 3");
+
+        document.undo_once().unwrap();
+        assert_eq!(document.text(), text);
+
+        let mut document = Document::from_with_language("from os import path, sep\nl = [b, d]", "py");
+        document.insert_sorted("environ").unwrap();
+        assert_eq!(document.lines()[0].content, "from os import environ, path, sep");
+        document.set_cursor_and_mark(&Position::from(1, 6)).unwrap();
+        document.insert_sorted("e").unwrap();
+        document.insert_sorted("a").unwrap();
+        assert_eq!(document.lines()[1].content, "l = [a, b, d, e]");
+
+        let mut document = Document::from_with_language("const o = {};", "js");
+        document.set_cursor_and_mark(&Position::from(0, 11)).unwrap();
+        document.insert_sorted("a: 1").unwrap();
+        assert_eq!(document.text(), "const o = {a: 1};");
+
+        assert!(Document::from_with_language("x = 1", "py").insert_sorted("y").is_err());
+        assert!(Document::from("plain").insert_sorted("y").is_err());
+    }
+}
@@ -0,0 +1,408 @@
+//! Resolving spoken phrases into concrete document locations.
+//!
+//! Speech recognizers produce numbers either as digits ("42") or as words
+//! ("forty two"), so everything here accepts both. Phrases are matched
+//! case-insensitively, word by word.
+
+use crate::document::{Document, Position};
+use crate::util::Oops;
+
+/// A line named by a spoken phrase.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum LineTarget {
+    /// "line 42": a line number, counting from 1.
+    Absolute(usize),
+
+    /// "down seven lines", "up three": an offset from the cursor's line.
+    Relative(isize),
+
+    /// "first line", "top of the file".
+    First,
+
+    /// "last line", "bottom of the file".
+    Last,
+
+    /// "middle of the file".
+    Middle
+}
+
+/// An inclusive run of lines named by a spoken phrase, like "lines five
+/// through nine" or "here to down three".
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct LineRange {
+    pub first: LineTarget,
+    pub last: LineTarget
+}
+
+/// Which lines a [`LineTarget`] counts.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum LineCounting {
+    /// Count every line of the document, as line numbers do. A target inside
+    /// a fold lands on the fold's first line.
+    Document,
+
+    /// Count only the lines on screen, skipping rows hidden by folds.
+    Visual
+}
+
+/// The NATO phonetic alphabet, from `'a'` to `'z'`.
+pub const PHONETIC_ALPHABET: [&str; 26] = [
+    "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel",
+    "india", "juliett", "kilo", "lima", "mike", "november", "oscar", "papa",
+    "quebec", "romeo", "sierra", "tango", "uniform", "victor", "whiskey",
+    "xray", "yankee", "zulu"
+];
+
+/// Returns the letter a phonetic alphabet word stands for. Common variant
+/// spellings ("juliet", "x-ray", "alfa") are accepted.
+///
+/// # Examples
+/// ```
+/// use ls_core::spoken::*;
+/// assert_eq!(phonetic_letter("Charlie"), Some('c'));
+/// assert_eq!(phonetic_letter("x-ray"), Some('x'));
+/// assert_eq!(phonetic_letter("banana"), None);
+/// ```
+pub fn phonetic_letter(word: &str) -> Option<char> {
+    let word = word.to_lowercase().replace('-', "");
+    let word = match word.as_str() {
+        "alfa" => "alpha",
+        "juliet" => "juliett",
+        "whisky" => "whiskey",
+        w => w
+    };
+
+    PHONETIC_ALPHABET.iter()
+        .position(|w| *w == word)
+        .map(|i| (b'a' + i as u8) as char)
+}
+
+pub(crate) const ONES: [&str; 20] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+    "ten", "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen",
+    "seventeen", "eighteen", "nineteen"
+];
+
+pub(crate) const TENS: [&str; 8] = ["twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+
+/// Parses a number from the start of `words`, written in digits or in
+/// English words. Returns the number and how many words it took, or `None`
+/// if `words` does not start with a number.
+///
+/// # Examples
+/// ```
+/// use ls_core::spoken::*;
+/// assert_eq!(parse_number(&["42"]), Some((42, 1)));
+/// assert_eq!(parse_number(&["forty", "two", "lines"]), Some((42, 2)));
+/// assert_eq!(parse_number(&["one", "hundred", "and", "five"]), Some((105, 4)));
+/// assert_eq!(parse_number(&["two", "thousand", "twenty"]), Some((2020, 3)));
+/// assert_eq!(parse_number(&["lines"]), None);
+/// ```
+pub fn parse_number(words: &[&str]) -> Option<(usize, usize)> {
+    if let Some(n) = words.first().and_then(|w| w.parse::<usize>().ok()) {
+        return Some((n, 1));
+    }
+
+    let mut total = 0;
+    let mut current = 0;
+    let mut used = 0;
+    let mut any = false;
+
+    // Which digits the number so far leaves open: a ones word can only
+    // follow a tens word or a scale, and a tens word only a scale, so "one
+    // five" is two numbers rather than six.
+    let mut ones_open = true;
+    let mut tens_open = true;
+
+    for (i, word) in words.iter().enumerate() {
+        let word = word.to_lowercase();
+
+        if let Some(n) = ONES.iter().position(|w| *w == word) {
+            if !ones_open || ((n == 0 || n >= 10) && !tens_open) {
+                break;
+            }
+            current += n;
+            ones_open = false;
+            tens_open = false;
+        } else if let Some(n) = TENS.iter().position(|w| *w == word) {
+            if !tens_open {
+                break;
+            }
+            current += (n + 2) * 10;
+            tens_open = false;
+        } else if word == "hundred" && any {
+            current = current.max(1) * 100;
+            ones_open = true;
+            tens_open = true;
+        } else if word == "thousand" && any {
+            total += current.max(1) * 1000;
+            current = 0;
+            ones_open = true;
+            tens_open = true;
+        } else if word == "and" && any && i + 1 < words.len() {
+            continue;
+        } else {
+            break;
+        }
+
+        any = true;
+        used = i + 1;
+    }
+
+    if any { Some((total + current, used)) } else { None }
+}
+
+impl LineTarget {
+    /// Parses a spoken line target.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::spoken::*;
+    /// assert_eq!(LineTarget::parse("line 42"), Some(LineTarget::Absolute(42)));
+    /// assert_eq!(LineTarget::parse("Down seven lines"), Some(LineTarget::Relative(7)));
+    /// assert_eq!(LineTarget::parse("up one line"), Some(LineTarget::Relative(-1)));
+    /// assert_eq!(LineTarget::parse("last line"), Some(LineTarget::Last));
+    /// assert_eq!(LineTarget::parse("middle of the file"), Some(LineTarget::Middle));
+    /// assert_eq!(LineTarget::parse("line forty"), Some(LineTarget::Absolute(40)));
+    /// assert_eq!(LineTarget::parse("line"), None);
+    /// ```
+    pub fn parse(phrase: &str) -> Option<LineTarget> {
+        let lowered = phrase.to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["first", "line"] | ["top", "of", "the", "file"] | ["top", "of", "file"] | ["top"] => {
+                return Some(LineTarget::First)
+            },
+            ["last", "line"] | ["bottom", "of", "the", "file"] | ["bottom", "of", "file"] | ["bottom"] => {
+                return Some(LineTarget::Last)
+            },
+            ["middle", "of", "the", "file"] | ["middle", "of", "file"] | ["middle"] => {
+                return Some(LineTarget::Middle)
+            },
+            _ => ()
+        }
+
+        let words = match words.as_slice() {
+            ["go", "to", rest @ ..] => rest,
+            all => all
+        };
+
+        let (first, rest) = words.split_first()?;
+        let sign = match *first {
+            "line" => 0,
+            "down" => 1,
+            "up" => -1,
+            _ => return None
+        };
+
+        let (n, used) = parse_number(rest)?;
+        match &rest[used..] {
+            [] => (),
+            ["line"] | ["lines"] if sign != 0 => (),
+            _ => return None
+        }
+
+        if sign == 0 {
+            Some(LineTarget::Absolute(n))
+        } else {
+            Some(LineTarget::Relative(sign * n as isize))
+        }
+    }
+}
+
+/// Words which join the ends of a spoken [`LineRange`].
+const RANGE_JOINERS: &[&str] = &["through", "thru", "to", "until"];
+
+impl LineRange {
+    /// Parses a spoken line range: two ends joined by "through", "to", or
+    /// "until", optionally after "lines" or "from". Each end is a line
+    /// number, with or without "line", "this line" or "here" for the
+    /// cursor's line, "end" for the last line, or any [`LineTarget`].
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::spoken::*;
+    /// assert_eq!(
+    ///     LineRange::parse("lines five through nine"),
+    ///     Some(LineRange { first: LineTarget::Absolute(5), last: LineTarget::Absolute(9) })
+    /// );
+    /// assert_eq!(
+    ///     LineRange::parse("from here to down three"),
+    ///     Some(LineRange { first: LineTarget::Relative(0), last: LineTarget::Relative(3) })
+    /// );
+    /// assert_eq!(
+    ///     LineRange::parse("line 12 to the end"),
+    ///     Some(LineRange { first: LineTarget::Absolute(12), last: LineTarget::Last })
+    /// );
+    /// assert_eq!(LineRange::parse("lines five"), None);
+    /// ```
+    pub fn parse(phrase: &str) -> Option<LineRange> {
+        let lowered = phrase.to_lowercase();
+        let words: Vec<&str> = lowered.split_whitespace().collect();
+        let words = match words.as_slice() {
+            ["from", rest @ ..] => rest,
+            all => all
+        };
+        let words = match words {
+            ["lines", rest @ ..] => rest,
+            ["line", rest @ ..] if parse_number(rest).is_some() => rest,
+            all => all
+        };
+
+        let joiner = words.iter().position(|w| RANGE_JOINERS.contains(w))?;
+        let end = |words: &[&str]| match words {
+            ["this"] | ["this", "line"] | ["here"] | ["current", "line"] => Some(LineTarget::Relative(0)),
+            ["end"] | ["the", "end"] => Some(LineTarget::Last),
+            _ => match parse_number(words) {
+                Some((n, used)) if used == words.len() => Some(LineTarget::Absolute(n)),
+                _ => LineTarget::parse(&words.join(" "))
+            }
+        };
+
+        Some(LineRange { first: end(&words[..joiner])?, last: end(&words[joiner + 1..])? })
+    }
+}
+
+impl Document {
+    /// Returns the first and last rows `range` names, in order. Line numbers
+    /// count every line, as the [gutter](crate::gutters) numbers them, and
+    /// relative ends count the lines on screen, as relative numbers do. A
+    /// range ending on a folded line takes in the rows the fold hides.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::spoken::*;
+    /// let mut document = Document::from("0\n1\n2\n3\n4\n5\n6");
+    /// document.fold_rows(4, 5).unwrap();
+    /// document.set_cursor_and_mark(&Position::from(2, 0)).unwrap();
+    ///
+    /// let range = LineRange::parse("down two through line 2").unwrap();
+    /// assert_eq!(document.resolve_line_range(&range), (1, 5));
+    /// ```
+    pub fn resolve_line_range(&self, range: &LineRange) -> (usize, usize) {
+        let row = |target: LineTarget| match target {
+            LineTarget::Relative(_) => self.resolve_line_target(target, LineCounting::Visual),
+            _ => self.resolve_line_target(target, LineCounting::Document)
+        };
+
+        let (a, b) = (row(range.first), row(range.last));
+        let (first, last) = (a.min(b), a.max(b));
+        let last = self.fold_at_row(last).map_or(last, |(_, fold)| fold.last_row);
+        (first, last)
+    }
+
+    /// Returns the row `target` names, counting lines as `counting` says,
+    /// clamped to the document. Relative targets are taken from the
+    /// cursor's row.
+    pub fn resolve_line_target(&self, target: LineTarget, counting: LineCounting) -> usize {
+        let rows: Vec<usize> = match counting {
+            LineCounting::Document => (0..self.rows()).collect(),
+            LineCounting::Visual => (0..self.rows()).filter(|r| !self.row_hidden(*r)).collect()
+        };
+
+        let cursor_row = self.cursor().position.row;
+        let current = rows.iter().rposition(|r| *r <= cursor_row).unwrap_or(0);
+        let last = rows.len() - 1;
+
+        let index = match target {
+            LineTarget::Absolute(n) => n.saturating_sub(1).min(last),
+            LineTarget::Relative(n) if n < 0 => current.saturating_sub(n.unsigned_abs()),
+            LineTarget::Relative(n) => (current + n as usize).min(last),
+            LineTarget::First => 0,
+            LineTarget::Last => last,
+            LineTarget::Middle => last / 2
+        };
+
+        let mut row = rows[index];
+        while self.row_hidden(row) {
+            row -= 1;
+        }
+        row
+    }
+
+    /// Moves the cursor and mark to the line `target` names (see
+    /// [`Document::resolve_line_target`]), returning the new position.
+    /// Relative moves keep the cursor's column where the line allows; other
+    /// targets go to the end of the line's indentation.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::spoken::*;
+    /// let mut document = Document::from("a\n  b\nc\nd\ne");
+    /// document.fold_rows(1, 3).unwrap();
+    ///
+    /// let visual = LineTarget::parse("line 3").unwrap();
+    /// assert_eq!(document.go_to_line(visual, LineCounting::Visual), Ok(Position::from(4, 0)));
+    /// assert_eq!(document.go_to_line(visual, LineCounting::Document), Ok(Position::from(1, 2)));
+    ///
+    /// let down = LineTarget::parse("down twenty lines").unwrap();
+    /// assert_eq!(document.go_to_line(down, LineCounting::Document), Ok(Position::from(4, 1)));
+    /// ```
+    pub fn go_to_line(&mut self, target: LineTarget, counting: LineCounting) -> Result<Position, Oops> {
+        let position = self.line_target_position(target, counting);
+        self.set_cursor_and_mark(&position)?;
+        Ok(position)
+    }
+
+    /// Returns where [`Document::go_to_line`] would move the cursor.
+    pub fn line_target_position(&self, target: LineTarget, counting: LineCounting) -> Position {
+        let row = self.resolve_line_target(target, counting);
+
+        match target {
+            LineTarget::Relative(_) => Position::from(row, self.cursor().position.column.min(self.lines()[row].length)),
+            _ => self.indentation_end(row)
+        }
+    }
+
+    /// Returns the position just past the leading whitespace of `row`.
+    pub(crate) fn indentation_end(&self, row: usize) -> Position {
+        Position::from(row, self.lines()[row].content.chars().take_while(|c| c.is_whitespace()).count())
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_clamp_and_skip_folds() {
+        let mut document = Document::from("0\n1\n2\n3\n4\n5\n6");
+        assert_eq!(document.resolve_line_target(LineTarget::Absolute(0), LineCounting::Document), 0);
+        assert_eq!(document.resolve_line_target(LineTarget::Absolute(99), LineCounting::Document), 6);
+        assert_eq!(document.resolve_line_target(LineTarget::Middle, LineCounting::Document), 3);
+        assert_eq!(document.resolve_line_target(LineTarget::Relative(-3), LineCounting::Document), 0);
+
+        document.fold_rows(1, 4).unwrap();
+        document.set_cursor_and_mark(&Position::from(1, 0)).unwrap();
+        assert_eq!(document.resolve_line_target(LineTarget::Relative(1), LineCounting::Visual), 5);
+        assert_eq!(document.resolve_line_target(LineTarget::Relative(1), LineCounting::Document), 1);
+        assert_eq!(document.resolve_line_target(LineTarget::Relative(4), LineCounting::Document), 5);
+        assert_eq!(document.resolve_line_target(LineTarget::Middle, LineCounting::Visual), 1);
+        assert_eq!(document.resolve_line_target(LineTarget::Last, LineCounting::Visual), 6);
+
+        assert_eq!(LineTarget::parse("up 2 lines"), Some(LineTarget::Relative(-2)));
+        assert_eq!(LineTarget::parse("go to line twelve"), Some(LineTarget::Absolute(12)));
+        assert_eq!(LineTarget::parse("line 4 lines"), None);
+        assert_eq!(LineTarget::parse("sideways 3"), None);
+    }
+
+    #[test]
+    fn numbers_stop_where_another_would_start() {
+        assert_eq!(parse_number(&["one", "five"]), Some((1, 1)));
+        assert_eq!(parse_number(&["twelve", "three"]), Some((12, 1)));
+        assert_eq!(parse_number(&["twenty", "thirty"]), Some((20, 1)));
+        assert_eq!(parse_number(&["three", "forty"]), Some((3, 1)));
+        assert_eq!(parse_number(&["twenty", "twelve"]), Some((20, 1)));
+        assert_eq!(parse_number(&["one", "hundred", "twenty", "three"]), Some((123, 4)));
+        assert_eq!(parse_number(&["two", "thousand", "and", "nine"]), Some((2009, 4)));
+
+        assert_eq!(LineTarget::parse("line one five"), None);
+        assert_eq!(LineTarget::parse("line twenty thirty"), None);
+        assert_eq!(LineTarget::parse("line twenty one"), Some(LineTarget::Absolute(21)));
+    }
+}
@@ -0,0 +1,532 @@
+//! The parsing backend: tree sitter's parser, trees, and nodes, from
+//! wherever the build target can get them.
+//!
+//! Parsing code uses the types here rather than naming `tree_sitter`
+//! directly. On native targets they are tree sitter's own, with grammars
+//! compiled in. Tree sitter's C runtime cannot be built for
+//! `wasm32-unknown-unknown`, so the browser build instead wraps
+//! [web-tree-sitter](https://github.com/tree-sitter/tree-sitter/tree/master/lib/binding_web)
+//! through JS imports, behind the same names and method signatures.
+//!
+//! Under wasm, the host loads web-tree-sitter and its grammars before
+//! creating documents, and registers each grammar with
+//! [`register_language`](crate::language::register_language):
+//!
+//! ```js
+//! import TreeSitter from "web-tree-sitter";
+//! await TreeSitter.init();
+//! globalThis.TreeSitter = TreeSitter;
+//! register_language("rs", await TreeSitter.Language.load("tree-sitter-rust.wasm"));
+//! ```
+//!
+//! web-tree-sitter measures columns in UTF-16 code units; the wrappers here
+//! convert them to the UTF-8 byte columns tree sitter uses, so callers see
+//! the same positions on either backend. The web backend always reparses in
+//! full, since incremental edits would need the same conversion against
+//! text the tree no longer has, and so reports the whole new tree as
+//! changed.
+//!
+//! Without the `parsing` feature there is nothing to parse with, and only
+//! [`Parser`] and [`Node`] are defined, as empty types, so functions taking
+//! or returning them keep their signatures.
+
+#[cfg(all(feature = "parsing", not(target_arch = "wasm32")))]
+pub use tree_sitter::{InputEdit, Language, Node, Parser, Point, Range, Tree, TreeCursor};
+
+#[cfg(all(feature = "parsing", target_arch = "wasm32"))]
+pub use web::{InputEdit, Language, Node, Parser, Point, Range, Tree, TreeCursor};
+
+#[cfg(not(feature = "parsing"))]
+pub use disabled::{Node, Parser};
+
+/// Stand-ins for builds without the `parsing` feature, which can never be
+/// constructed.
+#[cfg(not(feature = "parsing"))]
+mod disabled {
+    /// A parser, which this build has none of.
+    #[derive(Debug)]
+    pub enum Parser {}
+
+    /// A syntax tree node, which this build has none of.
+    #[derive(Clone, Copy, Debug)]
+    pub enum Node {}
+}
+
+#[cfg(all(feature = "parsing", target_arch = "wasm32"))]
+mod web {
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+    use std::rc::Rc;
+
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_name = TreeSitter)]
+        type JsParser;
+
+        #[wasm_bindgen(constructor, js_class = "TreeSitter")]
+        fn new() -> JsParser;
+
+        #[wasm_bindgen(method, catch, js_name = setLanguage)]
+        fn set_language(this: &JsParser, language: &JsValue) -> Result<(), JsValue>;
+
+        #[wasm_bindgen(method, catch)]
+        fn parse(this: &JsParser, input: &str) -> Result<JsTree, JsValue>;
+
+        #[wasm_bindgen(method, js_name = delete)]
+        fn delete_parser(this: &JsParser);
+
+        type JsTree;
+
+        #[wasm_bindgen(method, getter, js_name = rootNode)]
+        fn root_node(this: &JsTree) -> JsNode;
+
+        #[wasm_bindgen(method, js_name = delete)]
+        fn delete_tree(this: &JsTree);
+
+        #[derive(Clone)]
+        type JsNode;
+
+        #[wasm_bindgen(method, getter)]
+        fn id(this: &JsNode) -> f64;
+
+        #[wasm_bindgen(method, getter, js_name = type)]
+        fn kind(this: &JsNode) -> String;
+
+        #[wasm_bindgen(method, js_name = isNamed)]
+        fn is_named(this: &JsNode) -> bool;
+
+        #[wasm_bindgen(method, js_name = isMissing)]
+        fn is_missing(this: &JsNode) -> bool;
+
+        #[wasm_bindgen(method, getter, js_name = startPosition)]
+        fn start_position(this: &JsNode) -> JsPoint;
+
+        #[wasm_bindgen(method, getter, js_name = endPosition)]
+        fn end_position(this: &JsNode) -> JsPoint;
+
+        #[wasm_bindgen(method, getter, js_name = childCount)]
+        fn child_count(this: &JsNode) -> u32;
+
+        #[wasm_bindgen(method, getter, js_name = namedChildCount)]
+        fn named_child_count(this: &JsNode) -> u32;
+
+        #[wasm_bindgen(method)]
+        fn child(this: &JsNode, index: u32) -> Option<JsNode>;
+
+        #[wasm_bindgen(method, js_name = namedChild)]
+        fn named_child(this: &JsNode, index: u32) -> Option<JsNode>;
+
+        #[wasm_bindgen(method, js_name = childForFieldName)]
+        fn child_by_field_name(this: &JsNode, name: &str) -> Option<JsNode>;
+
+        #[wasm_bindgen(method, getter)]
+        fn parent(this: &JsNode) -> Option<JsNode>;
+
+        #[wasm_bindgen(method, getter, js_name = nextSibling)]
+        fn next_sibling(this: &JsNode) -> Option<JsNode>;
+
+        #[wasm_bindgen(method, getter, js_name = previousSibling)]
+        fn prev_sibling(this: &JsNode) -> Option<JsNode>;
+
+        #[wasm_bindgen(method, getter, js_name = nextNamedSibling)]
+        fn next_named_sibling(this: &JsNode) -> Option<JsNode>;
+
+        #[wasm_bindgen(method, getter, js_name = previousNamedSibling)]
+        fn prev_named_sibling(this: &JsNode) -> Option<JsNode>;
+
+        #[wasm_bindgen(method, js_name = namedDescendantForPosition)]
+        fn named_descendant_for_position(this: &JsNode, start: &JsValue, end: &JsValue) -> Option<JsNode>;
+
+        #[wasm_bindgen(method)]
+        fn walk(this: &JsNode) -> JsTreeCursor;
+
+        type JsPoint;
+
+        #[wasm_bindgen(method, getter)]
+        fn row(this: &JsPoint) -> u32;
+
+        #[wasm_bindgen(method, getter)]
+        fn column(this: &JsPoint) -> u32;
+
+        type JsTreeCursor;
+
+        #[wasm_bindgen(method, js_name = currentNode)]
+        fn current_node(this: &JsTreeCursor) -> JsNode;
+
+        #[wasm_bindgen(method, js_name = currentFieldName)]
+        fn current_field_name(this: &JsTreeCursor) -> Option<String>;
+
+        #[wasm_bindgen(method, js_name = gotoFirstChild)]
+        fn goto_first_child(this: &JsTreeCursor) -> bool;
+
+        #[wasm_bindgen(method, js_name = gotoNextSibling)]
+        fn goto_next_sibling(this: &JsTreeCursor) -> bool;
+
+        #[wasm_bindgen(method, js_name = gotoParent)]
+        fn goto_parent(this: &JsTreeCursor) -> bool;
+
+        #[wasm_bindgen(method, js_name = delete)]
+        fn delete_cursor(this: &JsTreeCursor);
+
+        #[wasm_bindgen(js_namespace = JSON, js_name = parse)]
+        fn json(text: &str) -> JsValue;
+    }
+
+    thread_local! {
+        static NAMES: RefCell<HashSet<&'static str>> = RefCell::new(HashSet::new());
+    }
+
+    /// Returns `name` with a static lifetime, as tree sitter gives node
+    /// kinds and field names. There are only as many as the grammars have.
+    fn intern(name: String) -> &'static str {
+        NAMES.with(|names| {
+            let mut names = names.borrow_mut();
+            match names.get(name.as_str()) {
+                Some(interned) => *interned,
+                None => {
+                    let interned: &'static str = Box::leak(name.into_boxed_str());
+                    names.insert(interned);
+                    interned
+                }
+            }
+        })
+    }
+
+    /// A grammar loaded by web-tree-sitter.
+    #[derive(Clone)]
+    pub struct Language(JsValue);
+
+    impl Language {
+        /// Wraps a `TreeSitter.Language` the host has loaded.
+        pub fn from_js(language: JsValue) -> Language {
+            Language(language)
+        }
+    }
+
+    /// A position in a tree, with the column in UTF-8 bytes.
+    #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash, Debug, Default)]
+    pub struct Point {
+        pub row: usize,
+        pub column: usize
+    }
+
+    impl Point {
+        pub fn new(row: usize, column: usize) -> Point {
+            Point { row, column }
+        }
+    }
+
+    /// The extent of a node.
+    #[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+    pub struct Range {
+        pub start_byte: usize,
+        pub end_byte: usize,
+        pub start_point: Point,
+        pub end_point: Point
+    }
+
+    /// An edit to the text under a tree.
+    #[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+    pub struct InputEdit {
+        pub start_byte: usize,
+        pub old_end_byte: usize,
+        pub new_end_byte: usize,
+        pub start_position: Point,
+        pub old_end_position: Point,
+        pub new_end_position: Point
+    }
+
+    /// Parses text with one grammar.
+    pub struct Parser(JsParser);
+
+    impl Parser {
+        pub fn new() -> Parser {
+            Parser(JsParser::new())
+        }
+
+        pub fn set_language(&mut self, language: Language) -> Result<(), JsValue> {
+            self.0.set_language(&language.0)
+        }
+
+        /// Parses `text` in full. `old_tree` is accepted for tree sitter's
+        /// signature but not used.
+        pub fn parse(&mut self, text: impl AsRef<str>, old_tree: Option<&Tree>) -> Option<Tree> {
+            let _ = old_tree;
+            let text = text.as_ref();
+            let tree = self.0.parse(text).ok()?;
+            Some(Tree {
+                inner: tree,
+                lines: Rc::new(text.split('\n').map(String::from).collect()),
+                nodes: RefCell::new(vec![])
+            })
+        }
+    }
+
+    impl Default for Parser {
+        fn default() -> Parser {
+            Parser::new()
+        }
+    }
+
+    impl Drop for Parser {
+        fn drop(&mut self) {
+            self.0.delete_parser();
+        }
+    }
+
+    /// A parse tree, with the text it was parsed from for converting
+    /// columns, and the JS nodes its [`Node`]s refer to.
+    pub struct Tree {
+        inner: JsTree,
+        lines: Rc<Vec<String>>,
+        nodes: RefCell<Vec<JsNode>>
+    }
+
+    impl Tree {
+        /// Keeps `node` for the life of the tree, so [`Node`] can be `Copy`.
+        fn node(&self, node: JsNode) -> Node<'_> {
+            let mut nodes = self.nodes.borrow_mut();
+            nodes.push(node);
+            Node { tree: self, slot: nodes.len() - 1 }
+        }
+
+        /// Converts a web-tree-sitter point into UTF-8 byte columns.
+        fn point_from_js(&self, point: &JsPoint) -> Point {
+            let row = point.row() as usize;
+            let units = point.column() as usize;
+            let column = self.lines.get(row).map_or(0, |line| {
+                let mut counted = 0;
+                line.char_indices()
+                    .find(|(_, c)| {
+                        let reached = counted >= units;
+                        counted += c.len_utf16();
+                        reached
+                    })
+                    .map_or(line.len(), |(byte, _)| byte)
+            });
+            Point { row, column }
+        }
+
+        /// Converts `point` into a web-tree-sitter point.
+        fn point_to_js(&self, point: &Point) -> JsValue {
+            let units: usize = self.lines.get(point.row).map_or(0, |line| {
+                line[..point.column.min(line.len())].chars().map(char::len_utf16).sum()
+            });
+            json(&format!("{{\"row\":{},\"column\":{}}}", point.row, units))
+        }
+
+        /// Returns the byte offset of `point` in the whole text.
+        fn byte_of(&self, point: &Point) -> usize {
+            self.lines.iter().take(point.row).map(|line| line.len() + 1).sum::<usize>() + point.column
+        }
+
+        pub fn root_node(&self) -> Node<'_> {
+            self.node(self.inner.root_node())
+        }
+
+        pub fn walk(&self) -> TreeCursor<'_> {
+            self.root_node().walk()
+        }
+
+        /// Does nothing, since the web backend reparses in full.
+        pub fn edit(&mut self, edit: &InputEdit) {
+            let _ = edit;
+        }
+
+        /// Returns the whole of `other`, since the web backend reparses in
+        /// full.
+        pub fn changed_ranges(&self, other: &Tree) -> impl Iterator<Item = Range> {
+            std::iter::once(other.root_node().range())
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            self.inner.delete_tree();
+        }
+    }
+
+    /// A node in a [`Tree`].
+    #[derive(Clone, Copy)]
+    pub struct Node<'tree> {
+        tree: &'tree Tree,
+        slot: usize
+    }
+
+    impl<'tree> Node<'tree> {
+        fn js(&self) -> JsNode {
+            self.tree.nodes.borrow()[self.slot].clone()
+        }
+
+        fn wrap(&self, node: Option<JsNode>) -> Option<Node<'tree>> {
+            node.map(|node| self.tree.node(node))
+        }
+
+        pub fn kind(&self) -> &'static str {
+            intern(self.js().kind())
+        }
+
+        pub fn is_named(&self) -> bool {
+            self.js().is_named()
+        }
+
+        pub fn is_error(&self) -> bool {
+            self.kind() == "ERROR"
+        }
+
+        pub fn is_missing(&self) -> bool {
+            self.js().is_missing()
+        }
+
+        pub fn start_position(&self) -> Point {
+            self.tree.point_from_js(&self.js().start_position())
+        }
+
+        pub fn end_position(&self) -> Point {
+            self.tree.point_from_js(&self.js().end_position())
+        }
+
+        pub fn range(&self) -> Range {
+            let (start_point, end_point) = (self.start_position(), self.end_position());
+            Range {
+                start_byte: self.tree.byte_of(&start_point),
+                end_byte: self.tree.byte_of(&end_point),
+                start_point,
+                end_point
+            }
+        }
+
+        pub fn byte_range(&self) -> std::ops::Range<usize> {
+            let range = self.range();
+            range.start_byte..range.end_byte
+        }
+
+        pub fn child_count(&self) -> usize {
+            self.js().child_count() as usize
+        }
+
+        pub fn named_child_count(&self) -> usize {
+            self.js().named_child_count() as usize
+        }
+
+        pub fn child(&self, i: usize) -> Option<Node<'tree>> {
+            self.wrap(self.js().child(i as u32))
+        }
+
+        pub fn named_child(&self, i: usize) -> Option<Node<'tree>> {
+            self.wrap(self.js().named_child(i as u32))
+        }
+
+        pub fn child_by_field_name(&self, field_name: impl AsRef<[u8]>) -> Option<Node<'tree>> {
+            let name = String::from_utf8_lossy(field_name.as_ref());
+            self.wrap(self.js().child_by_field_name(&name))
+        }
+
+        pub fn children<'a>(&self, cursor: &'a mut TreeCursor<'tree>) -> std::vec::IntoIter<Node<'tree>> {
+            let _ = cursor;
+            (0..self.child_count()).filter_map(|i| self.child(i)).collect::<Vec<_>>().into_iter()
+        }
+
+        pub fn named_children<'a>(&self, cursor: &'a mut TreeCursor<'tree>) -> std::vec::IntoIter<Node<'tree>> {
+            let _ = cursor;
+            (0..self.named_child_count()).filter_map(|i| self.named_child(i)).collect::<Vec<_>>().into_iter()
+        }
+
+        pub fn children_by_field_name<'a>(&self, field_name: &str, cursor: &'a mut TreeCursor<'tree>) -> std::vec::IntoIter<Node<'tree>> {
+            let _ = cursor;
+            let mut children = vec![];
+            let mut walk = self.walk();
+            if walk.goto_first_child() {
+                loop {
+                    if walk.field_name() == Some(field_name) {
+                        children.push(walk.node());
+                    }
+                    if !walk.goto_next_sibling() {
+                        break;
+                    }
+                }
+            }
+            children.into_iter()
+        }
+
+        pub fn parent(&self) -> Option<Node<'tree>> {
+            self.wrap(self.js().parent())
+        }
+
+        pub fn next_sibling(&self) -> Option<Node<'tree>> {
+            self.wrap(self.js().next_sibling())
+        }
+
+        pub fn prev_sibling(&self) -> Option<Node<'tree>> {
+            self.wrap(self.js().prev_sibling())
+        }
+
+        pub fn next_named_sibling(&self) -> Option<Node<'tree>> {
+            self.wrap(self.js().next_named_sibling())
+        }
+
+        pub fn prev_named_sibling(&self) -> Option<Node<'tree>> {
+            self.wrap(self.js().prev_named_sibling())
+        }
+
+        pub fn named_descendant_for_point_range(&self, start: Point, end: Point) -> Option<Node<'tree>> {
+            let (start, end) = (self.tree.point_to_js(&start), self.tree.point_to_js(&end));
+            self.wrap(self.js().named_descendant_for_position(&start, &end))
+        }
+
+        pub fn walk(&self) -> TreeCursor<'tree> {
+            TreeCursor { tree: self.tree, inner: self.js().walk() }
+        }
+    }
+
+    impl PartialEq for Node<'_> {
+        fn eq(&self, other: &Self) -> bool {
+            std::ptr::eq(self.tree, other.tree) && self.js().id() == other.js().id()
+        }
+    }
+
+    impl Eq for Node<'_> {}
+
+    impl std::fmt::Debug for Node<'_> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            let range = self.range();
+            write!(f, "{{Node {} {:?} - {:?}}}", self.kind(), range.start_point, range.end_point)
+        }
+    }
+
+    /// Walks a [`Tree`] from a node.
+    pub struct TreeCursor<'tree> {
+        tree: &'tree Tree,
+        inner: JsTreeCursor
+    }
+
+    impl<'tree> TreeCursor<'tree> {
+        pub fn node(&self) -> Node<'tree> {
+            self.tree.node(self.inner.current_node())
+        }
+
+        pub fn field_name(&self) -> Option<&'static str> {
+            self.inner.current_field_name().map(intern)
+        }
+
+        pub fn goto_first_child(&mut self) -> bool {
+            self.inner.goto_first_child()
+        }
+
+        pub fn goto_next_sibling(&mut self) -> bool {
+            self.inner.goto_next_sibling()
+        }
+
+        pub fn goto_parent(&mut self) -> bool {
+            self.inner.goto_parent()
+        }
+    }
+
+    impl Drop for TreeCursor<'_> {
+        fn drop(&mut self) {
+            self.inner.delete_cursor();
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! Renders a [`Viewport`] to ANSI-escaped strings, for terminal frontends
+//! and golden-file tests of the render model, optionally beside its
+//! [gutter](crate::gutters).
+//!
+//! Only available with the `terminal` feature.
+
+use std::collections::HashMap;
+
+use crate::document::Position;
+use crate::gutters::GutterRow;
+use crate::render::{Viewport, RenderLine};
+
+/// SGR parameter strings (the part between `ESC [` and `m`) used to style
+/// each channel of the render model.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Theme {
+    /// Keyed by [`HighlightSpan::class`](crate::render::HighlightSpan).
+    pub highlights: HashMap<&'static str, String>,
+
+    /// Keyed by virtual text style. Styles not present use
+    /// `default_virtual_text`.
+    pub virtual_text: HashMap<String, String>,
+    pub default_virtual_text: String,
+
+    pub selection: String,
+    pub fold_marker: String,
+
+    /// Keyed by [`Sign::style`](crate::gutters::Sign). Styles not present
+    /// use `default_sign`.
+    pub signs: HashMap<String, String>,
+    pub default_sign: String,
+    pub line_number: String
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        let mut highlights = HashMap::new();
+        highlights.insert("comment", String::from("90"));
+        highlights.insert("string", String::from("32"));
+        highlights.insert("number", String::from("33"));
+        highlights.insert("keyword", String::from("35"));
+        highlights.insert("type", String::from("36"));
+
+        let mut signs = HashMap::new();
+        signs.insert(String::from("error"), String::from("31"));
+        signs.insert(String::from("warning"), String::from("33"));
+        signs.insert(String::from("information"), String::from("34"));
+        signs.insert(String::from("hint"), String::from("2"));
+        signs.insert(String::from("bookmark"), String::from("36"));
+
+        Theme {
+            highlights,
+            virtual_text: HashMap::new(),
+            default_virtual_text: String::from("2;3"),
+            selection: String::from("7"),
+            fold_marker: String::from("2"),
+            signs,
+            default_sign: String::new(),
+            line_number: String::from("2")
+        }
+    }
+}
+
+/// Returns the escape sequence selecting `sgr`, after resetting any previous
+/// style.
+fn style(sgr: &str) -> String {
+    if sgr.is_empty() {
+        String::from("\x1b[0m")
+    } else {
+        format!("\x1b[0;{}m", sgr)
+    }
+}
+
+/// Renders `line` to a single string. Text within the selection
+/// `selection` (beginning, ending) is drawn with the theme's selection style
+/// layered over its highlight. The string ends with the style reset if any
+/// style was applied.
+pub fn render_line(line: &RenderLine, selection: (Position, Position), theme: &Theme) -> String {
+    let mut out = String::new();
+    let mut current = String::new();
+
+    let selected = |column: usize| {
+        let p = Position::from(line.row, column);
+        selection.0 <= p && p < selection.1
+    };
+
+    let mut set_style = |out: &mut String, sgr: String| {
+        if sgr != current {
+            out.push_str(&style(&sgr));
+            current = sgr;
+        }
+    };
+
+    let chars: Vec<char> = line.content.chars().collect();
+
+    for column in 0..=chars.len() {
+        for vt in line.virtual_text.iter().filter(|vt| vt.column == column) {
+            let sgr = theme.virtual_text.get(&vt.style).unwrap_or(&theme.default_virtual_text);
+            set_style(&mut out, sgr.clone());
+            out.push_str(&vt.text);
+        }
+
+        if column == chars.len() {
+            break;
+        }
+
+        let mut sgr: Vec<&str> = vec![];
+        if let Some(span) = line.highlights.iter().find(|h| h.start <= column && column < h.end) {
+            if let Some(s) = theme.highlights.get(span.class) {
+                sgr.push(s);
+            }
+        }
+        if selected(column) {
+            sgr.push(&theme.selection);
+        }
+
+        set_style(&mut out, sgr.join(";"));
+        out.push(chars[column]);
+    }
+
+    if let Some(hidden) = line.folded {
+        set_style(&mut out, theme.fold_marker.clone());
+        out.push_str(&format!(" ⋯ {} {}", hidden, if hidden == 1 { "line" } else { "lines" }));
+    }
+
+    set_style(&mut out, String::new());
+    out
+}
+
+/// Renders every line of `viewport`, one string per screen row.
+///
+/// # Examples
+/// ```
+/// use ls_core::document::*;
+/// use ls_core::terminal::*;
+/// let document = Document::from("a\nb");
+/// let lines = render(&document.viewport(0, 10), &Theme::default());
+/// assert_eq!(lines, vec!["a", "b"]);
+/// ```
+pub fn render(viewport: &Viewport, theme: &Theme) -> Vec<String> {
+    let selection = if viewport.cursor <= viewport.mark {
+        (viewport.cursor, viewport.mark)
+    } else {
+        (viewport.mark, viewport.cursor)
+    };
+
+    viewport.lines.iter()
+        .map(|line| render_line(line, selection, theme))
+        .collect()
+}
+
+/// Returns how many columns the widest line number in `gutter` takes.
+fn number_width(gutter: &[GutterRow]) -> usize {
+    gutter.iter().filter_map(|row| row.number).max().map_or(1, |n| n.to_string().len())
+}
+
+/// Renders `gutter` to one string per screen row, all equally wide: a
+/// column showing the first character of the row's first sign, then the
+/// line number right-aligned, then a space.
+///
+/// # Examples
+/// ```
+/// use ls_core::document::*;
+/// use ls_core::gutters::GutterOptions;
+/// use ls_core::terminal::*;
+/// let mut document = Document::from("a\nb");
+/// document.add_bookmark("here", &Position::from(1, 0), None).unwrap();
+/// let theme = Theme { line_number: String::new(), ..Theme::default() };
+/// let gutter = render_gutter(&document.gutter(0, 10, &GutterOptions::default()), &theme);
+/// assert_eq!(gutter, vec![" 1 ", "\x1b[0;36mh\x1b[0m2 "]);
+/// ```
+pub fn render_gutter(gutter: &[GutterRow], theme: &Theme) -> Vec<String> {
+    let width = number_width(gutter);
+
+    gutter.iter().map(|row| {
+        let mut out = String::new();
+        let styled = |out: &mut String, sgr: &str, text: &str| {
+            if sgr.is_empty() {
+                out.push_str(text);
+            } else {
+                out.push_str(&style(sgr));
+                out.push_str(text);
+                out.push_str(&style(""));
+            }
+        };
+
+        match row.signs.first().and_then(|sign| Some((sign, sign.text.chars().next()?))) {
+            Some((sign, c)) => {
+                let sgr = theme.signs.get(&sign.style).unwrap_or(&theme.default_sign);
+                styled(&mut out, sgr, &c.to_string());
+            },
+            None => out.push(' ')
+        }
+
+        let number = row.number.map_or(String::new(), |n| n.to_string());
+        styled(&mut out, &theme.line_number, &format!("{:>1$}", number, width));
+        out.push(' ');
+        out
+    }).collect()
+}
+
+/// Renders every line of `viewport` as [`render`] does, each after the
+/// gutter of the first screen row showing it, from `gutter` as
+/// [`render_gutter`] renders it.
+pub fn render_with_gutter(viewport: &Viewport, gutter: &[GutterRow], theme: &Theme) -> Vec<String> {
+    let margins = render_gutter(gutter, theme);
+    let blank = " ".repeat(number_width(gutter) + 2);
+
+    render(viewport, theme).into_iter()
+        .zip(viewport.lines.iter())
+        .map(|(text, line)| {
+            let margin = gutter.iter().position(|row| row.row == line.row).map_or(&blank, |i| &margins[i]);
+            format!("{}{}", margin, text)
+        })
+        .collect()
+}
+
+/// Returns the escape sequence which places the terminal cursor at the
+/// viewport's cursor, assuming the viewport is drawn from the top-left of
+/// the screen. Virtual text before the cursor is accounted for. Returns
+/// `None` if the cursor is not on screen.
+pub fn cursor_escape(viewport: &Viewport) -> Option<String> {
+    let cursor = viewport.cursor;
+    let screen_row = viewport.lines.iter().position(|l| l.row == cursor.row)?;
+
+    let line = &viewport.lines[screen_row];
+    let virtual_width: usize = line.virtual_text.iter()
+        .filter(|vt| vt.column <= cursor.column)
+        .map(|vt| vt.text.chars().count())
+        .sum();
+
+    Some(format!("\x1b[{};{}H", screen_row + 1, cursor.column + virtual_width + 1))
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{Document, Range};
+    use crate::gutters::GutterOptions;
+
+    #[test]
+    fn golden() {
+        let mut document = Document::from("ab\ncd\ne\nf\ng");
+        document.add_virtual_text(&Position::from(0, 1), "=", "hint").unwrap();
+        document.fold_rows(2, 3).unwrap();
+        document.set_selection(&Range::from(1, 1, 2, 0)).unwrap();
+
+        let mut theme = Theme::default();
+        theme.virtual_text.insert(String::from("hint"), String::from("34"));
+
+        let viewport = document.viewport(0, 10);
+        assert_eq!(render(&viewport, &theme), vec![
+            "a\x1b[0;34m=\x1b[0mb",
+            "c\x1b[0;7md\x1b[0m",
+            "e\x1b[0;2m ⋯ 1 line\x1b[0m",
+            "g",
+        ]);
+        assert_eq!(cursor_escape(&viewport), Some(String::from("\x1b[3;1H")));
+
+        document.set_cursor_and_mark(&Position::from(0, 2)).unwrap();
+        assert_eq!(cursor_escape(&document.viewport(0, 10)), Some(String::from("\x1b[1;4H")));
+        assert_eq!(cursor_escape(&document.viewport(1, 10)), None);
+
+        let theme = Theme { line_number: String::new(), ..Theme::default() };
+        let gutter = document.gutter(0, 10, &GutterOptions::default());
+        let viewport = document.viewport(0, 10);
+        assert_eq!(render_with_gutter(&viewport, &gutter, &theme)[2], " 3 e\x1b[0;2m ⋯ 1 line\x1b[0m");
+        assert_eq!(render_with_gutter(&viewport, &gutter[..1], &theme)[3], "   g");
+
+        document.fold_rows(2, 4).unwrap();
+        assert_eq!(render(&document.viewport(0, 10), &theme)[2], "e\x1b[0;2m ⋯ 2 lines\x1b[0m");
+    }
+
+    #[cfg(feature = "parsing")]
+    #[test]
+    fn golden_highlights() {
+        let document = Document::from_with_language("let x = 1;", "rs");
+        assert_eq!(render(&document.viewport(0, 1), &Theme::default()), vec![
+            "\x1b[0;35mlet\x1b[0m x = \x1b[0;33m1\x1b[0m;",
+        ]);
+    }
+}
@@ -0,0 +1,175 @@
+//! "Delete that": removal which cleans up after itself.
+//!
+//! Removing a syntax node with [`Document::remove`] leaves its separators
+//! behind: deleting `b` from `f(a, b)` gives `f(a, )`. A tidy delete widens
+//! the removal to take the neighboring comma or semicolon along, drops lines
+//! left empty, and avoids leaving doubled or dangling spaces.
+
+use crate::document::{Document, Position, Range, RemoveOptions};
+#[cfg(feature = "parsing")]
+use crate::syntax;
+use crate::motions::TextObject;
+use crate::util::Oops;
+
+/// Tokens which separate list elements and statements.
+pub const SEPARATORS: &[&str] = &[",", ";"];
+
+impl Document {
+    /// Returns the range a tidy delete of `range` would remove. See
+    /// [`Document::tidy_delete`].
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let document = Document::from("one two three\n    four\nfive");
+    /// assert_eq!(document.tidy_delete_range(&Range::from(0, 4, 0, 7)), Range::from(0, 4, 0, 8));
+    /// assert_eq!(document.tidy_delete_range(&Range::from(0, 8, 0, 13)), Range::from(0, 7, 0, 13));
+    /// assert_eq!(document.tidy_delete_range(&Range::from(1, 4, 1, 8)), Range::from(1, 0, 2, 0));
+    /// ```
+    pub fn tidy_delete_range(&self, range: &Range) -> Range {
+        if range.empty() || !self.range_valid(range) {
+            return *range;
+        }
+
+        #[cfg(feature = "parsing")]
+        {
+            if let Some(separated) = self.separated_range(range) {
+                return self.widen_over_spaces(&separated);
+            }
+        }
+
+        self.widen_over_spaces(range)
+    }
+
+    /// Removes `range` as widened by [`Document::tidy_delete_range`],
+    /// returning the range actually removed.
+    ///
+    /// When `range` is exactly a syntax node with a separator after it, the
+    /// separator and the space following it are removed too; failing that, a
+    /// separator before it is. Lines left blank are removed entirely.
+    pub fn tidy_delete(&mut self, range: &Range) -> Result<Range, Oops> {
+        let widened = self.tidy_delete_range(range);
+        self.remove(&RemoveOptions::exact_at(&widened))?;
+        Ok(widened)
+    }
+
+    /// Tidily deletes the selection or, if it is empty, the innermost named
+    /// syntax node at the cursor. Returns the range removed, or `Err` if the
+    /// selection is empty and there is no parse tree.
+    pub fn delete_that(&mut self) -> Result<Range, Oops> {
+        let selection = self.selection();
+        if !selection.empty() {
+            return self.tidy_delete(&selection);
+        }
+
+        let range = self.resolve(&TextObject::Node)?;
+        self.tidy_delete(&range)
+    }
+
+    /// Returns the outermost syntax node spanning exactly `range`, or if
+    /// `range` is empty, the innermost named node containing it.
+    #[cfg(feature = "parsing")]
+    pub(crate) fn node_at(&self, range: &Range) -> Option<syntax::Node<'_>> {
+        let point = |p: &Position| syntax::Point::new(
+            p.row,
+            crate::util::cp_index_to_byte(&self.lines()[p.row].content, p.column).unwrap()
+        );
+        let (start, end) = (point(&range.beginning), point(&range.ending));
+        let mut node = self.parse_tree()?.root_node().named_descendant_for_point_range(start, end)?;
+
+        if range.empty() {
+            return Some(node);
+        }
+        if node.start_position() != start || node.end_position() != end {
+            return None;
+        }
+        while let Some(parent) = node.parent() {
+            if parent.start_position() != start || parent.end_position() != end {
+                break;
+            }
+            node = parent;
+        }
+        Some(node)
+    }
+
+    /// Widens `range`, which must be exactly a syntax node, over the
+    /// separator following or preceding it.
+    #[cfg(feature = "parsing")]
+    fn separated_range(&self, range: &Range) -> Option<Range> {
+        let node = self.node_at(range)?;
+        let is_separator = |n: &syntax::Node| SEPARATORS.contains(&n.kind());
+
+        if let Some(next) = node.next_sibling().filter(is_separator) {
+            let mut ending = self.range_from_ts(&next.range()).ending;
+            if let Some(after) = next.next_sibling() {
+                let following = self.range_from_ts(&after.range()).beginning;
+                if following.row == ending.row {
+                    ending = following;
+                }
+            }
+            return Some(Range { beginning: range.beginning, ending });
+        }
+
+        node.prev_sibling().filter(is_separator).map(|previous| Range {
+            beginning: self.range_from_ts(&previous.range()).beginning,
+            ending: range.ending
+        })
+    }
+
+    /// Widens `range` so that removing it leaves neither a blank line nor
+    /// doubled or dangling spaces.
+    fn widen_over_spaces(&self, range: &Range) -> Range {
+        let is_space = |c: &char| *c == ' ' || *c == '\t';
+        let before: Vec<char> = self.lines()[range.beginning.row].content.chars().take(range.beginning.column).collect();
+        let after: Vec<char> = self.lines()[range.ending.row].content.chars().skip(range.ending.column).collect();
+        let spaces_before = before.iter().rev().take_while(|c| is_space(c)).count();
+        let spaces_after = after.iter().take_while(|c| is_space(c)).count();
+        let mut result = *range;
+
+        if spaces_before == before.len() && spaces_after == after.len() {
+            let last = self.rows() - 1;
+            if range.ending.row < last {
+                result = Range::from(range.beginning.row, 0, range.ending.row + 1, 0);
+            } else if range.beginning.row > 0 {
+                let previous = range.beginning.row - 1;
+                result.beginning = Position::from(previous, self.lines()[previous].length);
+                result.ending.column = self.lines()[last].length;
+            } else {
+                result = Range::from(0, 0, 0, self.lines()[0].length);
+            }
+        } else if spaces_after == after.len() || after.get(spaces_after).is_some_and(|c| ")]},;".contains(*c)) {
+            result.beginning.column -= spaces_before;
+            result.ending.column += spaces_after;
+        } else if spaces_before > 0 || before.last().is_some_and(|c| "([{".contains(*c)) {
+            result.ending.column += spaces_after;
+        }
+
+        result
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "parsing"))]
+mod tests {
+    use crate::document::*;
+
+    #[test]
+    fn separators_go_with_nodes() {
+        let mut document = Document::from_with_language("fn f() {\n    g(a, b, c);\n    h();\n}\n", "rs");
+
+        assert_eq!(document.tidy_delete(&Range::from(1, 6, 1, 7)), Ok(Range::from(1, 6, 1, 9)));
+        assert_eq!(document.text(), "fn f() {\n    g(b, c);\n    h();\n}\n");
+
+        assert_eq!(document.tidy_delete(&Range::from(1, 9, 1, 10)), Ok(Range::from(1, 7, 1, 10)));
+        assert_eq!(document.text(), "fn f() {\n    g(b);\n    h();\n}\n");
+
+        document.set_cursor_and_mark(&Position::from(2, 4)).unwrap();
+        document.delete_that().unwrap();
+        assert_eq!(document.text(), "fn f() {\n    g(b);\n    ();\n}\n");
+
+        document.set_selection(&Range::from(1, 4, 1, 9)).unwrap();
+        assert_eq!(document.delete_that(), Ok(Range::from(1, 0, 2, 0)));
+        assert_eq!(document.text(), "fn f() {\n    ();\n}\n");
+    }
+}
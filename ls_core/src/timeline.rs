@@ -0,0 +1,220 @@
+//! Export of the undo history as a timeline, for history panels and
+//! time-based voice commands ("go back to ten minutes ago").
+
+use crate::document::{Document, Position, Range, Change, ChangePacket};
+use crate::util;
+
+/// The longest preview, in codepoints, before it is truncated with `…`.
+pub const PREVIEW_LENGTH: usize = 40;
+
+/// One [`ChangePacket`] of a document's history.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct TimelineEntry {
+    pub revision: u64,
+    pub label: Option<String>,
+
+    /// When the packet was first recorded; see [`ChangePacket::timestamp`].
+    pub timestamp: u64,
+
+    /// The ranges of text the packet inserted, and the (empty) ranges where
+    /// it removed text. Each range is in the coordinates of the document at
+    /// the moment that part of the packet was made.
+    pub ranges: Vec<Range>,
+
+    /// A preview of the text the packet inserted, with line breaks shown
+    /// as `⏎`. Text from separate changes is separated by spaces.
+    pub inserted: String,
+
+    /// A preview of the text the packet removed.
+    pub removed: String,
+
+    /// True if the packet has been undone and is waiting on the redo stack.
+    pub undone: bool
+}
+
+/// A copy of a document's lines, edited while walking the history so that
+/// the text of every packet can be recovered.
+pub(crate) struct Scratch {
+    pub(crate) lines: Vec<String>
+}
+
+impl Scratch {
+    pub(crate) fn insert(&mut self, text: &[String], position: &Position) -> Range {
+        let line = &self.lines[position.row];
+        let before = String::from(util::slice(line, ..position.column));
+        let after = String::from(util::slice(line, position.column..));
+
+        let mut inserted: Vec<String> = text.to_vec();
+        let ending = position.after_insert(position, text);
+        inserted[0] = before + &inserted[0];
+        inserted.last_mut().unwrap().push_str(&after);
+
+        self.lines.splice(position.row..=position.row, inserted);
+        Range { beginning: *position, ending }
+    }
+
+    pub(crate) fn remove(&mut self, range: &Range) -> Vec<String> {
+        let first = &self.lines[range.beginning.row];
+        let last = &self.lines[range.ending.row];
+        let before = String::from(util::slice(first, ..range.beginning.column));
+        let after = String::from(util::slice(last, range.ending.column..));
+
+        let mut removed: Vec<String> = self.lines[range.beginning.row..=range.ending.row].to_vec();
+        let last_index = removed.len() - 1;
+        removed[last_index] = String::from(util::slice(&removed[last_index], ..range.ending.column));
+        removed[0] = String::from(util::slice(&removed[0], range.beginning.column..));
+
+        self.lines.splice(range.beginning.row..=range.ending.row, vec![before + &after]);
+        removed
+    }
+}
+
+fn preview(text: &[String]) -> String {
+    let joined = text.join(" ").replace('\n', "⏎");
+    if joined.chars().count() > PREVIEW_LENGTH {
+        let mut truncated: String = joined.chars().take(PREVIEW_LENGTH).collect();
+        truncated.push('…');
+        truncated
+    } else {
+        joined
+    }
+}
+
+impl TimelineEntry {
+    fn new(packet: &ChangePacket, undone: bool) -> TimelineEntry {
+        TimelineEntry {
+            revision: packet.revision(),
+            label: packet.label().map(String::from),
+            timestamp: packet.timestamp(),
+            ranges: vec![],
+            inserted: String::new(),
+            removed: String::new(),
+            undone
+        }
+    }
+}
+
+impl Document {
+    /// Returns the document's history, oldest first: every packet that can be
+    /// undone, followed by every packet that can be redone (marked `undone`).
+    ///
+    /// This replays the history on a copy of the text, so it costs time
+    /// proportional to the size of the history.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("");
+    /// document.checkpoint_labeled("greet");
+    /// document.insert("hello\nworld", &InsertOptions::exact()).unwrap();
+    /// document.checkpoint();
+    /// document.remove(&RemoveOptions::exact_at(&Range::from(0, 0, 1, 0))).unwrap();
+    /// document.undo(1);
+    ///
+    /// let timeline = document.timeline();
+    /// assert_eq!(timeline[0].label.as_deref(), Some("greet"));
+    /// assert_eq!(timeline[0].inserted, "hello⏎world");
+    /// assert_eq!(timeline[0].ranges, vec![Range::from(0, 0, 1, 5)]);
+    /// assert_eq!(timeline[1].removed, "hello⏎");
+    /// assert!(timeline[1].undone);
+    /// ```
+    pub fn timeline(&self) -> Vec<TimelineEntry> {
+        let current: Vec<String> = self.lines().iter().map(|l| l.content.clone()).collect();
+        let mut entries = vec![];
+
+        // Walk backwards through the undo stack, undoing on a copy. The
+        // inverse of an insertion is a removal and vice versa.
+        let mut scratch = Scratch { lines: current.clone() };
+        for packet in self.undo_redo().undo_packets() {
+            let mut entry = TimelineEntry::new(packet, false);
+            let mut inserted = vec![];
+            let mut removed = vec![];
+
+            for change in packet.changes().iter().rev() {
+                match change {
+                    Change::Remove { range } => {
+                        entry.ranges.push(*range);
+                        inserted.push(scratch.remove(range).join("\n"));
+                    },
+                    Change::Insert { text, position } => {
+                        entry.ranges.push(Range { beginning: *position, ending: *position });
+                        scratch.insert(text, position);
+                        removed.push(text.join("\n"));
+                    },
+                    _ => ()
+                }
+            }
+
+            entry.ranges.reverse();
+            inserted.reverse();
+            removed.reverse();
+            entry.inserted = preview(&inserted);
+            entry.removed = preview(&removed);
+            entries.push(entry);
+        }
+        entries.reverse();
+
+        // Walk forwards through the redo stack, redoing on another copy.
+        let mut scratch = Scratch { lines: current };
+        for packet in self.undo_redo().redo_packets() {
+            let mut entry = TimelineEntry::new(packet, true);
+            let mut inserted = vec![];
+            let mut removed = vec![];
+
+            for change in packet.changes().iter().rev() {
+                match change {
+                    Change::Insert { text, position } => {
+                        entry.ranges.push(scratch.insert(text, position));
+                        inserted.push(text.join("\n"));
+                    },
+                    Change::Remove { range } => {
+                        entry.ranges.push(Range { beginning: range.beginning, ending: range.beginning });
+                        removed.push(scratch.remove(range).join("\n"));
+                    },
+                    _ => ()
+                }
+            }
+
+            entry.inserted = preview(&inserted);
+            entry.removed = preview(&removed);
+            entries.push(entry);
+        }
+
+        entries
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::{InsertOptions, RemoveOptions};
+
+    #[test]
+    fn timeline_replays_history() {
+        let mut document = Document::from("abc");
+        document.insert("🦄", &InsertOptions::exact_at(&Range::from(0, 1, 0, 2))).unwrap();
+        document.checkpoint();
+        document.insert(&"x".repeat(50), &InsertOptions::exact()).unwrap();
+        document.checkpoint();
+        document.remove(&RemoveOptions::exact_at(&Range::from(0, 0, 0, 10))).unwrap();
+        document.undo(2);
+
+        let timeline = document.timeline();
+        assert_eq!(timeline.len(), 3);
+
+        assert_eq!(timeline[0].inserted, "🦄");
+        assert_eq!(timeline[0].removed, "b");
+        assert!(!timeline[0].undone);
+        assert!(timeline[0].timestamp > 0);
+
+        assert_eq!(timeline[1].inserted.chars().count(), PREVIEW_LENGTH + 1);
+        assert!(timeline[1].undone);
+        assert_eq!(timeline[2].removed, "xxxxxxxxxx");
+        assert_eq!(timeline[2].ranges, vec![Range::from(0, 0, 0, 0)]);
+        assert!(timeline.windows(2).all(|w| w[0].revision < w[1].revision || w[1].undone));
+
+        assert_eq!(document.text(), "a🦄c");
+    }
+}
@@ -0,0 +1,177 @@
+//! The parse tree as JSON, for web views and debugging tools which want to
+//! show a document's structure without parsing
+//! [`Document::parse_tree_pretty_print`] output.
+//!
+//! Only available with the `serialization` feature. A tree looks like this:
+//!
+//! ```json
+//! {
+//!   "kind": "function_item",
+//!   "range": { "beginning": { "row": 0, "column": 0 },
+//!              "ending": { "row": 0, "column": 11 } },
+//!   "text": "fn add() {}",
+//!   "children": [
+//!     { "kind": "fn", "range": ..., "text": "fn" },
+//!     { "kind": "identifier", "field": "name", "range": ..., "text": "add" },
+//!     ...
+//!   ]
+//! }
+//! ```
+//!
+//! `field` is left out for nodes which are not a named field of their
+//! parent, and `children` for leaves. A node whose children were cut off by
+//! the depth limit has `"truncated": true` instead.
+
+use serde::{Serialize, Deserialize};
+
+use crate::document::{Document, Range};
+#[cfg(feature = "parsing")]
+use crate::syntax;
+use crate::util::Oops;
+
+/// How many characters of each node's text are included.
+pub const TEXT_PREVIEW_LENGTH: usize = 32;
+
+/// One node of the parse tree.
+#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize)]
+pub struct TreeNode {
+    pub kind: String,
+
+    /// The name of the field of its parent this node fills, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+
+    pub range: Range,
+
+    /// The first [`TEXT_PREVIEW_LENGTH`] characters of the node's text.
+    pub text: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+
+    /// Were this node's children left out because of the depth limit?
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool
+}
+
+/// Does `a` overlap or touch `b`?
+#[cfg(feature = "parsing")]
+fn touches(a: &Range, b: &Range) -> bool {
+    a.beginning <= b.ending && b.beginning <= a.ending
+}
+
+impl Document {
+    /// Returns the node `node`, which fills `field` of its parent, with its
+    /// descendants down to `depth` more levels which touch `within`.
+    #[cfg(feature = "parsing")]
+    fn tree_node(&self, node: &syntax::Node, field: Option<&str>, depth: Option<usize>, within: Option<&Range>) -> TreeNode {
+        let range = self.range_from_ts(&node.range());
+        let text = self.text_range(&range)
+            .unwrap_or_default()
+            .chars()
+            .take(TEXT_PREVIEW_LENGTH)
+            .collect();
+
+        let mut children = vec![];
+        let truncated = depth == Some(0) && node.child_count() > 0;
+
+        if !truncated {
+            let mut cursor = node.walk();
+            if cursor.goto_first_child() {
+                loop {
+                    let child = cursor.node();
+                    let keep = within.is_none_or(|within| touches(&self.range_from_ts(&child.range()), within));
+                    if keep {
+                        children.push(self.tree_node(&child, cursor.field_name(), depth.map(|d| d - 1), within));
+                    }
+                    if !cursor.goto_next_sibling() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        TreeNode {
+            kind: String::from(node.kind()),
+            field: field.map(String::from),
+            range,
+            text,
+            children,
+            truncated
+        }
+    }
+
+    /// Returns the parse tree as JSON (see the [module documentation](self)),
+    /// nesting at most `depth_limit` levels below the root and, if `range`
+    /// is given, leaving out nodes which do not touch it. Returns `Err` if
+    /// `range` is invalid or there is no parse tree.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// use ls_core::tree_json::*;
+    /// let document = Document::from_with_language("use hello;\nfn f() {}", "rs");
+    ///
+    /// let json = document.parse_tree_json(Some(1), Some(&Range::from(0, 2, 0, 2))).unwrap();
+    /// let root: TreeNode = serde_json::from_str(&json).unwrap();
+    /// assert_eq!(root.kind, "source_file");
+    /// assert_eq!(root.children.len(), 1);
+    /// assert_eq!(root.children[0].kind, "use_declaration");
+    /// assert_eq!(root.children[0].text, "use hello;");
+    /// assert!(root.children[0].truncated);
+    /// ```
+    #[cfg(feature = "parsing")]
+    pub fn parse_tree_json(&self, depth_limit: Option<usize>, range: Option<&Range>) -> Result<String, Oops> {
+        if let Some(range) = range {
+            if !self.range_valid(range) {
+                return Err(Oops::InvalidRange(*range, "parse_tree_json"));
+            }
+        }
+
+        let tree = self.parse_tree().ok_or(Oops::CannotParse("parse_tree_json"))?;
+        let root = self.tree_node(&tree.root_node(), None, depth_limit, range);
+        serde_json::to_string(&root).map_err(|_| Oops::Ouch("parse_tree_json"))
+    }
+
+    /// Returns the parse tree as JSON.
+    ///
+    /// Without the `parsing` feature there is never a parse tree, so this
+    /// always returns an [`Oops`].
+    #[cfg(not(feature = "parsing"))]
+    pub fn parse_tree_json(&self, _depth_limit: Option<usize>, _range: Option<&Range>) -> Result<String, Oops> {
+        Err(Oops::CannotParse("parse_tree_json"))
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "parsing"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_depth_and_range() {
+        let document = Document::from_with_language("fn add(a: u8, b: u8) -> u8 {\n    a + b\n}\nfn other() {}", "rs");
+
+        let root: TreeNode = serde_json::from_str(&document.parse_tree_json(None, None).unwrap()).unwrap();
+        assert_eq!(root.children.len(), 2);
+        let function = &root.children[0];
+        assert_eq!(function.field, None);
+        assert_eq!(function.range, Range::from(0, 0, 2, 1));
+        assert_eq!(function.text.chars().count(), TEXT_PREVIEW_LENGTH);
+        let name = function.children.iter().find(|c| c.field.as_deref() == Some("name")).unwrap();
+        assert_eq!((name.kind.as_str(), name.text.as_str()), ("identifier", "add"));
+        assert!(!name.truncated && name.children.is_empty());
+
+        let json = document.parse_tree_json(Some(0), None).unwrap();
+        assert!(json.contains("\"truncated\":true") && !json.contains("children"));
+
+        let root: TreeNode = serde_json::from_str(&document.parse_tree_json(None, Some(&Range::from(3, 3, 3, 4))).unwrap()).unwrap();
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.children[0].children.len(), 1);
+        assert_eq!(root.children[0].children[0].text, "other");
+
+        assert!(document.parse_tree_json(None, Some(&Range::from(9, 0, 9, 0))).is_err());
+        assert!(Document::from("plain").parse_tree_json(None, None).is_err());
+    }
+}
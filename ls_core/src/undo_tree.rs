@@ -0,0 +1,217 @@
+//! The undo tree: redos which editing would have forgotten, kept as
+//! branches which can be returned to.
+//!
+//! Undoing and then editing used to throw the redo stack away. Dictation
+//! makes that common: a misrecognition is undone, the phrase is said again,
+//! and whatever was undone past is gone. Now the redo stack is kept as a
+//! [`Branch`] forking from the node it was split off at, and the undo and
+//! redo stacks remain the path from the start of history through the
+//! current node to the tip of the current branch.
+//!
+//! Nodes are named by [`ChangePacket::id`]: node `n` is the text as it
+//! stands after the packet with id `n`, and node 0 is the start of history.
+//! [`Document::jump_to_node`] undoes and redoes, switching branches on the
+//! way as needed, to reach any node. Branches forking from packets which
+//! were [forgotten](UndoRedoStacks::forget_oldest) or
+//! [compacted](crate::compaction) away can no longer be reached.
+
+use crate::document::{Document, ChangePacket, UndoRedoStacks};
+use crate::util::Oops;
+
+/// Redos split off from the undo history by an edit.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Branch {
+    pub(crate) base: u64,
+
+    /// The packets, as the redo stack held them: the last is redone first.
+    pub(crate) packets: Vec<ChangePacket>
+}
+
+impl Branch {
+    /// Returns the node the branch forks from.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Returns the nodes on the branch, from the first after its base to its
+    /// tip.
+    pub fn nodes(&self) -> Vec<u64> {
+        self.packets.iter().rev().map(|packet| packet.id()).collect()
+    }
+
+    /// Returns the branch's packets in the order they would be redone.
+    pub fn packets(&self) -> impl Iterator<Item = &ChangePacket> {
+        self.packets.iter().rev()
+    }
+}
+
+/// Returns true if one of `packets` is `node`.
+fn holds(packets: &[ChangePacket], node: u64) -> bool {
+    packets.iter().any(|packet| packet.id() == node)
+}
+
+impl UndoRedoStacks {
+    /// Returns the current node: the id of the newest undo packet, or 0 if
+    /// there are none.
+    pub fn current_node(&self) -> u64 {
+        self.undo_stack.last().map_or(0, |packet| packet.id())
+    }
+
+    /// Returns the branches split off from the undo history, oldest first.
+    pub fn branches(&self) -> &[Branch] {
+        &self.branches
+    }
+
+    /// Forgets every branch. This cannot be undone!
+    pub fn forget_branches(&mut self) {
+        self.branches.clear();
+    }
+
+    /// Returns true if `node` can be reached through at most `depth`
+    /// branches.
+    fn reaches(&self, node: u64, depth: usize) -> bool {
+        node == 0
+            || holds(&self.undo_stack, node)
+            || holds(&self.redo_stack, node)
+            || (depth > 0 && self.branches.iter()
+                .any(|branch| holds(&branch.packets, node) && self.reaches(branch.base, depth - 1)))
+    }
+}
+
+impl Document {
+    /// Undoes and redoes until the document is at `node` (see the
+    /// [module documentation](self)), switching to the branch holding it if
+    /// need be. The redo stack switched away from becomes a branch itself.
+    /// Returns `(undone, redone)`, the number of packets undone and redone to
+    /// get there, or `Err` without changing anything if `node` can't be
+    /// reached.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("");
+    /// document.insert("the cat", &InsertOptions::exact()).unwrap();
+    /// document.checkpoint();
+    /// document.insert(" sat", &InsertOptions::exact()).unwrap();
+    /// let sat = document.undo_redo().current_node();
+    ///
+    /// document.undo_once().unwrap();
+    /// document.insert(" sad", &InsertOptions::exact()).unwrap();
+    /// let branch = &document.undo_redo().branches()[0];
+    /// assert_eq!(branch.nodes(), vec![sat]);
+    ///
+    /// assert_eq!(document.jump_to_node(sat), Ok((1, 1)));
+    /// assert_eq!(document.text(), "the cat sat");
+    /// assert_eq!(document.undo_redo().branches().len(), 1);
+    /// assert!(document.jump_to_node(sat + 100).is_err());
+    /// ```
+    pub fn jump_to_node(&mut self, node: u64) -> Result<(usize, usize), Oops> {
+        let depth = self.undo_redo.branches.len();
+        if !self.undo_redo.reaches(node, depth) {
+            return Err(Oops::InvalidIndex(node as usize, "jump_to_node"));
+        }
+        self.jump_to_reachable(node)
+    }
+
+    /// Does the work of [`Document::jump_to_node`] for a reachable `node`.
+    fn jump_to_reachable(&mut self, node: u64) -> Result<(usize, usize), Oops> {
+        if node == 0 || holds(&self.undo_redo.undo_stack, node) {
+            // Node 0 is reached by undoing everything.
+            let mut undone = 0;
+            while !self.undo_redo.undo_stack.is_empty() && (node == 0 || self.undo_redo.current_node() != node) {
+                self.undo_once()?;
+                undone += 1;
+            }
+            return Ok((undone, 0));
+        }
+
+        if node != 0 && holds(&self.undo_redo.redo_stack, node) {
+            let mut redone = 0;
+            while self.undo_redo.current_node() != node {
+                self.redo_once()?;
+                redone += 1;
+            }
+            return Ok((0, redone));
+        }
+
+        let find = |document: &Document| document.undo_redo.branches.iter()
+            .position(|branch| holds(&branch.packets, node))
+            .ok_or(Oops::InvalidIndex(node as usize, "jump_to_node"));
+        let base = self.undo_redo.branches[find(self)?].base;
+        let (undone, redone) = self.jump_to_reachable(base)?;
+
+        // Getting to the base may have switched branches, moving this one.
+        let branch = self.undo_redo.branches.remove(find(self)?);
+        let redos = std::mem::replace(&mut self.undo_redo.redo_stack, branch.packets);
+        if !redos.is_empty() {
+            let base = self.undo_redo.current_node();
+            self.undo_redo.branches.push(Branch { base, packets: redos });
+        }
+
+        let (_, more) = self.jump_to_reachable(node)?;
+        Ok((undone, redone + more))
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compaction::CompactionPolicy;
+    use crate::document::{InsertOptions, RemoveOptions, Range};
+
+    #[test]
+    fn every_node_stays_reachable_across_branches() {
+        let mut document = Document::from("");
+        let say = |document: &mut Document, text: &str| {
+            document.insert(text, &InsertOptions::exact()).unwrap();
+            document.checkpoint();
+            (document.undo_redo().current_node(), document.text())
+        };
+
+        let one = say(&mut document, "one");
+        let two = say(&mut document, " two");
+        let three = say(&mut document, " three");
+        document.undo(2);
+        let won = say(&mut document, " won");
+        document.undo_once().unwrap();
+        document.remove(&RemoveOptions::exact_at(&Range::from(0, 0, 0, 1))).unwrap();
+        let removed = (document.undo_redo().current_node(), document.text());
+
+        assert_eq!(document.undo_redo().branches().len(), 2);
+        assert_eq!(document.undo_redo().branches()[0].nodes(), vec![two.0, three.0]);
+        assert_eq!(document.undo_redo().branches()[1].base(), one.0);
+
+        for (node, text) in [&three, &won, &two, &removed, &one, &three].iter() {
+            document.jump_to_node(*node).unwrap();
+            assert_eq!(document.text(), *text);
+            assert_eq!(document.undo_redo().current_node(), *node);
+        }
+
+        assert_eq!(document.jump_to_node(0), Ok((3, 0)));
+        assert_eq!(document.text(), "");
+        assert_eq!(document.undo_redo().branches().len(), 2);
+
+        document.forget_undo_redo().unwrap();
+        assert!(document.undo_redo().branches().is_empty());
+    }
+
+    #[test]
+    fn compacted_nodes_can_be_jumped_to() {
+        let mut document = Document::from("");
+        for word in ["one", " two", " three"].iter() {
+            document.insert(word, &InsertOptions::exact()).unwrap();
+            document.checkpoint();
+        }
+        let two = document.undo_redo().undo_packets().nth(1).unwrap().id();
+
+        document.compact_history(&CompactionPolicy { keep: 1, period: 0, snapshot_every: 0 });
+        assert_eq!(document.undo_redo().undo_packets().nth(1).unwrap().id(), two);
+
+        assert_eq!(document.jump_to_node(0), Ok((2, 0)));
+        assert_eq!(document.text(), "");
+        assert_eq!(document.jump_to_node(two), Ok((0, 1)));
+        assert_eq!(document.text(), "one two");
+    }
+}
@@ -7,6 +7,8 @@ use crate::document;
 use std::ops::{Bound, RangeBounds};
 use lazy_static::lazy_static;
 use regex::Regex;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
 
 lazy_static!{
     pub static ref LINE_SPLIT: Regex = Regex::new(r"\r?\n").unwrap();
@@ -19,6 +21,7 @@ pub enum Oops {
     Ouch(&'static str),
     NonexistentAnchor(document::AnchorHandle),
     CannotRemoveAnchor(document::AnchorHandle),
+    DuplicateAnchor(document::AnchorHandle),
     NoMoreUndos(usize),
     NoMoreRedos(usize),
     InvalidIndex(usize, &'static str),
@@ -26,6 +29,31 @@ pub enum Oops {
     InvalidRange(document::Range, &'static str),
     EmptyString(&'static str),
     CannotParse(&'static str),
+    InvalidEncoding(&'static str),
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = Date, js_name = now)]
+    fn date_now() -> f64;
+}
+
+/// Returns the current time in milliseconds since the Unix epoch. Uses
+/// `Date.now()` under wasm, where the system clock is unavailable.
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> u64 {
+    date_now() as u64
+}
+
+/// Returns the current time in milliseconds since the Unix epoch. Uses
+/// `Date.now()` under wasm, where the system clock is unavailable.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
 }
 
 /// Returns the substring of `s` starting at Unicode codepoint index `start`
@@ -0,0 +1,144 @@
+//! Inline virtual text: strings shown within a document's lines
+//! (parameter hints, evaluation results, and so on) which are not part of
+//! the document's text.
+//!
+//! Virtual text is attached to an [`Anchor`](crate::document::Anchor) and
+//! moves with it. It never appears in [`Document::text`] and is not
+//! recorded on the undo stack.
+
+use crate::document::{Document, Position, AnchorHandle};
+use crate::util::Oops;
+
+/// A string displayed inline at an anchor's position.
+///
+/// `style` is an opaque class name handed to the renderer, separate from
+/// syntax highlighting, so that frontends can style hints and results
+/// however they like.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct VirtualText {
+    pub text: String,
+    pub style: String
+}
+
+impl VirtualText {
+    /// Returns the virtual text `text` with style class `style`.
+    pub fn from(text: &str, style: &str) -> VirtualText {
+        VirtualText {
+            text: String::from(text),
+            style: String::from(style)
+        }
+    }
+}
+
+impl Document {
+    /// Creates a floating anchor at `position` and attaches `text` to it with
+    /// style class `style`. Returns the new anchor's handle, or `Err` if the
+    /// position is invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("foo(1, 2)");
+    /// let hint = document.add_virtual_text(&Position::from(0, 4), "x: ", "hint").unwrap();
+    /// assert_eq!(document.text(), "foo(1, 2)");
+    /// assert_eq!(document.virtual_text(hint).unwrap().text, "x: ");
+    /// ```
+    pub fn add_virtual_text(&mut self, position: &Position, text: &str, style: &str)
+        -> Result<AnchorHandle, Oops>
+    {
+        let handle = self.create_floating_anchor(position)?;
+        self.virtual_text.insert(handle, VirtualText::from(text, style));
+        Ok(handle)
+    }
+
+    /// Attaches `value` to the existing anchor `handle`, replacing any virtual
+    /// text it already had, or removes its virtual text if `value` is `None`.
+    /// Returns `Err` if `handle` does not exist.
+    pub fn set_virtual_text(&mut self, handle: AnchorHandle, value: Option<VirtualText>) -> Result<(), Oops> {
+        if self.anchor(handle).is_none() {
+            return Err(Oops::NonexistentAnchor(handle));
+        }
+
+        let row = self.anchor(handle).unwrap().position.row;
+        self.damage.record(row, row);
+
+        match value {
+            None => { self.virtual_text.remove(&handle); },
+            Some(v) => { self.virtual_text.insert(handle, v); }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the virtual text attached to `handle`. If the anchor is floating,
+    /// it is removed as well. Returns `Err` if there is no virtual text there.
+    pub fn remove_virtual_text(&mut self, handle: AnchorHandle) -> Result<(), Oops> {
+        if self.virtual_text.remove(&handle).is_none() {
+            return Err(Oops::NonexistentAnchor(handle));
+        }
+
+        if self.anchor_is_floating(handle) {
+            self.remove_floating_anchor(handle)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the virtual text attached to `handle`, if any.
+    pub fn virtual_text(&self, handle: AnchorHandle) -> Option<&VirtualText> {
+        match self.anchor(handle) {
+            None => None,
+            Some(_) => self.virtual_text.get(&handle)
+        }
+    }
+
+    /// Returns all virtual text on `row` as `(position, handle, text)` triples,
+    /// sorted by column. Virtual text whose anchor no longer exists is skipped.
+    pub fn virtual_text_on_row(&self, row: usize) -> Vec<(Position, AnchorHandle, &VirtualText)> {
+        let mut result: Vec<(Position, AnchorHandle, &VirtualText)> = self.virtual_text
+            .iter()
+            .filter_map(|(handle, vt)| {
+                self.anchor(*handle)
+                    .filter(|a| a.position.row == row)
+                    .map(|a| (a.position, *handle, vt))
+            })
+            .collect();
+
+        result.sort_by_key(|(position, handle, _)| (*position, *handle));
+        result
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::*;
+
+    #[test]
+    fn virtual_text_follows_edits() {
+        let mut document = Document::from("let x = f(1, 2);");
+        let a = document.add_virtual_text(&Position::from(0, 10), "a: ", "hint").unwrap();
+        let b = document.add_virtual_text(&Position::from(0, 13), "b: ", "hint").unwrap();
+
+        document.insert("first", &InsertOptions::exact_at(&Range::from(0, 4, 0, 5))).unwrap();
+        assert_eq!(document.text(), "let first = f(1, 2);");
+        assert_eq!(document.anchor(a).unwrap().position, Position::from(0, 14));
+        assert_eq!(document.anchor(b).unwrap().position, Position::from(0, 17));
+        assert_eq!(document.undo_redo().depth(), (1, 0));
+
+        document.undo(1);
+        assert_eq!(document.text(), "let x = f(1, 2);");
+        assert_eq!(document.anchor(a).unwrap().position, Position::from(0, 10));
+
+        let row = document.virtual_text_on_row(0);
+        assert_eq!(row.len(), 2);
+        assert_eq!(row[0].1, a);
+        assert_eq!(row[1].1, b);
+
+        document.remove_virtual_text(a).unwrap();
+        assert!(document.anchor(a).is_none());
+        assert_eq!(document.virtual_text_on_row(0).len(), 1);
+    }
+}
@@ -0,0 +1,114 @@
+//! JavaScript bindings for [`Document`], exported through `wasm_bindgen`.
+//!
+//! These are deliberately thin: each method forwards to the Rust API and
+//! converts [`Oops`](crate::util::Oops) into a JS error string.
+
+use wasm_bindgen::prelude::*;
+use crate::document::{Document, ChangePacket, InsertOptions};
+
+/// A [`Document`] owned by JavaScript.
+#[wasm_bindgen]
+pub struct JsDocument {
+    document: Document
+}
+
+/// Converts packet labels into JS values: a string, or `null` if unlabeled.
+fn labels<'a>(packets: impl Iterator<Item = &'a ChangePacket>) -> Vec<JsValue> {
+    packets
+        .map(|p| match p.label() {
+            None => JsValue::NULL,
+            Some(label) => JsValue::from_str(label)
+        })
+        .collect()
+}
+
+#[wasm_bindgen]
+impl JsDocument {
+    /// Creates a document holding `text` in language `language`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(text: &str, language: &str) -> JsDocument {
+        JsDocument {
+            document: Document::from_with_language(text, language)
+        }
+    }
+
+    /// Returns the text of the document.
+    pub fn text(&self) -> String {
+        self.document.text()
+    }
+
+    /// Inserts `text` exactly, replacing the selection.
+    pub fn insert(&mut self, text: &str) -> Result<(), JsValue> {
+        self.document.insert(text, &InsertOptions::exact())
+            .map_err(|oops| JsValue::from_str(&format!("{:?}", oops)))
+    }
+
+    /// Starts a new undo packet, labeled `label` if given.
+    pub fn checkpoint(&mut self, label: Option<String>) {
+        match label {
+            None => self.document.checkpoint(),
+            Some(l) => self.document.checkpoint_labeled(&l)
+        }
+    }
+
+    /// Does whatever idle work is due at `now`, milliseconds since the Unix
+    /// epoch as `Date.now()` gives them. Returns true if anything was done.
+    pub fn tick(&mut self, now: f64) -> bool {
+        self.document.tick_at(now as u64).did_work()
+    }
+
+    /// Reports that the user finished an utterance which took `duration`
+    /// milliseconds to say.
+    pub fn note_utterance(&mut self, duration: f64) {
+        self.document.note_utterance(duration as u64);
+    }
+
+    /// Undoes up to `count` packets, returning how many were undone.
+    pub fn undo(&mut self, count: usize) -> usize {
+        self.document.undo(count).count
+    }
+
+    /// Redoes up to `count` packets, returning how many were redone.
+    pub fn redo(&mut self, count: usize) -> usize {
+        self.document.redo(count).count
+    }
+
+    /// Returns the number of packets which can be undone.
+    pub fn undo_depth(&self) -> usize {
+        self.document.undo_redo().depth().0
+    }
+
+    /// Returns the number of packets which can be redone.
+    pub fn redo_depth(&self) -> usize {
+        self.document.undo_redo().depth().1
+    }
+
+    /// Returns the document's revision number. Returned as a double, since
+    /// JS numbers hold integers exactly up to 2^53.
+    pub fn revision(&self) -> f64 {
+        self.document.revision() as f64
+    }
+
+    /// Returns the hash of the document's text as 16 hex digits, since JS
+    /// numbers cannot hold it exactly.
+    pub fn content_hash(&self) -> String {
+        format!("{:016x}", self.document.content_hash())
+    }
+
+    /// Returns the labels of the undoable packets, most recent first.
+    /// Unlabeled packets are `null`.
+    pub fn undo_labels(&self) -> Vec<JsValue> {
+        labels(self.document.undo_redo().undo_packets())
+    }
+
+    /// Returns the labels of the redoable packets, most recently undone first.
+    /// Unlabeled packets are `null`.
+    pub fn redo_labels(&self) -> Vec<JsValue> {
+        labels(self.document.undo_redo().redo_packets())
+    }
+
+    /// Returns the revision numbers of the undoable packets, most recent first.
+    pub fn undo_revisions(&self) -> Vec<f64> {
+        self.document.undo_redo().undo_packets().map(|p| p.revision() as f64).collect()
+    }
+}
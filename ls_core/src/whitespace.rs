@@ -0,0 +1,131 @@
+//! Per-line whitespace information, for renderers which draw whitespace
+//! glyphs and linters which flag whitespace problems.
+//!
+//! Each [`Line`] computes its [`LineWhitespace`] the first time it is asked
+//! and keeps it until the line is edited.
+
+use crate::document::{Document, Line};
+
+/// Characters which look like spaces, or like nothing at all, but are not
+/// ordinary spaces or tabs.
+///
+/// Every Unicode whitespace character other than space and tab is treated as
+/// unusual too.
+pub const INVISIBLE_CHARS: &[char] = &['\u{200b}', '\u{200c}', '\u{200d}', '\u{2060}', '\u{feff}'];
+
+/// Where the whitespace in a line is.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct LineWhitespace {
+    /// The number of characters of leading whitespace.
+    pub indentation: usize,
+
+    /// How many of those are tabs.
+    pub leading_tabs: usize,
+
+    /// How many of those are spaces.
+    pub leading_spaces: usize,
+
+    /// The column at which trailing whitespace begins, if there is any. A
+    /// line of only whitespace is all trailing.
+    pub trailing: Option<usize>,
+
+    /// The columns and characters of non-breaking, zero-width, and other
+    /// unusual spaces. See [`INVISIBLE_CHARS`].
+    pub unusual: Vec<(usize, char)>
+}
+
+/// Is `c` a space or tab?
+fn is_plain_space(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+/// Is `c` whitespace, or invisible, but not a space or tab?
+pub fn is_unusual_space(c: char) -> bool {
+    !is_plain_space(c) && (c.is_whitespace() || INVISIBLE_CHARS.contains(&c))
+}
+
+impl LineWhitespace {
+    /// Examines `content`, a single line of text.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::whitespace::*;
+    /// let info = LineWhitespace::of("\t  let x\u{a0}= 1;  ");
+    /// assert_eq!((info.indentation, info.leading_tabs, info.leading_spaces), (3, 1, 2));
+    /// assert_eq!(info.trailing, Some(13));
+    /// assert_eq!(info.unusual, vec![(8, '\u{a0}')]);
+    /// assert!(info.mixed_indentation());
+    /// ```
+    pub fn of(content: &str) -> LineWhitespace {
+        let mut result = LineWhitespace::default();
+        let mut length = 0;
+        let mut last_visible = None;
+
+        for (column, c) in content.chars().enumerate() {
+            match c {
+                '\t' | ' ' if last_visible.is_none() && result.indentation == column => {
+                    result.indentation += 1;
+                    if c == '\t' {
+                        result.leading_tabs += 1;
+                    } else {
+                        result.leading_spaces += 1;
+                    }
+                },
+                c if is_plain_space(c) => (),
+                c => {
+                    if is_unusual_space(c) {
+                        result.unusual.push((column, c));
+                    }
+                    last_visible = Some(column);
+                }
+            }
+            length = column + 1;
+        }
+
+        let end_of_text = last_visible.map_or(0, |column| column + 1);
+        if end_of_text < length {
+            result.trailing = Some(end_of_text);
+        }
+        result
+    }
+
+    /// Does the indentation contain both tabs and spaces?
+    pub fn mixed_indentation(&self) -> bool {
+        self.leading_tabs > 0 && self.leading_spaces > 0
+    }
+
+    /// Is there anything a linter would complain about: trailing
+    /// whitespace, mixed indentation, or unusual spaces?
+    pub fn has_issues(&self) -> bool {
+        self.trailing.is_some() || self.mixed_indentation() || !self.unusual.is_empty()
+    }
+}
+
+impl Line {
+    /// Returns this line's whitespace information, computing it if needed.
+    pub fn whitespace(&self) -> &LineWhitespace {
+        self.whitespace.get_or_init(|| LineWhitespace::of(&self.content))
+    }
+}
+
+impl Document {
+    /// Returns the rows of every line with whitespace issues. See
+    /// [`LineWhitespace::has_issues`].
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("ok\nbad \n\t ok?");
+    /// assert_eq!(document.whitespace_issues(), vec![1, 2]);
+    /// document.remove(&RemoveOptions::exact_at(&Range::from(1, 3, 1, 4))).unwrap();
+    /// assert_eq!(document.whitespace_issues(), vec![2]);
+    /// ```
+    pub fn whitespace_issues(&self) -> Vec<usize> {
+        self.lines()
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.whitespace().has_issues())
+            .map(|(row, _)| row)
+            .collect()
+    }
+}
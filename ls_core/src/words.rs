@@ -0,0 +1,178 @@
+//! Words: runs of word characters, as defined per language. The word under
+//! the cursor is the target of many spoken commands ("select word",
+//! "delete word", "next one").
+
+use crate::document::{Document, Position, Range, RemoveOptions};
+use crate::motions::TextObject;
+use crate::search::SearchOptions;
+use crate::segmentation::Segmentation;
+use crate::util::{self, Oops};
+
+/// Returns the characters, beyond letters, digits, and `_`, which count as
+/// part of a word in `language`: `$` in JavaScript and TypeScript, `-` in
+/// CSS, HTML, and Lisps.
+pub fn extra_word_chars(language: &str) -> &'static [char] {
+    match language {
+        "js" | "ts" | "tsx" => &['$'],
+        "css" | "scss" | "html" | "lisp" | "clj" | "el" => &['-'],
+        _ => &[]
+    }
+}
+
+impl Document {
+    /// Returns whether `c` is part of a word in this document's language.
+    pub fn is_word_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || extra_word_chars(self.language()).contains(&c)
+    }
+
+    /// Returns the range of the word at `position`: the word containing the
+    /// character after `position`, or else the word ending at `position`.
+    /// Returns `None` if there is no such word or `position` is invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let document = Document::from_with_language("let $el = x+y;", "js");
+    /// assert_eq!(document.word_at(&Position::from(0, 5)), Some(Range::from(0, 4, 0, 7)));
+    /// assert_eq!(document.word_at(&Position::from(0, 11)), Some(Range::from(0, 10, 0, 11)));
+    /// assert_eq!(document.word_at(&Position::from(0, 3)), Some(Range::from(0, 0, 0, 3)));
+    /// assert_eq!(document.word_at(&Position::from(0, 8)), None);
+    /// ```
+    pub fn word_at(&self, position: &Position) -> Option<Range> {
+        if !self.position_valid(position) {
+            return None;
+        }
+
+        let chars: Vec<char> = self.lines()[position.row].content.chars().collect();
+        let word = |i: usize| i < chars.len() && self.is_word_char(chars[i]);
+
+        let column = if word(position.column) {
+            position.column
+        } else if position.column > 0 && word(position.column - 1) {
+            position.column - 1
+        } else {
+            return None;
+        };
+
+        let mut beginning = column;
+        while beginning > 0 && word(beginning - 1) {
+            beginning -= 1;
+        }
+        let mut ending = column;
+        while word(ending) {
+            ending += 1;
+        }
+
+        Some(Range::from(position.row, beginning, position.row, ending))
+    }
+
+    /// Returns the sub-words of the word at `word`, in order.
+    pub(crate) fn subwords_in(&self, word: &Range) -> Vec<Range> {
+        let line = &self.lines()[word.beginning.row].content;
+        let text = util::slice(line, word.beginning.column..word.ending.column);
+        let (row, column) = (word.beginning.row, word.beginning.column);
+
+        Segmentation::default()
+            .spans(text)
+            .into_iter()
+            .map(|span| Range::from(row, column + span.start, row, column + span.end))
+            .collect()
+    }
+
+    /// Returns the range of the sub-word at `position`: of the sub-words
+    /// of the word at `position`, the one containing the character after
+    /// `position`, or else the one ending at `position`, or else, on a
+    /// separator, the next one. Returns `None` if there is no such word or
+    /// it is all separators.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let document = Document::from("let x = parseHTTP_response;");
+    /// assert_eq!(document.subword_at(&Position::from(0, 10)), Some(Range::from(0, 8, 0, 13)));
+    /// assert_eq!(document.subword_at(&Position::from(0, 17)), Some(Range::from(0, 13, 0, 17)));
+    /// assert_eq!(document.subword_at(&Position::from(0, 4)), Some(Range::from(0, 4, 0, 5)));
+    /// ```
+    pub fn subword_at(&self, position: &Position) -> Option<Range> {
+        let word = self.word_at(position)?;
+        let subwords = self.subwords_in(&word);
+        let column = position.column;
+
+        subwords.iter()
+            .find(|s| s.beginning.column <= column && column < s.ending.column)
+            .or_else(|| subwords.iter().find(|s| s.ending.column == column))
+            .or_else(|| subwords.iter().find(|s| s.beginning.column > column))
+            .copied()
+    }
+
+    /// Returns the text of the word at the cursor, if any.
+    pub fn word_at_cursor(&self) -> Option<String> {
+        let range = self.word_at(&self.cursor().position)?;
+        let line = &self.lines()[range.beginning.row].content;
+        Some(String::from(util::slice(line, range.beginning.column..range.ending.column)))
+    }
+
+    /// Selects the word at the cursor, returning its range. Returns `Err` if
+    /// there is no word at the cursor.
+    pub fn select_word(&mut self) -> Result<Range, Oops> {
+        let range = self.resolve(&TextObject::Word)?;
+        self.set_selection(&range)?;
+        Ok(range)
+    }
+
+    /// Removes the word at the cursor, returning its text. Returns `Err` if
+    /// there is no word at the cursor.
+    pub fn delete_word(&mut self) -> Result<String, Oops> {
+        let range = self.resolve(&TextObject::Word)?;
+        let word = self.text_range(&range).unwrap();
+        self.remove(&RemoveOptions::exact_at(&range))?;
+        Ok(word)
+    }
+
+    /// Selects the next (or, if not `forward`, previous) whole-word
+    /// occurrence of the word at the cursor, wrapping around the document.
+    /// Returns the new selection, or `Err` if there is no word at the cursor
+    /// or it occurs only once.
+    ///
+    /// # Examples
+    /// ```
+    /// use ls_core::document::*;
+    /// let mut document = Document::from("x = max(x, xs)\nreturn x");
+    /// document.set_cursor_and_mark(&Position::from(0, 0)).unwrap();
+    /// assert_eq!(document.search_word_at_cursor(true), Ok(Range::from(0, 8, 0, 9)));
+    /// assert_eq!(document.search_word_at_cursor(true), Ok(Range::from(1, 7, 1, 8)));
+    /// assert_eq!(document.search_word_at_cursor(false), Ok(Range::from(0, 8, 0, 9)));
+    /// ```
+    pub fn search_word_at_cursor(&mut self, forward: bool) -> Result<Range, Oops> {
+        let word = self.word_at_cursor().ok_or(Oops::Ouch("no word at cursor"))?;
+        let from = self.word_at(&self.cursor().position).unwrap().beginning;
+
+        let found = self.find_next(&word, &from, forward, &SearchOptions::whole_word())
+            .ok_or(Oops::Ouch("no other occurrence of word"))?;
+        self.set_selection(&found)?;
+        Ok(found)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_and_delete_word() {
+        let mut document = Document::from("margin-top: 0;");
+        document.set_cursor_and_mark(&Position::from(0, 8)).unwrap();
+        assert_eq!(document.select_word(), Ok(Range::from(0, 7, 0, 10)));
+
+        document.set_language("css").unwrap();
+        document.set_cursor_and_mark(&Position::from(0, 8)).unwrap();
+        assert_eq!(document.delete_word(), Ok(String::from("margin-top")));
+        assert_eq!(document.text(), ": 0;");
+
+        document.set_cursor_and_mark(&Position::from(0, 1)).unwrap();
+        assert!(document.delete_word().is_err());
+        assert!(document.search_word_at_cursor(true).is_err());
+    }
+}
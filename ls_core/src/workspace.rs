@@ -0,0 +1,348 @@
+//! A workspace: what the core knows about every open document at once.
+//!
+//! Documents are independent of one another, so the host tells the
+//! [`Workspace`] about each by an id of its choosing (usually the path) and
+//! calls [`Workspace::update_document`] after edits. The workspace keeps a
+//! symbol index built from each document's [outline](Document::outline),
+//! so "open function parse line" can find `parse_line` in a file which
+//! isn't focused. Updating is incremental: a document whose revision hasn't
+//! changed since it was last indexed is skipped, and only its own symbols
+//! are replaced when it has.
+//!
+//! The workspace also owns *derived* documents: scratch buffers whose text
+//! is computed from a source document, such as a preview of its parse tree.
+//! [`Workspace::update_document`] is the workspace's change notification,
+//! so when a source's revision moves on, every document derived from it,
+//! and every document derived from those, is recomputed. Only the lines
+//! which changed are rewritten, so cursors and scroll positions in a
+//! preview survive its source being edited.
+
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use crate::document::{Document, Position, Range};
+use crate::fuzzy;
+use crate::util::Oops;
+
+/// A symbol defined in a workspace document.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Symbol {
+    pub name: String,
+
+    /// The id of the document defining it.
+    pub document: String,
+
+    /// The range of its name.
+    pub range: Range,
+
+    /// The kind of the definition, such as `function_item`.
+    pub kind: String
+}
+
+/// A symbol which matched a spoken name, with how well it matched (see
+/// [`fuzzy::score`]).
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SymbolMatch {
+    pub symbol: Symbol,
+    pub score: u32
+}
+
+/// Computes a derived document's text from its source.
+pub type Derivation = Rc<dyn Fn(&Document) -> String>;
+
+/// Returns a derivation which shows the source's parse tree, as
+/// [`Document::parse_tree_pretty_print`] prints it, or nothing if it has
+/// none.
+pub fn parse_tree_view() -> Derivation {
+    Rc::new(|source: &Document| source.parse_tree_pretty_print().unwrap_or_default())
+}
+
+/// A document whose text is computed from another's.
+struct Derived {
+    source: String,
+    derivation: Derivation,
+    document: Document
+}
+
+/// Open documents' symbols, by name, and the documents derived from them.
+#[derive(Default)]
+pub struct Workspace {
+    /// The revision each document was indexed at.
+    revisions: HashMap<String, u64>,
+
+    /// Every symbol, by name.
+    symbols: BTreeMap<String, Vec<Symbol>>,
+
+    /// Derived documents, by id.
+    derived: BTreeMap<String, Derived>
+}
+
+impl Workspace {
+    /// Returns a workspace which knows no documents.
+    pub fn new() -> Workspace {
+        Workspace::default()
+    }
+
+    /// Indexes the symbols of `document`, known as `id`, replacing those
+    /// indexed for `id` before, and recomputes the documents derived from
+    /// it. Returns false without doing anything if `document` is at the
+    /// revision it was last indexed at.
+    pub fn update_document(&mut self, id: &str, document: &Document) -> bool {
+        if self.revisions.get(id) == Some(&document.revision()) {
+            return false;
+        }
+        self.refresh_derived_from(id, document);
+
+        self.remove_symbols(id);
+        self.revisions.insert(String::from(id), document.revision());
+        for definition in document.outline() {
+            self.symbols.entry(definition.name.clone()).or_default().push(Symbol {
+                name: definition.name,
+                document: String::from(id),
+                range: definition.range,
+                kind: definition.kind
+            });
+        }
+        true
+    }
+
+    /// Forgets the document known as `id`, as when it is closed, along
+    /// with the documents derived from it. Returns false if it was not known.
+    pub fn forget_document(&mut self, id: &str) -> bool {
+        self.remove_symbols(id);
+        self.forget_derived_from(id);
+        self.revisions.remove(id).is_some()
+    }
+
+    /// Adds a document known as `id`, in `language`, whose text is
+    /// `derivation` of `source`, the document known as `source_id`, and
+    /// keeps it up to date as `source` is updated. Returns `Err` if `id` is
+    /// already taken or `source_id` is not known.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::rc::Rc;
+    /// use ls_core::document::*;
+    /// use ls_core::workspace::*;
+    /// let mut workspace = Workspace::new();
+    /// let mut notes = Document::from("b\na");
+    /// workspace.update_document("notes", &notes);
+    ///
+    /// let sorted: Derivation = Rc::new(|source: &Document| {
+    ///     let mut lines: Vec<String> = source.lines().iter().map(|l| l.content.clone()).collect();
+    ///     lines.sort();
+    ///     lines.join("\n")
+    /// });
+    /// workspace.derive_document("notes (sorted)", "", "notes", &notes, sorted).unwrap();
+    /// assert_eq!(workspace.derived_document("notes (sorted)").unwrap().text(), "a\nb");
+    ///
+    /// notes.insert("\n0", &InsertOptions::exact_at(&Range::from(1, 1, 1, 1))).unwrap();
+    /// workspace.update_document("notes", &notes);
+    /// assert_eq!(workspace.derived_document("notes (sorted)").unwrap().text(), "0\na\nb");
+    /// ```
+    pub fn derive_document(&mut self, id: &str, language: &str, source_id: &str, source: &Document,
+        derivation: Derivation) -> Result<(), Oops>
+    {
+        if !self.revisions.contains_key(source_id) {
+            return Err(Oops::Ouch("derive_document - unknown source"));
+        }
+        self.add_derived(id, language, source_id, source, derivation)
+    }
+
+    /// Like [`Workspace::derive_document`], but derives from the derived
+    /// document known as `source_id`.
+    pub fn derive_from_derived(&mut self, id: &str, language: &str, source_id: &str,
+        derivation: Derivation) -> Result<(), Oops>
+    {
+        let source = self.derived.remove(source_id).ok_or(Oops::Ouch("derive_from_derived - unknown source"))?;
+        let result = self.add_derived(id, language, source_id, &source.document, derivation);
+        self.derived.insert(String::from(source_id), source);
+        result
+    }
+
+    /// Adds the derived document known as `id`, or returns `Err` if `id` is
+    /// taken.
+    fn add_derived(&mut self, id: &str, language: &str, source_id: &str, source: &Document,
+        derivation: Derivation) -> Result<(), Oops>
+    {
+        if self.revisions.contains_key(id) || self.derived.contains_key(id) || id == source_id {
+            return Err(Oops::Ouch("derive_document - id already taken"));
+        }
+
+        let document = Document::from_with_language(&derivation(source), language);
+        self.derived.insert(String::from(id), Derived { source: String::from(source_id), derivation, document });
+        Ok(())
+    }
+
+    /// Returns the derived document known as `id`, if any.
+    pub fn derived_document(&self, id: &str) -> Option<&Document> {
+        self.derived.get(id).map(|derived| &derived.document)
+    }
+
+    /// Returns the derived document known as `id` mutably, to move its
+    /// cursor or scroll it. Edits to its text are overwritten the next time
+    /// its source changes.
+    pub fn derived_document_mut(&mut self, id: &str) -> Option<&mut Document> {
+        self.derived.get_mut(id).map(|derived| &mut derived.document)
+    }
+
+    /// Returns the ids of the documents derived directly from `source_id`.
+    pub fn derived_from(&self, source_id: &str) -> Vec<&str> {
+        self.derived.iter()
+            .filter(|(_, derived)| derived.source == source_id)
+            .map(|(id, _)| id.as_str())
+            .collect()
+    }
+
+    /// Removes the derived document known as `id`, and those derived from
+    /// it. Returns false if there was none.
+    pub fn remove_derived(&mut self, id: &str) -> bool {
+        self.forget_derived_from(id);
+        self.derived.remove(id).is_some()
+    }
+
+    /// Recomputes the documents derived from `source`, known as `source_id`,
+    /// and those derived from them in turn.
+    fn refresh_derived_from(&mut self, source_id: &str, source: &Document) {
+        let ids: Vec<String> = self.derived_from(source_id).into_iter().map(String::from).collect();
+        for id in ids {
+            let mut derived = self.derived.remove(&id).unwrap();
+            let text = (derived.derivation)(source);
+            let document = &mut derived.document;
+            let last = document.rows() - 1;
+            let whole = Range { beginning: Position::from(0, 0), ending: Position::from(last, document.lines()[last].length) };
+            if document.text() != text && document.replace_changed(&whole, &text, "derive").is_ok() {
+                document.forget_undo_redo().ok();
+            }
+
+            self.refresh_derived_from(&id, &derived.document);
+            self.derived.insert(id, derived);
+        }
+    }
+
+    /// Removes every document derived, directly or not, from `source_id`.
+    fn forget_derived_from(&mut self, source_id: &str) {
+        let ids: Vec<String> = self.derived_from(source_id).into_iter().map(String::from).collect();
+        for id in ids {
+            self.remove_derived(&id);
+        }
+    }
+
+    /// Returns the ids of the documents known, in no particular order.
+    pub fn documents(&self) -> impl Iterator<Item = &str> {
+        self.revisions.keys().map(String::as_str)
+    }
+
+    /// Returns the symbols named exactly `name`.
+    pub fn symbols_named(&self, name: &str) -> &[Symbol] {
+        self.symbols.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Returns up to `limit` symbols matching spoken `query`, best first.
+    /// Ties are broken by name, then by document id.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "parsing")] {
+    /// use ls_core::document::*;
+    /// use ls_core::workspace::*;
+    /// let mut workspace = Workspace::new();
+    /// let lexer = Document::from_with_language("fn parse_line() {}\nfn skip() {}", "rs");
+    /// let main = Document::from_with_language("fn main() {}", "rs");
+    /// workspace.update_document("src/lexer.rs", &lexer);
+    /// workspace.update_document("src/main.rs", &main);
+    ///
+    /// let found = workspace.find_symbols("parse line", 5);
+    /// assert_eq!(found[0].symbol.document, "src/lexer.rs");
+    /// assert_eq!(found[0].symbol.range, Range::from(0, 3, 0, 13));
+    /// # }
+    /// ```
+    pub fn find_symbols(&self, query: &str, limit: usize) -> Vec<SymbolMatch> {
+        let mut result: Vec<SymbolMatch> = self.symbols.iter()
+            .filter_map(|(name, symbols)| fuzzy::score(query, name).map(|score| (score, symbols)))
+            .flat_map(|(score, symbols)| symbols.iter().map(move |symbol| SymbolMatch { symbol: symbol.clone(), score }))
+            .collect();
+
+        result.sort_by(|a, b| b.score.cmp(&a.score)
+            .then_with(|| a.symbol.name.cmp(&b.symbol.name))
+            .then_with(|| a.symbol.document.cmp(&b.symbol.document)));
+        result.truncate(limit);
+        result
+    }
+
+    /// Removes the symbols indexed for `id`.
+    fn remove_symbols(&mut self, id: &str) {
+        if !self.revisions.contains_key(id) {
+            return;
+        }
+        self.symbols.retain(|_, symbols| {
+            symbols.retain(|symbol| symbol.document != id);
+            !symbols.is_empty()
+        });
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+#[cfg(all(test, feature = "parsing"))]
+mod tests {
+    use super::*;
+    use crate::document::InsertOptions;
+
+    #[test]
+    fn index_follows_edits() {
+        let mut workspace = Workspace::new();
+        let mut a = Document::from_with_language("fn helper() {}\n", "rs");
+        let b = Document::from_with_language("def helper():\n    pass\n", "py");
+        assert!(workspace.update_document("a.rs", &a));
+        assert!(workspace.update_document("b.py", &b));
+        assert!(!workspace.update_document("a.rs", &a));
+        assert_eq!(workspace.symbols_named("helper").len(), 2);
+
+        a.insert("struct Thing;\n", &InsertOptions::exact_at(&Range::from(1, 0, 1, 0))).unwrap();
+        assert!(workspace.update_document("a.rs", &a));
+        assert_eq!(workspace.symbols_named("helper").len(), 2);
+        assert_eq!(workspace.symbols_named("Thing")[0].range, Range::from(1, 7, 1, 12));
+        assert!(workspace.find_symbols("zebra", 5).is_empty());
+    }
+
+    #[test]
+    fn index_forgets_closed_documents() {
+        let mut workspace = Workspace::new();
+        workspace.update_document("a.rs", &Document::from_with_language("fn helper() {}\n", "rs"));
+        workspace.update_document("b.py", &Document::from_with_language("def helper():\n    pass\n", "py"));
+
+        assert!(workspace.forget_document("b.py"));
+        assert!(!workspace.forget_document("b.py"));
+        assert_eq!(workspace.symbols_named("helper")[0].document, "a.rs");
+        assert_eq!(workspace.documents().count(), 1);
+    }
+
+    #[test]
+    fn derived_documents_follow_their_sources() {
+        let mut workspace = Workspace::new();
+        let mut a = Document::from_with_language("fn helper() {}\nstruct Thing;\n", "rs");
+        workspace.update_document("a.rs", &a);
+
+        workspace.derive_document("a.rs (tree)", "", "a.rs", &a, parse_tree_view()).unwrap();
+        workspace.derive_from_derived("a.rs (tree) lines", "", "a.rs (tree)", Rc::new(|tree: &Document| tree.rows().to_string())).unwrap();
+        assert!(workspace.derive_document("a.rs", "", "a.rs", &a, parse_tree_view()).is_err());
+        assert!(workspace.derive_document("x", "", "nowhere", &a, parse_tree_view()).is_err());
+        let tree = workspace.derived_document("a.rs (tree)").unwrap().text();
+        assert!(tree.contains("struct_item"));
+
+        let preview = workspace.derived_document_mut("a.rs (tree)").unwrap();
+        preview.set_cursor_and_mark(&Position::from(1, 0)).unwrap();
+        a.insert("fn more() {}\n", &InsertOptions::exact_at(&Range::from(2, 0, 2, 0))).unwrap();
+        workspace.update_document("a.rs", &a);
+        let preview = workspace.derived_document("a.rs (tree)").unwrap();
+        assert_ne!(preview.text(), tree);
+        assert_eq!(preview.cursor().position, Position::from(1, 0));
+        assert_eq!(preview.undo_redo().depth(), (0, 0));
+        let lines = workspace.derived_document("a.rs (tree) lines").unwrap().text();
+        assert_eq!(lines, preview.rows().to_string());
+
+        assert!(workspace.forget_document("a.rs"));
+        assert!(workspace.derived_document("a.rs (tree) lines").is_none());
+    }
+}